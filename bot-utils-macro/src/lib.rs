@@ -1,4 +1,4 @@
-use proc_macro::{TokenStream, TokenTree};
+use proc_macro::TokenStream;
 
 #[proc_macro]
 pub fn declare_tuple_helpers(content: TokenStream) -> TokenStream {
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table client_config add column alias_hints boolean not null default false
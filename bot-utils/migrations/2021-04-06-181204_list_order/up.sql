@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table client_config add column list_order integer not null default 0
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+alter table client_config drop column roll_shorthand
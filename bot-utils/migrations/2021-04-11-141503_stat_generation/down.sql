@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+alter table client_config drop column stats_min_total;
+alter table client_config drop column stats_roll
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table client_config add column stats_roll text;
+alter table client_config add column stats_min_total bigint
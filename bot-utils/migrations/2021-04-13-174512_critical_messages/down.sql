@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+alter table client_config drop column fumble_message;
+alter table client_config drop column critical_message
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table client_config add column critical_message text;
+alter table client_config add column fumble_message text
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table client_config add column show_luck bool not null default 0
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table client_config add column roll_history text not null default '{}'
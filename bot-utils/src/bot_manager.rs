@@ -1,17 +1,35 @@
-use crate::client_utils::{rolls::RollExecutor, storage::GlobalStorage, ClientUtilsBuilder};
+use crate::client_utils::{
+    rolls::{RollExecutor, RollSettings},
+    storage::{Backend, GlobalStorage},
+    ClientUtilsBuilder,
+};
 use crate::tuple_helpers::*;
 pub use async_trait::async_trait;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    convert::{TryFrom, TryInto},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::join;
+use toml::{map::Map, Value};
 
 pub struct BotManager<B: BotWrapper> {
     global_handle: ClientUtilsBuilder,
     bots: B,
+    finished: Arc<tokio::sync::watch::Sender<bool>>,
 }
 
 impl<B: BotWrapper> BotManager<B> {
     pub async fn run(self) {
-        let (_, r) = join!(self.global_handle.wait(), self.bots.run().join());
+        let finished = self.finished;
+        let bots = self.bots.run();
+        let (_, r) = join!(self.global_handle.wait(), async move {
+            let r = bots.join().await;
+            // bots may also stop on their own, nothing is left to do after all of them did
+            let _ = finished.send(true);
+            r
+        });
         log::info!("bots finished");
         ResultChain::result(r).unwrap();
     }
@@ -43,17 +61,83 @@ impl StopListener for tokio::sync::watch::Receiver<bool> {
 pub struct BotManagerBuilder<BB: BotBuilderWrapper> {
     bots: BB,
     storage: GlobalStorage,
-    roll_timeout: std::time::Duration,
-    rng_reseed: std::time::Duration,
+    /// read again on SIGHUP
+    config_path: PathBuf,
+    roll_settings: RollSettings,
     rng_workers: u32,
     db_handle: std::thread::JoinHandle<()>,
 }
 
+/// the config file at `path`, empty if it can't be read
+fn read_config(path: &Path) -> Map<String, Value> {
+    match toml::from_slice(&match std::fs::read(path) {
+        Ok(a) => a,
+        Err(e) => {
+            log::warn!("Unable to read config file: {}", e);
+            vec![]
+        }
+    }) {
+        Ok(a) => a,
+        Err(e) => {
+            log::warn!("Unable to parse config: {}", e);
+            Map::new()
+        }
+    }
+}
+
+/// the value of `key`, missing or invalid ones are replaced by `default`
+fn config_value<T: TryFrom<i64>>(config: &mut Map<String, Value>, key: &str, default: i64) -> T {
+    match config
+        .get(key)
+        .and_then(|t| t.as_integer())
+        .and_then(|t| t.try_into().ok())
+    {
+        Some(t) => t,
+        None => {
+            log::warn!("unable to read {}, overwriting with {}", key, default);
+            config.insert(key.to_string(), Value::from(default));
+            T::try_from(default).unwrap_or_else(|_| panic!("invalid default for {}", key))
+        }
+    }
+}
+
+/// settings of the roll executor in `config`, filling in the missing ones
+fn roll_settings(config: &mut Map<String, Value>) -> RollSettings {
+    RollSettings {
+        timeout: Duration::from_millis(config_value(config, "roll_timeout_ms", 2000)),
+        rng_reseed: Duration::from_secs(config_value(config, "rng_reseed_s", 300)),
+        max_queue: config_value(config, "roll_queue_size", 256),
+        dice_limit: config_value(config, "roll_dice_limit", 1_000_000),
+    }
+}
+
+/// SIGHUP reloads the roll settings from the config file, SIGUSR1 reseeds the crypto rng
+///
+/// handles `rolls` until the bots are stopped, the rng provider only stops once every one of
+/// them is dropped
 #[cfg(target_family = "unix")]
-async fn wait_hup() {
+async fn handle_signals<S: StopListener>(
+    config_path: PathBuf,
+    rolls: Arc<RollExecutor>,
+    mut stop: S,
+) {
     use tokio::signal::unix::*;
-    let mut signal = signal(SignalKind::hangup()).unwrap();
-    signal.recv().await;
+    let mut hangup = signal(SignalKind::hangup()).unwrap();
+    let mut usr1 = signal(SignalKind::user_defined1()).unwrap();
+    loop {
+        tokio::select! {
+            _ = hangup.recv()=>{
+                let settings = roll_settings(&mut read_config(&config_path));
+                rolls.update(settings);
+                log::info!("Received SIGHUP: reloaded the roll settings {:?}", settings)
+            }
+            _ = usr1.recv()=>{
+                rolls.reseed().await;
+                log::info!("Received SIGUSR1: reseeded the rng")
+            }
+            _ = stop.wait_stop()=>{break;}
+        }
+    }
 }
 
 impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
@@ -62,23 +146,8 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
         S: ToString,
         BC: BotConfigWrapper<Output = BB>,
     {
-        use std::convert::TryInto;
-        use toml::{map::Map, Value};
         let config_path = PathBuf::from(config_path.to_string());
-        let mut config: Map<String, Value> =
-            match toml::from_slice(&match std::fs::read(&config_path) {
-                Ok(a) => a,
-                Err(e) => {
-                    log::warn!("Unable to read config file: {}", e);
-                    vec![]
-                }
-            }) {
-                Ok(a) => a,
-                Err(e) => {
-                    log::warn!("Unable to parse config: {}", e);
-                    Map::new()
-                }
-            };
+        let mut config = read_config(&config_path);
         let db_path = std::env::var("DB_PATH").unwrap_or_else(|_| {
             config
                 .get("db_path")
@@ -87,65 +156,19 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
                 .expect("No db_path given through \"DB_PATH\" env var or \"db_path\" config key")
         });
 
-        let db_queue_size: usize = match config
-            .get("db_queue_size")
-            .and_then(|s| s.as_integer())
-            .and_then(|s| s.try_into().ok())
-        {
-            Some(i) => i,
-            None => {
-                log::warn!("unable to read db_queue_size, overwriting with 64");
-                config.insert("db_queue_size".to_string(), Value::from(64));
-                64
-            }
-        };
-        let roll_timeout: std::time::Duration = std::time::Duration::from_millis(
-            match config
-                .get("roll_timeout_ms")
-                .and_then(|t| t.as_integer())
-                .and_then(|t| t.try_into().ok())
-            {
-                Some(t) => t,
-                None => {
-                    log::warn!("unable to read roll_timeout_ms, overwriting with 2000");
-                    config.insert("roll_timeout_ms".to_string(), Value::from(2000));
-                    2000
-                }
-            },
-        );
-        let rng_reseed = std::time::Duration::from_secs(
-            match config
-                .get("rng_reseed_s")
-                .and_then(|t| t.as_integer())
-                .and_then(|t| t.try_into().ok())
-            {
-                Some(t) => t,
-                None => {
-                    log::warn!("unable to read rng_reseed_s, overwriting with 300");
-                    config.insert("rng_reseed_s".to_string(), toml::Value::from(300));
-                    300
-                }
-            },
-        );
-        let rng_workers: u32 = match config
-            .get("rng_workers")
-            .and_then(|t| t.as_integer())
-            .and_then(|t| t.try_into().ok())
-        {
-            Some(t) => t,
-            None => {
-                log::warn!("unable to read rng_workers, overwriting with 4");
-                config.insert("rng_workers".to_string(), toml::Value::from(4));
-                4
-            }
-        };
+        let db_queue_size: usize = config_value(&mut config, "db_queue_size", 64);
+        let rng_workers: u32 = config_value(&mut config, "rng_workers", 4);
+        let roll_settings = roll_settings(&mut config);
 
         let builders: BB = bots.config(&mut config);
 
-        let (storage, db_handle) = GlobalStorage::new(db_path, db_queue_size).unwrap();
+        let backend = Backend::of_url(&db_path);
+        log::info!("using {} database", backend);
+        let (storage, db_handle) = GlobalStorage::new(backend, db_path, db_queue_size)
+            .unwrap_or_else(|err| panic!("unable to open database: {}", err));
 
         let config_value: Value = config.into();
-        match std::fs::write(config_path, toml::to_vec(&config_value).unwrap()) {
+        match std::fs::write(&config_path, toml::to_vec(&config_value).unwrap()) {
             Ok(_) => {}
             Err(e) => {
                 log::error!("Error writing config: {}", e)
@@ -155,8 +178,8 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
         BotManagerBuilder {
             bots: builders,
             storage,
-            roll_timeout,
-            rng_reseed,
+            config_path,
+            roll_settings,
             rng_workers,
             db_handle,
         }
@@ -168,6 +191,14 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
         <<BB::Output as JoinChain>::Output as ResultChain<tokio::task::JoinError>>::Output,
     > {
         let (finished_sender, finished_receiver) = tokio::sync::watch::channel(false);
+        let finished_sender = Arc::new(finished_sender);
+        let finished = finished_sender.clone();
+        // registered before any bot starts, the default action of SIGTERM skips the shutdown
+        #[cfg(target_family = "unix")]
+        let mut terminate = {
+            use tokio::signal::unix::*;
+            signal(SignalKind::terminate()).unwrap()
+        };
         tokio::task::spawn(async move {
             #[cfg(target_family = "unix")]
             {
@@ -175,8 +206,8 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
                     _ = tokio::signal::ctrl_c()=>{
                         log::info!("Received Ctrl-C: Shutting down")
                     }
-                    _ = wait_hup()=>{
-                        log::info!("Received SIGHUP: Shutting down")
+                    _ = terminate.recv()=>{
+                        log::info!("Received SIGTERM: Shutting down")
                     }
                 };
             }
@@ -186,22 +217,32 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
                     _ => log::info!("Received Ctrl-C: Shutting down"),
                 }
             }
-            finished_sender.send(true).unwrap();
+            let _ = finished_sender.send(true);
             tokio::time::sleep(std::time::Duration::from_secs(15)).await;
             log::error!("Waiting for runtime shutdown timed out.");
             std::process::abort()
         });
+        let settings = self.roll_settings;
         let (handle, roll) = RollExecutor::new(
             self.rng_workers,
-            self.roll_timeout,
-            self.rng_reseed,
+            settings.timeout,
+            settings.rng_reseed,
+            settings.max_queue,
+            settings.dice_limit,
             finished_receiver.clone(),
         )
         .await;
+        let roll = Arc::new(roll);
+        #[cfg(target_family = "unix")]
+        tokio::task::spawn(handle_signals(
+            self.config_path,
+            roll.clone(),
+            finished_receiver.clone(),
+        ));
         let db_handle_task = self.db_handle;
         let db_handle = tokio::task::spawn_blocking(move || db_handle_task.join().unwrap());
         let bot_config_builder = Arc::new(std::sync::Mutex::new(ClientUtilsBuilder {
-            rolls: std::sync::Arc::new(roll),
+            rolls: roll,
             storage: std::sync::Arc::new(self.storage),
             join_handles: vec![handle, db_handle],
         }));
@@ -222,6 +263,31 @@ impl<BB: BotBuilderWrapper + Send> BotManagerBuilder<BB> {
                 .into_inner()
                 .unwrap(),
             bots,
+            finished,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll_settings() {
+        let mut config: Map<String, Value> =
+            toml::from_str("roll_timeout_ms = 500\nroll_queue_size = -3").unwrap();
+        assert_eq!(
+            roll_settings(&mut config),
+            RollSettings {
+                timeout: Duration::from_millis(500),
+                rng_reseed: Duration::from_secs(300),
+                max_queue: 256,
+                dice_limit: 1_000_000,
+            }
+        );
+        // invalid and missing values are replaced by their defaults
+        assert_eq!(config.get("roll_queue_size"), Some(&Value::from(256)));
+        assert_eq!(config.get("rng_reseed_s"), Some(&Value::from(300)));
+        assert_eq!(config.get("roll_timeout_ms"), Some(&Value::from(500)));
+    }
+}
@@ -1,17 +1,17 @@
 pub use super::{
-    storage::{ClientId, StorageHandle},
-    VersionedRollExpr,
+    storage::{ClientId, DiceKind, ListOrder, Lookup, StorageHandle},
+    AliasCallError, Critical, VersionedRollExpr,
 };
 use nom::{
     branch::alt,
-    bytes::complete::{tag, tag_no_case},
-    character::complete::{multispace0, multispace1, satisfy},
-    combinator::{eof, map, recognize, success},
-    multi::{many0, many1},
-    sequence::{delimited, pair, preceded, terminated},
+    bytes::complete::{tag, tag_no_case, take_till1},
+    character::complete::{digit1, multispace0, multispace1, satisfy},
+    combinator::{eof, map, recognize, rest, success, verify},
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
-use robins_dice_roll::parser;
+use robins_dice_roll::{parser, Expression, FilteredDice, LabeledExpression, NarrativePool, Term};
 use std::sync::Arc;
 use unicode_categories::UnicodeCategories;
 
@@ -24,18 +24,346 @@ pub enum Command {
     GetCommandPrefix,
     SetRollInfo(bool),
     GetRollInfo,
+    /// tell how far a result is from the average
+    SetShowLuck(bool),
+    GetShowLuck,
     AddRollPrefix(String),
     RemoveRollPrefix(String),
+    ClearRollPrefixes,
+    /// overrides the command prefix in the channel of the message, `None` removes it
+    SetChannelCommandPrefix(Option<String>),
+    AddChannelRollPrefix(String),
+    RemoveChannelRollPrefix(String),
     ListRollPrefix,
+    AddPrivilegedRole(String),
+    RemovePrivilegedRole(String),
+    ListPrivilegedRoles,
     AddAlias(String, VersionedRollExpr),
     RemoveAlias(String),
-    ListAliases,
+    /// removes every shared alias
+    ClearAliases,
+    PromoteAlias(String),
+    CopyAliasFromDm(String),
+    /// every alias, or only the given page of [super::ALIAS_PAGE_SIZE] of them
+    ListAliases(Option<u32>),
     AliasRoll(Vec<Arc<VersionedRollExpr>>),
     Roll(VersionedRollExpr),
+    /// several expressions separated by `;`, rolled one after another
+    MultiRoll(Vec<VersionedRollExpr>),
+    /// roll whose result only the user who requested it sees
+    RollPrivate(VersionedRollExpr),
+    /// expressions of an earlier result rolled again, never parsed from a message
+    Reroll(Vec<Arc<VersionedRollExpr>>),
+    /// roll again what the user rolled last in the channel
+    RepeatLast,
+    /// several commands separated by `;`, run one after another
+    Multiple(Vec<Command>),
+    /// roll statement that ends with an operator, like `1d6 +`
+    TrailingOperator(String),
+    /// parameterized alias that can't be filled in with the given arguments
+    InvalidAliasCall(AliasCallError),
+    Pause,
+    Resume,
+    /// read the configuration from the database again
+    Reload,
+    PoolOdds(FilteredDice),
+    Odds(Term),
+    Narrative(NarrativePool),
+    SetAliasHints(bool),
+    UnknownAlias(String),
+    SetModifier(Option<i64>),
+    GetModifier,
+    SetListOrder(ListOrder),
+    GetListOrder,
+    SetRollShorthand(Option<String>),
+    GetRollShorthand,
+    /// announcement of a critical, `None` to stop announcing it
+    SetCriticalMessage(Critical, Option<String>),
+    GetCriticalMessage(Critical),
+    GenStats,
+    SetStatsRoll(Option<VersionedRollExpr>),
+    SetStatsMinTotal(Option<i64>),
+    GetStatsConfig,
+    ExportConfig,
+    /// configuration created by [Command::ExportConfig]
+    ImportConfig(String),
+    /// latest rolls of the user
+    RollHistory,
+    /// `true` forbids rolling the kind of dice, `false` allows it again
+    SetDiceBanned(DiceKind, bool),
+    /// most faces of a die, `None` for no limit
+    SetMaxFaces(Option<u32>),
+    GetDicePolicy,
+}
+
+impl Command {
+    /// name of the variant, to tell commands apart in logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::Help => "Help",
+            Command::RollHelp => "RollHelp",
+            Command::Info => "Info",
+            Command::SetCommandPrefix(..) => "SetCommandPrefix",
+            Command::GetCommandPrefix => "GetCommandPrefix",
+            Command::SetRollInfo(..) => "SetRollInfo",
+            Command::GetRollInfo => "GetRollInfo",
+            Command::SetShowLuck(..) => "SetShowLuck",
+            Command::GetShowLuck => "GetShowLuck",
+            Command::AddRollPrefix(..) => "AddRollPrefix",
+            Command::RemoveRollPrefix(..) => "RemoveRollPrefix",
+            Command::ClearRollPrefixes => "ClearRollPrefixes",
+            Command::SetChannelCommandPrefix(..) => "SetChannelCommandPrefix",
+            Command::AddChannelRollPrefix(..) => "AddChannelRollPrefix",
+            Command::RemoveChannelRollPrefix(..) => "RemoveChannelRollPrefix",
+            Command::ListRollPrefix => "ListRollPrefix",
+            Command::AddPrivilegedRole(..) => "AddPrivilegedRole",
+            Command::RemovePrivilegedRole(..) => "RemovePrivilegedRole",
+            Command::ListPrivilegedRoles => "ListPrivilegedRoles",
+            Command::AddAlias(..) => "AddAlias",
+            Command::RemoveAlias(..) => "RemoveAlias",
+            Command::ClearAliases => "ClearAliases",
+            Command::PromoteAlias(..) => "PromoteAlias",
+            Command::CopyAliasFromDm(..) => "CopyAliasFromDm",
+            Command::ListAliases(..) => "ListAliases",
+            Command::AliasRoll(..) => "AliasRoll",
+            Command::Roll(..) => "Roll",
+            Command::MultiRoll(..) => "MultiRoll",
+            Command::RollPrivate(..) => "RollPrivate",
+            Command::Reroll(..) => "Reroll",
+            Command::RepeatLast => "RepeatLast",
+            Command::Multiple(..) => "Multiple",
+            Command::TrailingOperator(..) => "TrailingOperator",
+            Command::InvalidAliasCall(..) => "InvalidAliasCall",
+            Command::Pause => "Pause",
+            Command::Resume => "Resume",
+            Command::Reload => "Reload",
+            Command::PoolOdds(..) => "PoolOdds",
+            Command::Odds(..) => "Odds",
+            Command::Narrative(..) => "Narrative",
+            Command::SetAliasHints(..) => "SetAliasHints",
+            Command::UnknownAlias(..) => "UnknownAlias",
+            Command::SetModifier(..) => "SetModifier",
+            Command::GetModifier => "GetModifier",
+            Command::SetListOrder(..) => "SetListOrder",
+            Command::GetListOrder => "GetListOrder",
+            Command::SetRollShorthand(..) => "SetRollShorthand",
+            Command::GetRollShorthand => "GetRollShorthand",
+            Command::SetCriticalMessage(..) => "SetCriticalMessage",
+            Command::GetCriticalMessage(..) => "GetCriticalMessage",
+            Command::GenStats => "GenStats",
+            Command::SetStatsRoll(..) => "SetStatsRoll",
+            Command::SetStatsMinTotal(..) => "SetStatsMinTotal",
+            Command::GetStatsConfig => "GetStatsConfig",
+            Command::ExportConfig => "ExportConfig",
+            Command::ImportConfig(..) => "ImportConfig",
+            Command::RollHistory => "RollHistory",
+            Command::SetDiceBanned(..) => "SetDiceBanned",
+            Command::SetMaxFaces(..) => "SetMaxFaces",
+            Command::GetDicePolicy => "GetDicePolicy",
+        }
+    }
+    /// the command rolls dice, those count towards the rate limit
+    pub(crate) fn rolls(&self) -> bool {
+        matches!(
+            self,
+            Command::Roll(_)
+                | Command::AliasRoll(_)
+                | Command::MultiRoll(_)
+                | Command::RollPrivate(_)
+                | Command::Reroll(_)
+                | Command::RepeatLast
+                | Command::Odds(_)
+                | Command::Narrative(_)
+                | Command::GenStats
+        )
+    }
+}
+
+/// most commands a single message may contain
+const MAX_COMMANDS: usize = 4;
+
+/// longest message still treated as a possible alias invocation
+const ALIAS_HINT_MAX_LEN: usize = 32;
+/// maximum number of suggested alias names
+const ALIAS_HINT_SUGGESTIONS: usize = 3;
+
+/// characters alias names and roll shorthands consist of
+fn is_name_char(c: char) -> bool {
+    !(c == '$' || c.is_separator() || c.is_other())
 }
 
 fn chars_set(input: &str) -> IResult<&str, char> {
-    satisfy(|c| !(c == '$' || c.is_separator() || c.is_other()))(input)
+    satisfy(is_name_char)(input)
+}
+
+/// alias names given in quotes may contain spaces, but neither start nor end with one
+fn valid_alias_name(name: &str) -> bool {
+    !name.is_empty() && name.trim() == name && !name.chars().any(|c| c == '$' || c.is_other())
+}
+
+/// whether `name` and `expr` could have been added with the alias command
+pub(crate) fn valid_alias(name: &str, expr: &VersionedRollExpr) -> bool {
+    valid_alias_name(name)
+        && match expr {
+            VersionedRollExpr::V1(e) => e.term().variables().is_empty(),
+            VersionedRollExpr::V2(e) => without_vars(e),
+            VersionedRollExpr::Template(t) => valid_template(t),
+        }
+}
+
+/// piece of an alias template
+#[derive(Debug, PartialEq, Eq)]
+enum TemplatePart<'a> {
+    Text(&'a str),
+    /// `$n`, replaced by the `n`th argument
+    Argument(usize),
+}
+
+fn template_parts(template: &str) -> Vec<TemplatePart<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        let digits = rest[i + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - i - 1);
+        match rest[i + 1..i + 1 + digits].parse() {
+            Ok(n) => {
+                parts.push(TemplatePart::Text(&rest[..i]));
+                parts.push(TemplatePart::Argument(n));
+                rest = &rest[i + 1 + digits..];
+            }
+            Err(_) => {
+                parts.push(TemplatePart::Text(&rest[..=i]));
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    parts.push(TemplatePart::Text(rest));
+    parts
+}
+
+/// number of arguments `template` takes, the highest `$n` it contains
+fn template_parameters(template: &str) -> usize {
+    template_parts(template)
+        .into_iter()
+        .filter_map(|part| match part {
+            TemplatePart::Argument(n) => Some(n),
+            TemplatePart::Text(_) => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// `template` with every `$n` replaced by the `n`th of `args`, which has to be long enough
+fn fill_template(template: &str, args: &[&str]) -> String {
+    template_parts(template)
+        .into_iter()
+        .map(|part| match part {
+            TemplatePart::Text(text) => text,
+            TemplatePart::Argument(n) => args[n - 1],
+        })
+        .collect()
+}
+
+/// roll statement `template` becomes once its arguments are filled in
+fn parse_filled_template(template: &str, args: &[&str]) -> Option<LabeledExpression> {
+    let text = fill_template(template, args);
+    let mut parser = terminated(parse_labeled, pair(multispace0, eof));
+    parser(&text).ok().map(|(_, expr)| expr)
+}
+
+/// whether `template` takes arguments, all of them used as `$1`..`$n`, and is a roll statement
+/// once they are filled in
+fn valid_template(template: &str) -> bool {
+    let parameters = template_parameters(template);
+    parameters > 0
+        && !template_parts(template).contains(&TemplatePart::Argument(0))
+        && parse_filled_template(template, &vec!["1"; parameters]).is_some()
+}
+
+/// the roll statement of `alias` called with `args`, templates get them filled in
+fn call_alias(
+    alias: &str,
+    expr: Arc<VersionedRollExpr>,
+    args: &[&str],
+) -> Result<Arc<VersionedRollExpr>, AliasCallError> {
+    let template = match expr.as_ref() {
+        VersionedRollExpr::Template(t) => t,
+        _ => return Ok(expr),
+    };
+    let expected = template_parameters(template);
+    if expected != args.len() {
+        return Err(AliasCallError::Arguments {
+            alias: alias.to_owned(),
+            expected,
+            given: args.len(),
+        });
+    }
+    match parse_filled_template(template, args) {
+        Some(expr) => Ok(Arc::new(VersionedRollExpr::V2(expr))),
+        None => Err(AliasCallError::Invalid {
+            alias: alias.to_owned(),
+            text: fill_template(template, args),
+        }),
+    }
+}
+
+/// text of a parameterized alias, reaching up to the next `;`
+fn parse_template(input: &str) -> IResult<&str, String> {
+    verify(
+        map(take_till1(|c| c == ';'), |t: &str| t.trim_end().to_owned()),
+        |t: &String| valid_template(t),
+    )(input)
+}
+
+/// text between double quotes, `\"` and `\\` stand for a quote and a backslash
+fn parse_quoted_string(input: &str) -> IResult<&str, String> {
+    let error = || nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Char));
+    let mut chars = input.strip_prefix('"').ok_or_else(error)?.chars();
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok((chars.as_str(), string)),
+            '\\' => match chars.clone().next() {
+                Some(escaped @ ('"' | '\\')) => {
+                    chars.next();
+                    string.push(escaped)
+                }
+                _ => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+    Err(error())
+}
+
+/// a prefix, in quotes if it contains spaces
+fn parse_prefix(input: &str) -> IResult<&str, String> {
+    alt((
+        verify(parse_quoted_string, |s: &str| {
+            !s.is_empty() && !s.chars().any(|c| c.is_other())
+        }),
+        map(recognize(many1(chars_set)), str::to_owned),
+    ))(input)
+}
+
+/// the name of an alias, in quotes if it contains spaces
+fn parse_alias_name(input: &str) -> IResult<&str, String> {
+    alt((
+        verify(parse_quoted_string, |s: &str| valid_alias_name(s)),
+        map(recognize(many1(chars_set)), str::to_owned),
+    ))(input)
+}
+
+/// alias name followed by its arguments, like `atk 5`
+fn parse_alias_call(input: &str) -> IResult<&str, (&str, Vec<&str>)> {
+    terminated(
+        pair(
+            recognize(many1(chars_set)),
+            many1(preceded(multispace1, recognize(many1(chars_set)))),
+        ),
+        pair(multispace0, eof),
+    )(input)
 }
 
 fn parse_help(input: &str) -> IResult<&str, Command> {
@@ -56,15 +384,30 @@ fn parse_command_prefix(input: &str) -> IResult<&str, Command> {
             multispace0,
         ),
         alt((
+            preceded(
+                pair(alt((tag_no_case("channel"), tag_no_case("c"))), multispace1),
+                alt((
+                    map(
+                        preceded(
+                            pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace0),
+                            parse_prefix,
+                        ),
+                        |s| Command::SetChannelCommandPrefix(Some(s)),
+                    ),
+                    map(alt((tag_no_case("clear"), tag_no_case("c"))), |_| {
+                        Command::SetChannelCommandPrefix(None)
+                    }),
+                )),
+            ),
             map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
                 Command::GetCommandPrefix
             }),
             map(
                 preceded(
                     pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace0),
-                    recognize(many1(chars_set)),
+                    parse_prefix,
                 ),
-                |s| Command::SetCommandPrefix(s.to_owned()),
+                Command::SetCommandPrefix,
             ),
         )),
     )(input)
@@ -82,12 +425,74 @@ fn parse_roll_help(input: &str) -> IResult<&str, Command> {
     )(input)
 }
 
+fn parse_roll_history(input: &str) -> IResult<&str, Command> {
+    map(
+        alt((
+            tag_no_case("roll-history"),
+            tag_no_case("roll_history"),
+            tag_no_case("roll history"),
+            tag_no_case("history"),
+        )),
+        |_| Command::RollHistory,
+    )(input)
+}
+
 fn parse_info(input: &str) -> IResult<&str, Command> {
     map(alt((tag_no_case("info"), tag_no_case("i"))), |_| {
         Command::Info
     })(input)
 }
 
+fn parse_pool_odds(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(
+            alt((
+                tag_no_case("pool-odds"),
+                tag_no_case("pool_odds"),
+                tag_no_case("pool odds"),
+                tag_no_case("po"),
+            )),
+            multispace0,
+        ),
+        map(parser::parse_filtered_dice, Command::PoolOdds),
+    )(input)
+}
+
+fn parse_odds(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(
+            alt((
+                tag_no_case("roll-odds"),
+                tag_no_case("roll_odds"),
+                tag_no_case("odds"),
+            )),
+            multispace0,
+        ),
+        map(parser::parse_term, Command::Odds),
+    )(input)
+}
+
+fn parse_narrative(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(
+            alt((tag_no_case("narrative"), tag_no_case("nr"))),
+            multispace0,
+        ),
+        map(parser::parse_narrative_pool, Command::Narrative),
+    )(input)
+}
+
+fn parse_pause(input: &str) -> IResult<&str, Command> {
+    alt((
+        map(tag_no_case("pause"), |_| Command::Pause),
+        map(tag_no_case("resume"), |_| Command::Resume),
+    ))(input)
+}
+
+fn parse_reload(input: &str) -> IResult<&str, Command> {
+    map(tag_no_case("reload"), |_| Command::Reload)(input)
+}
+
 fn parse_roll_prefix(input: &str) -> IResult<&str, Command> {
     preceded(
         terminated(
@@ -100,32 +505,290 @@ fn parse_roll_prefix(input: &str) -> IResult<&str, Command> {
             multispace0,
         ),
         alt((
+            preceded(
+                pair(alt((tag_no_case("channel"), tag_no_case("c"))), multispace1),
+                alt((
+                    map(
+                        preceded(
+                            pair(alt((tag_no_case("add"), tag_no_case("a"))), multispace0),
+                            parse_prefix,
+                        ),
+                        Command::AddChannelRollPrefix,
+                    ),
+                    map(
+                        preceded(
+                            pair(alt((tag_no_case("remove"), tag_no_case("r"))), multispace0),
+                            parse_prefix,
+                        ),
+                        Command::RemoveChannelRollPrefix,
+                    ),
+                )),
+            ),
             map(alt((tag_no_case("list"), tag_no_case("l"))), |_| {
                 Command::ListRollPrefix
             }),
+            map(tag_no_case("clear"), |_| Command::ClearRollPrefixes),
+            map(
+                preceded(
+                    pair(alt((tag_no_case("add"), tag_no_case("a"))), multispace0),
+                    alt((parse_prefix, map(success(""), str::to_owned))),
+                ),
+                Command::AddRollPrefix,
+            ),
+            map(
+                preceded(
+                    pair(alt((tag_no_case("remove"), tag_no_case("r"))), multispace0),
+                    alt((parse_prefix, map(success(""), str::to_owned))),
+                ),
+                Command::RemoveRollPrefix,
+            ),
+        )),
+    )(input)
+}
+
+/// id of a role, either plain or as a `<@&id>` mention
+fn parse_role(input: &str) -> IResult<&str, String> {
+    map(
+        alt((delimited(tag("<@&"), digit1, tag(">")), digit1)),
+        |id: &str| id.to_owned(),
+    )(input)
+}
+
+fn parse_privileged_roles(input: &str) -> IResult<&str, Command> {
+    preceded(
+        terminated(
+            alt((
+                tag_no_case("privileged-roles"),
+                tag_no_case("privileged_roles"),
+                tag_no_case("privileged roles"),
+                tag_no_case("pr"),
+            )),
+            multispace0,
+        ),
+        alt((
+            map(alt((tag_no_case("list"), tag_no_case("l"))), |_| {
+                Command::ListPrivilegedRoles
+            }),
             map(
                 preceded(
                     pair(alt((tag_no_case("add"), tag_no_case("a"))), multispace0),
-                    recognize(many0(chars_set)),
+                    parse_role,
                 ),
-                |s| Command::AddRollPrefix(s.to_owned()),
+                Command::AddPrivilegedRole,
             ),
             map(
                 preceded(
                     pair(alt((tag_no_case("remove"), tag_no_case("r"))), multispace0),
-                    recognize(many0(chars_set)),
+                    parse_role,
                 ),
-                |s| Command::RemoveRollPrefix(s.to_owned()),
+                Command::RemovePrivilegedRole,
             ),
         )),
     )(input)
 }
 
+fn parse_genstats(input: &str) -> IResult<&str, Command> {
+    preceded(
+        alt((
+            tag_no_case("genstats"),
+            tag_no_case("gen-stats"),
+            tag_no_case("gen_stats"),
+            tag_no_case("gs"),
+        )),
+        alt((
+            preceded(
+                multispace1,
+                alt((
+                    map(
+                        preceded(
+                            pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace1),
+                            // one result for each of the six ability scores
+                            verify(parser::parse_expression, |e| {
+                                matches!(e, Expression::List(6, _))
+                                    && e.term().variables().is_empty()
+                            }),
+                        ),
+                        |e| {
+                            Command::SetStatsRoll(Some(VersionedRollExpr::V2(
+                                LabeledExpression::Unlabeled(e),
+                            )))
+                        },
+                    ),
+                    map(tag_no_case("reset"), |_| Command::SetStatsRoll(None)),
+                    preceded(
+                        pair(alt((tag_no_case("min"), tag_no_case("m"))), multispace1),
+                        alt((
+                            map(parser::parse_i64, |m| Command::SetStatsMinTotal(Some(m))),
+                            map(alt((tag_no_case("clear"), tag_no_case("off"))), |_| {
+                                Command::SetStatsMinTotal(None)
+                            }),
+                        )),
+                    ),
+                    map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
+                        Command::GetStatsConfig
+                    }),
+                )),
+            ),
+            success(Command::GenStats),
+        )),
+    )(input)
+}
+
+fn parse_roll_shorthand_command(input: &str) -> IResult<&str, Command> {
+    preceded(
+        terminated(
+            alt((
+                tag_no_case("roll-shorthand"),
+                tag_no_case("roll_shorthand"),
+                tag_no_case("roll shorthand"),
+                tag_no_case("rs"),
+            )),
+            multispace0,
+        ),
+        alt((
+            map(
+                preceded(
+                    pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace1),
+                    recognize(many1(chars_set)),
+                ),
+                |s: &str| Command::SetRollShorthand(Some(s.to_owned())),
+            ),
+            map(
+                alt((tag_no_case("clear"), tag_no_case("c"), tag_no_case("off"))),
+                |_| Command::SetRollShorthand(None),
+            ),
+            map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
+                Command::GetRollShorthand
+            }),
+        )),
+    )(input)
+}
+
+/// `config export`, or `config import` followed by the exported configuration
+///
+/// the configuration reaches up to the end of the message and may be wrapped in a code block
+fn parse_config(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(tag_no_case("config"), multispace1),
+        alt((
+            map(tag_no_case("export"), |_| Command::ExportConfig),
+            map(
+                preceded(pair(tag_no_case("import"), multispace0), rest),
+                |blob: &str| {
+                    let blob = blob.trim();
+                    let blob = blob
+                        .strip_prefix("```json")
+                        .or_else(|| blob.strip_prefix("```"))
+                        .and_then(|b| b.strip_suffix("```"))
+                        .unwrap_or(blob);
+                    Command::ImportConfig(blob.trim().to_owned())
+                },
+            ),
+        )),
+    )(input)
+}
+
+/// `critical` or `fumble`, followed by `set [message]`, `off` or nothing to show the message
+///
+/// the message reaches up to the next `;`, which separates commands
+fn parse_critical_message(input: &str) -> IResult<&str, Command> {
+    let (input, critical) = terminated(
+        alt((
+            map(alt((tag_no_case("critical"), tag_no_case("crit"))), |_| {
+                Critical::Hit
+            }),
+            map(tag_no_case("fumble"), |_| Critical::Fumble),
+        )),
+        multispace0,
+    )(input)?;
+    alt((
+        map(
+            preceded(
+                pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace1),
+                take_till1(|c| c == ';'),
+            ),
+            move |s: &str| Command::SetCriticalMessage(critical, Some(s.trim_end().to_owned())),
+        ),
+        map(alt((tag_no_case("clear"), tag_no_case("off"))), move |_| {
+            Command::SetCriticalMessage(critical, None)
+        }),
+        success(Command::GetCriticalMessage(critical)),
+    ))(input)
+}
+
+/// there are no values for variables, so expressions with names aren't rolls,
+/// they are more likely chat like `r ok` or the next command
+fn without_vars(expression: &LabeledExpression) -> bool {
+    match expression {
+        LabeledExpression::Unlabeled(e) | LabeledExpression::Labeled(e, _) => {
+            e.term().variables().is_empty()
+        }
+    }
+}
+
+fn parse_labeled(input: &str) -> IResult<&str, LabeledExpression> {
+    verify(parser::parse_labeled, without_vars)(input)
+}
+
+/// expressions to roll separated by `;`
+fn parse_rolls(input: &str) -> IResult<&str, Vec<LabeledExpression>> {
+    separated_list1(
+        delimited(multispace0, tag(";"), multispace0),
+        verify(parser::parse_multi_entry, without_vars),
+    )(input)
+}
+
+/// a single roll, or a multi roll if `;` separated several expressions
+fn roll_command(mut expressions: Vec<LabeledExpression>) -> Command {
+    if expressions.len() == 1 {
+        Command::Roll(VersionedRollExpr::V2(expressions.pop().unwrap()))
+    } else {
+        Command::MultiRoll(expressions.into_iter().map(VersionedRollExpr::V2).collect())
+    }
+}
+
+/// roll statements that only lack the term after their last operator
+fn parse_trailing_operator(input: &str) -> IResult<&str, Command> {
+    map(
+        recognize(terminated(parse_rolls, parser::parse_trailing_operator)),
+        |text: &str| Command::TrailingOperator(text.trim_end().to_string()),
+    )(input)
+}
+
 fn parse_roll_command(input: &str) -> IResult<&str, Command> {
     preceded(
         pair(alt((tag_no_case("roll"), tag_no_case("r"))), multispace0),
-        map(parser::parse_labeled, |e| {
-            Command::Roll(VersionedRollExpr::V2(e))
+        map(parse_rolls, roll_command),
+    )(input)
+}
+
+/// `!` or `roll .`
+fn parse_repeat_last(input: &str) -> IResult<&str, Command> {
+    map(
+        alt((
+            tag("!"),
+            preceded(
+                pair(alt((tag_no_case("roll"), tag_no_case("r"))), multispace0),
+                tag("."),
+            ),
+        )),
+        |_| Command::RepeatLast,
+    )(input)
+}
+
+fn parse_private_roll(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(
+            alt((
+                tag_no_case("gmroll"),
+                tag_no_case("gm-roll"),
+                tag_no_case("gm_roll"),
+                tag_no_case("whisper"),
+            )),
+            multispace0,
+        ),
+        map(verify(parser::parse_multi_entry, without_vars), |expr| {
+            Command::RollPrivate(VersionedRollExpr::V2(expr))
         }),
     )(input)
 }
@@ -136,29 +799,80 @@ fn parse_alias(input: &str) -> IResult<&str, Command> {
         alt((
             preceded(
                 pair(alt((tag_no_case("add"), tag_no_case("a"))), multispace0),
-                map(
-                    pair(
-                        terminated(recognize(many1(chars_set)), multispace1),
-                        parser::parse_labeled,
+                alt((
+                    map(
+                        pair(terminated(parse_alias_name, multispace1), parse_template),
+                        |(alias, template)| {
+                            Command::AddAlias(alias, VersionedRollExpr::Template(template))
+                        },
                     ),
-                    |(alias, expr)| {
-                        Command::AddAlias(alias.to_owned(), VersionedRollExpr::V2(expr))
-                    },
-                ),
+                    map(
+                        pair(terminated(parse_alias_name, multispace1), parse_labeled),
+                        |(alias, expr)| Command::AddAlias(alias, VersionedRollExpr::V2(expr)),
+                    ),
+                )),
             ),
             preceded(
                 pair(alt((tag_no_case("remove"), tag_no_case("r"))), multispace0),
-                map(recognize(many1(chars_set)), |alias| {
-                    Command::RemoveAlias(alias.to_owned())
-                }),
+                map(parse_alias_name, Command::RemoveAlias),
+            ),
+            map(
+                preceded(
+                    alt((tag_no_case("list"), tag_no_case("l"))),
+                    alt((
+                        map(preceded(multispace1, parser::parse_u32), Some),
+                        success(None),
+                    )),
+                ),
+                Command::ListAliases,
+            ),
+            map(tag_no_case("clear"), |_| Command::ClearAliases),
+            preceded(
+                pair(tag_no_case("promote"), multispace1),
+                map(parse_alias_name, Command::PromoteAlias),
+            ),
+            preceded(
+                pair(
+                    alt((
+                        tag_no_case("copy-from-dm"),
+                        tag_no_case("copy_from_dm"),
+                        tag_no_case("copy from dm"),
+                    )),
+                    multispace1,
+                ),
+                map(parse_alias_name, Command::CopyAliasFromDm),
+            ),
+            map(
+                preceded(pair(tag_no_case("hints"), multispace0), parse_bool),
+                Command::SetAliasHints,
             ),
-            map(alt((tag_no_case("list"), tag_no_case("l"))), |_| {
-                Command::ListAliases
-            }),
         )),
     )(input)
 }
 
+fn parse_bool(input: &str) -> IResult<&str, bool> {
+    alt((
+        map(
+            alt((
+                tag_no_case("true"),
+                tag_no_case("t"),
+                tag_no_case("on"),
+                tag("1"),
+            )),
+            |_| true,
+        ),
+        map(
+            alt((
+                tag_no_case("false"),
+                tag_no_case("f"),
+                tag_no_case("off"),
+                tag_no_case("0"),
+            )),
+            |_| false,
+        ),
+    ))(input)
+}
+
 fn parse_roll_info(input: &str) -> IResult<&str, Command> {
     preceded(
         pair(
@@ -177,16 +891,7 @@ fn parse_roll_info(input: &str) -> IResult<&str, Command> {
             map(
                 preceded(
                     pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace0),
-                    alt((
-                        map(
-                            alt((tag_no_case("true"), tag_no_case("t"), tag("1"))),
-                            |_| true,
-                        ),
-                        map(
-                            alt((tag_no_case("false"), tag_no_case("f"), tag_no_case("0"))),
-                            |_| false,
-                        ),
-                    )),
+                    parse_bool,
                 ),
                 Command::SetRollInfo,
             ),
@@ -194,40 +899,242 @@ fn parse_roll_info(input: &str) -> IResult<&str, Command> {
     )(input)
 }
 
-fn parse_command<'a>(input: &'a str, prefix: &str) -> IResult<&'a str, Command> {
+fn parse_luck(input: &str) -> IResult<&str, Command> {
     preceded(
-        tag(prefix),
+        pair(tag_no_case("luck"), multispace0),
         alt((
-            delimited(
-                multispace0,
-                alt((
-                    parse_help,
-                    parse_roll_help,
-                    parse_info,
-                    parse_command_prefix,
-                    parse_roll_prefix,
-                    parse_alias,
-                    parse_roll_info,
-                    parse_roll_command,
-                )),
-                pair(multispace0, eof),
-            ),
-            success(Command::Help),
+            map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
+                Command::GetShowLuck
+            }),
+            map(
+                preceded(
+                    pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace0),
+                    parse_bool,
+                ),
+                Command::SetShowLuck,
+            ),
         )),
     )(input)
 }
 
-fn parse_roll<'a>(input: &'a str, prefix: &str) -> IResult<&'a str, Command> {
+fn parse_modifier(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(
+            alt((
+                tag_no_case("modifier"),
+                tag_no_case("mod"),
+                tag_no_case("m"),
+            )),
+            multispace0,
+        ),
+        alt((
+            map(
+                preceded(
+                    pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace0),
+                    parser::parse_i64,
+                ),
+                |m| Command::SetModifier(Some(m)),
+            ),
+            map(alt((tag_no_case("clear"), tag_no_case("c"))), |_| {
+                Command::SetModifier(None)
+            }),
+            map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
+                Command::GetModifier
+            }),
+        )),
+    )(input)
+}
+
+fn parse_list_order(input: &str) -> IResult<&str, Command> {
+    preceded(
+        pair(
+            alt((
+                tag_no_case("list-order"),
+                tag_no_case("list_order"),
+                tag_no_case("list order"),
+                tag_no_case("lo"),
+            )),
+            multispace0,
+        ),
+        alt((
+            map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
+                Command::GetListOrder
+            }),
+            map(
+                preceded(
+                    pair(alt((tag_no_case("set"), tag_no_case("s"))), multispace0),
+                    alt((
+                        map(
+                            alt((
+                                tag_no_case("ascending"),
+                                tag_no_case("asc"),
+                                tag_no_case("a"),
+                            )),
+                            |_| ListOrder::Ascending,
+                        ),
+                        map(
+                            alt((
+                                tag_no_case("descending"),
+                                tag_no_case("desc"),
+                                tag_no_case("d"),
+                            )),
+                            |_| ListOrder::Descending,
+                        ),
+                        map(
+                            alt((
+                                tag_no_case("unsorted"),
+                                tag_no_case("none"),
+                                tag_no_case("u"),
+                                tag_no_case("n"),
+                            )),
+                            |_| ListOrder::Unsorted,
+                        ),
+                    )),
+                ),
+                Command::SetListOrder,
+            ),
+        )),
+    )(input)
+}
+
+/// `dice` followed by `ban <kind>`, `allow <kind>`, `max-faces <n|off>` or `get`
+fn parse_dice_policy(input: &str) -> IResult<&str, Command> {
+    let kind = |input| {
+        alt((
+            map(alt((tag_no_case("multiply"), tag_no_case("x"))), |_| {
+                DiceKind::Multiply
+            }),
+            map(alt((tag_no_case("fudge"), tag_no_case("f"))), |_| {
+                DiceKind::Fudge
+            }),
+            map(alt((tag_no_case("dynamic"), tag_no_case("d"))), |_| {
+                DiceKind::Dynamic
+            }),
+        ))(input)
+    };
+    preceded(
+        pair(tag_no_case("dice"), multispace1),
+        alt((
+            map(preceded(pair(tag_no_case("ban"), multispace1), kind), |k| {
+                Command::SetDiceBanned(k, true)
+            }),
+            map(
+                preceded(pair(tag_no_case("allow"), multispace1), kind),
+                |k| Command::SetDiceBanned(k, false),
+            ),
+            preceded(
+                pair(
+                    alt((
+                        tag_no_case("max-faces"),
+                        tag_no_case("max_faces"),
+                        tag_no_case("max faces"),
+                    )),
+                    multispace1,
+                ),
+                alt((
+                    map(parser::parse_u32, |n| Command::SetMaxFaces(Some(n))),
+                    map(alt((tag_no_case("clear"), tag_no_case("off"))), |_| {
+                        Command::SetMaxFaces(None)
+                    }),
+                )),
+            ),
+            map(alt((tag_no_case("get"), tag_no_case("g"))), |_| {
+                Command::GetDicePolicy
+            }),
+        )),
+    )(input)
+}
+
+fn parse_single_command(input: &str) -> IResult<&str, Command> {
+    alt((
+        // `history` would otherwise be taken for `h`
+        alt((parse_roll_history, parse_help)),
+        parse_roll_help,
+        parse_info,
+        parse_command_prefix,
+        parse_roll_prefix,
+        parse_privileged_roles,
+        parse_alias,
+        parse_roll_info,
+        parse_luck,
+        parse_pause,
+        parse_reload,
+        parse_pool_odds,
+        parse_odds,
+        parse_narrative,
+        parse_modifier,
+        parse_list_order,
+        parse_roll_shorthand_command,
+        parse_critical_message,
+        parse_config,
+        parse_genstats,
+        // nom can't choose between more than 21 parsers at once
+        alt((parse_dice_policy, parse_roll_command, parse_repeat_last)),
+    ))(input)
+}
+
+/// up to [MAX_COMMANDS] commands separated by `;`
+fn parse_commands(input: &str) -> IResult<&str, Command> {
     map(
-        delimited(
-            pair(tag(prefix), multispace0),
-            parser::parse_labeled,
-            pair(multispace0, eof),
+        verify(
+            separated_list1(
+                delimited(multispace0, tag(";"), multispace0),
+                parse_single_command,
+            ),
+            |commands: &Vec<Command>| commands.len() <= MAX_COMMANDS,
         ),
-        |e| Command::Roll(VersionedRollExpr::V2(e)),
+        |mut commands| {
+            if commands.len() == 1 {
+                commands.pop().unwrap()
+            } else {
+                Command::Multiple(commands)
+            }
+        },
     )(input)
 }
 
+fn parse_command<'a>(input: &'a str, prefix: &str) -> IResult<&'a str, Command> {
+    preceded(tag(prefix), parse_command_body)(input)
+}
+
+/// everything after the command prefix
+fn parse_command_body(input: &str) -> IResult<&str, Command> {
+    alt((
+        // the result is sent elsewhere, it can't be combined with other commands
+        delimited(multispace0, parse_private_roll, pair(multispace0, eof)),
+        delimited(multispace0, parse_commands, pair(multispace0, eof)),
+        preceded(
+            tuple((
+                multispace0,
+                alt((tag_no_case("roll"), tag_no_case("r"))),
+                multispace0,
+            )),
+            parse_trailing_operator,
+        ),
+        success(Command::Help),
+    ))(input)
+}
+
+/// all of `input` as roll statements
+fn parse_complete_rolls(input: &str) -> IResult<&str, Command> {
+    alt((
+        map(
+            terminated(parse_rolls, pair(multispace0, eof)),
+            roll_command,
+        ),
+        parse_trailing_operator,
+    ))(input)
+}
+
+fn parse_roll<'a>(input: &'a str, prefix: &str) -> IResult<&'a str, Command> {
+    preceded(pair(tag(prefix), multispace0), parse_complete_rolls)(input)
+}
+
+/// roll started by `shorthand` followed by whitespace, without any prefix
+fn parse_roll_shorthand<'a>(input: &'a str, shorthand: &str) -> IResult<&'a str, Command> {
+    preceded(pair(tag(shorthand), multispace1), parse_complete_rolls)(input)
+}
+
 fn parse_extra_aliases(input: &str) -> IResult<&str, Vec<String>> {
     many0(map(
         preceded(
@@ -238,52 +1145,176 @@ fn parse_extra_aliases(input: &str) -> IResult<&str, Vec<String>> {
     ))(input)
 }
 
+/// short single token, that could have been meant as an alias
+fn is_alias_like(input: &str) -> bool {
+    input.chars().count() <= ALIAS_HINT_MAX_LEN
+        && matches!(recognize(many1(chars_set))(input), Ok(("", _)))
+}
+
+/// levenshtein distance between `a` and `b`, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// alias names close enough to `name` to be suggested, best match first
+pub fn suggest_aliases<I: IntoIterator<Item = String>>(name: &str, aliases: I) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut candidates: Vec<(usize, String)> = aliases
+        .into_iter()
+        .map(|alias| (edit_distance(name, &alias), alias))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(ALIAS_HINT_SUGGESTIONS)
+        .map(|(_, alias)| alias)
+        .collect()
+}
+
 pub async fn parse<Id: ClientId>(
     string: &str,
     id: Id,
+    user: &str,
+    channel: Option<&str>,
     store: &StorageHandle<Id>,
-) -> Option<(Command, String, bool)> {
-    let storage_lookup = store
-        .get(id.clone(), {
-            let mut parsed = parse_extra_aliases(string)
-                .map(|a| a.1)
-                .unwrap_or_else(|_| Vec::new());
-            parsed.push(string.to_string());
-            parsed
-        })
+) -> Option<(Command, Lookup)> {
+    let mut storage_lookup = store
+        .get(
+            id.clone(),
+            Some(user.to_string()),
+            channel.map(|c| c.to_owned()),
+            {
+                let mut parsed = parse_extra_aliases(string)
+                    .map(|a| a.1)
+                    .unwrap_or_else(|_| Vec::new());
+                parsed.push(string.to_string());
+                if let Ok((_, (alias, _))) = parse_alias_call(string) {
+                    parsed.push(alias.to_string());
+                }
+                parsed
+            },
+        )
         .await;
-    let prefix = storage_lookup.0;
-    let roll_info = storage_lookup.3;
-    if let Ok((_, c)) = parse_command(string, &prefix) {
+    parse_lookup(string, &mut storage_lookup).map(|c| (c, storage_lookup))
+}
+
+/// interprets `string` with the client configuration in `storage_lookup`
+///
+/// matched aliases are moved out of `storage_lookup`
+pub fn parse_lookup(string: &str, storage_lookup: &mut Lookup) -> Option<Command> {
+    if let Ok((_, c)) = parse_command(string, &storage_lookup.command_prefix) {
         Some(c)
     } else if let Some(command) = storage_lookup
-        .1
+        .roll_prefixes
         .iter()
         .map(|prefix| parse_roll(string, prefix))
         .find_map(|r| r.ok().map(|res| res.1))
     {
         Some(command)
-    } else if !storage_lookup.2.is_empty() {
-        Some(Command::AliasRoll(storage_lookup.2))
+    } else if let Some(command) = storage_lookup
+        .roll_shorthand
+        .as_deref()
+        .and_then(|shorthand| parse_roll_shorthand(string, shorthand).ok())
+    {
+        Some(command.1)
+    } else if let Some(command) = alias_roll(string, std::mem::take(&mut storage_lookup.aliases)) {
+        Some(command)
+    } else if storage_lookup.alias_hints && is_alias_like(string) {
+        Some(Command::UnknownAlias(string.to_string()))
     } else {
         None
     }
-    .map(|c| (c, prefix, roll_info))
+}
+
+/// rolls the `aliases` found in `string`
+///
+/// only parameterized aliases can be called with arguments like `atk 5`, other messages
+/// starting with an alias are just chat
+fn alias_roll(string: &str, aliases: Vec<(String, Arc<VersionedRollExpr>)>) -> Option<Command> {
+    let called = match parse_alias_call(string) {
+        Ok((_, (name, args))) => aliases
+            .into_iter()
+            .find(|(alias, expr)| {
+                alias == name && matches!(expr.as_ref(), VersionedRollExpr::Template(_))
+            })
+            .map(|(alias, expr)| call_alias(&alias, expr, &args).map(|expr| vec![expr])),
+        Err(_) if aliases.is_empty() => None,
+        Err(_) => Some(
+            aliases
+                .into_iter()
+                .map(|(alias, expr)| call_alias(&alias, expr, &[]))
+                .collect(),
+        ),
+    };
+    called.map(|result| match result {
+        Ok(expressions) => Command::AliasRoll(expressions),
+        Err(err) => Command::InvalidAliasCall(err),
+    })
+}
+
+/// interprets `string` as if it followed the command prefix, for commands the client
+/// already knows to be meant for the bot
+pub async fn parse_unprefixed<Id: ClientId>(
+    string: &str,
+    id: Id,
+    user: &str,
+    channel: Option<&str>,
+    store: &StorageHandle<Id>,
+) -> (Command, Lookup) {
+    let lookup = store
+        .get(
+            id.clone(),
+            Some(user.to_string()),
+            channel.map(|c| c.to_owned()),
+            parse_extra_aliases(string)
+                .map(|a| a.1)
+                .unwrap_or_else(|_| Vec::new()),
+        )
+        .await;
+    let command = parse_command_body(string)
+        .map(|c| c.1)
+        .unwrap_or(Command::Help);
+    log::info!(client:? = id, command = command.kind(); "command parsed");
+    log::debug!("{:?}", &command);
+    (command, lookup)
 }
 
 pub async fn parse_logging<Id: ClientId>(
     string: &str,
     id: Id,
+    user: &str,
+    channel: Option<&str>,
     store: &StorageHandle<Id>,
-) -> Option<(Command, String, bool)> {
-    let command = parse(string, id, store).await;
-    log::info!("{:?}", &command);
+) -> Option<(Command, Lookup)> {
+    let command = parse(string, id.clone(), user, channel, store).await;
+    if let Some((command, _)) = &command {
+        log::info!(client:? = id, command = command.kind(); "command parsed");
+    }
+    log::debug!("{:?}", &command);
     command
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client_utils::DicePolicy;
     use robins_dice_roll::dice_types::*;
 
     #[test]
@@ -314,7 +1345,10 @@ mod tests {
                     Expression::Simple(Term::DiceThrow(SelectedDice::Unchanged(
                         FilteredDice::Simple(Dice {
                             throws: 1,
-                            dice: DiceType::Number(4)
+                            dice: DiceType::Number(4),
+                            explode: None,
+                            dynamic_throws: None,
+                            reroll: None
                         })
                     ))),
                     "label".to_string()
@@ -323,6 +1357,711 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_quoted() {
+        assert_eq!(
+            parse_quoted_string(r#""roll " 1"#),
+            Ok((" 1", "roll ".to_string()))
+        );
+        assert_eq!(
+            parse_quoted_string(r#""say \"hi\" \\ \n""#),
+            Ok(("", r#"say "hi" \ \n"#.to_string()))
+        );
+        assert!(parse_quoted_string(r#""open"#).is_err());
+        assert_eq!(
+            parse_command(r#"!cp set "roll ""#, "!"),
+            Ok(("", Command::SetCommandPrefix("roll ".to_string())))
+        );
+        assert_eq!(
+            parse_command(r#"!cp c s "dice: ""#, "!"),
+            Ok((
+                "",
+                Command::SetChannelCommandPrefix(Some("dice: ".to_string()))
+            ))
+        );
+        assert_eq!(
+            parse_command(r#"!rp add "/r ""#, "!"),
+            Ok(("", Command::AddRollPrefix("/r ".to_string())))
+        );
+        // an unclosed quote is an ordinary character
+        assert_eq!(
+            parse_command(r#"!cp set "!"#, "!"),
+            Ok(("", Command::SetCommandPrefix("\"!".to_string())))
+        );
+
+        assert_eq!(
+            parse_command(r#"!alias add "sneak attack" 3d6"#, "!"),
+            Ok((
+                "",
+                Command::AddAlias(
+                    "sneak attack".to_string(),
+                    VersionedRollExpr::V2(parser::parse_labeled("3d6").unwrap().1)
+                )
+            ))
+        );
+        assert_eq!(
+            parse_command(r#"!alias remove "sneak attack""#, "!"),
+            Ok(("", Command::RemoveAlias("sneak attack".to_string())))
+        );
+        assert_eq!(
+            parse_command(r#"!alias promote "atk""#, "!"),
+            Ok(("", Command::PromoteAlias("atk".to_string())))
+        );
+        assert_eq!(
+            parse_command(r#"!alias add " atk" 1d20"#, "!"),
+            Ok((r#"alias add " atk" 1d20"#, Command::Help))
+        );
+        assert_eq!(
+            parse_command(r#"!alias add "$1" 1d20"#, "!"),
+            Ok((r#"alias add "$1" 1d20"#, Command::Help))
+        );
+    }
+
+    #[test]
+    fn test_parse_multi_roll() {
+        let constant = |n| {
+            VersionedRollExpr::V2(LabeledExpression::Unlabeled(Expression::Simple(
+                Term::Constant(n),
+            )))
+        };
+        assert_eq!(
+            parse_command("!r 1 ; 2;3", "!"),
+            Ok((
+                "",
+                Command::MultiRoll(vec![constant(1), constant(2), constant(3)])
+            ))
+        );
+        assert_eq!(
+            parse_roll("/1; 2", "/"),
+            Ok(("", Command::MultiRoll(vec![constant(1), constant(2)])))
+        );
+        assert_eq!(
+            parse_roll_shorthand("r 1#init; 2", "r"),
+            Ok((
+                "",
+                Command::MultiRoll(vec![
+                    VersionedRollExpr::V2(LabeledExpression::Labeled(
+                        Expression::Simple(Term::Constant(1)),
+                        "init".to_string()
+                    )),
+                    constant(2)
+                ])
+            ))
+        );
+        assert_eq!(parse_roll("/1", "/"), Ok(("", Command::Roll(constant(1)))));
+        assert!(parse_roll("/1;", "/").is_err());
+    }
+
+    #[test]
+    fn test_parse_multiple_commands() {
+        let roll = |n| {
+            Command::Roll(VersionedRollExpr::V2(LabeledExpression::Unlabeled(
+                Expression::Simple(Term::Constant(n)),
+            )))
+        };
+        assert_eq!(
+            parse_command("!cp get ; roll 1", "!"),
+            Ok((
+                "",
+                Command::Multiple(vec![Command::GetCommandPrefix, roll(1)])
+            ))
+        );
+        assert_eq!(
+            parse_command("!r 1; cp g;r 2 ; 3", "!"),
+            Ok((
+                "",
+                Command::Multiple(vec![
+                    roll(1),
+                    Command::GetCommandPrefix,
+                    Command::MultiRoll(vec![
+                        VersionedRollExpr::V2(LabeledExpression::Unlabeled(Expression::Simple(
+                            Term::Constant(2)
+                        ))),
+                        VersionedRollExpr::V2(LabeledExpression::Unlabeled(Expression::Simple(
+                            Term::Constant(3)
+                        )))
+                    ])
+                ])
+            ))
+        );
+        assert_eq!(
+            parse_command(&format!("!{}", ["cp g"; MAX_COMMANDS + 1].join(";")), "!").map(|c| c.1),
+            Ok(Command::Help)
+        );
+        assert_eq!(
+            parse_command("!cp g; nonsense", "!").map(|c| c.1),
+            Ok(Command::Help)
+        );
+    }
+
+    #[test]
+    fn test_parse_alias_list() {
+        assert_eq!(
+            parse_command("!alias list", "!"),
+            Ok(("", Command::ListAliases(None)))
+        );
+        assert_eq!(
+            parse_command("!a l 3", "!"),
+            Ok(("", Command::ListAliases(Some(3))))
+        );
+        assert_eq!(
+            parse_command("!alias list 0", "!"),
+            Ok(("alias list 0", Command::Help))
+        );
+    }
+
+    #[test]
+    fn test_parse_clear() {
+        assert_eq!(
+            parse_command("!rp clear", "!"),
+            Ok(("", Command::ClearRollPrefixes))
+        );
+        assert_eq!(
+            parse_command("!alias clear", "!"),
+            Ok(("", Command::ClearAliases))
+        );
+        // a channel roll prefix is still added with the short form
+        assert_eq!(
+            parse_command("!rp c a clear", "!"),
+            Ok(("", Command::AddChannelRollPrefix("clear".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_parse_alias_hints() {
+        assert_eq!(
+            parse_command("!alias hints on", "!"),
+            Ok(("", Command::SetAliasHints(true)))
+        );
+        assert_eq!(
+            parse_command("!a hints f", "!"),
+            Ok(("", Command::SetAliasHints(false)))
+        );
+    }
+
+    #[test]
+    fn test_parse_alias_scopes() {
+        assert_eq!(
+            parse_command("!alias promote fireball", "!"),
+            Ok(("", Command::PromoteAlias("fireball".to_string())))
+        );
+        assert_eq!(
+            parse_command("!a copy-from-dm fireball", "!"),
+            Ok(("", Command::CopyAliasFromDm("fireball".to_string())))
+        );
+        assert!(parse_alias("alias promote").is_err());
+    }
+
+    #[test]
+    fn test_alias_templates() {
+        assert_eq!(template_parameters("1d20 + $1 + $2"), 2);
+        assert_eq!(template_parameters("$2d$1"), 2);
+        assert_eq!(
+            fill_template("$2d$1 + $10", &["6", "3", "", "", "", "", "", "", "", "4"]),
+            "3d6 + 4"
+        );
+        assert!(valid_template("1d20+$1"));
+        assert!(!valid_template("1d20"));
+        assert!(!valid_template("1d20 + $0"));
+        assert!(!valid_template("1d20 + + $1"));
+        assert_eq!(
+            parse_command("!alias add atk 1d20+$1", "!"),
+            Ok((
+                "",
+                Command::AddAlias(
+                    "atk".to_string(),
+                    VersionedRollExpr::Template("1d20+$1".to_string())
+                )
+            ))
+        );
+
+        let atk = || {
+            vec![(
+                "atk".to_string(),
+                Arc::new(VersionedRollExpr::Template("1d20+$1".to_string())),
+            )]
+        };
+        assert_eq!(
+            alias_roll("atk 5", atk()),
+            Some(Command::AliasRoll(vec![Arc::new(VersionedRollExpr::V2(
+                parser::parse_labeled("1d20+5").unwrap().1
+            ))]))
+        );
+        assert_eq!(
+            alias_roll("atk", atk()),
+            Some(Command::InvalidAliasCall(AliasCallError::Arguments {
+                alias: "atk".to_string(),
+                expected: 1,
+                given: 0
+            }))
+        );
+        assert_eq!(
+            alias_roll("atk 5 2", atk()),
+            Some(Command::InvalidAliasCall(AliasCallError::Arguments {
+                alias: "atk".to_string(),
+                expected: 1,
+                given: 2
+            }))
+        );
+        assert_eq!(
+            alias_roll("atk five", atk()),
+            Some(Command::InvalidAliasCall(AliasCallError::Invalid {
+                alias: "atk".to_string(),
+                text: "1d20+five".to_string()
+            }))
+        );
+        // plain aliases don't take arguments
+        let plain = Arc::new(VersionedRollExpr::V2(
+            parser::parse_labeled("1d6").unwrap().1,
+        ));
+        assert_eq!(
+            alias_roll("hello there", vec![("hello".to_string(), plain.clone())]),
+            None
+        );
+        assert_eq!(
+            alias_roll("hello", vec![("hello".to_string(), plain.clone())]),
+            Some(Command::AliasRoll(vec![plain]))
+        );
+    }
+
+    #[test]
+    fn test_alias_suggestions() {
+        assert!(is_alias_like("fireball"));
+        assert!(!is_alias_like("fire ball"));
+        assert!(!is_alias_like(&"a".repeat(ALIAS_HINT_MAX_LEN + 1)));
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("Attack", "attack"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        let aliases = vec![
+            "fireball".to_string(),
+            "firebolt".to_string(),
+            "stealth".to_string(),
+        ];
+        assert_eq!(
+            suggest_aliases("firebal", aliases.clone()),
+            vec!["fireball".to_string(), "firebolt".to_string()]
+        );
+        assert_eq!(
+            suggest_aliases("stelth", aliases.clone()),
+            vec!["stealth".to_string()]
+        );
+        assert!(suggest_aliases("hello", aliases).is_empty());
+    }
+
+    #[test]
+    fn test_parse_modifier() {
+        assert_eq!(
+            parse_command("!modifier set +2", "!"),
+            Ok(("", Command::SetModifier(Some(2))))
+        );
+        assert_eq!(
+            parse_command("!m s -3", "!"),
+            Ok(("", Command::SetModifier(Some(-3))))
+        );
+        assert_eq!(
+            parse_command("!mod clear", "!"),
+            Ok(("", Command::SetModifier(None)))
+        );
+        assert_eq!(parse_command("!m g", "!"), Ok(("", Command::GetModifier)));
+    }
+
+    #[test]
+    fn test_parse_luck() {
+        assert_eq!(
+            parse_command("!luck set on", "!"),
+            Ok(("", Command::SetShowLuck(true)))
+        );
+        assert_eq!(
+            parse_command("! luck s false", "!"),
+            Ok(("", Command::SetShowLuck(false)))
+        );
+        assert_eq!(
+            parse_command("!luck get", "!"),
+            Ok(("", Command::GetShowLuck))
+        );
+    }
+
+    #[test]
+    fn test_parse_command_body() {
+        for command in &["roll 1d20 + 5", "alias list", "cp set ?", "nonsense"] {
+            assert_eq!(
+                parse_command_body(command).map(|c| c.1),
+                parse_command(&format!("!{}", command), "!").map(|c| c.1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_channel_prefixes() {
+        assert_eq!(
+            parse_command("!cp channel set ?", "!"),
+            Ok(("", Command::SetChannelCommandPrefix(Some("?".to_string()))))
+        );
+        assert_eq!(
+            parse_command("!cp c c", "!"),
+            Ok(("", Command::SetChannelCommandPrefix(None)))
+        );
+        assert_eq!(
+            parse_command("!rp channel add x!", "!"),
+            Ok(("", Command::AddChannelRollPrefix("x!".to_string())))
+        );
+        assert_eq!(
+            parse_command("!rp c r x!", "!"),
+            Ok(("", Command::RemoveChannelRollPrefix("x!".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_parse_privileged_roles() {
+        assert_eq!(
+            parse_command("!privileged-roles add <@&1234>", "!"),
+            Ok(("", Command::AddPrivilegedRole("1234".to_string())))
+        );
+        assert_eq!(
+            parse_command("!pr r 1234", "!"),
+            Ok(("", Command::RemovePrivilegedRole("1234".to_string())))
+        );
+        assert_eq!(
+            parse_command("!pr list", "!"),
+            Ok(("", Command::ListPrivilegedRoles))
+        );
+    }
+
+    #[test]
+    fn test_parse_list_order() {
+        assert_eq!(
+            parse_command("!list-order set desc", "!"),
+            Ok(("", Command::SetListOrder(ListOrder::Descending)))
+        );
+        assert_eq!(
+            parse_command("!lo s a", "!"),
+            Ok(("", Command::SetListOrder(ListOrder::Ascending)))
+        );
+        assert_eq!(
+            parse_command("!list order set none", "!"),
+            Ok(("", Command::SetListOrder(ListOrder::Unsorted)))
+        );
+        assert_eq!(
+            parse_command("!lo get", "!"),
+            Ok(("", Command::GetListOrder))
+        );
+    }
+
+    fn lookup(roll_shorthand: Option<&str>) -> Lookup {
+        Lookup {
+            channel: None,
+            command_prefix: "rrb!".to_string(),
+            roll_prefixes: vec![],
+            aliases: vec![],
+            roll_info: true,
+            paused: false,
+            alias_hints: false,
+            list_order: ListOrder::Unsorted,
+            roll_shorthand: roll_shorthand.map(|s| s.to_string()),
+            stats_roll: None,
+            stats_min_total: None,
+            critical_message: None,
+            fumble_message: None,
+            show_luck: false,
+            privileged_roles: Vec::new(),
+            dice_policy: DicePolicy::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_genstats() {
+        assert_eq!(parse_command("!genstats", "!"), Ok(("", Command::GenStats)));
+        assert_eq!(parse_command("! gs ", "!"), Ok(("", Command::GenStats)));
+        assert_eq!(
+            parse_command("!gs set 6{3d6}", "!"),
+            Ok((
+                "",
+                Command::SetStatsRoll(Some(VersionedRollExpr::V2(
+                    parser::parse_labeled("6{3d6}").unwrap().1
+                )))
+            ))
+        );
+        assert_eq!(
+            parse_command("!gs set 5{3d6}", "!").map(|c| c.1),
+            Ok(Command::Help)
+        );
+        assert_eq!(
+            parse_command("!genstats reset", "!"),
+            Ok(("", Command::SetStatsRoll(None)))
+        );
+        assert_eq!(
+            parse_command("!gs min 70", "!"),
+            Ok(("", Command::SetStatsMinTotal(Some(70))))
+        );
+        assert_eq!(
+            parse_command("!gs m off", "!"),
+            Ok(("", Command::SetStatsMinTotal(None)))
+        );
+        assert_eq!(
+            parse_command("!gs get", "!"),
+            Ok(("", Command::GetStatsConfig))
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(
+            parse_command("!config export", "!"),
+            Ok(("", Command::ExportConfig))
+        );
+        assert_eq!(
+            parse_command("!config import {\"roll_info\": true; \"x\"}", "!"),
+            Ok((
+                "",
+                Command::ImportConfig("{\"roll_info\": true; \"x\"}".to_string())
+            ))
+        );
+        assert_eq!(
+            parse_command("!config import\n```json\n{}\n```\n", "!"),
+            Ok(("", Command::ImportConfig("{}".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_valid_alias() {
+        let expr = VersionedRollExpr::V2(parser::parse_labeled("1d20").unwrap().1);
+        assert!(valid_alias("attack", &expr));
+        assert!(valid_alias("two words", &expr));
+        assert!(!valid_alias(" padded", &expr));
+        assert!(!valid_alias("cost$", &expr));
+        assert!(!valid_alias("", &expr));
+        assert!(!valid_alias(
+            "vars",
+            &VersionedRollExpr::V2(parser::parse_labeled("1d20 + dex").unwrap().1)
+        ));
+    }
+
+    #[test]
+    fn test_parse_critical_message() {
+        assert_eq!(
+            parse_command("!critical set 🎯 Critical Hit! {value}", "!"),
+            Ok((
+                "",
+                Command::SetCriticalMessage(
+                    Critical::Hit,
+                    Some("🎯 Critical Hit! {value}".to_string())
+                )
+            ))
+        );
+        assert_eq!(
+            parse_command("!fumble s 💀 Fumble! ; crit", "!"),
+            Ok((
+                "",
+                Command::Multiple(vec![
+                    Command::SetCriticalMessage(Critical::Fumble, Some("💀 Fumble!".to_string())),
+                    Command::GetCriticalMessage(Critical::Hit),
+                ])
+            ))
+        );
+        assert_eq!(
+            parse_command("!fumble off", "!"),
+            Ok(("", Command::SetCriticalMessage(Critical::Fumble, None)))
+        );
+        assert_eq!(
+            parse_command("!Critical", "!"),
+            Ok(("", Command::GetCriticalMessage(Critical::Hit)))
+        );
+    }
+
+    #[test]
+    fn test_roll_shorthand() {
+        let roll = Command::Roll(VersionedRollExpr::V2(LabeledExpression::Unlabeled(
+            Expression::Simple(Term::Constant(20)),
+        )));
+        assert_eq!(parse_lookup("r 20", &mut lookup(None)), None);
+        assert_eq!(
+            parse_lookup("r 20", &mut lookup(Some("r"))),
+            Some(roll.clone())
+        );
+        assert_eq!(
+            parse_lookup("/r\t20 ", &mut lookup(Some("/r"))),
+            Some(roll.clone())
+        );
+        // plain conversation is left alone
+        assert_eq!(parse_lookup("r u there?", &mut lookup(Some("r"))), None);
+        assert_eq!(parse_lookup("r ok", &mut lookup(Some("r"))), None);
+        assert_eq!(parse_lookup("r20", &mut lookup(Some("r"))), None);
+        // the command prefix takes precedence
+        assert_eq!(
+            parse_lookup("rrb! r 20", &mut lookup(Some("rrb!"))),
+            Some(roll.clone())
+        );
+        assert_eq!(
+            parse_lookup("rrb! help", &mut lookup(Some("rrb!"))),
+            Some(Command::Help)
+        );
+        assert_eq!(
+            parse_command("!roll-shorthand set r", "!"),
+            Ok(("", Command::SetRollShorthand(Some("r".to_string()))))
+        );
+        assert_eq!(
+            parse_command("!rs off", "!"),
+            Ok(("", Command::SetRollShorthand(None)))
+        );
+        assert_eq!(
+            parse_command("!rs g", "!"),
+            Ok(("", Command::GetRollShorthand))
+        );
+    }
+
+    #[test]
+    fn test_parse_pause() {
+        assert_eq!(parse_command("!pause", "!"), Ok(("", Command::Pause)));
+        assert_eq!(parse_command("! RESUME ", "!"), Ok(("", Command::Resume)));
+    }
+
+    #[test]
+    fn test_parse_trailing_operator() {
+        for (input, text) in [
+            ("!r 1d6 +", "1d6 +"),
+            ("! roll 2d6 * ", "2d6 *"),
+            ("!r 1d20; 1d6/", "1d20; 1d6/"),
+        ] {
+            assert_eq!(
+                parse_command(input, "!"),
+                Ok(("", Command::TrailingOperator(text.to_string())))
+            );
+        }
+        assert_eq!(
+            parse_roll("r! 4d6k3 -", "r!"),
+            Ok(("", Command::TrailingOperator("4d6k3 -".to_string())))
+        );
+        assert_eq!(
+            parse_roll_shorthand("r 1d8 +", "r"),
+            Ok(("", Command::TrailingOperator("1d8 +".to_string())))
+        );
+        assert_eq!(
+            parse_command("!r 1d6 + + 2", "!").map(|c| c.1),
+            Ok(Command::Help)
+        );
+    }
+
+    #[test]
+    fn test_parse_reload() {
+        assert_eq!(parse_command("!reload", "!"), Ok(("", Command::Reload)));
+    }
+
+    #[test]
+    fn test_parse_private_roll() {
+        let constant = |n| {
+            VersionedRollExpr::V2(LabeledExpression::Unlabeled(Expression::Simple(
+                Term::Constant(n),
+            )))
+        };
+        assert_eq!(
+            parse_command("!gmroll 1", "!"),
+            Ok(("", Command::RollPrivate(constant(1))))
+        );
+        assert_eq!(
+            parse_command("! Whisper 1 ", "!"),
+            Ok(("", Command::RollPrivate(constant(1))))
+        );
+        assert_eq!(
+            parse_command("!gmroll 1; help", "!"),
+            Ok(("gmroll 1; help", Command::Help))
+        );
+    }
+
+    #[test]
+    fn test_parse_repeat_last() {
+        for input in ["!!", "! r .", "!roll."] {
+            assert_eq!(parse_command(input, "!"), Ok(("", Command::RepeatLast)));
+        }
+        assert_eq!(
+            parse_command("rrb!!", "rrb!"),
+            Ok(("", Command::RepeatLast))
+        );
+    }
+
+    #[test]
+    fn test_parse_dice_policy() {
+        assert_eq!(
+            parse_command("!dice ban multiply", "!"),
+            Ok(("", Command::SetDiceBanned(DiceKind::Multiply, true)))
+        );
+        assert_eq!(
+            parse_command("! dice allow f", "!"),
+            Ok(("", Command::SetDiceBanned(DiceKind::Fudge, false)))
+        );
+        assert_eq!(
+            parse_command("!dice max-faces 100", "!"),
+            Ok(("", Command::SetMaxFaces(Some(100))))
+        );
+        assert_eq!(
+            parse_command("!dice max faces off", "!"),
+            Ok(("", Command::SetMaxFaces(None)))
+        );
+        assert_eq!(
+            parse_command("!dice get", "!"),
+            Ok(("", Command::GetDicePolicy))
+        );
+    }
+
+    #[test]
+    fn test_parse_roll_history() {
+        for input in ["!roll-history", "! roll history", "!History "] {
+            assert_eq!(parse_command(input, "!"), Ok(("", Command::RollHistory)));
+        }
+    }
+
+    #[test]
+    fn test_parse_pool_odds() {
+        assert_eq!(
+            parse_command("!pool-odds 10d10>=8", "!"),
+            Ok((
+                "",
+                Command::PoolOdds(FilteredDice::Filtered(
+                    Dice {
+                        throws: 10,
+                        dice: DiceType::Number(10),
+                        explode: None,
+                        dynamic_throws: None,
+                        reroll: None
+                    },
+                    Filter::BiggerEq,
+                    8
+                ))
+            ))
+        );
+        assert!(matches!(
+            parse_command("!po 3d6", "!"),
+            Ok(("", Command::PoolOdds(FilteredDice::Simple(_))))
+        ));
+    }
+
+    #[test]
+    fn test_parse_odds() {
+        assert_eq!(
+            parse_command("!odds 2d6 + 1", "!"),
+            Ok(("", Command::Odds(parser::parse_term("2d6+1").unwrap().1)))
+        );
+        assert_eq!(
+            parse_command("!roll-odds 2d6+1", "!"),
+            Ok(("", Command::Odds(parser::parse_term("2d6+1").unwrap().1)))
+        );
+        assert_eq!(parse_command("!odds", "!"), Ok(("odds", Command::Help)));
+    }
+
+    #[test]
+    fn test_parse_narrative() {
+        assert_eq!(
+            parse_command("!narrative 2a 1p 2d", "!"),
+            Ok((
+                "",
+                Command::Narrative(parser::parse_narrative_pool("2a1p2d").unwrap().1)
+            ))
+        );
+        assert!(matches!(
+            parse_command("!nr 3b", "!"),
+            Ok(("", Command::Narrative(_)))
+        ));
+    }
+
     #[test]
     fn test_chars_set() {
         assert_eq!(chars_set("ä"), Ok(("", 'ä')));
@@ -0,0 +1,70 @@
+use super::{storage::ClientId, VersionedRollExpr};
+use cached::{Cached, SizedCache};
+use std::sync::{Arc, Mutex};
+
+/// client, user and channel a roll was requested in
+type RollKey<Id> = (Id, String, Option<String>);
+
+type Expressions = Vec<Arc<VersionedRollExpr>>;
+
+/// the expressions each user rolled last in a channel, to repeat them
+///
+/// they are only kept in memory, the least recently used are forgotten first
+#[derive(Debug, Clone)]
+pub(crate) struct LastRolls<Id: ClientId> {
+    rolls: Arc<Mutex<SizedCache<RollKey<Id>, Expressions>>>,
+}
+
+impl<Id: ClientId> LastRolls<Id> {
+    /// remembers the rolls of at most `size` users and channels
+    pub(crate) fn new(size: usize) -> LastRolls<Id> {
+        LastRolls {
+            rolls: Arc::new(Mutex::new(SizedCache::with_size(size.max(1)))),
+        }
+    }
+
+    pub(crate) fn get(&self, id: &Id, user: &str, channel: Option<&str>) -> Option<Expressions> {
+        self.rolls
+            .lock()
+            .unwrap()
+            .cache_get(&(id.clone(), user.to_string(), channel.map(|c| c.to_owned())))
+            .cloned()
+    }
+
+    pub(crate) fn set(&self, id: Id, user: &str, channel: Option<&str>, expressions: Expressions) {
+        self.rolls.lock().unwrap().cache_set(
+            (id, user.to_string(), channel.map(|c| c.to_owned())),
+            expressions,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(text: &str) -> Arc<VersionedRollExpr> {
+        Arc::new(VersionedRollExpr::V2(
+            robins_dice_roll::parser::parse_labeled(text).unwrap().1,
+        ))
+    }
+
+    #[test]
+    fn test_last_rolls() {
+        let last = LastRolls::<u64>::new(2);
+        last.set(1, "a", Some("c"), vec![expr("1d20")]);
+        last.set(1, "a", Some("c"), vec![expr("1d6"), expr("1d8")]);
+        last.set(1, "a", None, vec![expr("2d6")]);
+        assert_eq!(
+            last.get(&1, "a", Some("c")),
+            Some(vec![expr("1d6"), expr("1d8")])
+        );
+        assert_eq!(last.get(&1, "a", None), Some(vec![expr("2d6")]));
+        assert_eq!(last.get(&1, "b", Some("c")), None);
+        assert_eq!(last.get(&2, "a", Some("c")), None);
+        // the least recently used roll is forgotten
+        last.set(1, "b", None, vec![expr("1d4")]);
+        assert_eq!(last.get(&1, "a", Some("c")), None);
+        assert_eq!(last.get(&1, "b", None), Some(vec![expr("1d4")]));
+    }
+}
@@ -1,29 +1,170 @@
 pub use robins_dice_roll::dice_roll::{EvaluationErrors, ExpressionEvaluate};
+pub use robins_dice_roll::stats::{Distribution, PoolOdds, Statistics, StatsError};
 
 pub mod commands;
+mod last_rolls;
+mod modifiers;
+mod rate_limit;
+pub mod render;
 pub mod rolls;
 pub mod storage;
 
-use rolls::RollExecutor;
+use async_trait::async_trait;
+use last_rolls::LastRolls;
+use modifiers::Modifiers;
+pub use rate_limit::RateLimit;
+use rate_limit::RateLimiter;
+use rolls::{Busy, RollExecutor};
 use serde::{Deserialize, Serialize};
-use std::{future::Future, sync::Arc};
-pub use storage::ClientId;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+pub use storage::{
+    AliasLimitError, AliasLimits, AliasScope, ChannelPrefixes, ClientId, DiceKind, DicePolicy,
+    FullConfig, HistoryEntry, ImportError, ListOrder,
+};
 use storage::{GlobalStorage, StorageHandle};
 use tokio::task::JoinHandle;
 
-use robins_dice_roll::dice_types::{Expression, LabeledExpression};
+pub use robins_dice_roll::dice_types::{
+    DieOutcome, Filter, NarrativeDie, NarrativeRoll, Symbol, Tally,
+};
+use robins_dice_roll::dice_types::{Expression, LabeledExpression, Term};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VersionedRollExpr {
     V1(Expression),
     V2(LabeledExpression),
+    /// alias text with `$1`..`$n` placeholders, parsed once the arguments are filled in
+    Template(String),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// total of a single result, the dice it was rolled with and what happened to each of them
+///
+/// the outcomes leave out rerolled dice, so they might not match the dice one to one
+pub type RolledResult = (i64, Vec<i64>, Vec<DieOutcome>);
+
+#[derive(Debug, PartialEq)]
 pub struct RollExprResult {
-    pub roll: Result<Vec<(i64, Vec<i64>)>, EvaluationErrors>,
+    pub roll: Result<Vec<RolledResult>, EvaluationErrors>,
     pub text: String,
     pub label: Option<String>,
+    /// the expression counts successes instead of summing dice
+    pub successes: bool,
+    /// filter and target of a roll of filtered dice, to tell passing from failing dice
+    pub filter: Option<(Filter, u32)>,
+    /// `[comment]`s of the expression, each with the text of the term it annotates
+    pub comments: Vec<(String, String)>,
+    /// value of each `#label`ed term, only for expressions with a single result
+    pub breakdown: Vec<(String, i64)>,
+    /// global modifier of the user, that was added to the roll
+    pub modifier: Option<i64>,
+    /// everything rolled comes down to a single kept d20, so its results can be [Critical]s
+    pub natural_d20: bool,
+    /// critical announcements configured by the client, one for each critical result
+    pub announcements: Vec<String>,
+    /// mean and variance of the expression, only computed if the client shows how lucky a roll was
+    pub statistics: Option<Statistics>,
+    /// seed of the generator the roll was made with
+    pub seed: Option<rolls::RollSeed>,
+    /// time the roll workers took to evaluate the expression
+    pub duration: Option<Duration>,
+    /// sum of all entries of a list, `None` for expressions with a single result or if it overflows
+    pub total: Option<i64>,
+    /// expression the result was rolled from, without the modifier, to roll it again
+    pub expression: Option<Arc<VersionedRollExpr>>,
+}
+
+/// natural 20 or natural 1 on the single d20 of a roll
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Critical {
+    Hit,
+    Fumble,
+}
+
+impl fmt::Display for Critical {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Critical::Hit => write!(f, "critical"),
+            Critical::Fumble => write!(f, "fumble"),
+        }
+    }
+}
+
+/// longest critical announcement a client can configure
+pub const MAX_CRITICAL_MESSAGE_LEN: usize = 100;
+
+/// replaced by the rolled total in critical announcements
+pub const CRITICAL_VALUE_PLACEHOLDER: &str = "{value}";
+
+/// rolls remembered per user unless configured otherwise
+pub const DEFAULT_HISTORY_SIZE: usize = 10;
+
+/// aliases on each page of `alias list <page>`
+pub const ALIAS_PAGE_SIZE: usize = 20;
+
+/// a single page of the alias list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasPage {
+    /// name and expression of the aliases on this page, empty if there is no such page
+    pub aliases: Vec<(String, String)>,
+    /// starting at 1
+    pub page: u32,
+    pub pages: u32,
+}
+
+impl AliasPage {
+    /// page `page` of `aliases`, which are in the order they are listed in
+    pub fn new(aliases: Vec<(String, String)>, page: u32) -> AliasPage {
+        let pages = aliases.len().div_ceil(ALIAS_PAGE_SIZE) as u32;
+        AliasPage {
+            aliases: aliases
+                .into_iter()
+                .skip((page as usize).saturating_sub(1) * ALIAS_PAGE_SIZE)
+                .take(ALIAS_PAGE_SIZE)
+                .collect(),
+            page,
+            pages,
+        }
+    }
+}
+
+impl RollExprResult {
+    /// total and kind of every result showing a natural 20 or 1
+    pub fn criticals(&self) -> Vec<(i64, Critical)> {
+        match &self.roll {
+            Ok(results) if self.natural_d20 => results
+                .iter()
+                .filter_map(|(total, _, outcomes)| {
+                    match outcomes.iter().find(|o| matches!(o, DieOutcome::Kept(_))) {
+                        Some(DieOutcome::Kept(20)) => Some((*total, Critical::Hit)),
+                        Some(DieOutcome::Kept(1)) => Some((*total, Critical::Fumble)),
+                        _ => None,
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// fills in the announcements of every critical result, using the templates of `lookup`
+    fn announce_criticals(&mut self, lookup: &storage::Lookup) {
+        self.announcements = self
+            .criticals()
+            .into_iter()
+            .filter_map(|(total, critical)| {
+                let template = match critical {
+                    Critical::Hit => lookup.critical_message.as_ref(),
+                    Critical::Fumble => lookup.fumble_message.as_ref(),
+                }?;
+                Some(template.replace(CRITICAL_VALUE_PLACEHOLDER, &total.to_string()))
+            })
+            .collect();
+    }
 }
 
 impl std::fmt::Display for VersionedRollExpr {
@@ -35,120 +176,1048 @@ impl std::fmt::Display for VersionedRollExpr {
             VersionedRollExpr::V2(e) => {
                 write!(f, "{}", e)
             }
+            VersionedRollExpr::Template(t) => {
+                write!(f, "{}", t)
+            }
+        }
+    }
+}
+
+/// reasons copying an alias between scopes can fail
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AliasCopyError {
+    /// the alias does not exist in the source scope
+    NotFound,
+    /// the target scope already has an alias of that name
+    Exists,
+    /// there is no source scope to copy from
+    Unsupported,
+}
+
+impl fmt::Display for AliasCopyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasCopyError::NotFound => write!(f, "no such alias"),
+            AliasCopyError::Exists => write!(f, "an alias with this name already exists"),
+            AliasCopyError::Unsupported => write!(f, "there is nothing to copy from here"),
+        }
+    }
+}
+
+/// reasons a parameterized alias can't be rolled
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AliasCallError {
+    /// the alias takes a different number of arguments
+    Arguments {
+        alias: String,
+        expected: usize,
+        given: usize,
+    },
+    /// the alias with its arguments filled in is no roll statement
+    Invalid { alias: String, text: String },
+}
+
+impl fmt::Display for AliasCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasCallError::Arguments {
+                alias,
+                expected,
+                given,
+            } => write!(
+                f,
+                "`{}` takes {} argument{}, but {} {} given",
+                alias,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                given,
+                if *given == 1 { "was" } else { "were" }
+            ),
+            AliasCallError::Invalid { alias, text } => {
+                write!(
+                    f,
+                    "`{}` turned into `{}`, which can't be rolled",
+                    alias, text
+                )
+            }
+        }
+    }
+}
+
+/// aliases of another client, that can be copied into a users personal scope
+#[async_trait]
+pub trait AliasSource: Send + Sync {
+    async fn get_alias(&self, user: &str, alias: String) -> Option<Arc<VersionedRollExpr>>;
+}
+
+struct DmAliases<DmId: ClientId, F: Fn(&str) -> Option<DmId> + Send + Sync> {
+    store: StorageHandle<DmId>,
+    to_id: F,
+}
+
+#[async_trait]
+impl<DmId: ClientId, F: Fn(&str) -> Option<DmId> + Send + Sync> AliasSource for DmAliases<DmId, F> {
+    async fn get_alias(&self, user: &str, alias: String) -> Option<Arc<VersionedRollExpr>> {
+        match (self.to_id)(user) {
+            Some(id) => self.store.get_alias(id, AliasScope::Shared, alias).await,
+            None => None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub enum CommandResult {
     Help(String),
     RollHelp,
     Info,
     SetCommandPrefix(String),
     GetCommandPrefix(String),
+    /// the command prefix of the channel, `None` if it uses the one of the client again
+    SetChannelCommandPrefix(Option<String>),
     AddRollPrefix(Result<(), ()>),
+    /// the command prefix and a roll prefix would be the same, every roll would be taken for a
+    /// command
+    PrefixConflict(String),
     RemoveRollPrefix(Result<(), ()>),
+    /// number of removed roll prefixes
+    ClearRollPrefixes(usize),
     ListRollPrefix(Vec<String>),
+    AddPrivilegedRole(Result<(), ()>),
+    RemovePrivilegedRole(Result<(), ()>),
+    /// ids of the roles allowed to use privileged commands
+    ListPrivilegedRoles(Vec<String>),
     AddAlias,
+    /// the alias was refused, it would exceed the configured limits
+    AliasLimitReached(AliasLimitError),
     RemoveAlias(Result<(), ()>),
+    /// number of removed shared aliases
+    ClearAliases(usize),
+    CopyAlias(Result<(), AliasCopyError>),
+    /// every alias, sorted by name
     ListAliases(Vec<(String, String)>),
-    Roll(Vec<RollExprResult>, bool),
+    ListAliasPage(AliasPage),
+    Roll(Vec<RollExprResult>, bool, ListOrder),
+    /// same as [CommandResult::Roll], but only for the user who requested it
+    PrivateRoll(Vec<RollExprResult>, bool, ListOrder),
+    /// the user hasn't rolled anything in the channel yet
+    NothingToRepeat,
     GetRollInfo(bool),
     SetRollInfo,
+    GetShowLuck(bool),
+    SetShowLuck,
+    Pause,
+    Resume,
+    Reload,
+    PoolOdds(String, Result<PoolOdds, StatsError>),
+    Odds(String, Result<Arc<Distribution>, StatsError>),
+    /// the rolled pool and the faces it shows
+    Narrative(String, NarrativeRoll),
+    /// the roll workers are saturated, nothing was rolled
+    Busy,
+    /// the user rolled too often, nothing was rolled
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// ability scores, the number of discarded arrays below `min_total` and the display order
+    GenStats(Box<RollExprResult>, u32, ListOrder),
+    SetStatsRoll,
+    SetStatsMinTotal,
+    /// the ability score roll and the minimum total
+    GetStatsConfig(String, Option<i64>),
+    SetAliasHints,
+    UnknownAlias(String, Vec<String>),
+    SetModifier,
+    GetModifier(Option<i64>),
+    SetListOrder,
+    GetListOrder(ListOrder),
+    SetRollShorthand,
+    GetRollShorthand(Option<String>),
+    /// `Err` if the announcement is longer than [MAX_CRITICAL_MESSAGE_LEN]
+    SetCriticalMessage(Critical, Result<(), ()>),
+    GetCriticalMessage(Critical, Option<String>),
+    /// the configuration as JSON
+    ExportConfig(String),
+    ImportConfig(Result<(), ImportError>),
+    /// latest rolls of the user, the latest first
+    History(Vec<HistoryEntry>),
+    /// `Err` if the kind of dice already was banned or allowed
+    SetDiceBanned(DiceKind, bool, Result<(), ()>),
+    SetMaxFaces,
+    GetDicePolicy(DicePolicy),
+    /// the roll contains dice the client doesn't allow, nothing was rolled
+    DiceTypeNotAllowed(String),
     InsufficentPermission,
+    /// the roll statement ends with an operator, like `1d6 +`
+    TrailingOperator(String),
+    InvalidAliasCall(AliasCallError),
+    /// results of several commands of one message, in order
+    ///
+    /// every command is run on its own, a failing one doesn't stop the others
+    Multiple(Vec<CommandResult>),
+}
+
+/// ability scores below the minimum total are rerolled at most this often
+const MAX_STATS_ATTEMPTS: u32 = 20;
+
+/// 4d6 drop lowest for each of the six ability scores
+fn default_stats_roll() -> Arc<VersionedRollExpr> {
+    Arc::new(VersionedRollExpr::V2(
+        robins_dice_roll::parser::parse_labeled("6{4d6k3}")
+            .unwrap()
+            .1,
+    ))
+}
+
+/// result of `rolls`, with the criticals announced like configured in `lookup`
+fn roll_result(mut rolls: Vec<RollExprResult>, lookup: &storage::Lookup) -> CommandResult {
+    for roll in rolls.iter_mut() {
+        roll.announce_criticals(lookup);
+    }
+    CommandResult::Roll(rolls, lookup.roll_info, lookup.list_order)
+}
+
+/// what the client knows about the permissions of the author of a message
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    /// may use every privileged command, regardless of roles
+    pub administrator: bool,
+    /// ids of the roles the author has
+    pub roles: Vec<String>,
+}
+
+impl From<bool> for Access {
+    fn from(administrator: bool) -> Self {
+        Access {
+            administrator,
+            roles: Vec::new(),
+        }
+    }
+}
+
+/// checks for privileged access at most once per message
+struct Permission<Fn> {
+    check: Option<Fn>,
+    privileged_roles: Vec<String>,
+    access: Access,
+}
+
+impl<F: Future<Output = Access>, Fn: FnOnce() -> F> Permission<Fn> {
+    fn new(check: Fn, privileged_roles: Vec<String>) -> Permission<Fn> {
+        Permission {
+            check: Some(check),
+            privileged_roles,
+            access: Access::default(),
+        }
+    }
+
+    async fn access(&mut self) -> &Access {
+        if let Some(check) = self.check.take() {
+            self.access = check().await;
+        }
+        &self.access
+    }
+
+    /// administrators and members of a privileged role
+    async fn allowed(&mut self) -> bool {
+        self.access().await;
+        self.access.administrator
+            || self
+                .access
+                .roles
+                .iter()
+                .any(|role| self.privileged_roles.contains(role))
+    }
+
+    /// only administrators, privileged roles can't grant themselves to others
+    async fn administrator(&mut self) -> bool {
+        self.access().await.administrator
+    }
 }
 
 #[derive(Clone)]
 pub struct ClientUtils<Id: ClientId> {
     roll: Arc<RollExecutor>,
     store: StorageHandle<Id>,
+    modifiers: Modifiers<Id>,
+    last_rolls: LastRolls<Id>,
+    dm_aliases: Option<Arc<dyn AliasSource>>,
+    personal_aliases: bool,
+    alias_limits: AliasLimits,
+    rate_limiter: RateLimiter<Id>,
+    /// rolls remembered per user, none if 0
+    history_size: usize,
 }
 
 impl<Id: storage::ClientId> ClientUtils<Id> {
-    pub async fn eval<F: Future<Output = bool>, Fn: FnOnce() -> F>(
+    /// allows users to copy the aliases of their direct messages in `dm` into their
+    /// personal scope, `to_id` maps a user to the id of their direct messages
+    pub fn link_dm_aliases<DmId, F>(&mut self, dm: &ClientUtils<DmId>, to_id: F)
+    where
+        DmId: ClientId,
+        F: Fn(&str) -> Option<DmId> + Send + Sync + 'static,
+    {
+        self.dm_aliases = Some(Arc::new(DmAliases {
+            store: dm.store.clone(),
+            to_id,
+        }));
+    }
+
+    /// makes `alias add`, `alias remove` and `alias list` work on the personal aliases of the
+    /// invoking user, shared aliases are only created by promoting a personal one
+    pub fn scope_aliases_per_user(&mut self) {
+        self.personal_aliases = true;
+    }
+
+    /// scope alias changes of `user` go to, `None` if every alias is shared
+    fn personal_scope(&self, user: &str) -> Option<AliasScope> {
+        if self.personal_aliases {
+            Some(AliasScope::User(user.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// aliases `user` is able to roll, personal aliases shadow shared ones
+    async fn visible_aliases(&self, id: Id, user: &str) -> HashMap<String, Arc<VersionedRollExpr>> {
+        let mut aliases = self
+            .store
+            .get_all_alias(id.clone(), AliasScope::Shared)
+            .await;
+        if let Some(scope) = self.personal_scope(user) {
+            let personal = self.store.get_all_alias(id, scope).await;
+            let keys: HashSet<_> = personal.keys().map(|a| storage::alias_key(a)).collect();
+            aliases.retain(|alias, _| !keys.contains(&storage::alias_key(alias)));
+            aliases.extend(personal);
+        }
+        aliases
+    }
+
+    /// stores `expr` as `alias` in `target`, without overwriting an existing one
+    async fn copy_alias(
+        &self,
+        id: Id,
+        expr: Option<Arc<VersionedRollExpr>>,
+        target: AliasScope,
+        alias: String,
+    ) -> Result<(), AliasCopyError> {
+        let expr = expr.ok_or(AliasCopyError::NotFound)?;
+        self.store
+            .insert_alias(id, target, alias, expr)
+            .await
+            .map_err(|_| AliasCopyError::Exists)
+    }
+
+    /// roll `expr`, adding `modifier` to every result if one is set
+    ///
+    /// how lucky the roll was is only computed with `luck`
+    async fn roll_modified<Expr>(
+        &self,
+        expr: Expr,
+        modifier: Option<i64>,
+        luck: bool,
+    ) -> Result<RollExprResult, Busy>
+    where
+        Expr: std::borrow::Borrow<VersionedRollExpr> + Sized + Send + 'static,
+    {
+        match modifier {
+            Some(m) => {
+                let mut result = self.roll.roll(expr.borrow().with_modifier(m), luck).await?;
+                result.modifier = Some(m);
+                Ok(result)
+            }
+            None => self.roll.roll(expr, luck).await,
+        }
+    }
+
+    /// roll all `expressions` in order, stopping at the first one the workers reject
+    async fn roll_all<Expr>(
+        &self,
+        expressions: Vec<Expr>,
+        modifier: Option<i64>,
+        luck: bool,
+    ) -> Result<Vec<RollExprResult>, Busy>
+    where
+        Expr: std::borrow::Borrow<VersionedRollExpr> + Sized + Send + 'static,
+    {
+        let mut rolls = Vec::with_capacity(expressions.len());
+        for expr in expressions {
+            rolls.push(self.roll_modified(expr, modifier, luck).await?);
+        }
+        Ok(rolls)
+    }
+
+    /// roll all `expressions` of `user`, who can repeat them afterwards
+    async fn roll_remembered(
+        &self,
+        id: Id,
+        user: &str,
+        expressions: Vec<Arc<VersionedRollExpr>>,
+        lookup: &storage::Lookup,
+    ) -> CommandResult {
+        if let Err(dice) = expressions
+            .iter()
+            .try_for_each(|expr| lookup.dice_policy.check(expr))
+        {
+            return CommandResult::DiceTypeNotAllowed(dice);
+        }
+        self.last_rolls.set(
+            id.clone(),
+            user,
+            lookup.channel.as_deref(),
+            expressions.clone(),
+        );
+        let modifier = self.modifiers.get(&id, user);
+        match self
+            .roll_all(expressions.clone(), modifier, lookup.show_luck)
+            .await
+        {
+            Ok(mut rolls) => {
+                for (roll, expr) in rolls.iter_mut().zip(expressions) {
+                    roll.expression = Some(expr);
+                }
+                roll_result(rolls, lookup)
+            }
+            Err(Busy) => CommandResult::Busy,
+        }
+    }
+
+    /// rolls `expressions` of an earlier result of `user` again, with the current settings of
+    /// `id` but regardless of the prefixes and aliases the result was requested with
+    pub async fn reroll(
         &self,
         id: Id,
+        user: &str,
+        channel: Option<&str>,
+        expressions: Vec<Arc<VersionedRollExpr>>,
+    ) -> Option<CommandResult> {
+        let lookup = self
+            .store
+            .get(
+                id.clone(),
+                Some(user.to_string()),
+                channel.map(|c| c.to_owned()),
+                Vec::new(),
+            )
+            .await;
+        self.run(
+            id,
+            user,
+            Some((commands::Command::Reroll(expressions), lookup)),
+            || std::future::ready(Access::default()),
+        )
+        .await
+    }
+
+    /// complete configuration of `id`, for external tooling
+    pub async fn get_full_config(&self, id: Id) -> FullConfig {
+        self.store.get_full_config(id).await
+    }
+
+    pub async fn eval<F: Future<Output = Access>, Fn: FnOnce() -> F>(
+        &self,
+        id: Id,
+        user: &str,
+        channel: Option<&str>,
         message: &str,
         check_permission: Fn,
     ) -> Option<CommandResult> {
-        match commands::parse_logging(message, id.clone(), &self.store).await {
-            Some((command, command_prefix, roll_info)) => Some(match command {
-                commands::Command::Help => CommandResult::Help(command_prefix),
-                commands::Command::RollHelp => CommandResult::RollHelp,
-                commands::Command::Info => CommandResult::Info,
-                commands::Command::SetCommandPrefix(prefix) => {
-                    if check_permission().await {
-                        self.store.set_command_prefix(id, prefix.clone()).await;
-                        CommandResult::SetCommandPrefix(prefix)
-                    } else {
-                        CommandResult::InsufficentPermission
+        let parsed = commands::parse_logging(message, id.clone(), user, channel, &self.store).await;
+        self.run(id, user, parsed, check_permission).await
+    }
+
+    /// same as [ClientUtils::eval] for `command` without the command prefix, like a
+    /// command the client received through its own command interface
+    pub async fn eval_command<F: Future<Output = Access>, Fn: FnOnce() -> F>(
+        &self,
+        id: Id,
+        user: &str,
+        channel: Option<&str>,
+        command: &str,
+        check_permission: Fn,
+    ) -> Option<CommandResult> {
+        let parsed =
+            commands::parse_unprefixed(command, id.clone(), user, channel, &self.store).await;
+        self.run(id, user, Some(parsed), check_permission).await
+    }
+
+    async fn run<F: Future<Output = Access>, Fn: FnOnce() -> F>(
+        &self,
+        id: Id,
+        user: &str,
+        parsed: Option<(commands::Command, storage::Lookup)>,
+        check_permission: Fn,
+    ) -> Option<CommandResult> {
+        match parsed {
+            Some((command, lookup)) if lookup.paused && command != commands::Command::Resume => {
+                None
+            }
+            Some((commands::Command::Multiple(commands), lookup)) => {
+                let mut permission =
+                    Permission::new(check_permission, lookup.privileged_roles.clone());
+                let mut results = Vec::with_capacity(commands.len());
+                for command in commands {
+                    if let Some(result) = self
+                        .execute(id.clone(), user, command, &lookup, &mut permission)
+                        .await
+                    {
+                        results.push(result);
                     }
                 }
-                commands::Command::GetCommandPrefix => {
-                    CommandResult::GetCommandPrefix(command_prefix)
+                if results.is_empty() {
+                    None
+                } else {
+                    Some(CommandResult::Multiple(results))
                 }
-                commands::Command::AddRollPrefix(prefix) => {
-                    if check_permission().await {
-                        CommandResult::AddRollPrefix(self.store.add_roll_prefix(id, prefix).await)
-                    } else {
-                        CommandResult::InsufficentPermission
+            }
+            Some((command, lookup)) => {
+                let mut permission =
+                    Permission::new(check_permission, lookup.privileged_roles.clone());
+                self.execute(id, user, command, &lookup, &mut permission)
+                    .await
+            }
+            None => None,
+        }
+    }
+
+    /// runs a single `command` of `user`, `None` if there is nothing to respond
+    async fn execute<F: Future<Output = Access>, Fn: FnOnce() -> F>(
+        &self,
+        id: Id,
+        user: &str,
+        command: commands::Command,
+        lookup: &storage::Lookup,
+        permission: &mut Permission<Fn>,
+    ) -> Option<CommandResult> {
+        let kind = command.kind();
+        let result = self
+            .dispatch(id.clone(), user, command, lookup, permission)
+            .await;
+        if let Some(result) = &result {
+            log_rolls(&id, kind, result);
+            self.record_history(id, user, result).await;
+        }
+        result
+    }
+
+    /// adds the successful rolls of `result` to the history of `user`
+    async fn record_history(&self, id: Id, user: &str, result: &CommandResult) {
+        if self.history_size == 0 {
+            return;
+        }
+        let entries: Vec<_> = match result {
+            CommandResult::Roll(rolls, _, _) => rolls
+                .iter()
+                .filter_map(|roll| {
+                    let results = roll.roll.as_ref().ok()?;
+                    Some(HistoryEntry {
+                        expression: roll.text.clone(),
+                        label: roll.label.clone(),
+                        results: results.iter().map(|r| r.0).collect(),
+                    })
+                })
+                .collect(),
+            _ => return,
+        };
+        if !entries.is_empty() {
+            self.store
+                .push_history(id, user.to_string(), entries, self.history_size)
+                .await;
+        }
+    }
+
+    async fn dispatch<F: Future<Output = Access>, Fn: FnOnce() -> F>(
+        &self,
+        id: Id,
+        user: &str,
+        command: commands::Command,
+        lookup: &storage::Lookup,
+        permission: &mut Permission<Fn>,
+    ) -> Option<CommandResult> {
+        if command.rolls() {
+            if let Err(retry_after) = self.rate_limiter.check(&id, user, Instant::now()) {
+                return Some(CommandResult::RateLimited { retry_after });
+            }
+        }
+        Some(match command {
+            commands::Command::Help => CommandResult::Help(lookup.command_prefix.clone()),
+            commands::Command::RollHelp => CommandResult::RollHelp,
+            commands::Command::Info => CommandResult::Info,
+            commands::Command::SetCommandPrefix(prefix) => {
+                if !permission.allowed().await {
+                    CommandResult::InsufficentPermission
+                } else if lookup.roll_prefixes.contains(&prefix)
+                    || self
+                        .store
+                        .get_roll_prefixes(id.clone())
+                        .await
+                        .contains(&prefix)
+                {
+                    CommandResult::PrefixConflict(prefix)
+                } else {
+                    self.store.set_command_prefix(id, prefix.clone()).await;
+                    CommandResult::SetCommandPrefix(prefix)
+                }
+            }
+            commands::Command::GetCommandPrefix => {
+                CommandResult::GetCommandPrefix(lookup.command_prefix.clone())
+            }
+            commands::Command::AddRollPrefix(prefix) => {
+                if !permission.allowed().await {
+                    CommandResult::InsufficentPermission
+                } else if prefix == lookup.command_prefix
+                    || prefix == self.store.get_command_prefix(id.clone()).await
+                {
+                    CommandResult::PrefixConflict(prefix)
+                } else {
+                    CommandResult::AddRollPrefix(self.store.add_roll_prefix(id, prefix).await)
+                }
+            }
+            commands::Command::RemoveRollPrefix(prefix) => {
+                if permission.allowed().await {
+                    CommandResult::RemoveRollPrefix(self.store.remove_roll_prefix(id, prefix).await)
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::ClearRollPrefixes => {
+                if permission.allowed().await {
+                    CommandResult::ClearRollPrefixes(self.store.clear_roll_prefixes(id).await)
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::SetChannelCommandPrefix(prefix) => {
+                if !permission.allowed().await {
+                    CommandResult::InsufficentPermission
+                } else if let Some(conflict) =
+                    prefix.as_ref().filter(|p| lookup.roll_prefixes.contains(p))
+                {
+                    CommandResult::PrefixConflict(conflict.clone())
+                } else if let Some(channel) = lookup.channel.clone() {
+                    self.store
+                        .set_channel_command_prefix(id, channel, prefix.clone())
+                        .await;
+                    CommandResult::SetChannelCommandPrefix(prefix)
+                } else if let Some(prefix) = prefix {
+                    // without channels the whole client is a single channel
+                    self.store.set_command_prefix(id, prefix.clone()).await;
+                    CommandResult::SetCommandPrefix(prefix)
+                } else {
+                    CommandResult::SetChannelCommandPrefix(None)
+                }
+            }
+            commands::Command::AddChannelRollPrefix(prefix) => {
+                if !permission.allowed().await {
+                    CommandResult::InsufficentPermission
+                } else if prefix == lookup.command_prefix {
+                    CommandResult::PrefixConflict(prefix)
+                } else if let Some(channel) = lookup.channel.clone() {
+                    CommandResult::AddRollPrefix(
+                        self.store
+                            .add_channel_roll_prefix(id, channel, prefix)
+                            .await,
+                    )
+                } else {
+                    CommandResult::AddRollPrefix(self.store.add_roll_prefix(id, prefix).await)
+                }
+            }
+            commands::Command::RemoveChannelRollPrefix(prefix) => {
+                if !permission.allowed().await {
+                    CommandResult::InsufficentPermission
+                } else if let Some(channel) = lookup.channel.clone() {
+                    CommandResult::RemoveRollPrefix(
+                        self.store
+                            .remove_channel_roll_prefix(id, channel, prefix)
+                            .await,
+                    )
+                } else {
+                    CommandResult::RemoveRollPrefix(self.store.remove_roll_prefix(id, prefix).await)
+                }
+            }
+            commands::Command::AddPrivilegedRole(role) => {
+                if permission.administrator().await {
+                    CommandResult::AddPrivilegedRole(self.store.add_privileged_role(id, role).await)
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::RemovePrivilegedRole(role) => {
+                if permission.administrator().await {
+                    CommandResult::RemovePrivilegedRole(
+                        self.store.remove_privileged_role(id, role).await,
+                    )
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::ListPrivilegedRoles => {
+                CommandResult::ListPrivilegedRoles(lookup.privileged_roles.clone())
+            }
+            commands::Command::ListRollPrefix => {
+                CommandResult::ListRollPrefix(lookup.roll_prefixes.clone())
+            }
+            commands::Command::AddAlias(alias, expression) => {
+                let scope = match self.personal_scope(user) {
+                    Some(scope) => Some(scope),
+                    None if permission.allowed().await => Some(AliasScope::Shared),
+                    None => None,
+                };
+                if let Some(scope) = scope {
+                    match self
+                        .store
+                        .add_alias(id, scope, alias, expression, self.alias_limits)
+                        .await
+                    {
+                        Ok(()) => CommandResult::AddAlias,
+                        Err(err) => CommandResult::AliasLimitReached(err),
                     }
+                } else {
+                    CommandResult::InsufficentPermission
                 }
-                commands::Command::RemoveRollPrefix(prefix) => {
-                    if check_permission().await {
-                        CommandResult::RemoveRollPrefix(
-                            self.store.remove_roll_prefix(id, prefix).await,
-                        )
-                    } else {
-                        CommandResult::InsufficentPermission
+            }
+            commands::Command::RemoveAlias(alias) => {
+                let removed = match self.personal_scope(user) {
+                    Some(scope) => {
+                        self.store
+                            .remove_alias(id.clone(), scope, alias.clone())
+                            .await
                     }
+                    None => Err(()),
+                };
+                if removed.is_ok() {
+                    CommandResult::RemoveAlias(removed)
+                } else if permission.allowed().await {
+                    CommandResult::RemoveAlias(
+                        self.store.remove_alias(id, AliasScope::Shared, alias).await,
+                    )
+                } else {
+                    CommandResult::InsufficentPermission
                 }
-                commands::Command::ListRollPrefix => {
-                    CommandResult::ListRollPrefix(self.store.get_roll_prefixes(id).await)
+            }
+            commands::Command::ClearAliases => {
+                if permission.allowed().await {
+                    CommandResult::ClearAliases(
+                        self.store.clear_aliases(id, AliasScope::Shared).await,
+                    )
+                } else {
+                    CommandResult::InsufficentPermission
                 }
-                commands::Command::AddAlias(alias, expression) => {
-                    if check_permission().await {
-                        self.store.add_alias(id, alias, expression).await.unwrap();
-                        CommandResult::AddAlias
-                    } else {
-                        CommandResult::InsufficentPermission
+            }
+            commands::Command::PromoteAlias(alias) => {
+                if permission.allowed().await {
+                    let expr = self
+                        .store
+                        .get_alias(
+                            id.clone(),
+                            AliasScope::User(user.to_string()),
+                            alias.clone(),
+                        )
+                        .await;
+                    CommandResult::CopyAlias(
+                        self.copy_alias(id, expr, AliasScope::Shared, alias).await,
+                    )
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::CopyAliasFromDm(alias) => {
+                CommandResult::CopyAlias(match &self.dm_aliases {
+                    Some(dm) => {
+                        let expr = dm.get_alias(user, alias.clone()).await;
+                        self.copy_alias(id, expr, AliasScope::User(user.to_string()), alias)
+                            .await
                     }
+                    None => Err(AliasCopyError::Unsupported),
+                })
+            }
+            commands::Command::ListAliases(page) => {
+                let mut aliases: Vec<_> = self
+                    .visible_aliases(id, user)
+                    .await
+                    .into_iter()
+                    .map(|(key, value)| (key, value.to_string()))
+                    .collect();
+                // pages only make sense in a stable order
+                aliases.sort_by_cached_key(|(alias, _)| (storage::alias_key(alias), alias.clone()));
+                match page {
+                    Some(page) => CommandResult::ListAliasPage(AliasPage::new(aliases, page)),
+                    None => CommandResult::ListAliases(aliases),
+                }
+            }
+            commands::Command::AliasRoll(expressions) | commands::Command::Reroll(expressions) => {
+                self.roll_remembered(id, user, expressions, lookup).await
+            }
+            commands::Command::MultiRoll(expressions) => {
+                let expressions = expressions.into_iter().map(Arc::new).collect();
+                self.roll_remembered(id, user, expressions, lookup).await
+            }
+            commands::Command::Roll(expr) => {
+                self.roll_remembered(id, user, vec![Arc::new(expr)], lookup)
+                    .await
+            }
+            commands::Command::RepeatLast => {
+                match self.last_rolls.get(&id, user, lookup.channel.as_deref()) {
+                    Some(expressions) => self.roll_remembered(id, user, expressions, lookup).await,
+                    None => CommandResult::NothingToRepeat,
+                }
+            }
+            commands::Command::RollPrivate(expr) => {
+                if let Err(dice) = lookup.dice_policy.check(&expr) {
+                    return Some(CommandResult::DiceTypeNotAllowed(dice));
                 }
-                commands::Command::RemoveAlias(alias) => {
-                    if check_permission().await {
-                        CommandResult::RemoveAlias(self.store.remove_alias(id, alias).await)
+                let modifier = self.modifiers.get(&id, user);
+                match self.roll_modified(expr, modifier, lookup.show_luck).await {
+                    Ok(roll) => match roll_result(vec![roll], lookup) {
+                        CommandResult::Roll(rolls, info, order) => {
+                            CommandResult::PrivateRoll(rolls, info, order)
+                        }
+                        result => result,
+                    },
+                    Err(Busy) => CommandResult::Busy,
+                }
+            }
+            commands::Command::SetModifier(modifier) => {
+                self.modifiers.set(id, user, modifier);
+                CommandResult::SetModifier
+            }
+            commands::Command::SetListOrder(order) => {
+                self.store.set_list_order(id, order).await;
+                CommandResult::SetListOrder
+            }
+            commands::Command::GetListOrder => CommandResult::GetListOrder(lookup.list_order),
+            commands::Command::SetRollShorthand(shorthand) => {
+                if permission.allowed().await {
+                    self.store.set_roll_shorthand(id, shorthand).await;
+                    CommandResult::SetRollShorthand
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::GetRollShorthand => {
+                CommandResult::GetRollShorthand(lookup.roll_shorthand.clone())
+            }
+            commands::Command::SetCriticalMessage(critical, message) => {
+                if !permission.allowed().await {
+                    CommandResult::InsufficentPermission
+                } else if message
+                    .as_ref()
+                    .is_some_and(|m| m.chars().count() > MAX_CRITICAL_MESSAGE_LEN)
+                {
+                    CommandResult::SetCriticalMessage(critical, Err(()))
+                } else {
+                    self.store.set_critical_message(id, critical, message).await;
+                    CommandResult::SetCriticalMessage(critical, Ok(()))
+                }
+            }
+            commands::Command::GetCriticalMessage(critical) => CommandResult::GetCriticalMessage(
+                critical,
+                match critical {
+                    Critical::Hit => lookup.critical_message.clone(),
+                    Critical::Fumble => lookup.fumble_message.clone(),
+                },
+            ),
+            commands::Command::ExportConfig => {
+                if permission.allowed().await {
+                    CommandResult::ExportConfig(self.store.export(id).await)
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::ImportConfig(blob) => {
+                if permission.allowed().await {
+                    CommandResult::ImportConfig(self.store.import(id, &blob).await)
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::GenStats => {
+                let expr = lookup.stats_roll.clone().unwrap_or_else(default_stats_roll);
+                if let Err(dice) = lookup.dice_policy.check(&expr) {
+                    return Some(CommandResult::DiceTypeNotAllowed(dice));
+                }
+                let mut rerolls = 0;
+                loop {
+                    let result = match self.roll.roll(expr.clone(), lookup.show_luck).await {
+                        Ok(result) => result,
+                        Err(Busy) => break CommandResult::Busy,
+                    };
+                    let below_min = match (&result.roll, lookup.stats_min_total) {
+                        (Ok(scores), Some(min)) => {
+                            scores.iter().fold(0i64, |sum, s| sum.saturating_add(s.0)) < min
+                        }
+                        _ => false,
+                    };
+                    if below_min && rerolls + 1 < MAX_STATS_ATTEMPTS {
+                        rerolls += 1;
                     } else {
-                        CommandResult::InsufficentPermission
+                        break CommandResult::GenStats(
+                            Box::new(result),
+                            rerolls,
+                            lookup.list_order,
+                        );
                     }
                 }
-                commands::Command::ListAliases => CommandResult::ListAliases(
-                    self.store
-                        .get_all_alias(id)
-                        .await
-                        .into_iter()
-                        .map(|(key, value)| (key, value.to_string()))
-                        .collect(),
-                ),
-                commands::Command::AliasRoll(expressions) => {
-                    let mut rolls = Vec::with_capacity(expressions.len());
-                    for expr in expressions {
-                        rolls.push(self.roll.roll(expr).await);
-                    }
-                    CommandResult::Roll(rolls, roll_info)
+            }
+            commands::Command::SetStatsRoll(roll) => {
+                if permission.allowed().await {
+                    self.store.set_stats_roll(id, roll).await;
+                    CommandResult::SetStatsRoll
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::SetStatsMinTotal(min_total) => {
+                if permission.allowed().await {
+                    self.store.set_stats_min_total(id, min_total).await;
+                    CommandResult::SetStatsMinTotal
+                } else {
+                    CommandResult::InsufficentPermission
                 }
-                commands::Command::Roll(expr) => {
-                    CommandResult::Roll(vec![self.roll.roll(expr).await], roll_info)
+            }
+            commands::Command::GetStatsConfig => CommandResult::GetStatsConfig(
+                lookup
+                    .stats_roll
+                    .clone()
+                    .unwrap_or_else(default_stats_roll)
+                    .to_string(),
+                lookup.stats_min_total,
+            ),
+            commands::Command::GetModifier => {
+                CommandResult::GetModifier(self.modifiers.get(&id, user))
+            }
+            commands::Command::SetRollInfo(new) => {
+                self.store.set_roll_info(id, new).await;
+                CommandResult::SetRollInfo
+            }
+            commands::Command::GetRollInfo => CommandResult::GetRollInfo(lookup.roll_info),
+            commands::Command::SetShowLuck(show_luck) => {
+                self.store.set_show_luck(id, show_luck).await;
+                CommandResult::SetShowLuck
+            }
+            commands::Command::GetShowLuck => CommandResult::GetShowLuck(lookup.show_luck),
+            commands::Command::PoolOdds(dice) => {
+                if let Err(dice) = lookup
+                    .dice_policy
+                    .check_term(&Term::SuccessCount(dice.clone()))
+                {
+                    return Some(CommandResult::DiceTypeNotAllowed(dice));
                 }
-                commands::Command::SetRollInfo(new) => {
-                    self.store.set_roll_info(id, new).await;
-                    CommandResult::SetRollInfo
+                CommandResult::PoolOdds(dice.to_string(), robins_dice_roll::stats::pool_odds(&dice))
+            }
+            commands::Command::Odds(term) => {
+                // odds that can't be computed exactly are sampled by rolling the dice
+                if let Err(dice) = lookup.dice_policy.check_term(&term) {
+                    return Some(CommandResult::DiceTypeNotAllowed(dice));
                 }
-                commands::Command::GetRollInfo => CommandResult::GetRollInfo(roll_info),
-            }),
-            None => None,
+                let text = term.to_string();
+                match self.roll.distribution(term).await {
+                    Ok(distribution) => CommandResult::Odds(text, distribution),
+                    Err(Busy) => CommandResult::Busy,
+                }
+            }
+            commands::Command::Narrative(pool) => {
+                CommandResult::Narrative(pool.to_string(), self.roll.narrative(&pool).await)
+            }
+            commands::Command::SetAliasHints(alias_hints) => {
+                if permission.allowed().await {
+                    self.store.set_alias_hints(id, alias_hints).await;
+                    CommandResult::SetAliasHints
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::UnknownAlias(name) => {
+                let suggestions = commands::suggest_aliases(
+                    &name,
+                    self.visible_aliases(id, user).await.into_keys(),
+                );
+                if suggestions.is_empty() {
+                    return None;
+                }
+                CommandResult::UnknownAlias(name, suggestions)
+            }
+            commands::Command::RollHistory => {
+                CommandResult::History(self.store.get_history(id, user.to_string()).await)
+            }
+            commands::Command::SetDiceBanned(kind, banned) => {
+                if permission.allowed().await {
+                    CommandResult::SetDiceBanned(
+                        kind,
+                        banned,
+                        self.store.set_dice_banned(id, kind, banned).await,
+                    )
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::SetMaxFaces(max_faces) => {
+                if permission.allowed().await {
+                    self.store.set_max_faces(id, max_faces).await;
+                    CommandResult::SetMaxFaces
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::GetDicePolicy => {
+                CommandResult::GetDicePolicy(lookup.dice_policy.clone())
+            }
+            commands::Command::TrailingOperator(text) => CommandResult::TrailingOperator(text),
+            commands::Command::InvalidAliasCall(err) => CommandResult::InvalidAliasCall(err),
+            // only a whole message consists of several commands, see eval
+            commands::Command::Multiple(_) => return None,
+            commands::Command::Pause => {
+                if permission.allowed().await {
+                    self.store.set_paused(id, true).await;
+                    CommandResult::Pause
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::Reload => {
+                if permission.allowed().await {
+                    self.store.evict(id).await;
+                    CommandResult::Reload
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+            commands::Command::Resume => {
+                if permission.allowed().await {
+                    self.store.set_paused(id, false).await;
+                    CommandResult::Resume
+                } else if lookup.paused {
+                    return None;
+                } else {
+                    CommandResult::InsufficentPermission
+                }
+            }
+        })
+    }
+}
+/// logs every roll of `result` with its outcome and how long it took
+fn log_rolls<Id: ClientId>(id: &Id, command: &'static str, result: &CommandResult) {
+    let rolls = match result {
+        CommandResult::Roll(rolls, _, _) | CommandResult::PrivateRoll(rolls, _, _) => {
+            rolls.as_slice()
         }
+        CommandResult::GenStats(roll, _, _) => std::slice::from_ref(roll.as_ref()),
+        _ => return,
+    };
+    for roll in rolls {
+        let error = roll.roll.as_ref().err().map(|err| format!("{:?}", err));
+        let seed = roll.seed.map(hex::encode);
+        log::info!(
+            client:? = id,
+            command,
+            expression = roll.text.as_str(),
+            error = error.as_deref(),
+            duration_ms = roll.duration.map(|d| d.as_secs_f64() * 1000.0),
+            seed = seed.as_deref();
+            "roll result"
+        );
     }
 }
+
 pub struct ClientUtilsBuilder {
     pub(crate) rolls: Arc<RollExecutor>,
     pub(crate) storage: Arc<GlobalStorage>,
@@ -162,19 +1231,51 @@ impl ClientUtilsBuilder {
     pub fn get<Id: ClientId, S: ToString>(
         &mut self,
         client_type: S,
-        channel_size: usize,
         cache_size: usize,
     ) -> ClientUtils<Id> {
-        let (storage, join) =
-            StorageHandle::new(client_type, self.storage.clone(), channel_size, cache_size);
+        let (storage, join) = StorageHandle::new(client_type, self.storage.clone(), cache_size);
         self.join_handles.push(join);
         ClientUtils {
             roll: self.rolls.clone(),
             store: storage,
+            modifiers: Modifiers::new(),
+            last_rolls: LastRolls::new(cache_size),
+            dm_aliases: None,
+            personal_aliases: false,
+            alias_limits: AliasLimits::default(),
+            rate_limiter: RateLimiter::new(Some(RateLimit::default())),
+            history_size: DEFAULT_HISTORY_SIZE,
         }
     }
     pub fn get_from_config<Id: ClientId>(&mut self, config: ClientUtilsConfig) -> ClientUtils<Id> {
-        self.get(config.client_type, config.channel_size, config.cache_size)
+        let mut utils = self.get(config.client_type, config.cache_size);
+        utils.alias_limits = config.alias_limits;
+        utils.rate_limiter = RateLimiter::new(config.rate_limit);
+        utils.history_size = config.history_size;
+        utils
+    }
+    /// rolls and storage for tests, the database is kept in memory
+    #[cfg(test)]
+    pub(crate) async fn new_in_memory() -> ClientUtilsBuilder {
+        let (_, stop) = tokio::sync::watch::channel(false);
+        let (roll_handle, rolls) = RollExecutor::new(
+            1,
+            Duration::from_secs(2),
+            Duration::from_secs(300),
+            16,
+            1000,
+            stop,
+        )
+        .await;
+        let (storage, db_handle) = GlobalStorage::new_in_memory(8);
+        ClientUtilsBuilder {
+            rolls: Arc::new(rolls),
+            storage: Arc::new(storage),
+            join_handles: vec![
+                roll_handle,
+                tokio::task::spawn_blocking(move || db_handle.join().unwrap()),
+            ],
+        }
     }
     pub async fn wait(self) {
         let handles = self.join_handles;
@@ -189,9 +1290,13 @@ impl ClientUtilsBuilder {
 
 #[derive(Debug, Clone)]
 pub struct ClientUtilsConfig {
-    pub channel_size: usize,
     pub cache_size: usize,
     pub client_type: String,
+    pub alias_limits: AliasLimits,
+    /// `None` if rolls aren't limited
+    pub rate_limit: Option<RateLimit>,
+    /// rolls remembered per user, 0 turns the history off
+    pub history_size: usize,
 }
 
 impl ClientUtilsConfig {
@@ -200,40 +1305,585 @@ impl ClientUtilsConfig {
         config: &mut Map<String, Value>,
     ) -> ClientUtilsConfig {
         let client = client_type.to_string();
-        let channel_size: usize = match config
-            .get("queue_size")
+        let cache_size: usize = match config
+            .get("cache_size")
             .and_then(|v| v.as_integer())
             .and_then(|i| i.try_into().ok())
         {
             Some(i) => i,
             None => {
                 log::warn!(
-                    "Unable to read queue_size for {}, using default of 64",
+                    "Unable to read cache_size for {}, using default of 1024",
                     &client
                 );
-                config.insert("queue_size".to_string(), Value::from(64));
-                64
+                config.insert("cache_size".to_string(), Value::from(1024));
+                1024
             }
         };
-        let cache_size: usize = match config
-            .get("cache_size")
+        let defaults = AliasLimits::default();
+        let max_aliases: usize = match config
+            .get("max_aliases")
             .and_then(|v| v.as_integer())
             .and_then(|i| i.try_into().ok())
         {
             Some(i) => i,
             None => {
                 log::warn!(
-                    "Unable to read cache_size for {}, using default of 1024",
-                    &client
+                    "Unable to read max_aliases for {}, using default of {}",
+                    &client,
+                    defaults.max_aliases
                 );
-                config.insert("cache_size".to_string(), Value::from(1024));
-                1024
+                config.insert(
+                    "max_aliases".to_string(),
+                    Value::from(defaults.max_aliases as i64),
+                );
+                defaults.max_aliases
+            }
+        };
+        let max_alias_len: usize = match config
+            .get("max_alias_len")
+            .and_then(|v| v.as_integer())
+            .and_then(|i| i.try_into().ok())
+        {
+            Some(i) => i,
+            None => {
+                log::warn!(
+                    "Unable to read max_alias_len for {}, using default of {}",
+                    &client,
+                    defaults.max_alias_len
+                );
+                config.insert(
+                    "max_alias_len".to_string(),
+                    Value::from(defaults.max_alias_len as i64),
+                );
+                defaults.max_alias_len
+            }
+        };
+        let default_limit = RateLimit::default();
+        let rate_limit_rolls: u32 = match config
+            .get("rate_limit_rolls")
+            .and_then(|v| v.as_integer())
+            .and_then(|i| i.try_into().ok())
+        {
+            Some(i) => i,
+            None => {
+                log::warn!(
+                    "Unable to read rate_limit_rolls for {}, using default of {}",
+                    &client,
+                    default_limit.rolls
+                );
+                config.insert(
+                    "rate_limit_rolls".to_string(),
+                    Value::from(default_limit.rolls),
+                );
+                default_limit.rolls
+            }
+        };
+        let rate_limit_window_s: u64 = match config
+            .get("rate_limit_window_s")
+            .and_then(|v| v.as_integer())
+            .and_then(|i| i.try_into().ok())
+        {
+            Some(i) => i,
+            None => {
+                log::warn!(
+                    "Unable to read rate_limit_window_s for {}, using default of {}",
+                    &client,
+                    default_limit.window.as_secs()
+                );
+                config.insert(
+                    "rate_limit_window_s".to_string(),
+                    Value::from(default_limit.window.as_secs() as i64),
+                );
+                default_limit.window.as_secs()
+            }
+        };
+        let history_size: usize = match config
+            .get("history_size")
+            .and_then(|v| v.as_integer())
+            .and_then(|i| i.try_into().ok())
+        {
+            Some(i) => i,
+            None => {
+                log::warn!(
+                    "Unable to read history_size for {}, using default of {}",
+                    &client,
+                    DEFAULT_HISTORY_SIZE
+                );
+                config.insert(
+                    "history_size".to_string(),
+                    Value::from(DEFAULT_HISTORY_SIZE as i64),
+                );
+                DEFAULT_HISTORY_SIZE
             }
         };
         ClientUtilsConfig {
-            channel_size,
             cache_size,
             client_type: client,
+            history_size,
+            alias_limits: AliasLimits {
+                max_aliases,
+                max_alias_len,
+            },
+            // 0 rolls turn the limit off
+            rate_limit: if rate_limit_rolls == 0 {
+                None
+            } else {
+                Some(RateLimit {
+                    rolls: rate_limit_rolls,
+                    window: Duration::from_secs(rate_limit_window_s),
+                })
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup() -> storage::Lookup {
+        storage::Lookup {
+            channel: None,
+            command_prefix: "rrb!".to_string(),
+            roll_prefixes: Vec::new(),
+            aliases: Vec::new(),
+            roll_info: false,
+            paused: false,
+            alias_hints: false,
+            list_order: ListOrder::Unsorted,
+            roll_shorthand: None,
+            stats_roll: None,
+            stats_min_total: None,
+            critical_message: Some("🎯 Critical Hit! ({value})".to_string()),
+            fumble_message: None,
+            show_luck: false,
+            privileged_roles: Vec::new(),
+            dice_policy: DicePolicy::default(),
+        }
+    }
+
+    #[test]
+    fn test_criticals() {
+        let mut roll = RollExprResult {
+            roll: Ok(vec![
+                (25, vec![20], vec![DieOutcome::Kept(20)]),
+                (6, vec![1], vec![DieOutcome::Kept(1)]),
+                (13, vec![8], vec![DieOutcome::Kept(8)]),
+                (
+                    25,
+                    vec![1, 20],
+                    vec![DieOutcome::Dropped(1), DieOutcome::Kept(20)],
+                ),
+            ]),
+            text: "4{2d20k1 + 5}".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: true,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            roll.criticals(),
+            vec![
+                (25, Critical::Hit),
+                (6, Critical::Fumble),
+                (25, Critical::Hit)
+            ]
+        );
+        roll.announce_criticals(&lookup());
+        assert_eq!(
+            roll.announcements,
+            vec!["🎯 Critical Hit! (25)", "🎯 Critical Hit! (25)"]
+        );
+        roll.natural_d20 = false;
+        assert_eq!(roll.criticals(), Vec::new());
+    }
+
+    #[tokio::test]
+    async fn test_permission() {
+        let gm = || {
+            std::future::ready(Access {
+                administrator: false,
+                roles: vec!["7".to_string()],
+            })
+        };
+        let mut permission = Permission::new(gm, vec!["7".to_string()]);
+        assert!(permission.allowed().await);
+        assert!(!permission.administrator().await);
+        let mut permission = Permission::new(gm, vec!["8".to_string()]);
+        assert!(!permission.allowed().await);
+        let mut permission = Permission::new(|| std::future::ready(Access::from(true)), vec![]);
+        assert!(permission.allowed().await);
+        assert!(permission.administrator().await);
+    }
+
+    #[tokio::test]
+    async fn test_eval_in_memory() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        assert_eq!(
+            utils
+                .eval(1, "user", None, "rrb!alias add atk 1d20+5", admin)
+                .await,
+            Some(CommandResult::AddAlias)
+        );
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!alias list", admin).await,
+            Some(CommandResult::ListAliases(vec![(
+                "atk".to_string(),
+                "1d20 + 5".to_string()
+            )]))
+        );
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_show_luck() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        let statistics = |result: Option<CommandResult>| match result {
+            Some(CommandResult::Roll(mut rolls, _, _)) => rolls.remove(0).statistics,
+            result => panic!("no roll: {:?}", result),
+        };
+        assert_eq!(
+            statistics(utils.eval(1, "user", None, "rrb!r 1d20", admin).await),
+            None
+        );
+        utils
+            .eval(1, "user", None, "rrb!luck set true", admin)
+            .await;
+        assert!(statistics(utils.eval(1, "user", None, "rrb!r 1d20", admin).await).is_some());
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_prefix_conflict() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!rp add r!", admin).await,
+            Some(CommandResult::AddRollPrefix(Ok(())))
+        );
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!cp set r!", admin).await,
+            Some(CommandResult::PrefixConflict("r!".to_string()))
+        );
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!rp add rrb!", admin).await,
+            Some(CommandResult::PrefixConflict("rrb!".to_string()))
+        );
+        assert_eq!(
+            utils
+                .eval(1, "user", Some("7"), "rrb!cp c s r!", admin)
+                .await,
+            Some(CommandResult::PrefixConflict("r!".to_string()))
+        );
+        assert_eq!(
+            utils
+                .eval(1, "user", Some("7"), "rrb!rp c a rrb!", admin)
+                .await,
+            Some(CommandResult::PrefixConflict("rrb!".to_string()))
+        );
+        // nothing was changed, rolls still work
+        assert!(matches!(
+            utils.eval(1, "user", None, "r!1", admin).await,
+            Some(CommandResult::Roll(..))
+        ));
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!cp set ?", admin).await,
+            Some(CommandResult::SetCommandPrefix("?".to_string()))
+        );
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_repeat_last() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        let results = |result: Option<CommandResult>| match result {
+            Some(CommandResult::Roll(rolls, _, _)) => rolls
+                .into_iter()
+                .map(|roll| roll.roll.unwrap()[0].0)
+                .collect::<Vec<_>>(),
+            result => panic!("no roll: {:?}", result),
+        };
+        assert_eq!(
+            utils.eval(1, "user", Some("7"), "rrb!!", admin).await,
+            Some(CommandResult::NothingToRepeat)
+        );
+        assert_eq!(
+            results(utils.eval(1, "user", Some("7"), "rrb!r 3; 4", admin).await),
+            vec![3, 4]
+        );
+        assert_eq!(
+            results(utils.eval(1, "user", Some("7"), "rrb!r .", admin).await),
+            vec![3, 4]
+        );
+        // the modifier of the user applies to the repeated roll
+        utils.eval(1, "user", Some("7"), "rrb!m s 2", admin).await;
+        assert_eq!(
+            results(utils.eval(1, "user", Some("7"), "rrb!!", admin).await),
+            vec![5, 6]
+        );
+        // other users and channels have nothing to repeat
+        for (user, channel) in [("other", Some("7")), ("user", Some("8")), ("user", None)] {
+            assert_eq!(
+                utils.eval(1, user, channel, "rrb!!", admin).await,
+                Some(CommandResult::NothingToRepeat)
+            );
+        }
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_reroll() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        let rolled = |result: Option<CommandResult>| match result {
+            Some(CommandResult::Roll(rolls, _, _)) => rolls
+                .into_iter()
+                .map(|roll| (roll.roll.unwrap()[0].0, roll.expression.unwrap()))
+                .unzip::<_, _, Vec<_>, Vec<_>>(),
+            result => panic!("no roll: {:?}", result),
+        };
+        utils
+            .eval(1, "user", Some("7"), "rrb!alias add atk 3", admin)
+            .await;
+        let (results, expressions) = rolled(utils.eval(1, "user", Some("7"), "atk", admin).await);
+        assert_eq!(results, vec![3]);
+        // neither the alias nor the command prefix are needed any more
+        utils
+            .eval(1, "user", Some("7"), "rrb!alias remove atk", admin)
+            .await;
+        utils
+            .eval(1, "user", Some("7"), "rrb!cp set ?", admin)
+            .await;
+        assert_eq!(utils.eval(1, "user", Some("7"), "atk", admin).await, None);
+        utils.eval(1, "user", Some("7"), "?m s 2", admin).await;
+        let (results, _) = rolled(
+            utils
+                .reroll(1, "user", Some("7"), expressions.clone())
+                .await,
+        );
+        assert_eq!(results, vec![5]);
+        // the rerolled expressions can be repeated like any other roll
+        utils.eval(1, "user", Some("7"), "?r 1", admin).await;
+        utils
+            .reroll(1, "user", Some("7"), expressions.clone())
+            .await;
+        let (results, _) = rolled(utils.eval(1, "user", Some("7"), "?!", admin).await);
+        assert_eq!(results, vec![5]);
+        // a paused client doesn't reroll
+        utils.eval(1, "user", Some("7"), "?pause", admin).await;
+        assert_eq!(utils.reroll(1, "user", Some("7"), expressions).await, None);
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_dice_policy() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        let user = || std::future::ready(Access::from(false));
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!dice ban x", user).await,
+            Some(CommandResult::InsufficentPermission)
+        );
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!dice ban x", admin).await,
+            Some(CommandResult::SetDiceBanned(
+                DiceKind::Multiply,
+                true,
+                Ok(())
+            ))
+        );
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!dice ban x", admin).await,
+            Some(CommandResult::SetDiceBanned(
+                DiceKind::Multiply,
+                true,
+                Err(())
+            ))
+        );
+        assert_eq!(
+            utils
+                .eval(1, "user", None, "rrb!dice max-faces 20", admin)
+                .await,
+            Some(CommandResult::SetMaxFaces)
+        );
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!dice get", user).await,
+            Some(CommandResult::GetDicePolicy(DicePolicy {
+                banned: vec![DiceKind::Multiply],
+                max_faces: Some(20),
+            }))
+        );
+        for (roll, dice) in [
+            ("rrb!r 2d6x + 1", "2d6x"),
+            ("rrb!r 1d20; 1d100", "1d100"),
+            ("rrb!gmroll 1d100", "1d100"),
+            ("rrb!r 1d(21)", "1d(21)"),
+            ("rrb!r 1d(6)", "1d(6)"),
+            ("rrb!odds 1d100", "1d100"),
+        ] {
+            assert_eq!(
+                utils.eval(1, "user", None, roll, user).await,
+                Some(CommandResult::DiceTypeNotAllowed(dice.to_string()))
+            );
+        }
+        assert!(matches!(
+            utils.eval(1, "user", None, "rrb!r 1d20 + 1dF", user).await,
+            Some(CommandResult::Roll(..))
+        ));
+        // other clients are not affected
+        assert!(matches!(
+            utils.eval(2, "user", None, "rrb!r 2d6x", user).await,
+            Some(CommandResult::Roll(..))
+        ));
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_dice_policy_genstats() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        utils
+            .eval(1, "user", None, "rrb!dice max-faces 20", admin)
+            .await;
+        // the default stats roll only uses d6
+        assert!(matches!(
+            utils.eval(1, "user", None, "rrb!gs", admin).await,
+            Some(CommandResult::GenStats(..))
+        ));
+        utils
+            .eval(1, "user", None, "rrb!gs set 6{1d100}", admin)
+            .await;
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!gs", admin).await,
+            Some(CommandResult::DiceTypeNotAllowed("1d100".to_string()))
+        );
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_dice_policy_pool_odds() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        utils
+            .eval(1, "user", None, "rrb!dice max-faces 10", admin)
+            .await;
+        assert!(matches!(
+            utils.eval(1, "user", None, "rrb!po 10d10>=8", admin).await,
+            Some(CommandResult::PoolOdds(_, Ok(_)))
+        ));
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!po 10d12>=8", admin).await,
+            Some(CommandResult::DiceTypeNotAllowed("10d12".to_string()))
+        );
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_private_roll() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let utils: ClientUtils<u64> = builder.get("test", 8);
+        let admin = || std::future::ready(Access::from(true));
+        match utils.eval(1, "user", None, "rrb!gmroll 7", admin).await {
+            Some(CommandResult::PrivateRoll(rolls, _, _)) => {
+                assert_eq!(rolls[0].roll.as_ref().unwrap()[0].0, 7)
+            }
+            result => panic!("no private roll: {:?}", result),
+        }
+        // secret rolls are kept out of the history, which is shown to everyone
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!history", admin).await,
+            Some(CommandResult::History(Vec::new()))
+        );
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_roll_history() {
+        let mut builder = ClientUtilsBuilder::new_in_memory().await;
+        let mut utils: ClientUtils<u64> = builder.get("test", 8);
+        utils.history_size = 2;
+        let admin = || std::future::ready(Access::from(true));
+        for roll in ["rrb!r 1", "rrb!r 2; 3{4}", "rrb!r 1d0"] {
+            utils.eval(1, "user", None, roll, admin).await;
+        }
+        let entry = |expression: &str, results: Vec<i64>| HistoryEntry {
+            expression: expression.to_string(),
+            label: None,
+            results,
+        };
+        // only the latest successful rolls are kept, the latest first
+        assert_eq!(
+            utils.eval(1, "user", None, "rrb!history", admin).await,
+            Some(CommandResult::History(vec![
+                entry("3{4}", vec![4, 4, 4]),
+                entry("2", vec![2])
+            ]))
+        );
+        assert_eq!(
+            utils.eval(1, "other", None, "rrb!history", admin).await,
+            Some(CommandResult::History(Vec::new()))
+        );
+        drop(utils);
+        builder.wait().await;
+    }
+
+    #[test]
+    fn test_alias_page() {
+        let aliases: Vec<_> = (0..45)
+            .map(|i| (format!("alias{}", i), "1d20".to_string()))
+            .collect();
+        let page = AliasPage::new(aliases.clone(), 3);
+        assert_eq!(page.pages, 3);
+        assert_eq!(page.aliases, aliases[40..]);
+        assert_eq!(AliasPage::new(aliases.clone(), 1).aliases, aliases[..20]);
+        assert!(AliasPage::new(aliases, 4).aliases.is_empty());
+        assert_eq!(AliasPage::new(Vec::new(), 1).pages, 0);
+    }
+
+    #[test]
+    fn test_stored_fudge() {
+        let expr = VersionedRollExpr::V2(robins_dice_roll::parser::parse_labeled("4dF").unwrap().1);
+        let stored = serde_json::to_string(&expr).unwrap();
+        // fudge dice were stored without sides before they had any
+        let legacy = stored.replace(r#"{"Fudge":{"sides":1}}"#, r#""Fudge""#);
+        assert_ne!(legacy, stored);
+        assert_eq!(
+            serde_json::from_str::<VersionedRollExpr>(&legacy).unwrap(),
+            expr
+        );
+        assert_eq!(
+            serde_json::from_str::<VersionedRollExpr>(&stored).unwrap(),
+            expr
+        );
+    }
+}
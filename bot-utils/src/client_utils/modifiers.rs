@@ -0,0 +1,116 @@
+use super::{storage::ClientId, VersionedRollExpr};
+use robins_dice_roll::dice_types::{Expression, LabeledExpression, Operation, Term};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// temporary per user modifiers, added to every roll of that user until cleared
+///
+/// modifiers are only kept in memory and do not survive a restart
+#[derive(Debug, Clone)]
+pub(crate) struct Modifiers<Id: ClientId> {
+    modifiers: Arc<Mutex<HashMap<(Id, String), i64>>>,
+}
+
+impl<Id: ClientId> Modifiers<Id> {
+    pub(crate) fn new() -> Modifiers<Id> {
+        Modifiers {
+            modifiers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub(crate) fn get(&self, id: &Id, user: &str) -> Option<i64> {
+        self.modifiers
+            .lock()
+            .unwrap()
+            .get(&(id.clone(), user.to_string()))
+            .copied()
+    }
+
+    /// set the modifier of `user`, `None` or `Some(0)` clear it
+    pub(crate) fn set(&self, id: Id, user: &str, modifier: Option<i64>) {
+        let mut modifiers = self.modifiers.lock().unwrap();
+        match modifier {
+            Some(m) if m != 0 => {
+                modifiers.insert((id, user.to_string()), m);
+            }
+            _ => {
+                modifiers.remove(&(id, user.to_string()));
+            }
+        }
+    }
+}
+
+/// `term + modifier`, written as a subtraction for negative modifiers
+fn add_modifier(term: &Term, modifier: i64) -> Term {
+    let (op, constant) = match modifier.checked_neg() {
+        Some(negated) if modifier < 0 => (Operation::Sub, negated),
+        _ => (Operation::Add, modifier),
+    };
+    Term::Calculation(
+        Box::new(term.clone()),
+        op,
+        Box::new(Term::Constant(constant)),
+    )
+}
+
+fn expression_with_modifier(expr: &Expression, modifier: i64) -> Expression {
+    match expr {
+        Expression::Simple(t) => Expression::Simple(add_modifier(t, modifier)),
+        // every entry of a list gets the modifier
+        Expression::List(n, t) => Expression::List(*n, add_modifier(t, modifier)),
+    }
+}
+
+impl VersionedRollExpr {
+    /// copy of this expression with `modifier` added to each result
+    pub fn with_modifier(&self, modifier: i64) -> VersionedRollExpr {
+        match self {
+            VersionedRollExpr::V1(e) => {
+                VersionedRollExpr::V1(expression_with_modifier(e, modifier))
+            }
+            VersionedRollExpr::V2(LabeledExpression::Unlabeled(e)) => VersionedRollExpr::V2(
+                LabeledExpression::Unlabeled(expression_with_modifier(e, modifier)),
+            ),
+            VersionedRollExpr::V2(LabeledExpression::Labeled(e, l)) => VersionedRollExpr::V2(
+                LabeledExpression::Labeled(expression_with_modifier(e, modifier), l.clone()),
+            ),
+            // templates are filled in before they are rolled
+            VersionedRollExpr::Template(_) => self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modifiers() {
+        let modifiers = Modifiers::<u64>::new();
+        modifiers.set(1, "a", Some(2));
+        modifiers.set(2, "a", Some(-1));
+        assert_eq!(modifiers.get(&1, "a"), Some(2));
+        assert_eq!(modifiers.get(&2, "a"), Some(-1));
+        assert_eq!(modifiers.get(&1, "b"), None);
+        modifiers.set(1, "a", Some(0));
+        assert_eq!(modifiers.get(&1, "a"), None);
+        modifiers.set(2, "a", None);
+        assert_eq!(modifiers.get(&2, "a"), None);
+    }
+
+    #[test]
+    fn test_with_modifier() {
+        let expr = VersionedRollExpr::V2(
+            robins_dice_roll::parser::parse_labeled("3{1d20}#attack")
+                .unwrap()
+                .1,
+        );
+        assert_eq!(expr.with_modifier(2).to_string(), "3{1d20 + 2}");
+        assert_eq!(expr.with_modifier(-2).to_string(), "3{1d20 - 2}");
+        let expr =
+            VersionedRollExpr::V2(robins_dice_roll::parser::parse_labeled("2 * 3").unwrap().1);
+        assert_eq!(expr.with_modifier(1).to_string(), "2 * 3 + 1");
+    }
+}
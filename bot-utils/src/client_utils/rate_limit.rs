@@ -0,0 +1,118 @@
+use super::storage::ClientId;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// at most `rolls` rolls of a single user within `window`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub rolls: u32,
+    pub window: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            rolls: 10,
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+/// users whose rolls have all left the window are dropped once this many are tracked
+const PRUNE_USERS: usize = 1024;
+
+/// times of the recent rolls of each user, oldest first
+type RecentRolls<Id> = HashMap<(Id, String), VecDeque<Instant>>;
+
+/// sliding window of the recent rolls of each user
+///
+/// like modifiers the rolls are only kept in memory
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter<Id: ClientId> {
+    limit: Option<RateLimit>,
+    rolls: Arc<Mutex<RecentRolls<Id>>>,
+}
+
+impl<Id: ClientId> RateLimiter<Id> {
+    /// `None` never limits any roll
+    pub(crate) fn new(limit: Option<RateLimit>) -> RateLimiter<Id> {
+        RateLimiter {
+            limit,
+            rolls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// records a roll of `user` at `now`, or the time until the next one is allowed
+    pub(crate) fn check(&self, id: &Id, user: &str, now: Instant) -> Result<(), Duration> {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let mut rolls = self.rolls.lock().unwrap();
+        if rolls.len() >= PRUNE_USERS {
+            rolls.retain(|_, r| r.back().is_some_and(|last| *last + limit.window > now));
+        }
+        let recent = rolls.entry((id.clone(), user.to_string())).or_default();
+        while recent
+            .front()
+            .is_some_and(|first| *first + limit.window <= now)
+        {
+            recent.pop_front();
+        }
+        match recent.front() {
+            Some(first) if recent.len() >= limit.rolls as usize => Err(*first + limit.window - now),
+            _ => {
+                recent.push_back(now);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit() {
+        let limiter = RateLimiter::<u64>::new(Some(RateLimit {
+            rolls: 2,
+            window: Duration::from_secs(10),
+        }));
+        let start = Instant::now();
+        assert_eq!(limiter.check(&1, "user", start), Ok(()));
+        assert_eq!(
+            limiter.check(&1, "user", start + Duration::from_secs(4)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.check(&1, "user", start + Duration::from_secs(6)),
+            Err(Duration::from_secs(4))
+        );
+        // other users and clients have windows of their own
+        assert_eq!(
+            limiter.check(&1, "other", start + Duration::from_secs(6)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.check(&2, "user", start + Duration::from_secs(6)),
+            Ok(())
+        );
+        // the first roll left the window
+        assert_eq!(
+            limiter.check(&1, "user", start + Duration::from_secs(10)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.check(&1, "user", start + Duration::from_secs(11)),
+            Err(Duration::from_secs(3))
+        );
+        let unlimited = RateLimiter::<u64>::new(None);
+        for _ in 0..100 {
+            assert_eq!(unlimited.check(&1, "user", start), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,1002 @@
+//! text of roll results, independent of the chat platform showing it
+
+use super::{
+    DieOutcome, Distribution, EvaluationErrors, Filter, HistoryEntry, ListOrder, NarrativeDie,
+    NarrativeRoll, PoolOdds, RollExprResult, RolledResult, Symbol,
+};
+
+/// markup the rendered text is formatted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Markdown,
+    BBCode,
+    /// the subset of HTML understood by Telegram
+    Html,
+    Plain,
+}
+
+impl Style {
+    /// `text` as it is, with the characters the markup would interpret escaped
+    pub fn escape(self, text: &str) -> String {
+        match self {
+            Style::Html => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;"),
+            Style::Markdown | Style::BBCode | Style::Plain => text.to_string(),
+        }
+    }
+
+    pub fn code(self, text: &str) -> String {
+        match self {
+            Style::Markdown => format!("`{}`", text),
+            Style::BBCode => format!("[code]{}[/code]", text),
+            Style::Html => format!("<code>{}</code>", text),
+            Style::Plain => text.to_string(),
+        }
+    }
+
+    pub fn bold(self, text: &str) -> String {
+        match self {
+            Style::Markdown => format!("**{}**", text),
+            Style::BBCode => format!("[b]{}[/b]", text),
+            Style::Html => format!("<b>{}</b>", text),
+            Style::Plain => text.to_string(),
+        }
+    }
+
+    pub fn italic(self, text: &str) -> String {
+        match self {
+            Style::Markdown => format!("*{}*", text),
+            Style::BBCode => format!("[i]{}[/i]", text),
+            Style::Html => format!("<i>{}</i>", text),
+            Style::Plain => text.to_string(),
+        }
+    }
+
+    /// struck through, plain text puts it in parentheses instead
+    pub fn strike(self, text: &str) -> String {
+        match self {
+            Style::Markdown => format!("~~{}~~", text),
+            Style::BBCode => format!("[s]{}[/s]", text),
+            Style::Html => format!("<s>{}</s>", text),
+            Style::Plain => format!("({})", text),
+        }
+    }
+
+    /// `text` in a preformatted block, its lines keep their alignment
+    pub fn block(self, text: &str) -> String {
+        match self {
+            Style::Markdown => format!("```\n{}\n```", text),
+            Style::BBCode => format!("[code]{}[/code]", text),
+            Style::Html => format!("<pre>{}</pre>", self.escape(text)),
+            Style::Plain => text.to_string(),
+        }
+    }
+
+    /// `lines` set apart from the text before them
+    pub fn quote(self, lines: &[String]) -> String {
+        match self {
+            Style::Markdown => lines
+                .iter()
+                .map(|l| format!("> {}", l))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Style::BBCode => format!("[quote]{}[/quote]", lines.join("\n")),
+            Style::Html => format!("<blockquote>{}</blockquote>", lines.join("\n")),
+            Style::Plain => lines
+                .iter()
+                .map(|l| format!("  {}", l))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// results of a roll in display `order`, the dice of each entry stay with its total
+pub fn ordered(results: &[RolledResult], order: ListOrder) -> Vec<&RolledResult> {
+    let mut ordered: Vec<&RolledResult> = results.iter().collect();
+    match order {
+        ListOrder::Unsorted => {}
+        ListOrder::Ascending => ordered.sort_by_key(|r| r.0),
+        ListOrder::Descending => ordered.sort_by_key(|r| std::cmp::Reverse(r.0)),
+    }
+    ordered
+}
+
+/// longer expressions are cut off in the timeout message
+const TIMEOUT_EXPRESSION_LEN: usize = 64;
+
+/// `text` cut off after `max` characters
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// message for a failed roll of `text`
+///
+/// `timeout_message` is used for timeouts as it is, with `{expression}`
+/// replaced by the rolled expression, lists also tell how many of their rolls were done
+pub fn error_message(
+    error: &EvaluationErrors,
+    text: &str,
+    timeout_message: &str,
+    style: Style,
+) -> String {
+    match error {
+        EvaluationErrors::DivideByZero => style.italic("Division by 0 detected"),
+        EvaluationErrors::Timeout { completed, total } => {
+            let message = timeout_message.replace(
+                "{expression}",
+                &style.escape(&truncate(text, TIMEOUT_EXPRESSION_LEN).replace('`', "'")),
+            );
+            if *total > 1 {
+                format!(
+                    "{}\n{}",
+                    message,
+                    style.italic(&format!("timed out after {} of {} rolls", completed, total))
+                )
+            } else {
+                message
+            }
+        }
+        EvaluationErrors::Overflow => style.italic("Overflow detected"),
+        EvaluationErrors::NegativeExponent => style.italic("Negative exponent detected"),
+        EvaluationErrors::DiceLimitExceeded => style.italic("Too many dice in a single roll"),
+        EvaluationErrors::Internal => style.italic("Something went wrong while rolling"),
+        EvaluationErrors::UndefinedVariable(name) => style.italic(&format!(
+            "Unknown name {}",
+            style.escape(&truncate(name, TIMEOUT_EXPRESSION_LEN))
+        )),
+        // errors added to the dice roller later
+        _ => style.italic("Something went wrong while rolling"),
+    }
+}
+
+/// the rolled expression with its results, followed by its comments and labeled terms
+pub fn render_content(roll: &RollExprResult, results: &[&RolledResult], style: Style) -> String {
+    // comments are listed below the roll instead of inline
+    let text = roll
+        .comments
+        .iter()
+        .fold(roll.text.clone(), |text, (comment, _)| {
+            text.replacen(&format!(" [{}]", comment), "", 1)
+        });
+    let roll_line = format!(
+        "{} {} [{}]{}",
+        style.escape(&text),
+        style.escape("=>"),
+        results
+            .iter()
+            .map(|result| style.code(&result.0.to_string()))
+            .reduce(|r1, r2| format!("{}, {}", r1, r2))
+            .unwrap_or_else(|| " ".to_string()),
+        if roll.successes { " successes" } else { "" }
+    );
+    let roll_line = match roll.modifier {
+        Some(m) => format!(
+            "{} {}",
+            roll_line,
+            style.italic(&format!("(modifier {:+})", m))
+        ),
+        None => roll_line,
+    };
+    let details: Vec<String> = roll
+        .comments
+        .iter()
+        .map(|(comment, term)| {
+            format!(
+                "{} {}",
+                style.code(&style.escape(term)),
+                style.italic(&style.escape(comment))
+            )
+        })
+        .chain(roll.breakdown.iter().map(|(label, value)| {
+            format!(
+                "{}: {}",
+                style.escape(label),
+                style.code(&value.to_string())
+            )
+        }))
+        .collect();
+    let roll_line = if details.is_empty() {
+        roll_line
+    } else {
+        format!("{}\n{}", roll_line, style.quote(&details))
+    };
+    if let Some(l) = &roll.label {
+        format!("{}\n{}", style.bold(&style.escape(l)), roll_line)
+    } else {
+        roll_line
+    }
+}
+
+/// a single die, bold if it passes `filter` and struck through if it fails
+fn render_die(die: i64, filter: Option<(Filter, u32)>, style: Style) -> String {
+    let text = style.code(&die.to_string());
+    match filter {
+        Some((filter, target)) if filter.matches(die, target) => style.bold(&text),
+        Some(_) => style.strike(&text),
+        None => text,
+    }
+}
+
+/// a single die, struck through if it doesn't count and bold if it passed `filter`
+fn render_outcome(outcome: DieOutcome, filter: Option<(Filter, u32)>, style: Style) -> String {
+    let text = style.code(&outcome.value().to_string());
+    match outcome {
+        DieOutcome::Kept(_) if filter.is_some() => style.bold(&text),
+        DieOutcome::Kept(_) => text,
+        DieOutcome::Dropped(_) | DieOutcome::Filtered(_) => style.strike(&text),
+    }
+}
+
+/// the dice of each result on a line of its own
+pub fn render_dice(
+    results: &[&RolledResult],
+    filter: Option<(Filter, u32)>,
+    style: Style,
+) -> String {
+    results
+        .iter()
+        .map(|(_, dice, outcomes)| {
+            // rerolled dice have no outcome, those rolls are shown by their filter only
+            let dice: Vec<String> = if dice.len() == outcomes.len() {
+                outcomes
+                    .iter()
+                    .map(|o| render_outcome(*o, filter, style))
+                    .collect()
+            } else {
+                dice.iter().map(|d| render_die(*d, filter, style)).collect()
+            };
+            format!(
+                "[{}]",
+                dice.into_iter()
+                    .reduce(|r1, r2| format!("{}, {}", r1, r2))
+                    .unwrap_or_default()
+            )
+        })
+        .reduce(|r1, r2| format!("{}\n{}", r1, r2))
+        .unwrap_or_default()
+}
+
+/// how many standard deviations a single result is away from the average
+fn render_luck(roll: &RollExprResult, style: Style) -> Option<String> {
+    let z = match (&roll.roll, &roll.statistics) {
+        (Ok(results), Some(statistics)) if results.len() == 1 => {
+            statistics.z_score(results[0].0)?
+        }
+        _ => return None,
+    };
+    Some(style.italic(&if z.abs() < 0.05 {
+        "you rolled exactly average".to_string()
+    } else {
+        format!(
+            "you rolled {:.1}σ {} average",
+            z.abs(),
+            if z > 0.0 { "above" } else { "below" }
+        )
+    }))
+}
+
+/// sum of all results of a list
+pub fn render_total(roll: &RollExprResult, style: Style) -> Option<String> {
+    roll.total
+        .map(|total| format!("Total: {}", style.code(&total.to_string())))
+}
+
+/// seed and evaluation time of a roll, to check how it was made
+pub fn render_audit(roll: &RollExprResult) -> Option<String> {
+    match (&roll.seed, roll.duration) {
+        (Some(seed), Some(duration)) => Some(format!(
+            "seed {} · rolled in {:.2} ms",
+            hex::encode(seed),
+            duration.as_secs_f64() * 1000.0
+        )),
+        _ => None,
+    }
+}
+
+/// the whole result of a roll as a single text, followed by its dice if `show_dice`
+pub fn render(
+    roll: &RollExprResult,
+    order: ListOrder,
+    show_dice: bool,
+    timeout_message: &str,
+    style: Style,
+) -> String {
+    match &roll.roll {
+        Ok(results) => {
+            let results = ordered(results, order);
+            let mut content = render_content(roll, &results, style);
+            if let Some(luck) = render_luck(roll, style) {
+                content = format!("{}\n{}", content, luck);
+            }
+            for announcement in &roll.announcements {
+                content = format!("{}\n{}", content, style.bold(&style.escape(announcement)));
+            }
+            if show_dice {
+                if let Some(total) = render_total(roll, style) {
+                    content = format!("{}\n{}", content, total);
+                }
+                format!("{}\n{}", content, render_dice(&results, roll.filter, style))
+            } else {
+                content
+            }
+        }
+        Err(e) => error_message(e, &roll.text, timeout_message, style),
+    }
+}
+
+/// remembered rolls a line each, in the order they are given
+pub fn render_history(entries: &[HistoryEntry], style: Style) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let line = format!(
+                "{} {} [{}]",
+                style.escape(&entry.expression),
+                style.escape("=>"),
+                entry
+                    .results
+                    .iter()
+                    .map(|r| style.code(&r.to_string()))
+                    .reduce(|r1, r2| format!("{}, {}", r1, r2))
+                    .unwrap_or_else(|| " ".to_string())
+            );
+            match &entry.label {
+                Some(l) => format!("{}: {}", style.bold(&style.escape(l)), line),
+                None => line,
+            }
+        })
+        .reduce(|l1, l2| format!("{}\n{}", l1, l2))
+        .unwrap_or_else(|| style.italic("no rolls yet"))
+}
+
+/// rows with a smaller probability of exactly this value are left out of odds tables
+const MIN_SHOWN_PROBABILITY: f64 = 0.0005;
+const MAX_SHOWN_ROWS: usize = 20;
+
+/// the most likely rows, in their original order
+fn shown_rows<K: Ord + Copy>(rows: impl Iterator<Item = (K, f64)>) -> Vec<(K, f64)> {
+    let mut rows: Vec<(K, f64)> = rows.filter(|(_, p)| *p >= MIN_SHOWN_PROBABILITY).collect();
+    if rows.len() > MAX_SHOWN_ROWS {
+        rows.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        rows.truncate(MAX_SHOWN_ROWS);
+        rows.sort_unstable_by_key(|r| r.0);
+    }
+    rows
+}
+
+/// chances of each number of successes of a pool, as aligned plain text
+pub fn pool_odds_table(odds: &PoolOdds) -> String {
+    shown_rows(odds.distribution.iter().copied().enumerate())
+        .iter()
+        .map(|(k, p)| {
+            format!(
+                "{:>5} | {:>7.2}% | {:>7.2}%",
+                k,
+                p * 100.0,
+                odds.at_least(*k) * 100.0
+            )
+        })
+        .fold("    k | exactly  | at least".to_string(), |table, row| {
+            format!("{}\n{}", table, row)
+        })
+}
+
+/// chances of each result of a roll, as aligned plain text
+pub fn distribution_table(distribution: &Distribution) -> String {
+    let values = (distribution.min..).zip(distribution.probabilities.iter().copied());
+    shown_rows(values)
+        .iter()
+        .map(|(v, p)| {
+            format!(
+                "{:>7} | {:>7.2}% | {:>7.2}%",
+                v,
+                p * 100.0,
+                distribution.at_least(*v) * 100.0
+            )
+        })
+        .fold("  value | exactly  | at least".to_string(), |table, row| {
+            format!("{}\n{}", table, row)
+        })
+}
+
+fn die_name(die: NarrativeDie) -> &'static str {
+    match die {
+        NarrativeDie::Boost => "Boost",
+        NarrativeDie::Setback => "Setback",
+        NarrativeDie::Ability => "Ability",
+        NarrativeDie::Difficulty => "Difficulty",
+        NarrativeDie::Proficiency => "Proficiency",
+        NarrativeDie::Challenge => "Challenge",
+    }
+}
+
+fn face_text(face: &[Symbol]) -> String {
+    if face.is_empty() {
+        "blank".to_string()
+    } else {
+        face.iter()
+            .map(Symbol::to_string)
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+}
+
+/// one line per kind of narrative die with the faces it showed, as plain text
+pub fn render_faces(roll: &NarrativeRoll) -> String {
+    let mut lines: Vec<(NarrativeDie, Vec<String>)> = Vec::new();
+    for (die, face) in &roll.0 {
+        match lines.last_mut() {
+            Some((last, faces)) if last == die => faces.push(face_text(face)),
+            _ => lines.push((*die, vec![face_text(face)])),
+        }
+    }
+    lines
+        .iter()
+        .map(|(die, faces)| format!("{}: {}", die_name(*die), faces.join(", ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const ABILITIES: [&str; 6] = ["STR", "DEX", "CON", "INT", "WIS", "CHA"];
+
+/// ability scores a line each, named in the usual order, followed by their total
+pub fn render_scores(results: &[&RolledResult], style: Style) -> String {
+    let rows = results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let dice = render_dice(&[*r], None, style);
+            match ABILITIES.get(i) {
+                Some(ability) => format!(
+                    "{} {} {}",
+                    style.code(ability),
+                    style.bold(&r.0.to_string()),
+                    dice
+                ),
+                None => format!(
+                    "{} {} {}",
+                    style.code(&(i + 1).to_string()),
+                    style.bold(&r.0.to_string()),
+                    dice
+                ),
+            }
+        })
+        .fold(String::new(), |rows, row| format!("{}{}\n", rows, row));
+    format!(
+        "{}Total: {}",
+        rows,
+        style.bold(
+            &results
+                .iter()
+                .fold(0i64, |sum, r| sum.saturating_add(r.0))
+                .to_string()
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_utils::Statistics;
+
+    #[test]
+    fn test_render_faces() {
+        use Symbol::*;
+        let roll = NarrativeRoll(vec![
+            (NarrativeDie::Ability, &[Success, Advantage]),
+            (NarrativeDie::Ability, &[]),
+            (NarrativeDie::Challenge, &[Despair]),
+        ]);
+        assert_eq!(
+            render_faces(&roll),
+            "Ability: success + advantage, blank\nChallenge: despair"
+        );
+    }
+
+    #[test]
+    fn test_render_scores() {
+        let scores: Vec<RolledResult> = vec![
+            (15, vec![6, 5, 4, 1], Vec::new()),
+            (12, vec![4, 4, 4, 3], Vec::new()),
+            (8, vec![3, 3, 2, 1], Vec::new()),
+            (13, vec![6, 4, 3, 3], Vec::new()),
+            (10, vec![5, 3, 2, 2], Vec::new()),
+            (14, vec![6, 6, 2, 1], Vec::new()),
+        ];
+        assert_eq!(
+            render_scores(&ordered(&scores, ListOrder::Descending), Style::Markdown),
+            "`STR` **15** [`6`, `5`, `4`, `1`]\n\
+             `DEX` **14** [`6`, `6`, `2`, `1`]\n\
+             `CON` **13** [`6`, `4`, `3`, `3`]\n\
+             `INT` **12** [`4`, `4`, `4`, `3`]\n\
+             `WIS` **10** [`5`, `3`, `2`, `2`]\n\
+             `CHA` **8** [`3`, `3`, `2`, `1`]\n\
+             Total: **72**"
+        );
+        assert_eq!(
+            render_scores(&ordered(&scores[..1], ListOrder::Unsorted), Style::Html),
+            "<code>STR</code> <b>15</b> [<code>6</code>, <code>5</code>, <code>4</code>, <code>1</code>]\n\
+             Total: <b>15</b>"
+        );
+    }
+
+    fn list_roll() -> RollExprResult {
+        RollExprResult {
+            roll: Ok(vec![
+                (7, vec![3, 4], Vec::new()),
+                (12, vec![6, 6], Vec::new()),
+                (2, vec![1, 1], Vec::new()),
+                (7, vec![5, 2], Vec::new()),
+            ]),
+            text: "4{2d6}".to_string(),
+            label: Some("loot".to_string()),
+            successes: false,
+            filter: None,
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        }
+    }
+
+    fn pool_roll() -> RollExprResult {
+        RollExprResult {
+            roll: Ok(vec![(2, vec![8, 3, 10, 7], Vec::new())]),
+            text: "4d10>=8s".to_string(),
+            label: None,
+            successes: true,
+            filter: Some((Filter::BiggerEq, 8)),
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        }
+    }
+
+    fn annotated_roll() -> RollExprResult {
+        RollExprResult {
+            roll: Ok(vec![(21, vec![17, 4], Vec::new())]),
+            text: "1d20#attack + 1d6 [fire]".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: vec![("fire".to_string(), "1d6".to_string())],
+            breakdown: vec![("attack".to_string(), 17)],
+            modifier: Some(2),
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        }
+    }
+
+    fn render(roll: &RollExprResult, order: ListOrder, style: Style) -> (String, String) {
+        let results = ordered(roll.roll.as_ref().unwrap(), order);
+        (
+            render_content(roll, &results, style),
+            render_dice(&results, roll.filter, style),
+        )
+    }
+
+    #[test]
+    fn test_render_unsorted() {
+        assert_eq!(
+            render(&list_roll(), ListOrder::Unsorted, Style::Markdown),
+            (
+                "**loot**\n4{2d6} => [`7`, `12`, `2`, `7`]".to_string(),
+                "[`3`, `4`]\n[`6`, `6`]\n[`1`, `1`]\n[`5`, `2`]".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_sorted() {
+        assert_eq!(
+            render(&list_roll(), ListOrder::Ascending, Style::Markdown),
+            (
+                "**loot**\n4{2d6} => [`2`, `7`, `7`, `12`]".to_string(),
+                "[`1`, `1`]\n[`3`, `4`]\n[`5`, `2`]\n[`6`, `6`]".to_string()
+            )
+        );
+        assert_eq!(
+            render(&list_roll(), ListOrder::Descending, Style::Markdown),
+            (
+                "**loot**\n4{2d6} => [`12`, `7`, `7`, `2`]".to_string(),
+                "[`6`, `6`]\n[`3`, `4`]\n[`5`, `2`]\n[`1`, `1`]".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_total() {
+        let mut roll = list_roll();
+        assert_eq!(render_total(&roll, Style::Markdown), None);
+        roll.total = Some(28);
+        assert_eq!(
+            render_total(&roll, Style::Markdown),
+            Some("Total: `28`".to_string())
+        );
+        // only shown together with the dice
+        assert_eq!(
+            super::render(&roll, ListOrder::Unsorted, false, "", Style::Plain),
+            "loot\n4{2d6} => [7, 12, 2, 7]"
+        );
+        assert_eq!(
+            super::render(&roll, ListOrder::Unsorted, true, "", Style::Plain),
+            "loot\n4{2d6} => [7, 12, 2, 7]\nTotal: 28\n[3, 4]\n[6, 6]\n[1, 1]\n[5, 2]"
+        );
+    }
+
+    #[test]
+    fn test_render_comments() {
+        let roll = RollExprResult {
+            roll: Ok(vec![(14, vec![9, 2], Vec::new())]),
+            text: "1d20 + 5 [to hit] + 1d4 [bless]".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: vec![
+                ("to hit".to_string(), "5".to_string()),
+                ("bless".to_string(), "1d4".to_string()),
+            ],
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            render(&roll, ListOrder::Unsorted, Style::Markdown).0,
+            "1d20 + 5 + 1d4 => [`14`]\n> `5` *to hit*\n> `1d4` *bless*"
+        );
+    }
+
+    #[test]
+    fn test_render_breakdown() {
+        let roll = RollExprResult {
+            roll: Ok(vec![(21, vec![17, 4], Vec::new())]),
+            text: "1d20#attack + 1d6#fire".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: Vec::new(),
+            breakdown: vec![("attack".to_string(), 17), ("fire".to_string(), 4)],
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            render(&roll, ListOrder::Unsorted, Style::Markdown).0,
+            "1d20#attack + 1d6#fire => [`21`]\n> attack: `17`\n> fire: `4`"
+        );
+    }
+
+    #[test]
+    fn test_render_pool() {
+        assert_eq!(
+            render(&pool_roll(), ListOrder::Unsorted, Style::Markdown),
+            (
+                "4d10>=8s => [`2`] successes".to_string(),
+                "[**`8`**, ~~`3`~~, **`10`**, ~~`7`~~]".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_bbcode() {
+        assert_eq!(
+            render(&pool_roll(), ListOrder::Unsorted, Style::BBCode),
+            (
+                "4d10>=8s => [[code]2[/code]] successes".to_string(),
+                "[[b][code]8[/code][/b], [s][code]3[/code][/s], \
+                 [b][code]10[/code][/b], [s][code]7[/code][/s]]"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            render(&annotated_roll(), ListOrder::Unsorted, Style::BBCode).0,
+            "1d20#attack + 1d6 => [[code]21[/code]] [i](modifier +2)[/i]\n\
+             [quote][code]1d6[/code] [i]fire[/i]\nattack: [code]17[/code][/quote]"
+        );
+    }
+
+    #[test]
+    fn test_render_html() {
+        assert_eq!(
+            render(&pool_roll(), ListOrder::Unsorted, Style::Html),
+            (
+                "4d10&gt;=8s =&gt; [<code>2</code>] successes".to_string(),
+                "[<b><code>8</code></b>, <s><code>3</code></s>, \
+                 <b><code>10</code></b>, <s><code>7</code></s>]"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            render(&annotated_roll(), ListOrder::Unsorted, Style::Html).0,
+            "1d20#attack + 1d6 =&gt; [<code>21</code>] <i>(modifier +2)</i>\n\
+             <blockquote><code>1d6</code> <i>fire</i>\nattack: <code>17</code></blockquote>"
+        );
+    }
+
+    #[test]
+    fn test_render_plain() {
+        assert_eq!(
+            render(&pool_roll(), ListOrder::Unsorted, Style::Plain),
+            (
+                "4d10>=8s => [2] successes".to_string(),
+                "[8, (3), 10, (7)]".to_string()
+            )
+        );
+        assert_eq!(
+            super::render(&list_roll(), ListOrder::Ascending, true, "", Style::Plain),
+            "loot\n4{2d6} => [2, 7, 7, 12]\n[1, 1]\n[3, 4]\n[5, 2]\n[6, 6]"
+        );
+        assert_eq!(
+            render(&annotated_roll(), ListOrder::Unsorted, Style::Plain).0,
+            "1d20#attack + 1d6 => [21] (modifier +2)\n  1d6 fire\n  attack: 17"
+        );
+    }
+
+    #[test]
+    fn test_error_message() {
+        let timeout = "*Rolling `{expression}` took too long.*";
+        assert_eq!(
+            error_message(
+                &EvaluationErrors::Timeout {
+                    completed: 0,
+                    total: 1
+                },
+                "100d6!",
+                timeout,
+                Style::Markdown
+            ),
+            "*Rolling `100d6!` took too long.*"
+        );
+        assert_eq!(
+            error_message(
+                &EvaluationErrors::Timeout {
+                    completed: 3,
+                    total: 10
+                },
+                "10{100d6!}",
+                timeout,
+                Style::Markdown
+            ),
+            "*Rolling `10{100d6!}` took too long.*\n*timed out after 3 of 10 rolls*"
+        );
+        assert_eq!(
+            error_message(
+                &EvaluationErrors::Timeout {
+                    completed: 0,
+                    total: 1
+                },
+                &"1d6+".repeat(20),
+                "timeout: {expression}",
+                Style::Markdown
+            ),
+            format!("timeout: {}…", "1d6+".repeat(16))
+        );
+        assert_eq!(
+            error_message(&EvaluationErrors::Overflow, "1d6", timeout, Style::Markdown),
+            "*Overflow detected*"
+        );
+        assert_eq!(
+            error_message(&EvaluationErrors::Overflow, "1d6", timeout, Style::BBCode),
+            "[i]Overflow detected[/i]"
+        );
+        assert_eq!(
+            error_message(&EvaluationErrors::Overflow, "1d6", timeout, Style::Plain),
+            "Overflow detected"
+        );
+    }
+
+    #[test]
+    fn test_render_outcomes() {
+        let roll = RollExprResult {
+            roll: Ok(vec![(
+                11,
+                vec![6, 1, 5, 2],
+                vec![
+                    DieOutcome::Kept(6),
+                    DieOutcome::Filtered(1),
+                    DieOutcome::Kept(5),
+                    DieOutcome::Dropped(2),
+                ],
+            )]),
+            text: "4d6>1k2".to_string(),
+            label: None,
+            successes: false,
+            filter: Some((Filter::Bigger, 1)),
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            render(&roll, ListOrder::Unsorted, Style::Markdown).1,
+            "[**`6`**, ~~`1`~~, **`5`**, ~~`2`~~]"
+        );
+        let keep = RollExprResult {
+            filter: None,
+            ..roll
+        };
+        assert_eq!(
+            render(&keep, ListOrder::Unsorted, Style::Plain).1,
+            "[6, (1), 5, (2)]"
+        );
+    }
+
+    #[test]
+    fn test_render_all_filtered() {
+        let roll = RollExprResult {
+            roll: Ok(vec![(
+                0,
+                vec![3, 6, 1],
+                vec![
+                    DieOutcome::Filtered(3),
+                    DieOutcome::Filtered(6),
+                    DieOutcome::Filtered(1),
+                ],
+            )]),
+            text: "3d6>100".to_string(),
+            label: None,
+            successes: false,
+            filter: Some((Filter::Bigger, 100)),
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            render(&roll, ListOrder::Unsorted, Style::Markdown),
+            (
+                "3d6>100 => [`0`]".to_string(),
+                "[~~`3`~~, ~~`6`~~, ~~`1`~~]".to_string()
+            )
+        );
+        // without any dice left there is nothing to list
+        let empty = RollExprResult {
+            roll: Ok(vec![(0, Vec::new(), Vec::new())]),
+            ..roll
+        };
+        assert_eq!(render(&empty, ListOrder::Unsorted, Style::Plain).1, "[]");
+    }
+
+    #[test]
+    fn test_render_announcements() {
+        let roll = RollExprResult {
+            roll: Ok(vec![(25, vec![20], vec![DieOutcome::Kept(20)])]),
+            text: "1d20 + 5".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: true,
+            announcements: vec!["🎯 Critical Hit! 25".to_string()],
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            super::render(&roll, ListOrder::Unsorted, true, "", Style::Markdown),
+            "1d20 + 5 => [`25`]\n**🎯 Critical Hit! 25**\n[`20`]"
+        );
+    }
+
+    #[test]
+    fn test_render_history() {
+        let entries = vec![
+            HistoryEntry {
+                expression: "1d20 + 5".to_string(),
+                label: Some("attack".to_string()),
+                results: vec![17],
+            },
+            HistoryEntry {
+                expression: "2{1d6}".to_string(),
+                label: None,
+                results: vec![3, 5],
+            },
+        ];
+        assert_eq!(
+            render_history(&entries, Style::Markdown),
+            "**attack**: 1d20 + 5 => [`17`]\n2{1d6} => [`3`, `5`]"
+        );
+        assert_eq!(render_history(&[], Style::Plain), "no rolls yet");
+    }
+
+    #[test]
+    fn test_render_audit() {
+        let mut roll = pool_roll();
+        assert_eq!(render_audit(&roll), None);
+        roll.seed = Some([0xab; 32]);
+        roll.duration = Some(std::time::Duration::from_micros(1250));
+        assert_eq!(
+            render_audit(&roll),
+            Some(format!("seed {} · rolled in 1.25 ms", "ab".repeat(32)))
+        );
+    }
+
+    #[test]
+    fn test_render_luck() {
+        let mut roll = RollExprResult {
+            roll: Ok(vec![(18, vec![6, 6, 6], Vec::new())]),
+            text: "3d6".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: Some(Statistics {
+                mean: 10.5,
+                variance: 8.75,
+            }),
+            seed: None,
+            duration: None,
+            total: None,
+            expression: None,
+        };
+        assert_eq!(
+            super::render(&roll, ListOrder::Unsorted, false, "", Style::Markdown),
+            "3d6 => [`18`]\n*you rolled 2.5σ above average*"
+        );
+        roll.roll = Ok(vec![(9, vec![3, 3, 3], Vec::new())]);
+        assert_eq!(
+            super::render(&roll, ListOrder::Unsorted, false, "", Style::Plain),
+            "3d6 => [9]\nyou rolled 0.5σ below average"
+        );
+        roll.statistics = None;
+        assert_eq!(
+            super::render(&roll, ListOrder::Unsorted, false, "", Style::Plain),
+            "3d6 => [9]"
+        );
+    }
+}
@@ -1,24 +1,212 @@
-use rand::{Rng, SeedableRng};
+use cached::{Cached, SizedCache};
+use rand::{Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
-use rand_xoshiro::Xoshiro256PlusPlus;
-use robins_dice_roll::{dice_roll::ExpressionEvaluate, LabeledExpression};
+use rand_xoshiro::{SplitMix64, Xoshiro256PlusPlus};
+use robins_dice_roll::{
+    dice_roll::{EvaluationErrors, EvaluationLimits},
+    stats::{self, Distribution, StatsError},
+    DiceType, Expression, Filter, FilteredDice, LabeledExpression, NarrativePool, NarrativeRoll,
+    SelectedDice, Selector, Term,
+};
 use std::{
     borrow::Borrow,
-    sync::{atomic::AtomicBool, Arc},
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 use tokio::{
-    sync::{mpsc, oneshot},
+    sync::{mpsc, oneshot, watch},
     task::spawn,
-    time::{interval, sleep, sleep_until, Instant},
+    time::{sleep, sleep_until, Instant},
 };
 
 use crate::bot_manager::StopListener;
 use rusty_pool::{Builder, ThreadPool};
 
+/// outermost term of `expr` that isn't just parentheses
+fn top_term(expr: &Expression) -> &Term {
+    let mut term = match expr {
+        Expression::Simple(t) => t,
+        Expression::List(_, t) => t,
+    };
+    while let Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) = term {
+        term = t;
+    }
+    term
+}
+
+/// comments of `expr` in order, each with the text of the term it annotates
+fn comments(expr: &Expression) -> Vec<(String, String)> {
+    fn collect(term: &Term, comments: &mut Vec<(String, String)>) {
+        match term {
+            Term::Commented(t, c) => {
+                collect(t, comments);
+                comments.push((c.clone(), t.to_string()));
+            }
+            Term::SubTerm(t) | Term::Labeled(t, _) | Term::Negate(t) => collect(t, comments),
+            Term::Calculation(l, _, r) => {
+                collect(l, comments);
+                collect(r, comments);
+            }
+            Term::Function(_, args) => args.iter().for_each(|t| collect(t, comments)),
+            _ => {}
+        }
+    }
+    let mut result = Vec::new();
+    collect(
+        match expr {
+            Expression::Simple(t) => t,
+            Expression::List(_, t) => t,
+        },
+        &mut result,
+    );
+    result
+}
+
+/// whether the result of `expr` is a number of successes
+fn counts_successes(expr: &Expression) -> bool {
+    matches!(top_term(expr), Term::SuccessCount(_))
+}
+
+/// filter and target the dice of `expr` are checked against, if it only consists of filtered dice
+fn dice_filter(expr: &Expression) -> Option<(Filter, u32)> {
+    match top_term(expr) {
+        Term::SuccessCount(FilteredDice::Filtered(_, filter, target))
+        | Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Filtered(_, filter, target)))
+        | Term::DiceThrow(SelectedDice::Selected(
+            FilteredDice::Filtered(_, filter, target),
+            _,
+            _,
+        )) => Some((*filter, *target)),
+        _ => None,
+    }
+}
+
+/// whether everything rolled by `expr` comes down to a single kept d20, like `1d20 + 5` or `2d20k1`
+fn natural_d20(expr: &Expression) -> bool {
+    fn collect<'a>(term: &'a Term, dice: &mut Vec<&'a Term>) {
+        match term {
+            Term::DiceThrow(_) | Term::SuccessCount(_) => dice.push(term),
+            Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) | Term::Negate(t) => {
+                collect(t, dice)
+            }
+            Term::Calculation(l, _, r) => {
+                collect(l, dice);
+                collect(r, dice);
+            }
+            Term::Function(_, args) => args.iter().for_each(|t| collect(t, dice)),
+            Term::Constant(_) | Term::Var(_) => {}
+        }
+    }
+    let mut dice = Vec::new();
+    collect(expr.term(), &mut dice);
+    let (d, kept) = match dice.as_slice() {
+        [Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(d)))] => (d, d.throws),
+        [Term::DiceThrow(SelectedDice::Selected(
+            FilteredDice::Simple(d),
+            Selector::Higher | Selector::Lower,
+            n,
+        ))] => (d, d.throws.min(*n)),
+        [Term::DiceThrow(SelectedDice::Selected(
+            FilteredDice::Simple(d),
+            Selector::DropHigher | Selector::DropLower,
+            n,
+        ))] => (d, d.throws.saturating_sub(*n)),
+        _ => return false,
+    };
+    d.dice == DiceType::Number(20) && d.explode.is_none() && d.dynamic_throws.is_none() && kept == 1
+}
+
+/// result of a roll of `text` that failed with `error`
+fn failed_roll(text: String, error: EvaluationErrors) -> super::RollExprResult {
+    super::RollExprResult {
+        roll: Err(error),
+        text,
+        label: None,
+        successes: false,
+        filter: None,
+        comments: Vec::new(),
+        breakdown: Vec::new(),
+        modifier: None,
+        natural_d20: false,
+        announcements: Vec::new(),
+        statistics: None,
+        seed: None,
+        duration: None,
+        total: None,
+        expression: None,
+    }
+}
+
+/// rolls `expr`, whose display form is `text`
+fn evaluate<T: FnMut() -> bool, R: Rng>(
+    expr: &Expression,
+    text: String,
+    label: Option<String>,
+    successes: bool,
+    timeout_f: &mut T,
+    dice_limit: u64,
+    rng: &mut R,
+) -> super::RollExprResult {
+    let (roll, breakdown) =
+        match expr.evaluate_detailed(timeout_f, &mut EvaluationLimits::new(dice_limit), rng) {
+            Ok(mut results) => {
+                // a list has no single breakdown to show
+                let breakdown = match results.as_mut_slice() {
+                    [(_, details)] => std::mem::take(&mut details.labels),
+                    _ => Vec::new(),
+                };
+                (
+                    Ok(results
+                        .into_iter()
+                        .map(|((total, dice), details)| (total, dice, details.outcomes))
+                        .collect::<Vec<_>>()),
+                    breakdown,
+                )
+            }
+            Err(e) => (Err(e), Vec::new()),
+        };
+    let total = match (expr, &roll) {
+        (Expression::List(_, _), Ok(results)) => results
+            .iter()
+            .try_fold(0i64, |total, result| total.checked_add(result.0)),
+        _ => None,
+    };
+    super::RollExprResult {
+        roll,
+        text,
+        label,
+        successes,
+        filter: dice_filter(expr),
+        comments: comments(expr),
+        breakdown,
+        modifier: None,
+        natural_d20: natural_d20(expr),
+        announcements: Vec::new(),
+        statistics: None,
+        seed: None,
+        duration: None,
+        total,
+        expression: None,
+    }
+}
+
+/// seed of the generator a roll was made with
+pub type RollSeed = <Xoshiro256PlusPlus as SeedableRng>::Seed;
+
+/// the seed [Xoshiro256PlusPlus::seed_from_u64] expands `seed` to
+fn expand_seed(seed: u64) -> RollSeed {
+    let mut expanded = RollSeed::default();
+    SplitMix64::seed_from_u64(seed).fill_bytes(&mut expanded);
+    expanded
+}
+
 #[derive(Debug)]
 enum RngProviderOps {
-    GetRng(oneshot::Sender<Xoshiro256PlusPlus>),
+    GetRng(oneshot::Sender<(RollSeed, Xoshiro256PlusPlus)>),
     SetCryptoRng(Box<ChaCha20Rng>),
 }
 
@@ -32,9 +220,11 @@ impl RngProvider {
         while let Some(op) = self.receiver.recv().await {
             match op {
                 RngProviderOps::GetRng(channel) => {
-                    let mut seed: <Xoshiro256PlusPlus as SeedableRng>::Seed = Default::default();
+                    let mut seed = RollSeed::default();
                     self.rng.fill(&mut seed);
-                    channel.send(Xoshiro256PlusPlus::from_seed(seed)).unwrap()
+                    channel
+                        .send((seed, Xoshiro256PlusPlus::from_seed(seed)))
+                        .unwrap()
                 }
                 RngProviderOps::SetCryptoRng(rng) => self.rng = *rng,
             }
@@ -42,8 +232,9 @@ impl RngProvider {
     }
 }
 
+/// the crypto rng is replaced by a freshly seeded one every period sent by `reseed`
 async fn start_rng_provider<Stop: StopListener>(
-    rng_reseed: Duration,
+    mut reseed: watch::Receiver<Duration>,
     mut stop: Stop,
 ) -> (tokio::task::JoinHandle<()>, mpsc::Sender<RngProviderOps>) {
     let (sender, receiver) = mpsc::channel(32);
@@ -58,31 +249,25 @@ async fn start_rng_provider<Stop: StopListener>(
     let sender_clone = sender.clone();
     (
         spawn(async move {
-            tokio::select! {
-                _ = sleep(rng_reseed)=>{
-
-                }
-                _ = stop.wait_stop()=>{
-                    drop(sender_clone);
-                    log::info!("stopped reseeding task");
-                    return rng_handle.await.unwrap();
-                }
-            };
-            let mut interval = interval(rng_reseed);
+            let mut period = *reseed.borrow();
             loop {
-                interval.tick().await;
                 tokio::select! {
-                    _ = interval.tick()=>{
-                        match sender_clone
-                    .send(RngProviderOps::SetCryptoRng(Box::from(ChaCha20Rng::from_entropy())))
-                    .await
-                {
-                    Ok(_) => {}
-                    Err(_) => {
-                        break;
+                    _ = sleep(period)=>{
+                        if sender_clone
+                            .send(RngProviderOps::SetCryptoRng(Box::from(ChaCha20Rng::from_entropy())))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
                     }
-                }
-
+                    // a new period starts counting from now, without any executor left there is
+                    // nothing to reseed for
+                    changed = reseed.changed()=>{
+                        match changed {
+                            Ok(_) => period = *reseed.borrow(),
+                            Err(_) => break,
+                        }
                     }
                     _ = stop.wait_stop()=>{break;}
                 }
@@ -95,19 +280,46 @@ async fn start_rng_provider<Stop: StopListener>(
     )
 }
 
+/// settings of a [RollExecutor] that may change while it is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollSettings {
+    pub timeout: Duration,
+    /// time between reseeds of the crypto rng
+    pub rng_reseed: Duration,
+    /// rolls waiting for a worker before further ones are refused as [Busy]
+    pub max_queue: usize,
+    /// most dice a single roll may throw
+    pub dice_limit: u64,
+}
+
+/// number of distributions kept, keyed by the normalized expression
+const DISTRIBUTION_CACHE_SIZE: usize = 256;
+
+/// all roll workers are busy and the queue of waiting rolls is full
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Busy;
+
 pub struct RollExecutor {
     pool: ThreadPool,
-    timeout: Duration,
+    settings: Mutex<RollSettings>,
     rng_gen: mpsc::Sender<RngProviderOps>,
+    /// period of the reseeding task
+    rng_reseed: watch::Sender<Duration>,
+    distributions: Mutex<SizedCache<String, Arc<Distribution>>>,
+    /// tasks handed to the pool that haven't been picked up by a worker yet
+    queued: Arc<AtomicUsize>,
 }
 impl RollExecutor {
     pub async fn new<Stop: StopListener>(
         size: u32,
         timeout: Duration,
         rng_reseed: Duration,
+        max_queue: usize,
+        dice_limit: u64,
         stop: Stop,
     ) -> (tokio::task::JoinHandle<()>, RollExecutor) {
-        let (handle, rng) = start_rng_provider(rng_reseed, stop).await;
+        let (reseed_sender, reseed_receiver) = watch::channel(rng_reseed);
+        let (handle, rng) = start_rng_provider(reseed_receiver, stop).await;
         (
             handle,
             RollExecutor {
@@ -116,72 +328,441 @@ impl RollExecutor {
                     .max_size(size)
                     .name("Roll Worker".to_string())
                     .build(),
-                timeout,
+                settings: Mutex::new(RollSettings {
+                    timeout,
+                    rng_reseed,
+                    max_queue,
+                    dice_limit,
+                }),
                 rng_gen: rng,
+                rng_reseed: reseed_sender,
+                distributions: Mutex::new(SizedCache::with_size(DISTRIBUTION_CACHE_SIZE)),
+                queued: Arc::new(AtomicUsize::new(0)),
             },
         )
     }
 
-    pub async fn roll<Expr>(&self, expr: Expr) -> super::RollExprResult
+    pub fn settings(&self) -> RollSettings {
+        *self.settings.lock().unwrap()
+    }
+
+    /// applies `settings` to the rolls started from now on, a changed reseed period starts
+    /// counting right away
+    pub fn update(&self, settings: RollSettings) {
+        let mut current = self.settings.lock().unwrap();
+        if current.rng_reseed != settings.rng_reseed {
+            let _ = self.rng_reseed.send(settings.rng_reseed);
+        }
+        *current = settings;
+    }
+
+    /// hands `task` to the pool, unless `max_queue` tasks are already waiting for a worker
+    ///
+    /// the result of `task` is sent to the returned receiver, a panicking task is caught
+    /// and closes it without sending anything
+    fn execute<T, F>(&self, task: F) -> Result<oneshot::Receiver<T>, Busy>
     where
-        Expr: Borrow<super::VersionedRollExpr> + Sized + Send + 'static,
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
     {
-        let text = format!("{}", expr.borrow());
-        let (result_sender, result_receiver) = oneshot::channel();
-        let (time_sender, time_receiver) = oneshot::channel();
-        let timeout_signal = Arc::new(AtomicBool::new(false));
-        let timeout_signal_clone = timeout_signal.clone();
+        let max_queue = self.settings().max_queue;
+        let _queued = self
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                if queued < max_queue {
+                    Some(queued + 1)
+                } else {
+                    None
+                }
+            })
+            .map_err(|_| Busy)?;
+        #[cfg(feature = "metrics")]
+        crate::metrics::queue_depth(_queued + 1);
+        let queued = self.queued.clone();
+        let (sender, receiver) = oneshot::channel();
+        self.pool.execute(move || {
+            let _queued = queued.fetch_sub(1, Ordering::SeqCst);
+            #[cfg(feature = "metrics")]
+            crate::metrics::queue_depth(_queued - 1);
+            match std::panic::catch_unwind(AssertUnwindSafe(task)) {
+                Ok(result) => {
+                    let _ = sender.send(result);
+                }
+                Err(_) => log::error!("a roll worker panicked"),
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// a generator seeded by the crypto rng, with its seed
+    async fn rng(&self) -> (RollSeed, Xoshiro256PlusPlus) {
         let (rng_send, rng_receive) = oneshot::channel();
         self.rng_gen
             .send(RngProviderOps::GetRng(rng_send))
             .await
             .unwrap();
-        let rng = rng_receive.await.unwrap();
-        self.pool.execute(move || {
-            time_sender.send(Instant::now()).unwrap();
+        rng_receive.await.unwrap()
+    }
+
+    /// replaces the crypto rng by a freshly seeded one right away, without waiting for the
+    /// next periodic reseed
+    pub async fn reseed(&self) {
+        self.rng_gen
+            .send(RngProviderOps::SetCryptoRng(Box::from(
+                ChaCha20Rng::from_entropy(),
+            )))
+            .await
+            .unwrap();
+    }
+
+    /// rolls `expr`, the statistics telling how lucky it was are only computed with `luck`
+    pub async fn roll<Expr>(&self, expr: Expr, luck: bool) -> Result<super::RollExprResult, Busy>
+    where
+        Expr: Borrow<super::VersionedRollExpr> + Sized + Send + 'static,
+    {
+        self.roll_with_timeout(expr, self.settings().timeout, luck)
+            .await
+    }
+
+    /// same as [RollExecutor::roll], but `expr` may take up to `timeout` instead of
+    /// the configured roll timeout
+    pub async fn roll_with_timeout<Expr>(
+        &self,
+        expr: Expr,
+        timeout: Duration,
+        luck: bool,
+    ) -> Result<super::RollExprResult, Busy>
+    where
+        Expr: Borrow<super::VersionedRollExpr> + Sized + Send + 'static,
+    {
+        let (seed, rng) = self.rng().await;
+        self.roll_with(expr, seed, rng, timeout, luck).await
+    }
+
+    /// rolls `expr` from `seed`, or from a fresh one if it is `None`
+    ///
+    /// the seed is returned with the result, rolling the same expression from
+    /// it again gives the same result
+    pub async fn roll_seeded<Expr>(
+        &self,
+        expr: Expr,
+        seed: Option<u64>,
+        luck: bool,
+    ) -> Result<(u64, super::RollExprResult), Busy>
+    where
+        Expr: Borrow<super::VersionedRollExpr> + Sized + Send + 'static,
+    {
+        let seed = match seed {
+            Some(seed) => seed,
+            None => self.rng().await.1.gen(),
+        };
+        let expanded = expand_seed(seed);
+        let result = self
+            .roll_with(
+                expr,
+                expanded,
+                Xoshiro256PlusPlus::from_seed(expanded),
+                self.settings().timeout,
+                luck,
+            )
+            .await?;
+        Ok((seed, result))
+    }
+
+    async fn roll_with<Expr>(
+        &self,
+        expr: Expr,
+        seed: RollSeed,
+        rng: Xoshiro256PlusPlus,
+        timeout: Duration,
+        luck: bool,
+    ) -> Result<super::RollExprResult, Busy>
+    where
+        Expr: Borrow<super::VersionedRollExpr> + Sized + Send + 'static,
+    {
+        let text = format!("{}", expr.borrow());
+        let failed = text.clone();
+        let (time_sender, time_receiver) = oneshot::channel();
+        let timeout_signal = Arc::new(AtomicBool::new(false));
+        let timeout_signal_clone = timeout_signal.clone();
+        let dice_limit = self.settings().dice_limit;
+        let result_receiver = self.execute(move || {
+            let start = Instant::now();
+            time_sender.send(start).unwrap();
             let mut rng = rng;
-            result_sender
-                .send(match expr.borrow() {
-                    super::VersionedRollExpr::V1(e) => super::RollExprResult {
-                        roll: e.evaluate(
-                            &mut move || timeout_signal.load(std::sync::atomic::Ordering::Relaxed),
-                            &mut rng,
-                        ),
-                        text,
-                        label: None,
-                    },
-                    super::VersionedRollExpr::V2(LabeledExpression::Unlabeled(e)) => {
-                        super::RollExprResult {
-                            roll: e.evaluate(
-                                &mut move || {
-                                    timeout_signal.load(std::sync::atomic::Ordering::Relaxed)
-                                },
-                                &mut rng,
-                            ),
-                            text,
-                            label: None,
-                        }
-                    }
-                    super::VersionedRollExpr::V2(LabeledExpression::Labeled(e, l)) => {
-                        super::RollExprResult {
-                            roll: e.evaluate(
-                                &mut move || {
-                                    timeout_signal.load(std::sync::atomic::Ordering::Relaxed)
-                                },
-                                &mut rng,
-                            ),
-                            text,
-                            label: Some(l.to_owned()),
-                        }
-                    }
-                })
-                .unwrap();
+            let mut timeout_f = move || timeout_signal.load(std::sync::atomic::Ordering::Relaxed);
+            // templates are filled in before rolling, an unfilled one has no value
+            let unfilled = Expression::Simple(Term::Var("$1".to_string()));
+            let (e, label, successes) = match expr.borrow() {
+                super::VersionedRollExpr::V1(e) => (e, None, false),
+                super::VersionedRollExpr::V2(LabeledExpression::Unlabeled(e)) => {
+                    (e, None, counts_successes(e))
+                }
+                super::VersionedRollExpr::V2(LabeledExpression::Labeled(e, l)) => {
+                    (e, Some(l.to_owned()), counts_successes(e))
+                }
+                super::VersionedRollExpr::Template(_) => (&unfilled, None, false),
+            };
+            let mut result = evaluate(
+                e,
+                text,
+                label,
+                successes,
+                &mut timeout_f,
+                dice_limit,
+                &mut rng,
+            );
+            if luck {
+                result.statistics = e.statistics_with_timeout(&mut timeout_f).ok();
+            }
+            result.seed = Some(seed);
+            result.duration = Some(start.elapsed());
+            result
+        })?;
+        spawn(async move {
+            if let Ok(start) = time_receiver.await {
+                sleep_until(start + timeout).await;
+                timeout_signal_clone.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
         });
-        let timeout_clone = self.timeout;
+        let result = result_receiver
+            .await
+            .unwrap_or_else(|_| failed_roll(failed, EvaluationErrors::Internal));
+        #[cfg(feature = "metrics")]
+        crate::metrics::roll_evaluated(&result.roll);
+        Ok(result)
+    }
+
+    /// rolls the narrative dice of `pool`
+    ///
+    /// pools are small enough to be rolled right away instead of on the roll workers
+    pub async fn narrative(&self, pool: &NarrativePool) -> NarrativeRoll {
+        pool.roll(&mut self.rng().await.1)
+    }
+
+    /// distribution of the results of `term`, computed on the roll workers
+    ///
+    /// results are cached by the display form of `term`, so equal expressions
+    /// share an entry regardless of their original spelling
+    pub async fn distribution(
+        &self,
+        term: Term,
+    ) -> Result<Result<Arc<Distribution>, StatsError>, Busy> {
+        let key = term.to_string();
+        if let Some(distribution) = self.distributions.lock().unwrap().cache_get(&key) {
+            return Ok(Ok(distribution.clone()));
+        }
+        let (time_sender, time_receiver) = oneshot::channel();
+        let timeout_signal = Arc::new(AtomicBool::new(false));
+        let timeout_signal_clone = timeout_signal.clone();
+        let (_, mut rng) = self.rng().await;
+        let dice_limit = self.settings().dice_limit;
+        let result_receiver = self.execute(move || {
+            time_sender.send(Instant::now()).unwrap();
+            stats::distribution(
+                &term,
+                dice_limit,
+                &mut move || timeout_signal.load(std::sync::atomic::Ordering::Relaxed),
+                &mut rng,
+            )
+        })?;
+        let timeout_clone = self.settings().timeout;
         spawn(async move {
-            sleep_until(time_receiver.await.unwrap() + timeout_clone).await;
-            timeout_signal_clone.store(true, std::sync::atomic::Ordering::Relaxed);
+            if let Ok(start) = time_receiver.await {
+                sleep_until(start + timeout_clone).await;
+                timeout_signal_clone.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
         });
-        result_receiver.await.unwrap()
+        // a panicking worker leaves nothing to show, like an expression that isn't supported
+        let result = result_receiver
+            .await
+            .unwrap_or(Err(StatsError::Unsupported));
+        Ok(result.map(|distribution| {
+            let distribution = Arc::new(distribution);
+            self.distributions
+                .lock()
+                .unwrap()
+                .cache_set(key, distribution.clone());
+            distribution
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use robins_dice_roll::parser::{parse_expression, parse_labeled};
+
+    async fn executor(max_queue: usize) -> RollExecutor {
+        let (_, stop) = tokio::sync::watch::channel(false);
+        RollExecutor::new(
+            1,
+            Duration::from_secs(2),
+            Duration::from_secs(300),
+            max_queue,
+            1000,
+            stop,
+        )
+        .await
+        .1
+    }
+
+    #[test]
+    fn test_dice_filter() {
+        let filter = |input: &str| dice_filter(&parse_expression(input).unwrap().1);
+        assert_eq!(filter("10d10>=8s"), Some((Filter::BiggerEq, 8)));
+        assert_eq!(filter("((4d6>2k2))"), Some((Filter::Bigger, 2)));
+        assert_eq!(filter("3{2d6<3}"), Some((Filter::Smaller, 3)));
+        assert_eq!(filter("4d6>2 + 1"), None);
+        assert_eq!(filter("2d6"), None);
+    }
+
+    #[test]
+    fn test_natural_d20() {
+        let natural = |input: &str| natural_d20(&parse_expression(input).unwrap().1);
+        assert!(natural("1d20"));
+        assert!(natural("(1d20 + 5) [attack]"));
+        assert!(natural("2d20k1 - 1"));
+        assert!(natural("2d20dh1"));
+        assert!(natural("3{1d20 + 2}"));
+        assert!(!natural("2d20"));
+        assert!(!natural("1d20 + 1d4"));
+        assert!(!natural("1d20!"));
+        assert!(!natural("1d20>10"));
+        assert!(!natural("1d12"));
+    }
+
+    #[tokio::test]
+    async fn test_busy() {
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("1d6").unwrap().1);
+        let roll = executor(4).await.roll(expr.clone(), false).await.unwrap();
+        assert!(roll.roll.is_ok());
+        assert_eq!(executor(0).await.roll(expr, false).await.err(), Some(Busy));
+    }
+
+    #[tokio::test]
+    async fn test_worker_panic() {
+        let executor = executor(4).await;
+        let panicked = executor
+            .execute(|| -> u32 { panic!("bad expression") })
+            .unwrap();
+        assert!(panicked.await.is_err());
+        // the worker that panicked is still able to roll
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("1d6").unwrap().1);
+        assert!(executor.roll(expr, false).await.unwrap().roll.is_ok());
+        assert_eq!(
+            failed_roll("1d6".to_string(), EvaluationErrors::Internal).roll,
+            Err(EvaluationErrors::Internal)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_settings() {
+        let executor = executor(4).await;
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("10d6").unwrap().1);
+        assert!(executor
+            .roll(expr.clone(), false)
+            .await
+            .unwrap()
+            .roll
+            .is_ok());
+        executor.update(RollSettings {
+            dice_limit: 5,
+            max_queue: 0,
+            ..executor.settings()
+        });
+        assert_eq!(executor.roll(expr.clone(), false).await.err(), Some(Busy));
+        executor.update(RollSettings {
+            max_queue: 4,
+            ..executor.settings()
+        });
+        assert!(executor.roll(expr, false).await.unwrap().roll.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_roll_with_timeout() {
+        let executor = executor(4).await;
+        executor.update(RollSettings {
+            dice_limit: 10_000_000,
+            ..executor.settings()
+        });
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("2000000d6").unwrap().1);
+        assert!(matches!(
+            executor
+                .roll_with_timeout(expr.clone(), Duration::ZERO, false)
+                .await
+                .unwrap()
+                .roll,
+            Err(EvaluationErrors::Timeout { .. })
+        ));
+        assert!(executor.roll(expr, false).await.unwrap().roll.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reseed() {
+        let executor = executor(4).await;
+        let (before, _) = executor.rng().await;
+        executor.reseed().await;
+        let (after, _) = executor.rng().await;
+        assert_ne!(before, after);
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("1d6").unwrap().1);
+        assert!(executor.roll(expr, false).await.unwrap().roll.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_roll_seeded() {
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("10{4d20}").unwrap().1);
+        let executor = executor(4).await;
+        let (seed, first) = executor
+            .roll_seeded(expr.clone(), None, false)
+            .await
+            .unwrap();
+        let (again, second) = executor.roll_seeded(expr, Some(seed), false).await.unwrap();
+        assert_eq!(again, seed);
+        assert_eq!(first.roll, second.roll);
+        assert_eq!(first.seed, Some(expand_seed(seed)));
+        assert_eq!(first.seed, second.seed);
+        assert!(first.duration.is_some());
+        let results = first.roll.unwrap();
+        assert_eq!(first.total, Some(results.iter().map(|r| r.0).sum()));
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("4d20").unwrap().1);
+        assert_eq!(executor.roll(expr, false).await.unwrap().total, None);
+    }
+
+    #[test]
+    fn test_expand_seed() {
+        assert_eq!(
+            Xoshiro256PlusPlus::from_seed(expand_seed(42)),
+            Xoshiro256PlusPlus::seed_from_u64(42)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_distribution_dice_limit() {
+        let executor = executor(4).await;
+        let term = |input| robins_dice_roll::parser::parse_term(input).unwrap().1;
+        // exploding dice can't be computed exactly, they are sampled within the dice limit
+        assert!(executor.distribution(term("10d6!")).await.unwrap().is_ok());
+        assert_eq!(
+            executor.distribution(term("4000000000d6!")).await.unwrap(),
+            Err(StatsError::TooLarge)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dice_limit() {
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("10{100d6}").unwrap().1);
+        assert!(executor(4)
+            .await
+            .roll(expr, false)
+            .await
+            .unwrap()
+            .roll
+            .is_ok());
+        let expr = super::super::VersionedRollExpr::V2(parse_labeled("1001d6").unwrap().1);
+        assert_eq!(
+            executor(4).await.roll(expr, false).await.unwrap().roll,
+            Err(robins_dice_roll::dice_roll::EvaluationErrors::DiceLimitExceeded)
+        );
     }
 }
@@ -13,17 +13,23 @@
  */
 
 use diesel::prelude::*;
-use serde::{de::DeserializeOwned, Serialize};
-use std::hash::Hash;
-use std::sync::Arc;
-use std::{collections::HashMap, fmt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, Mutex};
+use std::{
+    collections::{hash_map::RandomState, HashMap, VecDeque},
+    fmt,
+};
 use tokio::{
     sync::{mpsc, oneshot},
     task::spawn,
 };
+#[allow(non_local_definitions)]
 mod schema;
-use super::VersionedRollExpr;
+use super::{Critical, VersionedRollExpr};
 use cached::{Cached, SizedCache};
+use robins_dice_roll::dice_types::{DiceType, LabeledExpression, Term};
+#[allow(non_local_definitions)]
 mod cc {
     use super::schema::client_config;
     #[derive(Debug, Queryable, Clone, Identifiable, Insertable)]
@@ -34,6 +40,20 @@ mod cc {
         pub(crate) roll_prefix: String,
         pub(crate) aliases: String,
         pub(crate) roll_info: bool,
+        pub(crate) paused: bool,
+        pub(crate) alias_hints: bool,
+        pub(crate) list_order: i32,
+        pub(crate) user_aliases: String,
+        pub(crate) roll_shorthand: Option<String>,
+        pub(crate) stats_roll: Option<String>,
+        pub(crate) stats_min_total: Option<i64>,
+        pub(crate) critical_message: Option<String>,
+        pub(crate) fumble_message: Option<String>,
+        pub(crate) show_luck: bool,
+        pub(crate) privileged_roles: String,
+        pub(crate) channel_prefixes: String,
+        pub(crate) roll_history: String,
+        pub(crate) dice_policy: String,
     }
     impl ClientConfig {
         pub(crate) fn new(id: String) -> ClientConfig {
@@ -43,6 +63,20 @@ mod cc {
                 roll_prefix: "[]".to_string(),
                 aliases: "{}".to_string(),
                 roll_info: false,
+                paused: false,
+                alias_hints: false,
+                list_order: 0,
+                user_aliases: "{}".to_string(),
+                roll_shorthand: None,
+                stats_roll: None,
+                stats_min_total: None,
+                critical_message: None,
+                fumble_message: None,
+                show_luck: false,
+                privileged_roles: "[]".to_string(),
+                channel_prefixes: "{}".to_string(),
+                roll_history: "{}".to_string(),
+                dice_policy: "{}".to_string(),
             }
         }
     }
@@ -54,6 +88,20 @@ mod cc {
         pub(crate) roll_prefix: Option<String>,
         pub(crate) aliases: Option<String>,
         pub(crate) roll_info: Option<bool>,
+        pub(crate) paused: Option<bool>,
+        pub(crate) alias_hints: Option<bool>,
+        pub(crate) list_order: Option<i32>,
+        pub(crate) user_aliases: Option<String>,
+        pub(crate) roll_shorthand: Option<Option<String>>,
+        pub(crate) stats_roll: Option<Option<String>>,
+        pub(crate) stats_min_total: Option<Option<i64>>,
+        pub(crate) critical_message: Option<Option<String>>,
+        pub(crate) fumble_message: Option<Option<String>>,
+        pub(crate) show_luck: Option<bool>,
+        pub(crate) privileged_roles: Option<String>,
+        pub(crate) channel_prefixes: Option<String>,
+        pub(crate) roll_history: Option<String>,
+        pub(crate) dice_policy: Option<String>,
     }
 }
 
@@ -84,15 +132,178 @@ struct Client<'s, Id: ClientId> {
     client_id: Id,
 }
 
+type Aliases = HashMap<String, Arc<VersionedRollExpr>>;
+
+/// dice a client can forbid regardless of their faces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiceKind {
+    /// `dNx`
+    Multiply,
+    /// `dF`
+    Fudge,
+    /// `d(term)`, whose faces aren't known before it is rolled
+    Dynamic,
+}
+
+impl fmt::Display for DiceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceKind::Multiply => write!(f, "multiply"),
+            DiceKind::Fudge => write!(f, "fudge"),
+            DiceKind::Dynamic => write!(f, "dynamic"),
+        }
+    }
+}
+
+/// dice a client doesn't allow to be rolled
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DicePolicy {
+    #[serde(default)]
+    pub banned: Vec<DiceKind>,
+    /// most faces of a die, `None` for no limit
+    ///
+    /// the faces of dynamic dice aren't known before rolling, so a limit forbids them
+    #[serde(default)]
+    pub max_faces: Option<u32>,
+}
+
+impl DicePolicy {
+    /// `Err` with the first dice of `expr` that may not be rolled
+    pub fn check(&self, expr: &VersionedRollExpr) -> Result<(), String> {
+        match expr {
+            VersionedRollExpr::V1(e)
+            | VersionedRollExpr::V2(LabeledExpression::Unlabeled(e))
+            | VersionedRollExpr::V2(LabeledExpression::Labeled(e, _)) => self.check_term(e.term()),
+            // templates are checked once they are filled in
+            VersionedRollExpr::Template(_) => Ok(()),
+        }
+    }
+
+    /// `Err` with the first dice of `term` that may not be rolled
+    pub fn check_term(&self, term: &Term) -> Result<(), String> {
+        match term.dice_iter().find(|dice| {
+            let (kind, faces) = match dice.dice {
+                DiceType::Number(n) => (None, Some(n)),
+                DiceType::Multiply(n) => (Some(DiceKind::Multiply), Some(n)),
+                DiceType::Fudge { .. } => (Some(DiceKind::Fudge), None),
+                // any number of faces might come up
+                DiceType::Dynamic(_) => (Some(DiceKind::Dynamic), Some(u32::MAX)),
+            };
+            kind.is_some_and(|k| self.banned.contains(&k))
+                || faces
+                    .zip(self.max_faces)
+                    .is_some_and(|(faces, max)| faces > max)
+        }) {
+            Some(dice) => Err(dice.to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for DicePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.banned.is_empty() && self.max_faces.is_none() {
+            return write!(f, "every dice may be rolled");
+        }
+        if !self.banned.is_empty() {
+            let banned: Vec<_> = self.banned.iter().map(|k| k.to_string()).collect();
+            write!(f, "forbidden dice: {}", banned.join(", "))?;
+            if self.max_faces.is_some() {
+                write!(f, "; ")?;
+            }
+        }
+        if let Some(max) = self.max_faces {
+            write!(f, "at most {} faces", max)?;
+        }
+        Ok(())
+    }
+}
+
+/// a roll of a user, remembered for `roll-history`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// text of the rolled expression
+    pub expression: String,
+    pub label: Option<String>,
+    /// a single result, unless a list was rolled
+    pub results: Vec<i64>,
+}
+
+/// prefixes of a single channel, replacing those of the whole client
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelPrefixes {
+    pub command_prefix: Option<String>,
+    /// the roll prefixes of the client apply while this is empty
+    pub roll_prefixes: Vec<String>,
+}
+
+impl ChannelPrefixes {
+    fn is_empty(&self) -> bool {
+        self.command_prefix.is_none() && self.roll_prefixes.is_empty()
+    }
+}
+
+/// canonical form aliases are matched by, `Stats` and `stats ` name the same alias
+pub(crate) fn alias_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// entry of `aliases` named `name` in any casing
+fn find_alias<'a>(
+    aliases: &'a Aliases,
+    name: &str,
+) -> Option<(&'a String, &'a Arc<VersionedRollExpr>)> {
+    aliases.get_key_value(name).or_else(|| {
+        let key = alias_key(name);
+        aliases.iter().find(|(alias, _)| alias_key(alias) == key)
+    })
+}
+
+/// removes the entry of `aliases` named `name` in any casing
+fn take_alias(aliases: &mut Aliases, name: &str) -> Option<Arc<VersionedRollExpr>> {
+    let alias = find_alias(aliases, name)?.0.to_owned();
+    aliases.remove(&alias)
+}
+
+/// set of aliases a single alias operation works on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AliasScope {
+    /// aliases shared by everyone using the client
+    Shared,
+    /// personal aliases of a single user of the client
+    User(String),
+}
+
 #[derive(Debug, Clone)]
 struct ClientInformation {
     source: ClientConfig,
     roll_prefix: Vec<String>,
-    aliases: HashMap<String, Arc<VersionedRollExpr>>,
+    privileged_roles: Vec<String>,
+    channel_prefixes: HashMap<String, ChannelPrefixes>,
+    /// latest rolls of each user, the oldest first
+    roll_history: HashMap<String, VecDeque<HistoryEntry>>,
+    dice_policy: DicePolicy,
+    aliases: Aliases,
+    user_aliases: HashMap<String, Aliases>,
+    stats_roll: Option<Arc<VersionedRollExpr>>,
     command_prefix_changed: bool,
     roll_prefix_changed: bool,
     aliases_changed: bool,
     roll_info_changed: bool,
+    paused_changed: bool,
+    alias_hints_changed: bool,
+    list_order_changed: bool,
+    user_aliases_changed: bool,
+    roll_shorthand_changed: bool,
+    stats_roll_changed: bool,
+    stats_min_total_changed: bool,
+    critical_message_changed: bool,
+    fumble_message_changed: bool,
+    show_luck_changed: bool,
+    privileged_roles_changed: bool,
+    channel_prefixes_changed: bool,
+    roll_history_changed: bool,
+    dice_policy_changed: bool,
 }
 
 impl ClientInformation {
@@ -110,6 +321,58 @@ impl ClientInformation {
                 vec![]
             }
         };
+        let mut privileged_roles_changed = false;
+        let privileged_roles = match serde_json::from_str(&source.privileged_roles) {
+            Ok(r) => r,
+            Err(err) => {
+                log::warn!(
+                    "unable to parse privileged roles from {}: {}",
+                    &source.privileged_roles,
+                    err
+                );
+                privileged_roles_changed = true;
+                vec![]
+            }
+        };
+        let mut channel_prefixes_changed = false;
+        let channel_prefixes = match serde_json::from_str(&source.channel_prefixes) {
+            Ok(p) => p,
+            Err(err) => {
+                log::warn!(
+                    "unable to parse channel prefixes from {}: {}",
+                    &source.channel_prefixes,
+                    err
+                );
+                channel_prefixes_changed = true;
+                HashMap::new()
+            }
+        };
+        let mut roll_history_changed = false;
+        let roll_history = match serde_json::from_str(&source.roll_history) {
+            Ok(h) => h,
+            Err(err) => {
+                log::warn!(
+                    "unable to parse roll history from {}: {}",
+                    &source.roll_history,
+                    err
+                );
+                roll_history_changed = true;
+                HashMap::new()
+            }
+        };
+        let mut dice_policy_changed = false;
+        let dice_policy = match serde_json::from_str(&source.dice_policy) {
+            Ok(p) => p,
+            Err(err) => {
+                log::warn!(
+                    "unable to parse dice policy from {}: {}",
+                    &source.dice_policy,
+                    err
+                );
+                dice_policy_changed = true;
+                DicePolicy::default()
+            }
+        };
         let mut aliases_changed = false;
         let aliases = match serde_json::from_str(&source.aliases) {
             Ok(a) => a,
@@ -119,16 +382,84 @@ impl ClientInformation {
                 HashMap::new()
             }
         };
+        let mut user_aliases_changed = false;
+        let user_aliases = match serde_json::from_str(&source.user_aliases) {
+            Ok(a) => a,
+            Err(err) => {
+                log::warn!(
+                    "unable to parse user aliases from {}: {}",
+                    &source.user_aliases,
+                    err
+                );
+                user_aliases_changed = true;
+                HashMap::new()
+            }
+        };
+        let mut stats_roll_changed = false;
+        let stats_roll = match source.stats_roll.as_deref().map(serde_json::from_str) {
+            Some(Ok(r)) => Some(r),
+            Some(Err(err)) => {
+                log::warn!(
+                    "unable to parse stats roll from {:?}: {}",
+                    &source.stats_roll,
+                    err
+                );
+                stats_roll_changed = true;
+                None
+            }
+            None => None,
+        };
         ClientInformation {
             source,
             roll_prefix,
+            privileged_roles,
+            channel_prefixes,
+            roll_history,
+            dice_policy,
             aliases,
+            user_aliases,
+            stats_roll,
             command_prefix_changed: false,
             roll_prefix_changed,
             aliases_changed,
             roll_info_changed: false,
+            paused_changed: false,
+            alias_hints_changed: false,
+            list_order_changed: false,
+            user_aliases_changed,
+            roll_shorthand_changed: false,
+            stats_roll_changed,
+            stats_min_total_changed: false,
+            critical_message_changed: false,
+            fumble_message_changed: false,
+            show_luck_changed: false,
+            privileged_roles_changed,
+            channel_prefixes_changed,
+            roll_history_changed,
+            dice_policy_changed,
         }
     }
+    /// whether anything hasn't been written to the database yet
+    fn changed(&self) -> bool {
+        self.command_prefix_changed
+            || self.roll_prefix_changed
+            || self.aliases_changed
+            || self.roll_info_changed
+            || self.paused_changed
+            || self.alias_hints_changed
+            || self.list_order_changed
+            || self.user_aliases_changed
+            || self.roll_shorthand_changed
+            || self.stats_roll_changed
+            || self.stats_min_total_changed
+            || self.critical_message_changed
+            || self.fumble_message_changed
+            || self.show_luck_changed
+            || self.privileged_roles_changed
+            || self.channel_prefixes_changed
+            || self.roll_history_changed
+            || self.dice_policy_changed
+    }
 
     fn get_cmd_prefix(&self) -> &str {
         &self.source.command_prefix
@@ -144,12 +475,73 @@ impl ClientInformation {
         self.roll_prefix_changed = true;
         &mut self.roll_prefix
     }
-    fn get_aliases(&self) -> &HashMap<String, Arc<VersionedRollExpr>> {
-        &self.aliases
+    /// command prefix in `channel`, the one of the client unless the channel has its own
+    fn get_channel_cmd_prefix(&self, channel: Option<&str>) -> &str {
+        channel
+            .and_then(|c| self.channel_prefixes.get(c))
+            .and_then(|p| p.command_prefix.as_deref())
+            .unwrap_or_else(|| self.get_cmd_prefix())
+    }
+    /// roll prefixes in `channel`, the ones of the client unless the channel has its own
+    fn get_channel_roll_prefix(&self, channel: Option<&str>) -> &[String] {
+        match channel.and_then(|c| self.channel_prefixes.get(c)) {
+            Some(p) if !p.roll_prefixes.is_empty() => &p.roll_prefixes,
+            _ => self.get_roll_prefix(),
+        }
+    }
+    fn get_channel_prefixes_mut(&mut self, channel: &str) -> &mut ChannelPrefixes {
+        self.channel_prefixes_changed = true;
+        self.channel_prefixes.entry(channel.to_owned()).or_default()
+    }
+    /// drops the entry of a channel without any prefixes of its own left
+    fn prune_channel_prefixes(&mut self, channel: &str) {
+        if self
+            .channel_prefixes
+            .get(channel)
+            .is_some_and(|p| p.is_empty())
+        {
+            self.channel_prefixes.remove(channel);
+        }
+    }
+    fn get_privileged_roles(&self) -> &[String] {
+        &self.privileged_roles
+    }
+    fn get_privileged_roles_mut(&mut self) -> &mut Vec<String> {
+        self.privileged_roles_changed = true;
+        &mut self.privileged_roles
+    }
+    fn get_aliases(&self, scope: &AliasScope) -> Option<&Aliases> {
+        match scope {
+            AliasScope::Shared => Some(&self.aliases),
+            AliasScope::User(user) => self.user_aliases.get(user),
+        }
+    }
+    fn get_aliases_mut(&mut self, scope: &AliasScope) -> &mut Aliases {
+        match scope {
+            AliasScope::Shared => {
+                self.aliases_changed = true;
+                &mut self.aliases
+            }
+            AliasScope::User(user) => {
+                self.user_aliases_changed = true;
+                self.user_aliases.entry(user.to_owned()).or_default()
+            }
+        }
+    }
+    /// drops the entry of a user without any personal aliases left
+    fn prune_user_aliases(&mut self, scope: &AliasScope) {
+        if let AliasScope::User(user) = scope {
+            if self.user_aliases.get(user).is_some_and(|a| a.is_empty()) {
+                self.user_aliases.remove(user);
+            }
+        }
     }
-    fn get_aliases_mut(&mut self) -> &mut HashMap<String, Arc<VersionedRollExpr>> {
-        self.aliases_changed = true;
-        &mut self.aliases
+    /// alias `name` as seen by `user`, personal aliases shadow shared ones
+    fn lookup_alias(&self, user: Option<&str>, name: &str) -> Option<&Arc<VersionedRollExpr>> {
+        user.and_then(|u| self.user_aliases.get(u))
+            .and_then(|a| find_alias(a, name))
+            .or_else(|| find_alias(&self.aliases, name))
+            .map(|(_, expr)| expr)
     }
     fn get_roll_info(&self) -> bool {
         self.source.roll_info
@@ -158,6 +550,130 @@ impl ClientInformation {
         self.roll_info_changed = true;
         &mut self.source.roll_info
     }
+    fn get_paused(&self) -> bool {
+        self.source.paused
+    }
+    fn get_paused_mut(&mut self) -> &mut bool {
+        self.paused_changed = true;
+        &mut self.source.paused
+    }
+    fn get_alias_hints(&self) -> bool {
+        self.source.alias_hints
+    }
+    fn get_alias_hints_mut(&mut self) -> &mut bool {
+        self.alias_hints_changed = true;
+        &mut self.source.alias_hints
+    }
+    fn get_show_luck(&self) -> bool {
+        self.source.show_luck
+    }
+    fn get_show_luck_mut(&mut self) -> &mut bool {
+        self.show_luck_changed = true;
+        &mut self.source.show_luck
+    }
+    fn get_roll_shorthand(&self) -> Option<&str> {
+        self.source.roll_shorthand.as_deref()
+    }
+    fn get_roll_shorthand_mut(&mut self) -> &mut Option<String> {
+        self.roll_shorthand_changed = true;
+        &mut self.source.roll_shorthand
+    }
+    fn get_stats_roll(&self) -> Option<&Arc<VersionedRollExpr>> {
+        self.stats_roll.as_ref()
+    }
+    fn get_stats_roll_mut(&mut self) -> &mut Option<Arc<VersionedRollExpr>> {
+        self.stats_roll_changed = true;
+        &mut self.stats_roll
+    }
+    fn get_stats_min_total(&self) -> Option<i64> {
+        self.source.stats_min_total
+    }
+    fn get_stats_min_total_mut(&mut self) -> &mut Option<i64> {
+        self.stats_min_total_changed = true;
+        &mut self.source.stats_min_total
+    }
+    /// announcement template for `critical`
+    fn get_critical_message(&self, critical: Critical) -> Option<&str> {
+        match critical {
+            Critical::Hit => self.source.critical_message.as_deref(),
+            Critical::Fumble => self.source.fumble_message.as_deref(),
+        }
+    }
+    fn get_critical_message_mut(&mut self, critical: Critical) -> &mut Option<String> {
+        match critical {
+            Critical::Hit => {
+                self.critical_message_changed = true;
+                &mut self.source.critical_message
+            }
+            Critical::Fumble => {
+                self.fumble_message_changed = true;
+                &mut self.source.fumble_message
+            }
+        }
+    }
+    /// replaces everything with `config`, marking all of it as changed
+    fn import(&mut self, config: FullConfig) {
+        *self.get_cmd_prefix_mut() = config.command_prefix;
+        *self.get_roll_prefix_mut() = config.roll_prefixes;
+        *self.get_aliases_mut(&AliasScope::Shared) = config.aliases;
+        self.user_aliases = config.user_aliases;
+        self.user_aliases_changed = true;
+        *self.get_roll_info_mut() = config.roll_info;
+        *self.get_paused_mut() = config.paused;
+        *self.get_alias_hints_mut() = config.alias_hints;
+        self.set_list_order(config.list_order);
+        *self.get_roll_shorthand_mut() = config.roll_shorthand;
+        *self.get_stats_roll_mut() = config.stats_roll;
+        *self.get_stats_min_total_mut() = config.stats_min_total;
+        *self.get_critical_message_mut(Critical::Hit) = config.critical_message;
+        *self.get_critical_message_mut(Critical::Fumble) = config.fumble_message;
+        *self.get_show_luck_mut() = config.show_luck;
+        *self.get_privileged_roles_mut() = config.privileged_roles;
+        self.channel_prefixes = config.channel_prefixes;
+        self.channel_prefixes_changed = true;
+        *self.get_dice_policy_mut() = config.dice_policy;
+    }
+    fn get_dice_policy(&self) -> &DicePolicy {
+        &self.dice_policy
+    }
+    fn get_dice_policy_mut(&mut self) -> &mut DicePolicy {
+        self.dice_policy_changed = true;
+        &mut self.dice_policy
+    }
+    fn get_list_order(&self) -> ListOrder {
+        ListOrder::from_db(self.source.list_order)
+    }
+    /// appends `entries` to the history of `user`, keeping at most `max` of their rolls
+    fn push_history(&mut self, user: String, entries: Vec<HistoryEntry>, max: usize) {
+        self.roll_history_changed = true;
+        let history = self.roll_history.entry(user.clone()).or_default();
+        history.extend(entries);
+        while history.len() > max {
+            history.pop_front();
+        }
+        if history.is_empty() {
+            self.roll_history.remove(&user);
+        }
+    }
+    /// rolls of `user`, the latest first
+    fn get_history(&self, user: &str) -> Vec<HistoryEntry> {
+        self.roll_history
+            .get(user)
+            .map(|h| h.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+    fn set_list_order(&mut self, order: ListOrder) {
+        self.list_order_changed = true;
+        self.source.list_order = order.to_db();
+    }
+}
+
+/// lookups of a [StorageHandle::get_many] call, sent together once the last one is done
+#[derive(Debug)]
+struct ManyLookups {
+    lookups: Vec<Option<Lookup>>,
+    remaining: usize,
+    sender: Option<oneshot::Sender<Vec<Lookup>>>,
 }
 
 #[derive(Debug)]
@@ -167,79 +683,407 @@ enum StorageOps {
     GetRollPrefixes(oneshot::Sender<Vec<String>>),
     AddRollPrefix(String, oneshot::Sender<Result<(), ()>>),
     RemoveRollPrefix(String, oneshot::Sender<Result<(), ()>>),
-    GetAllAlias(oneshot::Sender<HashMap<String, Arc<VersionedRollExpr>>>),
-    GetAlias(String, oneshot::Sender<Option<Arc<VersionedRollExpr>>>),
-    AddAlias(String, VersionedRollExpr, oneshot::Sender<Result<(), ()>>),
-    RemoveAlias(String, oneshot::Sender<Result<(), ()>>),
+    /// removes every roll prefix of the client, sends how many there were
+    ClearRollPrefixes(oneshot::Sender<usize>),
+    SetChannelCommandPrefix(String, Option<String>, oneshot::Sender<()>),
+    AddChannelRollPrefix(String, String, oneshot::Sender<Result<(), ()>>),
+    RemoveChannelRollPrefix(String, String, oneshot::Sender<Result<(), ()>>),
+    AddPrivilegedRole(String, oneshot::Sender<Result<(), ()>>),
+    RemovePrivilegedRole(String, oneshot::Sender<Result<(), ()>>),
+    GetAllAlias(AliasScope, oneshot::Sender<Aliases>),
+    GetAlias(
+        AliasScope,
+        String,
+        oneshot::Sender<Option<Arc<VersionedRollExpr>>>,
+    ),
+    AddAlias(
+        AliasScope,
+        String,
+        VersionedRollExpr,
+        AliasLimits,
+        oneshot::Sender<Result<(), AliasLimitError>>,
+    ),
+    InsertAlias(
+        AliasScope,
+        String,
+        Arc<VersionedRollExpr>,
+        oneshot::Sender<Result<(), ()>>,
+    ),
+    RemoveAlias(AliasScope, String, oneshot::Sender<Result<(), ()>>),
+    /// removes every alias of the scope, sends how many there were
+    ClearAliases(AliasScope, oneshot::Sender<usize>),
     GetRollInfo(oneshot::Sender<bool>),
     SetRollInfo(bool, oneshot::Sender<()>),
+    SetPaused(bool, oneshot::Sender<()>),
+    SetAliasHints(bool, oneshot::Sender<()>),
+    SetShowLuck(bool, oneshot::Sender<()>),
+    GetListOrder(oneshot::Sender<ListOrder>),
+    SetListOrder(ListOrder, oneshot::Sender<()>),
+    SetRollShorthand(Option<String>, oneshot::Sender<()>),
+    SetStatsRoll(Option<VersionedRollExpr>, oneshot::Sender<()>),
+    SetStatsMinTotal(Option<i64>, oneshot::Sender<()>),
+    SetCriticalMessage(Critical, Option<String>, oneshot::Sender<()>),
+    /// lookup without user, channel or aliases, at the given position of a batch
+    GetMany(usize, Arc<Mutex<ManyLookups>>),
     Get(
+        Option<String>,
+        Option<String>,
         Vec<String>,
-        oneshot::Sender<(String, Vec<String>, Vec<Arc<VersionedRollExpr>>, bool)>,
+        oneshot::Sender<Lookup>,
     ),
+    GetFullConfig(oneshot::Sender<FullConfig>),
+    Import(Box<FullConfig>, oneshot::Sender<()>),
+    /// appends rolls of a user to their history, which keeps at most the given number of them
+    PushHistory(String, Vec<HistoryEntry>, usize, oneshot::Sender<()>),
+    /// bans or allows a kind of dice, `Err` if it already was
+    SetDiceBanned(DiceKind, bool, oneshot::Sender<Result<(), ()>>),
+    SetMaxFaces(Option<u32>, oneshot::Sender<()>),
+    GetHistory(String, oneshot::Sender<Vec<HistoryEntry>>),
+    /// forget the cached configuration, so it is loaded from the database again
+    Evict(oneshot::Sender<()>),
+}
+
+/// order in which the results of a list expression are displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListOrder {
+    Unsorted,
+    Ascending,
+    Descending,
+}
+
+impl ListOrder {
+    fn default_order() -> ListOrder {
+        ListOrder::Unsorted
+    }
+    fn from_db(value: i32) -> ListOrder {
+        match value {
+            1 => ListOrder::Ascending,
+            2 => ListOrder::Descending,
+            _ => ListOrder::Unsorted,
+        }
+    }
+    fn to_db(self) -> i32 {
+        match self {
+            ListOrder::Unsorted => 0,
+            ListOrder::Ascending => 1,
+            ListOrder::Descending => 2,
+        }
+    }
+}
+
+impl fmt::Display for ListOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListOrder::Unsorted => write!(f, "unsorted"),
+            ListOrder::Ascending => write!(f, "ascending"),
+            ListOrder::Descending => write!(f, "descending"),
+        }
+    }
+}
+
+/// everything needed to interpret a single message of a client
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    /// channel the message was sent in, if the client has channels
+    pub channel: Option<String>,
+    /// command prefix in the channel of the message
+    pub command_prefix: String,
+    /// roll prefixes in the channel of the message
+    pub roll_prefixes: Vec<String>,
+    /// name and expression of every alias found in the message
+    pub aliases: Vec<(String, Arc<VersionedRollExpr>)>,
+    pub roll_info: bool,
+    pub paused: bool,
+    pub alias_hints: bool,
+    pub list_order: ListOrder,
+    pub roll_shorthand: Option<String>,
+    /// list roll used to generate ability scores, `None` for the default
+    pub stats_roll: Option<Arc<VersionedRollExpr>>,
+    /// ability scores with a smaller total are rerolled
+    pub stats_min_total: Option<i64>,
+    /// announcement of a natural 20, see [Critical]
+    pub critical_message: Option<String>,
+    /// announcement of a natural 1, see [Critical]
+    pub fumble_message: Option<String>,
+    /// tell how far a result is from the average
+    pub show_luck: bool,
+    /// members with any of these roles may use privileged commands
+    pub privileged_roles: Vec<String>,
+    /// dice that may not be rolled
+    pub dice_policy: DicePolicy,
+}
+
+/// complete configuration of a client
+///
+/// settings added after the first ones are optional, so older exports can still be imported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FullConfig {
+    pub command_prefix: String,
+    pub roll_prefixes: Vec<String>,
+    pub aliases: Aliases,
+    #[serde(default)]
+    pub user_aliases: HashMap<String, Aliases>,
+    pub roll_info: bool,
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default)]
+    pub alias_hints: bool,
+    #[serde(default = "ListOrder::default_order")]
+    pub list_order: ListOrder,
+    #[serde(default)]
+    pub roll_shorthand: Option<String>,
+    #[serde(default)]
+    pub stats_roll: Option<Arc<VersionedRollExpr>>,
+    #[serde(default)]
+    pub stats_min_total: Option<i64>,
+    #[serde(default)]
+    pub critical_message: Option<String>,
+    #[serde(default)]
+    pub fumble_message: Option<String>,
+    #[serde(default)]
+    pub show_luck: bool,
+    #[serde(default)]
+    pub privileged_roles: Vec<String>,
+    #[serde(default)]
+    pub channel_prefixes: HashMap<String, ChannelPrefixes>,
+    #[serde(default)]
+    pub dice_policy: DicePolicy,
+}
+
+/// bounds on the aliases a single scope may hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AliasLimits {
+    /// most aliases in one scope
+    pub max_aliases: usize,
+    /// longest alias name in characters
+    pub max_alias_len: usize,
+}
+
+impl Default for AliasLimits {
+    fn default() -> Self {
+        AliasLimits {
+            max_aliases: 256,
+            max_alias_len: 32,
+        }
+    }
+}
+
+/// reasons [StorageHandle::add_alias] refuses an alias
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasLimitError {
+    /// the scope already holds `count` aliases and no more than `max` are allowed
+    Count { count: usize, max: usize },
+    /// the name is `len` characters long, longer than `max`
+    NameLength { len: usize, max: usize },
+}
+
+impl fmt::Display for AliasLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasLimitError::Count { count, max } => write!(
+                f,
+                "there are already {} aliases, no more than {} are allowed",
+                count, max
+            ),
+            AliasLimitError::NameLength { len, max } => write!(
+                f,
+                "the alias name has {} characters, no more than {} are allowed",
+                len, max
+            ),
+        }
+    }
+}
+
+/// reasons an exported configuration is rejected by [StorageHandle::import]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    /// the text is no exported configuration
+    Malformed(String),
+    /// the alias could never have been added with the alias command
+    InvalidAlias(String),
+    /// there is no command prefix, so no command could be sent anymore
+    EmptyCommandPrefix,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Malformed(err) => write!(f, "not an exported configuration: {}", err),
+            ImportError::InvalidAlias(alias) => write!(f, "`{}` is not a valid alias", alias),
+            ImportError::EmptyCommandPrefix => write!(f, "the command prefix is empty"),
+        }
+    }
+}
+
+impl FullConfig {
+    /// checks everything that the commands would have rejected
+    fn validate(&self) -> Result<(), ImportError> {
+        if self.command_prefix.is_empty() {
+            return Err(ImportError::EmptyCommandPrefix);
+        }
+        match self
+            .aliases
+            .iter()
+            .chain(self.user_aliases.values().flatten())
+            .find(|(name, expr)| !super::commands::valid_alias(name, expr))
+        {
+            Some((name, _)) => Err(ImportError::InvalidAlias(name.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// queries of the storage, implemented by every supported diesel connection
+trait Database: diesel::connection::SimpleConnection {
+    fn find_config(&self, client_id: &str) -> QueryResult<ClientConfig>;
+    fn insert_config(&self, config: &ClientConfig) -> QueryResult<usize>;
+    fn update_config(&self, client_id: &str, change: ClientConfigChangeset) -> QueryResult<usize>;
+}
+
+impl Database for SqliteConnection {
+    fn find_config(&self, client_id: &str) -> QueryResult<ClientConfig> {
+        use schema::client_config::dsl::*;
+        client_config.find(client_id).first(self)
+    }
+    fn insert_config(&self, config: &ClientConfig) -> QueryResult<usize> {
+        use schema::client_config::dsl::*;
+        diesel::insert_into(client_config)
+            .values(config)
+            .execute(self)
+    }
+    fn update_config(&self, client_id: &str, change: ClientConfigChangeset) -> QueryResult<usize> {
+        use schema::client_config::dsl::*;
+        diesel::update(client_config.find(client_id))
+            .set(change)
+            .execute(self)
+    }
+}
+
+type DbJob = Box<dyn Send + FnOnce(&dyn Database)>;
+
+/// kind of database a storage url points to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    Sqlite,
+    /// recognized only to refuse it, there is no postgres support yet
+    Postgres,
+}
+
+impl Backend {
+    /// postgres for `postgres://` and `postgresql://` urls, anything else is the path of a
+    /// sqlite database
+    pub(crate) fn of_url(db_url: &str) -> Backend {
+        if ["postgres://", "postgresql://"]
+            .iter()
+            .any(|scheme| db_url.starts_with(scheme))
+        {
+            Backend::Postgres
+        } else {
+            Backend::Sqlite
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Sqlite => write!(f, "sqlite"),
+            Backend::Postgres => write!(f, "postgres"),
+        }
+    }
 }
 
 pub(crate) struct GlobalStorage {
-    db_submit: mpsc::Sender<Box<dyn Send + FnOnce(&SqliteConnection)>>,
+    db_submit: mpsc::Sender<DbJob>,
 }
 
 impl GlobalStorage {
     pub(crate) fn new(
+        backend: Backend,
         db_url: String,
         channel_size: usize,
     ) -> diesel::ConnectionResult<(GlobalStorage, std::thread::JoinHandle<()>)> {
+        let (sender, receiver) = mpsc::channel(channel_size);
+        let worker = match backend {
+            Backend::Sqlite => Self::spawn_worker::<SqliteConnection>(db_url, receiver),
+            // sqlite would otherwise create a database file named like the url
+            Backend::Postgres => {
+                return Err(diesel::ConnectionError::InvalidConnectionUrl(format!(
+                    "{} is a postgres url, but only sqlite databases are supported",
+                    db_url
+                )))
+            }
+        };
+        Ok((GlobalStorage { db_submit: sender }, worker))
+    }
+    fn spawn_worker<C: Connection + Database + 'static>(
+        db_url: String,
+        mut receiver: mpsc::Receiver<DbJob>,
+    ) -> std::thread::JoinHandle<()> {
+        std::thread::Builder::new()
+            .name("db_worker".to_string())
+            .spawn(move || loop {
+                let db = C::establish(&db_url).unwrap();
+                match receiver.blocking_recv() {
+                    Some(f) => f(&db),
+                    None => {
+                        break log::info!("db worker queue closed");
+                    }
+                }
+            })
+            .unwrap()
+    }
+    /// storage in a database that only lives as long as its worker, for tests
+    ///
+    /// every connection to `:memory:` opens a database of its own, so the worker keeps
+    /// a single one for its whole life
+    #[cfg(test)]
+    pub(crate) fn new_in_memory(
+        channel_size: usize,
+    ) -> (GlobalStorage, std::thread::JoinHandle<()>) {
         let (sender, mut receiver) = mpsc::channel(channel_size);
-        Ok((
+        (
             GlobalStorage { db_submit: sender },
             std::thread::Builder::new()
                 .name("db_worker".to_string())
-                .spawn(move || loop {
-                    let db = SqliteConnection::establish(&db_url).unwrap();
-                    match receiver.blocking_recv() {
-                        Some(f) => f(&db),
-                        None => {
-                            break log::info!("db worker queue closed");
-                        }
+                .spawn(move || {
+                    let db = SqliteConnection::establish(":memory:").unwrap();
+                    tests::migrate(&db);
+                    while let Some(f) = receiver.blocking_recv() {
+                        f(&db)
                     }
+                    log::info!("db worker queue closed");
                 })
                 .unwrap(),
-        ))
+        )
     }
-    async fn get<Id: ClientId>(
-        &self,
-        client_id: String,
-        c_id: Id,
-        sender: mpsc::UnboundedSender<(Id, ClientConfig)>,
-    ) {
+    /// configuration of `client_id`, which is created if it doesn't exist yet
+    async fn get(&self, client_id: String) -> ClientConfig {
+        let (sender, receiver) = oneshot::channel();
         match self
             .db_submit
-            .send(Box::from(move |db: &SqliteConnection| {
-                use schema::client_config::dsl::*;
-                sender
-                    .send((
-                        c_id,
-                        match client_config.find(&client_id).first(db) {
-                            Ok(v) => v,
+            .send(Box::from(move |db: &dyn Database| {
+                let _ = sender.send(match db.find_config(&client_id) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        log::info!("Error getting {} from db: {}", &client_id, err);
+                        let conf = ClientConfig::new(client_id);
+                        match db.insert_config(&conf) {
+                            Ok(_) => {}
                             Err(err) => {
-                                log::info!("Error getting {} from db: {}", &client_id, err);
-                                let conf = ClientConfig::new(client_id);
-                                match diesel::insert_into(client_config).values(&conf).execute(db) {
-                                    Ok(_) => {}
-                                    Err(err) => {
-                                        log::warn!("{}", err);
-                                    }
-                                };
-                                conf
+                                log::warn!("{}", err);
                             }
-                        },
-                    ))
-                    .unwrap()
+                        };
+                        conf
+                    }
+                });
             }))
             .await
         {
             Ok(_) => {}
             Err(_) => panic!("unable to submit to db worker queue"),
         };
+        receiver.await.expect("db worker dropped a query")
     }
 
     async fn set(&self, config: &mut ClientInformation) {
@@ -270,56 +1114,222 @@ impl GlobalStorage {
             } else {
                 None
             },
-        };
-        let id_clone = config.source.id.to_string();
-        match self
-            .db_submit
-            .send(Box::new(move |db| {
-                diesel::update(schema::client_config::dsl::client_config.find(&id_clone))
-                    .set(change)
-                    .execute(db)
-                    .unwrap();
-            }))
-            .await
-        {
-            Ok(_) => {}
-            Err(_) => panic!("unable to submit to db queue"),
-        };
-    }
-}
-
-struct ClientStorage<Id: ClientId> {
-    client_type: String,
-    db_cache: SizedCache<Id, ClientInformation>,
-    query_cache: HashMap<Id, Vec<StorageOps>>,
-    global: Arc<GlobalStorage>,
-    receiver: mpsc::Receiver<(Id, StorageOps)>,
-}
-
-fn run_cmd(client: &mut ClientInformation, op: StorageOps) -> bool {
-    match op {
-        StorageOps::GetCommandPrefix(channel) => {
-            channel.send(client.get_cmd_prefix().to_owned()).unwrap();
-            false
-        }
-        StorageOps::SetCommandPrefix(prefix, channel) => {
-            *client.get_cmd_prefix_mut() = prefix;
-            channel.send(()).unwrap();
-            true
-        }
-        StorageOps::GetRollPrefixes(channel) => {
-            channel.send(client.get_roll_prefix().to_owned()).unwrap();
-            false
-        }
-        StorageOps::AddRollPrefix(prefix, channel) => {
-            channel
-                .send(if client.get_roll_prefix().contains(&prefix) {
-                    Err(())
-                } else {
-                    client.get_roll_prefix_mut().push(prefix);
-                    Ok(())
-                })
-                .unwrap();
+            paused: if config.paused_changed {
+                config.paused_changed = false;
+                Some(config.source.paused)
+            } else {
+                None
+            },
+            alias_hints: if config.alias_hints_changed {
+                config.alias_hints_changed = false;
+                Some(config.source.alias_hints)
+            } else {
+                None
+            },
+            list_order: if config.list_order_changed {
+                config.list_order_changed = false;
+                Some(config.source.list_order)
+            } else {
+                None
+            },
+            roll_shorthand: if config.roll_shorthand_changed {
+                config.roll_shorthand_changed = false;
+                Some(config.source.roll_shorthand.clone())
+            } else {
+                None
+            },
+            stats_roll: if config.stats_roll_changed {
+                config.stats_roll_changed = false;
+                Some(
+                    config
+                        .stats_roll
+                        .as_ref()
+                        .and_then(|r| serde_json::to_string(r).ok()),
+                )
+            } else {
+                None
+            },
+            stats_min_total: if config.stats_min_total_changed {
+                config.stats_min_total_changed = false;
+                Some(config.source.stats_min_total)
+            } else {
+                None
+            },
+            critical_message: if config.critical_message_changed {
+                config.critical_message_changed = false;
+                Some(config.source.critical_message.clone())
+            } else {
+                None
+            },
+            fumble_message: if config.fumble_message_changed {
+                config.fumble_message_changed = false;
+                Some(config.source.fumble_message.clone())
+            } else {
+                None
+            },
+            show_luck: if config.show_luck_changed {
+                config.show_luck_changed = false;
+                Some(config.source.show_luck)
+            } else {
+                None
+            },
+            dice_policy: if config.dice_policy_changed {
+                config.dice_policy_changed = false;
+                Some(
+                    serde_json::to_string(&config.dice_policy).unwrap_or_else(|_| "{}".to_string()),
+                )
+            } else {
+                None
+            },
+            roll_history: if config.roll_history_changed {
+                config.roll_history_changed = false;
+                Some(
+                    serde_json::to_string(&config.roll_history)
+                        .unwrap_or_else(|_| "{}".to_string()),
+                )
+            } else {
+                None
+            },
+            channel_prefixes: if config.channel_prefixes_changed {
+                config.channel_prefixes_changed = false;
+                Some(
+                    serde_json::to_string(&config.channel_prefixes)
+                        .unwrap_or_else(|_| "{}".to_string()),
+                )
+            } else {
+                None
+            },
+            privileged_roles: if config.privileged_roles_changed {
+                config.privileged_roles_changed = false;
+                Some(
+                    serde_json::to_string(&config.privileged_roles)
+                        .unwrap_or_else(|_| "[]".to_string()),
+                )
+            } else {
+                None
+            },
+            user_aliases: if config.user_aliases_changed {
+                config.user_aliases_changed = false;
+                Some(
+                    serde_json::to_string(&config.user_aliases)
+                        .unwrap_or_else(|_| "{}".to_string()),
+                )
+            } else {
+                None
+            },
+        };
+        let id_clone = config.source.id.to_string();
+        match self
+            .db_submit
+            .send(Box::new(move |db: &dyn Database| {
+                db.update_config(&id_clone, change).unwrap();
+            }))
+            .await
+        {
+            Ok(_) => {}
+            Err(_) => panic!("unable to submit to db queue"),
+        };
+    }
+}
+
+/// number of buckets the cache of a client type is split into
+const BUCKETS: usize = 16;
+
+/// cached clients whose ids hash to the same bucket
+struct Bucket<Id: ClientId> {
+    db_cache: SizedCache<Id, ClientInformation>,
+    /// operations waiting for their client to be loaded from the database
+    query_cache: HashMap<Id, Vec<StorageOps>>,
+}
+
+type Buckets<Id> = Vec<tokio::sync::Mutex<Bucket<Id>>>;
+
+/// clients in different buckets are served concurrently, operations on the clients of one bucket
+/// run one after another
+struct ClientStorage<Id: ClientId> {
+    client_type: String,
+    db_cache: Buckets<Id>,
+    hash_builder: RandomState,
+    global: Arc<GlobalStorage>,
+    /// receives the cached clients once the last reference to the storage is dropped, so
+    /// its join handle can write what is still changed
+    finished: Option<oneshot::Sender<Buckets<Id>>>,
+}
+
+impl<Id: ClientId> Drop for ClientStorage<Id> {
+    fn drop(&mut self) {
+        if let Some(finished) = self.finished.take() {
+            let _ = finished.send(std::mem::take(&mut self.db_cache));
+        }
+    }
+}
+
+/// configuration needed for a message of `user` in `message_channel`, with the `aliases` it
+/// may contain
+fn lookup(
+    client: &ClientInformation,
+    user: Option<String>,
+    message_channel: Option<String>,
+    aliases: Vec<String>,
+) -> Lookup {
+    Lookup {
+        command_prefix: client
+            .get_channel_cmd_prefix(message_channel.as_deref())
+            .to_owned(),
+        roll_prefixes: client
+            .get_channel_roll_prefix(message_channel.as_deref())
+            .to_owned(),
+        aliases: aliases
+            .into_iter()
+            .filter_map(|alias| {
+                let expr = client.lookup_alias(user.as_deref(), &alias)?.to_owned();
+                Some((alias, expr))
+            })
+            .collect(),
+        roll_info: client.get_roll_info(),
+        paused: client.get_paused(),
+        alias_hints: client.get_alias_hints(),
+        list_order: client.get_list_order(),
+        roll_shorthand: client.get_roll_shorthand().map(|s| s.to_owned()),
+        stats_roll: client.get_stats_roll().cloned(),
+        stats_min_total: client.get_stats_min_total(),
+        critical_message: client
+            .get_critical_message(Critical::Hit)
+            .map(|s| s.to_owned()),
+        fumble_message: client
+            .get_critical_message(Critical::Fumble)
+            .map(|s| s.to_owned()),
+        show_luck: client.get_show_luck(),
+        privileged_roles: client.get_privileged_roles().to_owned(),
+        dice_policy: client.get_dice_policy().clone(),
+        channel: message_channel,
+    }
+}
+
+fn run_cmd(client: &mut ClientInformation, op: StorageOps) -> bool {
+    match op {
+        StorageOps::GetCommandPrefix(channel) => {
+            channel.send(client.get_cmd_prefix().to_owned()).unwrap();
+            false
+        }
+        StorageOps::SetCommandPrefix(prefix, channel) => {
+            *client.get_cmd_prefix_mut() = prefix;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::GetRollPrefixes(channel) => {
+            channel.send(client.get_roll_prefix().to_owned()).unwrap();
+            false
+        }
+        StorageOps::AddRollPrefix(prefix, channel) => {
+            channel
+                .send(if client.get_roll_prefix().contains(&prefix) {
+                    Err(())
+                } else {
+                    client.get_roll_prefix_mut().push(prefix);
+                    Ok(())
+                })
+                .unwrap();
             true
         }
         StorageOps::RemoveRollPrefix(prefix, channel) => {
@@ -337,63 +1347,197 @@ fn run_cmd(client: &mut ClientInformation, op: StorageOps) -> bool {
                 .unwrap();
             true
         }
-        StorageOps::GetAllAlias(channel) => {
-            channel.send(client.get_aliases().to_owned()).unwrap();
-            false
+        StorageOps::ClearRollPrefixes(channel) => {
+            let cleared = client.get_roll_prefix().len();
+            client.get_roll_prefix_mut().clear();
+            channel.send(cleared).unwrap();
+            true
+        }
+        StorageOps::SetChannelCommandPrefix(message_channel, prefix, channel) => {
+            client
+                .get_channel_prefixes_mut(&message_channel)
+                .command_prefix = prefix;
+            client.prune_channel_prefixes(&message_channel);
+            channel.send(()).unwrap();
+            true
         }
-        StorageOps::GetAlias(name, channel) => {
+        StorageOps::AddChannelRollPrefix(message_channel, prefix, channel) => {
+            let prefixes = &mut client
+                .get_channel_prefixes_mut(&message_channel)
+                .roll_prefixes;
+            let result = if prefixes.contains(&prefix) {
+                Err(())
+            } else {
+                prefixes.push(prefix);
+                Ok(())
+            };
+            channel.send(result).unwrap();
+            true
+        }
+        StorageOps::RemoveChannelRollPrefix(message_channel, prefix, channel) => {
+            let prefixes = &mut client
+                .get_channel_prefixes_mut(&message_channel)
+                .roll_prefixes;
+            let result = prefixes
+                .iter()
+                .position(|p| p == &prefix)
+                .map(|p| {
+                    prefixes.remove(p);
+                })
+                .ok_or(());
+            client.prune_channel_prefixes(&message_channel);
+            channel.send(result).unwrap();
+            true
+        }
+        StorageOps::AddPrivilegedRole(role, channel) => {
+            let result = if client.get_privileged_roles().contains(&role) {
+                Err(())
+            } else {
+                client.get_privileged_roles_mut().push(role);
+                Ok(())
+            };
+            channel.send(result).unwrap();
+            result.is_ok()
+        }
+        StorageOps::RemovePrivilegedRole(role, channel) => {
+            let result = client
+                .get_privileged_roles()
+                .iter()
+                .position(|r| r == &role)
+                .map(|p| {
+                    client.get_privileged_roles_mut().remove(p);
+                })
+                .ok_or(());
+            channel.send(result).unwrap();
+            result.is_ok()
+        }
+        StorageOps::GetAllAlias(scope, channel) => {
             channel
-                .send(client.get_aliases().get(&name).map(|a| a.to_owned()))
+                .send(client.get_aliases(&scope).cloned().unwrap_or_default())
                 .unwrap();
             false
         }
-        StorageOps::AddAlias(alias, expr, channel) => {
-            let expression = Arc::from(expr);
+        StorageOps::GetAlias(scope, name, channel) => {
             channel
                 .send(
-                    match client.get_aliases_mut().insert(alias, expression.clone()) {
-                        Some(old) => {
-                            if old == expression {
-                                Err(())
-                            } else {
-                                Ok(())
-                            }
-                        }
-                        None => Ok(()),
-                    },
+                    client
+                        .get_aliases(&scope)
+                        .and_then(|a| find_alias(a, &name))
+                        .map(|(_, a)| a.to_owned()),
                 )
                 .unwrap();
+            false
+        }
+        StorageOps::InsertAlias(scope, alias, expr, channel) => {
+            if client
+                .get_aliases(&scope)
+                .is_some_and(|a| find_alias(a, &alias).is_some())
+            {
+                channel.send(Err(())).unwrap();
+                false
+            } else {
+                client
+                    .get_aliases_mut(&scope)
+                    .insert(alias.trim().to_string(), expr);
+                channel.send(Ok(())).unwrap();
+                true
+            }
+        }
+        StorageOps::AddAlias(scope, alias, expr, limits, channel) => {
+            let alias = alias.trim().to_string();
+            let len = alias.chars().count();
+            let (count, exists) = client
+                .get_aliases(&scope)
+                .map(|a| (a.len(), find_alias(a, &alias).is_some()))
+                .unwrap_or((0, false));
+            if len > limits.max_alias_len {
+                channel
+                    .send(Err(AliasLimitError::NameLength {
+                        len,
+                        max: limits.max_alias_len,
+                    }))
+                    .unwrap();
+                false
+            } else if !exists && count >= limits.max_aliases {
+                channel
+                    .send(Err(AliasLimitError::Count {
+                        count,
+                        max: limits.max_aliases,
+                    }))
+                    .unwrap();
+                false
+            } else {
+                let aliases = client.get_aliases_mut(&scope);
+                take_alias(aliases, &alias);
+                aliases.insert(alias, Arc::from(expr));
+                channel.send(Ok(())).unwrap();
+                true
+            }
+        }
+        StorageOps::RemoveAlias(scope, alias, channel) => {
+            let removed = take_alias(client.get_aliases_mut(&scope), &alias);
+            client.prune_user_aliases(&scope);
+            channel.send(removed.map(|_| ()).ok_or(())).unwrap();
+            true
+        }
+        StorageOps::ClearAliases(scope, channel) => {
+            let cleared = client.get_aliases(&scope).map_or(0, |a| a.len());
+            client.get_aliases_mut(&scope).clear();
+            client.prune_user_aliases(&scope);
+            channel.send(cleared).unwrap();
             true
         }
-        StorageOps::RemoveAlias(alias, channel) => {
+        StorageOps::GetMany(index, many) => {
+            let mut many = many.lock().unwrap();
+            many.lookups[index] = Some(lookup(client, None, None, Vec::new()));
+            many.remaining -= 1;
+            if many.remaining == 0 {
+                let lookups = many.lookups.drain(..).flatten().collect();
+                if let Some(sender) = many.sender.take() {
+                    sender.send(lookups).unwrap();
+                }
+            }
+            false
+        }
+        StorageOps::Get(user, message_channel, aliases, channel) => {
             channel
-                .send(
-                    client
-                        .get_aliases_mut()
-                        .remove(&alias)
-                        .map(|_| ())
-                        .ok_or(()),
-                )
+                .send(lookup(client, user, message_channel, aliases))
                 .unwrap();
-            true
+            false
         }
-        StorageOps::Get(aliases, channel) => {
+        StorageOps::GetFullConfig(channel) => {
             channel
-                .send((
-                    client.get_cmd_prefix().to_owned(),
-                    client.get_roll_prefix().to_owned(),
-                    {
-                        let a = client.get_aliases();
-                        aliases
-                            .iter()
-                            .filter_map(|alias| a.get(alias).map(|a| a.to_owned()))
-                            .collect()
-                    },
-                    client.get_roll_info(),
-                ))
+                .send(FullConfig {
+                    command_prefix: client.get_cmd_prefix().to_owned(),
+                    roll_prefixes: client.get_roll_prefix().to_owned(),
+                    aliases: client.aliases.to_owned(),
+                    user_aliases: client.user_aliases.to_owned(),
+                    roll_info: client.get_roll_info(),
+                    paused: client.get_paused(),
+                    alias_hints: client.get_alias_hints(),
+                    list_order: client.get_list_order(),
+                    roll_shorthand: client.get_roll_shorthand().map(|s| s.to_owned()),
+                    stats_roll: client.get_stats_roll().cloned(),
+                    stats_min_total: client.get_stats_min_total(),
+                    critical_message: client
+                        .get_critical_message(Critical::Hit)
+                        .map(|s| s.to_owned()),
+                    fumble_message: client
+                        .get_critical_message(Critical::Fumble)
+                        .map(|s| s.to_owned()),
+                    show_luck: client.get_show_luck(),
+                    privileged_roles: client.get_privileged_roles().to_owned(),
+                    channel_prefixes: client.channel_prefixes.to_owned(),
+                    dice_policy: client.get_dice_policy().clone(),
+                })
                 .unwrap();
             false
         }
+        StorageOps::Import(config, channel) => {
+            client.import(*config);
+            channel.send(()).unwrap();
+            true
+        }
         StorageOps::GetRollInfo(channel) => {
             channel.send(client.get_roll_info().to_owned()).unwrap();
             false
@@ -403,6 +1547,86 @@ fn run_cmd(client: &mut ClientInformation, op: StorageOps) -> bool {
             channel.send(()).unwrap();
             true
         }
+        StorageOps::SetPaused(paused, channel) => {
+            *client.get_paused_mut() = paused;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetAliasHints(alias_hints, channel) => {
+            *client.get_alias_hints_mut() = alias_hints;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetShowLuck(show_luck, channel) => {
+            *client.get_show_luck_mut() = show_luck;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetRollShorthand(shorthand, channel) => {
+            *client.get_roll_shorthand_mut() = shorthand;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetStatsRoll(roll, channel) => {
+            *client.get_stats_roll_mut() = roll.map(Arc::new);
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetStatsMinTotal(min_total, channel) => {
+            *client.get_stats_min_total_mut() = min_total;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetCriticalMessage(critical, message, channel) => {
+            *client.get_critical_message_mut(critical) = message;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::GetListOrder(channel) => {
+            channel.send(client.get_list_order()).unwrap();
+            false
+        }
+        StorageOps::SetListOrder(order, channel) => {
+            client.set_list_order(order);
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::SetDiceBanned(kind, banned, channel) => {
+            let policy = client.get_dice_policy_mut();
+            let result = match policy.banned.iter().position(|k| *k == kind) {
+                Some(_) if banned => Err(()),
+                Some(p) => {
+                    policy.banned.remove(p);
+                    Ok(())
+                }
+                None if banned => {
+                    policy.banned.push(kind);
+                    Ok(())
+                }
+                None => Err(()),
+            };
+            channel.send(result).unwrap();
+            true
+        }
+        StorageOps::SetMaxFaces(max_faces, channel) => {
+            client.get_dice_policy_mut().max_faces = max_faces;
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::PushHistory(user, entries, max, channel) => {
+            client.push_history(user, entries, max);
+            channel.send(()).unwrap();
+            true
+        }
+        StorageOps::GetHistory(user, channel) => {
+            channel.send(client.get_history(&user)).unwrap();
+            false
+        }
+        // handled before a client is looked up, a loaded one is already up to date
+        StorageOps::Evict(channel) => {
+            channel.send(()).unwrap();
+            false
+        }
     }
 }
 
@@ -410,86 +1634,68 @@ impl<Id: ClientId> ClientStorage<Id> {
     fn new<S: ToString>(
         client_type: S,
         global: Arc<GlobalStorage>,
-        channel_size: usize,
         cache_size: usize,
-    ) -> (ClientStorage<Id>, mpsc::Sender<(Id, StorageOps)>) {
-        let (sender, receiver) = mpsc::channel(channel_size);
+    ) -> (ClientStorage<Id>, oneshot::Receiver<Buckets<Id>>) {
+        let (finished, finished_receiver) = oneshot::channel();
+        let bucket_size = cache_size.div_ceil(BUCKETS).max(1);
         (
             ClientStorage {
                 client_type: client_type.to_string(),
-                db_cache: SizedCache::with_size(cache_size),
-                query_cache: HashMap::new(),
+                db_cache: (0..BUCKETS)
+                    .map(|_| {
+                        tokio::sync::Mutex::new(Bucket {
+                            db_cache: SizedCache::with_size(bucket_size),
+                            query_cache: HashMap::new(),
+                        })
+                    })
+                    .collect(),
+                hash_builder: RandomState::new(),
                 global,
-                receiver,
+                finished: Some(finished),
             },
-            sender,
+            finished_receiver,
         )
     }
-    async fn run(mut self) {
-        let (loaded_sender, mut loaded_receiver) = mpsc::unbounded_channel::<(Id, ClientConfig)>();
-        loop {
-            tokio::select! {
-                            biased;
-                            rcv = loaded_receiver.recv() =>{
-                                match rcv{
-                                    Some((id, config)) => {
-            let mut info = ClientInformation::new(config);
-                                if self
-                                    .query_cache
-                                    .remove(&id)
-                                    .into_iter()
-                                    .flat_map(|v| v.into_iter())
-                                    .map(|op| run_cmd(&mut info, op))
-                                    .reduce(|r1, r2| r1 | r2)
-                                    .unwrap_or(false)
-                                {
-                                    self.global.set(&mut info).await;
-                                }
-                                self.db_cache.cache_set(id, info);
-                                    }
-                                    None=> panic!("db receiver closed unexpectedly")
-                                }
-                            }
-                            rcv = self.receiver.recv() =>{
-                                match rcv{
-                                    Some((id, op)) => match self.db_cache.cache_get_mut(&id) {
-                    Some(info) => {
-                        if run_cmd(info, op) {
-                            self.global.set(info).await;
-                        }
-                    }
-                    None => match self.query_cache.get_mut(&id) {
-                        Some(queue) => queue.push(op),
-                        None => {
-                            self.query_cache.insert(id.clone(), vec![op]);
-                            let id_clone = id.clone();
-                            let sender_clone = loaded_sender.clone();
-                            self.global
-                                .get(
-                                    serde_json::to_string(&Client {
-                                        client_type: &self.client_type,
-                                        client_id: id_clone,
-                                    })
-                                    .unwrap(),
-                                    id,
-                                    sender_clone,
-                                )
-                                .await;
-                        }
-                    }
-                },
-                None => break,
-                                }
-                            }
-                        };
+    fn bucket(&self, id: &Id) -> &tokio::sync::Mutex<Bucket<Id>> {
+        &self.db_cache[self.hash_builder.hash_one(id) as usize % self.db_cache.len()]
+    }
+    async fn run(&self, id: Id, op: StorageOps) {
+        let mut guard = self.bucket(&id).lock().await;
+        let bucket = &mut *guard;
+        if let StorageOps::Evict(channel) = op {
+            bucket.db_cache.cache_remove(&id);
+            channel.send(()).unwrap();
+            return;
         }
-        drop(loaded_sender);
-        loop {
-            let rcv = loaded_receiver.recv().await;
-            match rcv {
-                Some((id, config)) => {
+        match bucket.db_cache.cache_get_mut(&id) {
+            Some(info) => {
+                #[cfg(feature = "metrics")]
+                crate::metrics::cache_hit();
+                if run_cmd(info, op) {
+                    self.global.set(info).await;
+                }
+            }
+            None => match bucket.query_cache.get_mut(&id) {
+                Some(queue) => queue.push(op),
+                None => {
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::cache_miss();
+                    bucket.query_cache.insert(id.clone(), vec![op]);
+                    // other clients of the bucket are served while this one is loaded
+                    drop(guard);
+                    let config = self
+                        .global
+                        .get(
+                            serde_json::to_string(&Client {
+                                client_type: &self.client_type,
+                                client_id: id.clone(),
+                            })
+                            .unwrap(),
+                        )
+                        .await;
                     let mut info = ClientInformation::new(config);
-                    if self
+                    let mut bucket = self.bucket(&id).lock().await;
+                    if bucket
                         .query_cache
                         .remove(&id)
                         .into_iter()
@@ -500,10 +1706,23 @@ impl<Id: ClientId> ClientStorage<Id> {
                     {
                         self.global.set(&mut info).await;
                     }
-                    self.db_cache.cache_set(id, info);
-                    continue;
+                    bucket.db_cache.cache_set(id, info);
+                }
+            },
+        }
+    }
+}
+
+/// writes every cached client with changes that haven't reached the database yet
+async fn flush<Id: ClientId>(global: &GlobalStorage, buckets: Buckets<Id>) {
+    for bucket in buckets {
+        let mut cache = bucket.into_inner().db_cache;
+        let ids: Vec<Id> = cache.key_order().cloned().collect();
+        for id in ids {
+            if let Some(mut info) = cache.cache_remove(&id) {
+                if info.changed() {
+                    global.set(&mut info).await;
                 }
-                None => break,
             }
         }
     }
@@ -511,126 +1730,1020 @@ impl<Id: ClientId> ClientStorage<Id> {
 
 #[derive(Clone)]
 pub struct StorageHandle<Id: ClientId> {
-    sender: mpsc::Sender<(Id, StorageOps)>,
+    storage: Arc<ClientStorage<Id>>,
 }
 
 impl<Id: ClientId> StorageHandle<Id> {
+    /// the join handle finishes once the handle and all its clones are dropped and every
+    /// operation is done
     pub(crate) fn new<S: ToString>(
         client_type: S,
         global: Arc<GlobalStorage>,
-        channel_size: usize,
         cache_size: usize,
     ) -> (StorageHandle<Id>, tokio::task::JoinHandle<()>) {
-        let (store, sender) = ClientStorage::new(client_type, global, channel_size, cache_size);
+        let (store, finished) = ClientStorage::new(client_type, global.clone(), cache_size);
         (
-            StorageHandle { sender },
-            spawn(async move { store.run().await }),
+            StorageHandle {
+                storage: Arc::new(store),
+            },
+            spawn(async move {
+                if let Ok(buckets) = finished.await {
+                    flush(&global, buckets).await;
+                }
+            }),
         )
     }
+    /// runs `op` on a task of its own, so it is finished even if the caller stops waiting for it
+    fn send(&self, id: Id, op: StorageOps) {
+        let storage = self.storage.clone();
+        spawn(async move { storage.run(id, op).await });
+    }
 
     pub async fn get_command_prefix(&self, id: Id) -> String {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::GetCommandPrefix(sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::GetCommandPrefix(sender));
         receiver.await.unwrap()
     }
     pub async fn set_command_prefix(&self, id: Id, prefix: String) {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::SetCommandPrefix(prefix, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::SetCommandPrefix(prefix, sender));
         receiver.await.unwrap()
     }
     pub async fn add_roll_prefix(&self, id: Id, prefix: String) -> Result<(), ()> {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::AddRollPrefix(prefix, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::AddRollPrefix(prefix, sender));
         receiver.await.unwrap()
     }
     pub async fn remove_roll_prefix(&self, id: Id, prefix: String) -> Result<(), ()> {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::RemoveRollPrefix(prefix, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::RemoveRollPrefix(prefix, sender));
+        receiver.await.unwrap()
+    }
+    /// removes every roll prefix of the client, returns how many there were
+    pub async fn clear_roll_prefixes(&self, id: Id) -> usize {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::ClearRollPrefixes(sender));
+        receiver.await.unwrap()
+    }
+    /// overrides the command prefix in `channel`, `None` restores the one of the client
+    pub async fn set_channel_command_prefix(
+        &self,
+        id: Id,
+        channel: String,
+        prefix: Option<String>,
+    ) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(
+            id,
+            StorageOps::SetChannelCommandPrefix(channel, prefix, sender),
+        );
+        receiver.await.unwrap()
+    }
+    pub async fn add_channel_roll_prefix(
+        &self,
+        id: Id,
+        channel: String,
+        prefix: String,
+    ) -> Result<(), ()> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(
+            id,
+            StorageOps::AddChannelRollPrefix(channel, prefix, sender),
+        );
+        receiver.await.unwrap()
+    }
+    pub async fn remove_channel_roll_prefix(
+        &self,
+        id: Id,
+        channel: String,
+        prefix: String,
+    ) -> Result<(), ()> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(
+            id,
+            StorageOps::RemoveChannelRollPrefix(channel, prefix, sender),
+        );
+        receiver.await.unwrap()
+    }
+    pub async fn add_privileged_role(&self, id: Id, role: String) -> Result<(), ()> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::AddPrivilegedRole(role, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn remove_privileged_role(&self, id: Id, role: String) -> Result<(), ()> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::RemovePrivilegedRole(role, sender));
         receiver.await.unwrap()
     }
     pub async fn get_roll_prefixes(&self, id: Id) -> Vec<String> {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::GetRollPrefixes(sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::GetRollPrefixes(sender));
         receiver.await.unwrap()
     }
     pub async fn add_alias(
         &self,
         id: Id,
+        scope: AliasScope,
         alias: String,
         expr: VersionedRollExpr,
+        limits: AliasLimits,
+    ) -> Result<(), AliasLimitError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::AddAlias(scope, alias, expr, limits, sender));
+        receiver.await.unwrap()
+    }
+    /// adds `alias` only if `scope` does not already contain an alias with that name
+    pub async fn insert_alias(
+        &self,
+        id: Id,
+        scope: AliasScope,
+        alias: String,
+        expr: Arc<VersionedRollExpr>,
     ) -> Result<(), ()> {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::AddAlias(alias, expr, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::InsertAlias(scope, alias, expr, sender));
         receiver.await.unwrap()
     }
-    pub async fn remove_alias(&self, id: Id, alias: String) -> Result<(), ()> {
+    pub async fn remove_alias(&self, id: Id, scope: AliasScope, alias: String) -> Result<(), ()> {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::RemoveAlias(alias, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::RemoveAlias(scope, alias, sender));
         receiver.await.unwrap()
     }
-    pub async fn get_alias(&self, id: Id, alias: String) -> Option<Arc<VersionedRollExpr>> {
+    /// removes every alias of `scope`, returns how many there were
+    pub async fn clear_aliases(&self, id: Id, scope: AliasScope) -> usize {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::GetAlias(alias, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::ClearAliases(scope, sender));
         receiver.await.unwrap()
     }
-    pub async fn get_all_alias(&self, id: Id) -> HashMap<String, Arc<VersionedRollExpr>> {
+    /// bans or allows rolling `kind`, `Err` if it already was
+    pub async fn set_dice_banned(&self, id: Id, kind: DiceKind, banned: bool) -> Result<(), ()> {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::GetAllAlias(sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::SetDiceBanned(kind, banned, sender));
+        receiver.await.unwrap()
+    }
+    /// forbids dice with more than `max_faces` faces, `None` allows all of them
+    pub async fn set_max_faces(&self, id: Id, max_faces: Option<u32>) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetMaxFaces(max_faces, sender));
+        receiver.await.unwrap()
+    }
+    /// remembers the rolls of `user`, dropping their oldest ones beyond `max`
+    pub async fn push_history(&self, id: Id, user: String, entries: Vec<HistoryEntry>, max: usize) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::PushHistory(user, entries, max, sender));
+        receiver.await.unwrap()
+    }
+    /// remembered rolls of `user`, the latest first
+    pub async fn get_history(&self, id: Id, user: String) -> Vec<HistoryEntry> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::GetHistory(user, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn get_alias(
+        &self,
+        id: Id,
+        scope: AliasScope,
+        alias: String,
+    ) -> Option<Arc<VersionedRollExpr>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::GetAlias(scope, alias, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn get_all_alias(&self, id: Id, scope: AliasScope) -> Aliases {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::GetAllAlias(scope, sender));
         receiver.await.unwrap()
     }
     pub async fn get_roll_info(&self, id: Id) -> bool {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::GetRollInfo(sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::GetRollInfo(sender));
         receiver.await.unwrap()
     }
     pub async fn set_roll_info(&self, id: Id, roll_info: bool) {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::SetRollInfo(roll_info, sender)))
-            .await
-            .unwrap();
+        self.send(id, StorageOps::SetRollInfo(roll_info, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn set_paused(&self, id: Id, paused: bool) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetPaused(paused, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn set_alias_hints(&self, id: Id, alias_hints: bool) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetAliasHints(alias_hints, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn set_show_luck(&self, id: Id, show_luck: bool) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetShowLuck(show_luck, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn set_roll_shorthand(&self, id: Id, shorthand: Option<String>) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetRollShorthand(shorthand, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn set_stats_roll(&self, id: Id, roll: Option<VersionedRollExpr>) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetStatsRoll(roll, sender));
+        receiver.await.unwrap()
+    }
+    pub async fn set_stats_min_total(&self, id: Id, min_total: Option<i64>) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetStatsMinTotal(min_total, sender));
+        receiver.await.unwrap()
+    }
+    /// announce `critical` with `message`, `None` to stop announcing it
+    pub async fn set_critical_message(&self, id: Id, critical: Critical, message: Option<String>) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(
+            id,
+            StorageOps::SetCriticalMessage(critical, message, sender),
+        );
+        receiver.await.unwrap()
+    }
+    pub async fn get_list_order(&self, id: Id) -> ListOrder {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::GetListOrder(sender));
         receiver.await.unwrap()
     }
+    pub async fn set_list_order(&self, id: Id, order: ListOrder) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::SetListOrder(order, sender));
+        receiver.await.unwrap()
+    }
+    /// configuration needed for a message of `user` in `channel`, with the `aliases` it
+    /// may contain
     pub async fn get(
         &self,
         id: Id,
+        user: Option<String>,
+        channel: Option<String>,
         aliases: Vec<String>,
-    ) -> (String, Vec<String>, Vec<Arc<VersionedRollExpr>>, bool) {
+    ) -> Lookup {
         let (sender, receiver) = oneshot::channel();
-        self.sender
-            .send((id, StorageOps::Get(aliases, sender)))
+        self.send(id, StorageOps::Get(user, channel, aliases, sender));
+        receiver.await.unwrap()
+    }
+    /// configuration of each of `ids`, like [StorageHandle::get] without user, channel
+    /// or aliases
+    ///
+    /// all requests are queued at once and answered together, instead of waiting for
+    /// each one before sending the next
+    pub async fn get_many(&self, ids: Vec<Id>) -> Vec<(Id, Lookup)> {
+        if ids.is_empty() {
+            return Vec::new();
+        }
+        let (sender, receiver) = oneshot::channel();
+        let many = Arc::new(Mutex::new(ManyLookups {
+            lookups: vec![None; ids.len()],
+            remaining: ids.len(),
+            sender: Some(sender),
+        }));
+        for (index, id) in ids.iter().enumerate() {
+            self.send(id.clone(), StorageOps::GetMany(index, many.clone()));
+        }
+        ids.into_iter().zip(receiver.await.unwrap()).collect()
+    }
+    pub async fn get_full_config(&self, id: Id) -> FullConfig {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::GetFullConfig(sender));
+        receiver.await.unwrap()
+    }
+    /// complete configuration of `id` as JSON, to be restored with [StorageHandle::import]
+    pub async fn export(&self, id: Id) -> String {
+        serde_json::to_string_pretty(&self.get_full_config(id).await).unwrap()
+    }
+    /// replaces the configuration of `id` with the `blob` created by [StorageHandle::export]
+    pub async fn import(&self, id: Id, blob: &str) -> Result<(), ImportError> {
+        let config: FullConfig =
+            serde_json::from_str(blob).map_err(|err| ImportError::Malformed(err.to_string()))?;
+        config.validate()?;
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::Import(Box::new(config), sender));
+        receiver.await.unwrap();
+        Ok(())
+    }
+    /// drop the cached configuration of `id`, the next access reads it from the database
+    pub async fn evict(&self, id: Id) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(id, StorageOps::Evict(sender));
+        receiver.await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::connection::SimpleConnection;
+    use robins_dice_roll::parser::parse_labeled;
+
+    /// applies all migrations to `db`
+    pub(super) fn migrate(db: &SqliteConnection) {
+        let mut migrations: Vec<_> =
+            std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.is_dir())
+                .collect();
+        migrations.sort();
+        for migration in migrations {
+            db.batch_execute(&std::fs::read_to_string(migration.join("up.sql")).unwrap())
+                .unwrap();
+        }
+    }
+
+    /// in-memory database of a test, its worker is shut down once this is dropped
+    struct TestDb {
+        global: Option<Arc<GlobalStorage>>,
+        worker: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl TestDb {
+        fn new() -> TestDb {
+            let (global, worker) = GlobalStorage::new_in_memory(8);
+            TestDb {
+                global: Some(Arc::new(global)),
+                worker: Some(worker),
+            }
+        }
+
+        fn global(&self) -> &GlobalStorage {
+            self.global.as_ref().unwrap()
+        }
+
+        /// storage of `test` clients, caching up to `cache_size` of them
+        fn storage(&self, cache_size: usize) -> (StorageHandle<u64>, tokio::task::JoinHandle<()>) {
+            StorageHandle::new("test", self.global.clone().unwrap(), cache_size)
+        }
+
+        /// runs `sql` once the jobs queued before it are done
+        async fn batch_execute(&self, sql: &'static str) {
+            let (sender, receiver) = oneshot::channel();
+            let job: DbJob = Box::new(move |db: &dyn Database| {
+                db.batch_execute(sql).unwrap();
+                sender.send(()).unwrap();
+            });
+            assert!(self.global().db_submit.send(job).await.is_ok());
+            receiver.await.unwrap();
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            // the worker stops once the last sender of its queue is gone
+            self.global.take();
+            if let Some(worker) = self.worker.take() {
+                // a failed test may still have storage holding on to the queue
+                if !std::thread::panicking() {
+                    worker.join().unwrap();
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_full_config() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+
+        let default = store.get_full_config(1).await;
+        assert_eq!(default.command_prefix, "rrb!");
+        assert!(default.roll_prefixes.is_empty());
+        assert!(default.aliases.is_empty());
+
+        let expr = VersionedRollExpr::V2(parse_labeled("4d6k3").unwrap().1);
+        store.set_command_prefix(1, "!".to_string()).await;
+        store.add_roll_prefix(1, "r!".to_string()).await.unwrap();
+        store
+            .add_alias(
+                1,
+                AliasScope::Shared,
+                "stat".to_string(),
+                expr.clone(),
+                AliasLimits::default(),
+            )
             .await
             .unwrap();
-        receiver.await.unwrap()
+        store
+            .add_alias(
+                1,
+                AliasScope::User("user".to_string()),
+                "mine".to_string(),
+                expr.clone(),
+                AliasLimits::default(),
+            )
+            .await
+            .unwrap();
+        store.set_roll_info(1, false).await;
+        store.set_alias_hints(1, true).await;
+        store.set_show_luck(1, true).await;
+        store
+            .add_privileged_role(1, "42".to_string())
+            .await
+            .unwrap();
+        store
+            .add_privileged_role(1, "43".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.add_privileged_role(1, "42".to_string()).await,
+            Err(())
+        );
+        store
+            .remove_privileged_role(1, "43".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.remove_privileged_role(1, "43".to_string()).await,
+            Err(())
+        );
+        store.set_list_order(1, ListOrder::Descending).await;
+        store.set_roll_shorthand(1, Some("r".to_string())).await;
+        let stats = VersionedRollExpr::V2(parse_labeled("6{3d6}").unwrap().1);
+        store.set_stats_roll(1, Some(stats.clone())).await;
+        store.set_stats_min_total(1, Some(60)).await;
+        store
+            .set_critical_message(1, Critical::Hit, Some("🎯 {value}!".to_string()))
+            .await;
+
+        let config = store.get_full_config(1).await;
+        assert_eq!(
+            config,
+            FullConfig {
+                command_prefix: "!".to_string(),
+                roll_prefixes: vec!["r!".to_string()],
+                aliases: vec![("stat".to_string(), Arc::new(expr.clone()))]
+                    .into_iter()
+                    .collect(),
+                user_aliases: vec![(
+                    "user".to_string(),
+                    vec![("mine".to_string(), Arc::new(expr))]
+                        .into_iter()
+                        .collect()
+                )]
+                .into_iter()
+                .collect(),
+                roll_info: false,
+                paused: false,
+                alias_hints: true,
+                list_order: ListOrder::Descending,
+                roll_shorthand: Some("r".to_string()),
+                stats_roll: Some(Arc::new(stats)),
+                stats_min_total: Some(60),
+                critical_message: Some("🎯 {value}!".to_string()),
+                fumble_message: None,
+                show_luck: true,
+                privileged_roles: vec!["42".to_string()],
+                channel_prefixes: HashMap::new(),
+                dice_policy: DicePolicy::default(),
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<FullConfig>(&serde_json::to_string(&config).unwrap()).unwrap(),
+            config
+        );
+        assert_eq!(store.get_full_config(2).await, default);
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[test]
+    fn test_backend_of_url() {
+        assert_eq!(
+            Backend::of_url("postgres://localhost/rolls"),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::of_url("postgresql://bot@localhost/rolls"),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::of_url("/var/lib/roll-bot/db.sqlite"),
+            Backend::Sqlite
+        );
+        assert_eq!(Backend::of_url("db.sqlite"), Backend::Sqlite);
+    }
+
+    #[test]
+    fn test_postgres_url() {
+        assert!(matches!(
+            GlobalStorage::new(
+                Backend::Postgres,
+                "postgres://localhost/rolls".to_string(),
+                8
+            ),
+            Err(diesel::ConnectionError::InvalidConnectionUrl(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_evict() {
+        let db = TestDb::new();
+        // room for every client in each bucket, so only evicting drops one
+        let (store, join) = db.storage(64);
+
+        store.set_command_prefix(1, "!".to_string()).await;
+        // the write is queued before the bucket of the client is unlocked again
+        store.get_command_prefix(1).await;
+        // the database runs its jobs in order, so the write is done once client 2 is read
+        store.get_command_prefix(2).await;
+        db.batch_execute("UPDATE client_config SET command_prefix = '?'")
+            .await;
+        assert_eq!(store.get_command_prefix(1).await, "!");
+        store.evict(1).await;
+        assert_eq!(store.get_command_prefix(1).await, "?");
+        // evicting a client that isn't cached does nothing
+        store.evict(3).await;
+        assert_eq!(store.get_command_prefix(3).await, "rrb!");
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_many() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+
+        assert!(store.get_many(Vec::new()).await.is_empty());
+        store.set_command_prefix(1, "!".to_string()).await;
+        let lookups = store.get_many(vec![1, 2, 1]).await;
+        assert_eq!(
+            lookups
+                .iter()
+                .map(|(id, lookup)| (*id, lookup.command_prefix.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "!"), (2, "rrb!"), (1, "!")]
+        );
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_buckets() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(64);
+        let other = (2..)
+            .find(|id| !std::ptr::eq(store.storage.bucket(id), store.storage.bucket(&1)))
+            .unwrap();
+        store.set_command_prefix(1, "!".to_string()).await;
+
+        let locked = store.storage.bucket(&1).lock().await;
+        let waiting = spawn({
+            let store = store.clone();
+            async move { store.get_command_prefix(1).await }
+        });
+        // a client in another bucket doesn't wait for the locked one, even while being loaded
+        assert_eq!(
+            tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                store.get_command_prefix(other)
+            )
+            .await
+            .unwrap(),
+            "rrb!"
+        );
+        assert!(!waiting.is_finished());
+        drop(locked);
+        assert_eq!(waiting.await.unwrap(), "!");
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+
+        let expr = VersionedRollExpr::V2(parse_labeled("1d20 + 5").unwrap().1);
+        store.set_command_prefix(1, "!".to_string()).await;
+        store.add_roll_prefix(1, "r!".to_string()).await.unwrap();
+        store
+            .add_alias(
+                1,
+                AliasScope::Shared,
+                "attack".to_string(),
+                expr,
+                AliasLimits::default(),
+            )
+            .await
+            .unwrap();
+        store.set_roll_info(1, true).await;
+        let blob = store.export(1).await;
+        assert_eq!(store.import(2, &blob).await, Ok(()));
+        store.evict(2).await;
+        assert_eq!(
+            store.get_full_config(2).await,
+            store.get_full_config(1).await
+        );
+
+        assert!(matches!(
+            store.import(3, "{\"command_prefix\": \"!\"}").await,
+            Err(ImportError::Malformed(_))
+        ));
+        let invalid = blob.replace("\"attack\"", "\"two$words\"");
+        assert_eq!(
+            store.import(3, &invalid).await,
+            Err(ImportError::InvalidAlias("two$words".to_string()))
+        );
+        assert_eq!(store.get_command_prefix(3).await, "rrb!");
+        // settings added later are optional
+        assert_eq!(
+            store
+                .import(
+                    3,
+                    r#"{"command_prefix": "?", "roll_prefixes": [], "aliases": {}, "roll_info": false}"#
+                )
+                .await,
+            Ok(())
+        );
+        assert_eq!(store.get_command_prefix(3).await, "?");
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_alias_scopes() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let user = AliasScope::User("user".to_string());
+        let shared = Arc::new(VersionedRollExpr::V2(parse_labeled("1d20").unwrap().1));
+        let personal = Arc::new(VersionedRollExpr::V2(parse_labeled("1d20+5").unwrap().1));
+
+        store
+            .insert_alias(1, AliasScope::Shared, "atk".to_string(), shared.clone())
+            .await
+            .unwrap();
+        store
+            .insert_alias(1, user.clone(), "atk".to_string(), personal.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .insert_alias(1, user.clone(), "atk".to_string(), shared.clone())
+                .await,
+            Err(())
+        );
+
+        // personal aliases shadow shared ones for their owner only
+        let lookup = |user: Option<&str>| {
+            store.get(
+                1,
+                user.map(|u| u.to_string()),
+                None,
+                vec!["atk".to_string()],
+            )
+        };
+        let atk = |expr: &Arc<VersionedRollExpr>| vec![("atk".to_string(), expr.clone())];
+        assert_eq!(lookup(Some("user")).await.aliases, atk(&personal));
+        assert_eq!(lookup(Some("other")).await.aliases, atk(&shared));
+        assert_eq!(lookup(None).await.aliases, atk(&shared));
+        assert_eq!(
+            store.get_alias(1, user.clone(), "atk".to_string()).await,
+            Some(personal.clone())
+        );
+
+        drop(store);
+        join.await.unwrap();
+        let (store, join) = db.storage(8);
+        assert_eq!(
+            store.get_all_alias(1, user.clone()).await.get("atk"),
+            Some(&personal)
+        );
+        store
+            .remove_alias(1, user.clone(), "atk".to_string())
+            .await
+            .unwrap();
+        assert!(store.get_full_config(1).await.user_aliases.is_empty());
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_clear() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let expr = Arc::new(VersionedRollExpr::V2(parse_labeled("1d20").unwrap().1));
+        let user = AliasScope::User("user".to_string());
+
+        for prefix in ["!", "?"] {
+            store.add_roll_prefix(1, prefix.to_string()).await.unwrap();
+        }
+        for alias in ["atk", "dmg"] {
+            store
+                .insert_alias(1, AliasScope::Shared, alias.to_string(), expr.clone())
+                .await
+                .unwrap();
+        }
+        store
+            .insert_alias(1, user.clone(), "atk".to_string(), expr.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.clear_roll_prefixes(1).await, 2);
+        assert_eq!(store.clear_aliases(1, AliasScope::Shared).await, 2);
+        assert_eq!(store.clear_aliases(1, AliasScope::Shared).await, 0);
+
+        // the cleared configuration was written to the database
+        drop(store);
+        join.await.unwrap();
+        let (store, join) = db.storage(8);
+        assert!(store.get_roll_prefixes(1).await.is_empty());
+        assert!(store.get_all_alias(1, AliasScope::Shared).await.is_empty());
+        // personal aliases are left alone
+        assert_eq!(store.get_all_alias(1, user).await.len(), 1);
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flush() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let expr = parse_labeled("1d20").unwrap().1;
+
+        store
+            .add_alias(
+                1,
+                AliasScope::Shared,
+                "atk".to_string(),
+                VersionedRollExpr::V2(expr),
+                AliasLimits::default(),
+            )
+            .await
+            .unwrap();
+        // a cached change that wasn't written yet
+        let mut info = ClientInformation::new(
+            db.global()
+                .get(
+                    serde_json::to_string(&Client {
+                        client_type: "test",
+                        client_id: 2u64,
+                    })
+                    .unwrap(),
+                )
+                .await,
+        );
+        *info.get_cmd_prefix_mut() = "?".to_string();
+        store
+            .storage
+            .bucket(&2)
+            .lock()
+            .await
+            .db_cache
+            .cache_set(2, info);
+        drop(store);
+        join.await.unwrap();
+
+        let (store, join) = db.storage(8);
+        assert!(store
+            .get_alias(1, AliasScope::Shared, "atk".to_string())
+            .await
+            .is_some());
+        assert_eq!(store.get_command_prefix(2).await, "?");
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let entry = |result: i64| HistoryEntry {
+            expression: result.to_string(),
+            label: None,
+            results: vec![result],
+        };
+
+        store
+            .push_history(1, "user".to_string(), vec![entry(1), entry(2)], 3)
+            .await;
+        store
+            .push_history(1, "user".to_string(), vec![entry(3), entry(4)], 3)
+            .await;
+        store
+            .push_history(1, "other".to_string(), vec![entry(5)], 3)
+            .await;
+        drop(store);
+        join.await.unwrap();
+
+        // the history was written to the database, without the oldest roll
+        let (store, join) = db.storage(8);
+        assert_eq!(
+            store.get_history(1, "user".to_string()).await,
+            vec![entry(4), entry(3), entry(2)]
+        );
+        assert_eq!(
+            store.get_history(1, "other".to_string()).await,
+            vec![entry(5)]
+        );
+        assert!(store.get_history(2, "user".to_string()).await.is_empty());
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dice_policy() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+
+        assert_eq!(
+            store.set_dice_banned(1, DiceKind::Multiply, false).await,
+            Err(())
+        );
+        assert_eq!(
+            store.set_dice_banned(1, DiceKind::Multiply, true).await,
+            Ok(())
+        );
+        assert_eq!(
+            store.set_dice_banned(1, DiceKind::Fudge, true).await,
+            Ok(())
+        );
+        assert_eq!(
+            store.set_dice_banned(1, DiceKind::Fudge, false).await,
+            Ok(())
+        );
+        store.set_max_faces(1, Some(20)).await;
+        drop(store);
+        join.await.unwrap();
+
+        let (store, join) = db.storage(8);
+        let policy = store.get_full_config(1).await.dice_policy;
+        assert_eq!(
+            policy,
+            DicePolicy {
+                banned: vec![DiceKind::Multiply],
+                max_faces: Some(20),
+            }
+        );
+        assert_eq!(
+            store.get_full_config(2).await.dice_policy,
+            DicePolicy::default()
+        );
+        let check = |expr| policy.check(&VersionedRollExpr::V2(parse_labeled(expr).unwrap().1));
+        assert_eq!(check("1d20 + 1dF"), Ok(()));
+        assert_eq!(check("1d20 + 2d4x"), Err("2d4x".to_string()));
+        assert_eq!(check("1d20 + 1d21"), Err("1d21".to_string()));
+        assert_eq!(check("1d(21)"), Err("1d(21)".to_string()));
+        assert_eq!(
+            DicePolicy::default()
+                .check(&VersionedRollExpr::V2(parse_labeled("1d1000x").unwrap().1)),
+            Ok(())
+        );
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_alias_case() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let expr = VersionedRollExpr::V2(parse_labeled("1d20").unwrap().1);
+
+        store
+            .add_alias(
+                1,
+                AliasScope::Shared,
+                "Foo".to_string(),
+                expr.clone(),
+                AliasLimits::default(),
+            )
+            .await
+            .unwrap();
+        for name in &["foo", "FOO", " foo "] {
+            assert_eq!(
+                store
+                    .get_alias(1, AliasScope::Shared, name.to_string())
+                    .await
+                    .as_deref(),
+                Some(&expr)
+            );
+        }
+        assert_eq!(
+            store
+                .get(1, None, None, vec!["fOo".to_string()])
+                .await
+                .aliases
+                .len(),
+            1
+        );
+        // the casing used when adding is kept for listing
+        let all = store.get_all_alias(1, AliasScope::Shared).await;
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["Foo"]);
+
+        // re-adding in another casing replaces the alias instead of duplicating it
+        store
+            .add_alias(
+                1,
+                AliasScope::Shared,
+                "FOO".to_string(),
+                expr.clone(),
+                AliasLimits::default(),
+            )
+            .await
+            .unwrap();
+        let all = store.get_all_alias(1, AliasScope::Shared).await;
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["FOO"]);
+        store
+            .remove_alias(1, AliasScope::Shared, "foo".to_string())
+            .await
+            .unwrap();
+        assert!(store.get_all_alias(1, AliasScope::Shared).await.is_empty());
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_alias_limits() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let expr = VersionedRollExpr::V2(parse_labeled("1d20").unwrap().1);
+        let limits = AliasLimits {
+            max_aliases: 2,
+            max_alias_len: 4,
+        };
+        let add = |alias: &str| {
+            store.add_alias(
+                1,
+                AliasScope::Shared,
+                alias.to_string(),
+                expr.clone(),
+                limits,
+            )
+        };
+
+        assert_eq!(
+            add("long").await.and(add("longer").await),
+            Err(AliasLimitError::NameLength { len: 6, max: 4 })
+        );
+        add("atk").await.unwrap();
+        assert_eq!(
+            add("dmg").await.and(add("heal").await),
+            Err(AliasLimitError::Count { count: 2, max: 2 })
+        );
+        // replacing an alias does not need room for another one
+        add("ATK").await.unwrap();
+        // other scopes are counted on their own
+        store
+            .add_alias(
+                1,
+                AliasScope::User("user".to_string()),
+                "heal".to_string(),
+                expr.clone(),
+                limits,
+            )
+            .await
+            .unwrap();
+
+        drop(store);
+        join.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_prefixes() {
+        let db = TestDb::new();
+        let (store, join) = db.storage(8);
+        let lookup = |channel: &str| store.get(1, None, Some(channel.to_string()), Vec::new());
+
+        store.add_roll_prefix(1, "r!".to_string()).await.unwrap();
+        store
+            .set_channel_command_prefix(1, "7".to_string(), Some("?".to_string()))
+            .await;
+        store
+            .add_channel_roll_prefix(1, "7".to_string(), "x!".to_string())
+            .await
+            .unwrap();
+        let channel = lookup("7").await;
+        assert_eq!(channel.command_prefix, "?");
+        assert_eq!(channel.roll_prefixes, vec!["x!".to_string()]);
+        assert_eq!(channel.channel.as_deref(), Some("7"));
+        // other channels fall back to the prefixes of the client
+        let other = lookup("8").await;
+        assert_eq!(other.command_prefix, "rrb!");
+        assert_eq!(other.roll_prefixes, vec!["r!".to_string()]);
+
+        store
+            .set_channel_command_prefix(1, "7".to_string(), None)
+            .await;
+        store
+            .remove_channel_roll_prefix(1, "7".to_string(), "x!".to_string())
+            .await
+            .unwrap();
+        assert_eq!(lookup("7").await.command_prefix, "rrb!");
+        assert!(store.get_full_config(1).await.channel_prefixes.is_empty());
+
+        drop(store);
+        join.await.unwrap();
     }
 }
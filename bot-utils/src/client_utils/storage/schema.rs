@@ -5,5 +5,19 @@ table! {
         roll_prefix -> Text,
         aliases -> Text,
         roll_info -> Bool,
+        paused -> Bool,
+        alias_hints -> Bool,
+        list_order -> Integer,
+        user_aliases -> Text,
+        roll_shorthand -> Nullable<Text>,
+        stats_roll -> Nullable<Text>,
+        stats_min_total -> Nullable<BigInt>,
+        critical_message -> Nullable<Text>,
+        fumble_message -> Nullable<Text>,
+        show_luck -> Bool,
+        privileged_roles -> Text,
+        channel_prefixes -> Text,
+        roll_history -> Text,
+        dice_policy -> Text,
     }
 }
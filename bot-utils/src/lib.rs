@@ -18,6 +18,9 @@ extern crate diesel;
 pub mod bot_manager;
 pub mod bots;
 pub mod client_utils;
+pub mod logging;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod tuple_helpers;
 
 pub use async_trait::async_trait;
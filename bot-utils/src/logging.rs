@@ -0,0 +1,148 @@
+//! logs records as single line JSON objects, with their key values as fields of their own
+
+use log::kv::{self, Key, Value, VisitSource, VisitValue};
+use serde_json::{Map, Value as Json};
+use std::{
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// writes JSON lines to stderr, filtered by `RUST_LOG` like `pretty_env_logger`
+pub struct JsonLogger {
+    filter: env_logger::filter::Filter,
+}
+
+impl JsonLogger {
+    pub fn from_env() -> JsonLogger {
+        JsonLogger {
+            filter: env_logger::filter::Builder::from_env("RUST_LOG").build(),
+        }
+    }
+}
+
+/// installs a [JsonLogger] as the global logger
+pub fn init_json() {
+    let logger = JsonLogger::from_env();
+    log::set_max_level(logger.filter.filter());
+    log::set_boxed_logger(Box::new(logger)).expect("a logger is already installed");
+}
+
+impl log::Log for JsonLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.filter.matches(record) {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|t| t.as_millis() as u64)
+                .unwrap_or(0);
+            let _ = writeln!(
+                std::io::stderr().lock(),
+                "{}",
+                record_json(record, timestamp)
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+struct JsonValue<'a>(&'a mut Json);
+
+impl<'v> VisitValue<'v> for JsonValue<'_> {
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        *self.0 = Json::String(value.to_string());
+        Ok(())
+    }
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        *self.0 = Json::Null;
+        Ok(())
+    }
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        *self.0 = Json::from(value);
+        Ok(())
+    }
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        *self.0 = Json::from(value);
+        Ok(())
+    }
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        *self.0 = Json::from(value);
+        Ok(())
+    }
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        *self.0 = Json::from(value);
+        Ok(())
+    }
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        *self.0 = Json::from(value);
+        Ok(())
+    }
+}
+
+struct Fields<'a>(&'a mut Map<String, Json>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let mut json = Json::Null;
+        value.visit(JsonValue(&mut json))?;
+        // key values never replace the fields every record has
+        self.0.entry(key.as_str()).or_insert(json);
+        Ok(())
+    }
+}
+
+/// `record` logged at `timestamp` milliseconds since the unix epoch
+fn record_json(record: &log::Record, timestamp: u64) -> Json {
+    let mut fields = Map::new();
+    fields.insert("ts".to_string(), Json::from(timestamp));
+    fields.insert("level".to_string(), Json::from(record.level().as_str()));
+    fields.insert("target".to_string(), Json::from(record.target()));
+    fields.insert("message".to_string(), Json::from(record.args().to_string()));
+    let _ = record.key_values().visit(&mut Fields(&mut fields));
+    Json::Object(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_json() {
+        let key_values = [
+            ("client", Value::from(3u64)),
+            ("command", Value::from("Roll")),
+            ("duration_ms", Value::from(1.5)),
+            ("seed", Value::null()),
+            ("level", Value::from("fake")),
+            ("error", Value::from_debug(&Some(1))),
+        ];
+        assert_eq!(
+            record_json(
+                &log::Record::builder()
+                    .level(log::Level::Info)
+                    .target("bot_utils")
+                    .args(format_args!("roll result"))
+                    .key_values(&key_values)
+                    .build(),
+                5
+            ),
+            json!({
+                "ts": 5,
+                "level": "INFO",
+                "target": "bot_utils",
+                "message": "roll result",
+                "client": 3,
+                "command": "Roll",
+                "duration_ms": 1.5,
+                "seed": null,
+                "error": "Some(1)",
+            })
+        );
+    }
+}
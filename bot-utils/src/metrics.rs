@@ -0,0 +1,122 @@
+//! counters of the rolls and the storage cache, only built with the `metrics` feature
+//!
+//! they are plain atomics shared by every client of the process, [snapshot] reads them
+
+use crate::client_utils::RolledResult;
+use robins_dice_roll::dice_roll::EvaluationErrors;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// name of each [EvaluationErrors] variant, in the order of [error_index]
+const ERROR_NAMES: [&str; 8] = [
+    "DivideByZero",
+    "Timeout",
+    "Overflow",
+    "NegativeExponent",
+    "DiceLimitExceeded",
+    "UndefinedVariable",
+    "Internal",
+    "Other",
+];
+
+static ROLLS: AtomicU64 = AtomicU64::new(0);
+static ROLL_ERRORS: [AtomicU64; ERROR_NAMES.len()] =
+    [const { AtomicU64::new(0) }; ERROR_NAMES.len()];
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+static QUEUE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+fn error_index(error: &EvaluationErrors) -> usize {
+    match error {
+        EvaluationErrors::DivideByZero => 0,
+        EvaluationErrors::Timeout { .. } => 1,
+        EvaluationErrors::Overflow => 2,
+        EvaluationErrors::NegativeExponent => 3,
+        EvaluationErrors::DiceLimitExceeded => 4,
+        EvaluationErrors::UndefinedVariable(_) => 5,
+        EvaluationErrors::Internal => 6,
+        // the enum is non exhaustive
+        _ => 7,
+    }
+}
+
+/// counts an evaluated roll, and its error if it failed
+pub(crate) fn roll_evaluated(roll: &Result<Vec<RolledResult>, EvaluationErrors>) {
+    ROLLS.fetch_add(1, Ordering::Relaxed);
+    if let Err(error) = roll {
+        ROLL_ERRORS[error_index(error)].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// the configuration of a client was already cached
+pub(crate) fn cache_hit() {
+    CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// the configuration of a client has to be loaded from the database
+pub(crate) fn cache_miss() {
+    CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// rolls waiting for a worker right now
+pub(crate) fn queue_depth(depth: usize) {
+    QUEUE_DEPTH.store(depth, Ordering::Relaxed);
+}
+
+/// values of the counters at a single point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    /// rolls evaluated, failed ones included
+    pub rolls: u64,
+    /// failed rolls by the name of their [EvaluationErrors] variant
+    pub roll_errors: Vec<(&'static str, u64)>,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// rolls waiting for a worker
+    pub queue_depth: usize,
+}
+
+pub fn snapshot() -> Metrics {
+    Metrics {
+        rolls: ROLLS.load(Ordering::Relaxed),
+        roll_errors: ERROR_NAMES
+            .iter()
+            .zip(ROLL_ERRORS.iter())
+            .map(|(name, count)| (*name, count.load(Ordering::Relaxed)))
+            .collect(),
+        cache_hits: CACHE_HITS.load(Ordering::Relaxed),
+        cache_misses: CACHE_MISSES.load(Ordering::Relaxed),
+        queue_depth: QUEUE_DEPTH.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(metrics: &Metrics, name: &str) -> u64 {
+        metrics
+            .roll_errors
+            .iter()
+            .find(|(n, _)| *n == name)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn test_roll_evaluated() {
+        // other tests roll at the same time, so only the growth is checked
+        let before = snapshot();
+        roll_evaluated(&Ok(vec![(6, vec![6], Vec::new())]));
+        roll_evaluated(&Err(EvaluationErrors::UndefinedVariable("$1".to_string())));
+        let after = snapshot();
+        assert!(after.rolls >= before.rolls + 2);
+        assert!(errors(&after, "UndefinedVariable") > errors(&before, "UndefinedVariable"));
+        assert_eq!(
+            ERROR_NAMES[error_index(&EvaluationErrors::Timeout {
+                completed: 0,
+                total: 1
+            })],
+            "Timeout"
+        );
+    }
+}
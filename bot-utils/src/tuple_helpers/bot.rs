@@ -17,3 +17,15 @@ where
         spawn(async move { self.run().await })
     }
 }
+
+impl<A, B> BotWrapper for (A, B)
+where
+    A: BotWrapper,
+    B: BotWrapper,
+{
+    type Output = (A::Output, B::Output);
+
+    fn run(self) -> Self::Output {
+        (self.0.run(), self.1.run())
+    }
+}
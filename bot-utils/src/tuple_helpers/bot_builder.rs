@@ -32,3 +32,26 @@ where
 
     type Result = Result<BB::B, JoinError>;
 }
+
+impl<A, B> BotBuilderWrapper for (A, B)
+where
+    A: BotBuilderWrapper,
+    B: BotBuilderWrapper,
+{
+    type Output = (A::Output, B::Output);
+
+    fn build<S: StopListener>(
+        self,
+        utils: Arc<Mutex<ClientUtilsBuilder>>,
+        stop: S,
+    ) -> Self::Output {
+        (
+            self.0.build(utils.clone(), stop.clone()),
+            self.1.build(utils, stop),
+        )
+    }
+
+    type Bot = (A::Bot, B::Bot);
+
+    type Result = (A::Result, B::Result);
+}
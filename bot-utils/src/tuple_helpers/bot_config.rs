@@ -17,3 +17,15 @@ where
         self.config(config)
     }
 }
+
+impl<A, B> BotConfigWrapper for (A, B)
+where
+    A: BotConfigWrapper,
+    B: BotConfigWrapper,
+{
+    type Output = (A::Output, B::Output);
+
+    fn config(self, config: &mut Map<String, Value>) -> Self::Output {
+        (self.0.config(config), self.1.config(config))
+    }
+}
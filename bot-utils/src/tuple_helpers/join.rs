@@ -4,7 +4,7 @@ use tokio::task::{JoinError, JoinHandle};
 
 #[async_trait]
 pub trait JoinChain: Send {
-    type Output: ResultChain<JoinError>;
+    type Output: ResultChain<JoinError> + Send;
     async fn join(self) -> Self::Output;
 }
 
@@ -19,3 +19,16 @@ where
         self.await
     }
 }
+
+#[async_trait]
+impl<A, B> JoinChain for (A, B)
+where
+    A: JoinChain,
+    B: JoinChain,
+{
+    type Output = (A::Output, B::Output);
+
+    async fn join(self) -> Self::Output {
+        tokio::join!(self.0.join(), self.1.join())
+    }
+}
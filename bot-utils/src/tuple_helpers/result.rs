@@ -10,3 +10,15 @@ impl<O, E> ResultChain<E> for Result<O, E> {
         self
     }
 }
+
+impl<A, B, E> ResultChain<E> for (A, B)
+where
+    A: ResultChain<E>,
+    B: ResultChain<E>,
+{
+    type Output = (A::Output, B::Output);
+
+    fn result(self) -> Result<Self::Output, E> {
+        Ok((self.0.result()?, self.1.result()?))
+    }
+}
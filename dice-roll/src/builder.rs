@@ -0,0 +1,370 @@
+/*
+Copyright 2021 Robin Marchart
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! constructors building the same terms the parser does, so their display form parses back to them
+//!
+//! ```
+//! use robins_dice_roll::{Dice, Expression, Selector, Term};
+//!
+//! let stat = Term::select(Dice::number(4, 6), Selector::Higher, 3) + Term::constant(1);
+//! assert_eq!(Expression::list(6, stat).to_string(), "6{4d6h3 + 1}");
+//! ```
+
+use crate::dice_types::{
+    Dice, DiceType, Explode, Expression, Filter, FilteredDice, Func, Operation, Reroll,
+    SelectedDice, Selector, Term, DEFAULT_FUDGE_SIDES,
+};
+use std::ops;
+
+impl Dice {
+    /// `throws` dice with `faces` faces, like `3d6`
+    #[must_use]
+    pub fn number(throws: u32, faces: u32) -> Dice {
+        Dice::of(throws, DiceType::Number(faces))
+    }
+
+    /// `throws` fudge dice, like `4dF`
+    #[must_use]
+    pub fn fudge(throws: u32) -> Dice {
+        Dice::of(
+            throws,
+            DiceType::Fudge {
+                sides: DEFAULT_FUDGE_SIDES,
+            },
+        )
+    }
+
+    /// `throws` dice showing the product of two dice with `faces` faces, like `2d6x`
+    #[must_use]
+    pub fn multiply(throws: u32, faces: u32) -> Dice {
+        Dice::of(throws, DiceType::Multiply(faces))
+    }
+
+    /// `throws` dice of `dice`
+    #[must_use]
+    pub fn of(throws: u32, dice: DiceType) -> Dice {
+        Dice {
+            throws,
+            dice,
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        }
+    }
+
+    #[must_use]
+    pub fn explode(self, explode: Explode) -> Dice {
+        Dice {
+            explode: Some(explode),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn reroll(self, reroll: Reroll) -> Dice {
+        Dice {
+            reroll: Some(reroll),
+            ..self
+        }
+    }
+}
+
+/// binding strength of `op`, operations with a higher one are evaluated first
+fn precedence(op: Operation) -> u8 {
+    match op {
+        Operation::Add | Operation::Sub => 0,
+        Operation::Mul | Operation::Div | Operation::Mod => 1,
+        Operation::Pow => 2,
+    }
+}
+
+/// `term` as an operand of `op`, in parentheses if it would bind to other operands otherwise
+fn operand(term: Term, op: Operation, right: bool) -> Box<Term> {
+    let grouped = match &term {
+        Term::Calculation(_, inner, _) => {
+            // ^ is right associative, all others are left associative
+            let right_assoc = op == Operation::Pow;
+            precedence(*inner) < precedence(op)
+                || (precedence(*inner) == precedence(op) && right != right_assoc)
+        }
+        _ => false,
+    };
+    if grouped {
+        Box::new(Term::SubTerm(Box::new(term)))
+    } else {
+        Box::new(term)
+    }
+}
+
+/// `term` in parentheses if it is a calculation, or an annotation that `annotation` can't follow,
+/// the parser only labels and comments single operands
+fn annotated(term: Term, annotation: fn(&Term) -> bool) -> Box<Term> {
+    match term {
+        Term::Calculation(..) => Box::new(Term::SubTerm(Box::new(term))),
+        term if annotation(&term) => Box::new(Term::SubTerm(Box::new(term))),
+        term => Box::new(term),
+    }
+}
+
+impl Term {
+    #[must_use]
+    pub fn constant(value: i64) -> Term {
+        Term::Constant(value)
+    }
+
+    /// the sum of `dice`
+    #[must_use]
+    pub fn dice(dice: Dice) -> Term {
+        Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(dice)))
+    }
+
+    /// the sum of the dice of `dice` kept by `filter` with `target`, like `3d6>4`
+    #[must_use]
+    pub fn filtered(dice: Dice, filter: Filter, target: u32) -> Term {
+        Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Filtered(
+            dice, filter, target,
+        )))
+    }
+
+    /// the sum of the `count` dice of `dice` chosen by `selector`, like `4d6k3`
+    #[must_use]
+    pub fn select(dice: Dice, selector: Selector, count: u32) -> Term {
+        Term::DiceThrow(SelectedDice::Selected(
+            FilteredDice::Simple(dice),
+            selector,
+            count,
+        ))
+    }
+
+    /// the number of dice of `dice` kept by `filter` with `target`, like `10d10>=8s`
+    #[must_use]
+    pub fn successes(dice: Dice, filter: Filter, target: u32) -> Term {
+        Term::SuccessCount(FilteredDice::Filtered(dice, filter, target))
+    }
+
+    #[must_use]
+    pub fn var(name: &str) -> Term {
+        Term::Var(name.to_string())
+    }
+
+    /// `self op other`, operands are put in parentheses where the operation would bind them
+    /// differently otherwise
+    #[must_use]
+    pub fn calculation(self, op: Operation, other: Term) -> Term {
+        Term::Calculation(operand(self, op, false), op, operand(other, op, true))
+    }
+
+    #[must_use]
+    pub fn pow(self, exponent: Term) -> Term {
+        self.calculation(Operation::Pow, exponent)
+    }
+
+    /// `min(a, b)` and the other functions of `args`
+    #[must_use]
+    pub fn function(func: Func, args: Vec<Term>) -> Term {
+        Term::Function(func, args)
+    }
+
+    /// `self#label`, in parentheses unless `self` is a single operand
+    #[must_use]
+    pub fn labeled(self, label: &str) -> Term {
+        Term::Labeled(
+            annotated(self, |t| {
+                matches!(t, Term::Labeled(..) | Term::Commented(..))
+            }),
+            label.to_string(),
+        )
+    }
+
+    /// `self [comment]`, in parentheses unless `self` is a single operand, which may be labeled
+    #[must_use]
+    pub fn commented(self, comment: &str) -> Term {
+        Term::Commented(
+            annotated(self, |t| matches!(t, Term::Commented(..))),
+            comment.to_string(),
+        )
+    }
+}
+
+impl ops::Add for Term {
+    type Output = Term;
+
+    fn add(self, other: Term) -> Term {
+        self.calculation(Operation::Add, other)
+    }
+}
+
+impl ops::Sub for Term {
+    type Output = Term;
+
+    fn sub(self, other: Term) -> Term {
+        self.calculation(Operation::Sub, other)
+    }
+}
+
+impl ops::Mul for Term {
+    type Output = Term;
+
+    fn mul(self, other: Term) -> Term {
+        self.calculation(Operation::Mul, other)
+    }
+}
+
+impl ops::Div for Term {
+    type Output = Term;
+
+    fn div(self, other: Term) -> Term {
+        self.calculation(Operation::Div, other)
+    }
+}
+
+impl ops::Rem for Term {
+    type Output = Term;
+
+    fn rem(self, other: Term) -> Term {
+        self.calculation(Operation::Mod, other)
+    }
+}
+
+impl ops::Neg for Term {
+    type Output = Term;
+
+    fn neg(self) -> Term {
+        match self {
+            Term::Calculation(_, _, _) => Term::Negate(Box::new(Term::SubTerm(Box::new(self)))),
+            term => Term::Negate(Box::new(term)),
+        }
+    }
+}
+
+impl Expression {
+    #[must_use]
+    pub fn simple(term: Term) -> Expression {
+        Expression::Simple(term)
+    }
+
+    /// `count` results of `term`, like `6{4d6k3}`
+    #[must_use]
+    pub fn list(count: u32, term: Term) -> Expression {
+        Expression::List(count, term)
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    /// `expr` is what the parser makes of its display form
+    fn round_trip(expr: Expression) -> String {
+        let text = expr.to_string();
+        assert_eq!(parse_expression(&text), Ok(("", expr)), "{}", text);
+        text
+    }
+
+    #[test]
+    fn test_builder() {
+        let d6 = |throws| Term::dice(Dice::number(throws, 6));
+        assert_eq!(
+            round_trip(Expression::simple(
+                Term::dice(Dice::number(1, 20)) + Term::var("STR")
+            )),
+            "1d20 + STR"
+        );
+        assert_eq!(
+            round_trip(Expression::list(
+                6,
+                Term::select(Dice::number(4, 6), Selector::Higher, 3)
+            )),
+            "6{4d6h3}"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                (d6(2) + Term::constant(1)) * Term::constant(2)
+            )),
+            "(2d6 + 1) * 2"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(d6(1) - (d6(2) - d6(3)))),
+            "1d6 - (2d6 - 3d6)"
+        );
+        assert_eq!(
+            round_trip(Expression::simple((d6(1) - d6(2)) - d6(3))),
+            "1d6 - 2d6 - 3d6"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                Term::constant(2).pow(Term::constant(3).pow(Term::constant(2)))
+            )),
+            "2 ^ 3 ^ 2"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                Term::constant(2)
+                    .pow(Term::constant(3))
+                    .pow(Term::constant(2))
+            )),
+            "(2 ^ 3) ^ 2"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                d6(1) + d6(2) % Term::constant(3) / d6(1)
+            )),
+            "1d6 + 2d6 % 3 / 1d6"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(-(d6(1) + Term::constant(2)))),
+            "-(1d6 + 2)"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                Term::successes(Dice::number(10, 10), Filter::BiggerEq, 8)
+                    + Term::filtered(
+                        Dice::number(4, 6).reroll(Reroll::Once(Filter::Smaller, 2)),
+                        Filter::Bigger,
+                        4
+                    )
+                    - Term::dice(Dice::fudge(4))
+            )),
+            "10d10>=8s + 4d6ro<2>4 - 4dF"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                Term::dice(Dice::multiply(1, 6).explode(Explode::Standard)).labeled("hit")
+                    + Term::function(Func::Max, vec![d6(1), Term::constant(2)]).commented("bonus")
+            )),
+            "1d6x!#hit + max(1d6, 2) [bonus]"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                (Term::constant(1) + Term::constant(2)).labeled("x") * Term::constant(3)
+            )),
+            "(1 + 2)#x * 3"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                -(Term::constant(1) + Term::constant(2)).labeled("x") * Term::constant(2)
+            )),
+            "-(1 + 2)#x * 2"
+        );
+        assert_eq!(
+            round_trip(Expression::simple(
+                d6(1).labeled("a").commented("b").labeled("c") + d6(2).commented("d")
+            )),
+            "(1d6#a [b])#c + 2d6 [d]"
+        );
+    }
+}
@@ -14,73 +14,187 @@ Copyright 2021 Robin Marchart
    limitations under the License.
 */
 
-use crate::dice_types::*;
+use crate::{
+    dice_types::*,
+    limits::{DiceLimits, MAX_DYNAMIC_FACES, MAX_DYNAMIC_THROWS},
+};
 use rand::{distributions::Uniform, Rng};
-use std::convert::TryInto;
+use std::{collections::HashMap, convert::TryInto};
 
 #[cfg(feature = "logging")]
 use log::debug;
 
 #[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum EvaluationErrors {
     DivideByZero,
-    Timeout,
+    /// the evaluation took too long, `completed` of the `total` results of a list were done
+    Timeout {
+        completed: u32,
+        total: u32,
+    },
     Overflow,
+    NegativeExponent,
+    DiceLimitExceeded,
+    /// no value was provided for the variable
+    UndefinedVariable(String),
+    /// the evaluation failed unexpectedly, without a result
+    Internal,
+}
+
+/// timeout of a single result, lists replace it with how far they got
+const TIMEOUT: EvaluationErrors = EvaluationErrors::Timeout {
+    completed: 0,
+    total: 1,
+};
+
+/// bounds on the resources a single evaluation may use
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EvaluationLimits {
+    /// number of dice that may still be rolled
+    pub dice: u64,
+}
+
+impl EvaluationLimits {
+    /// allow rolling at most `dice` dice in total
+    pub fn new(dice: u64) -> EvaluationLimits {
+        EvaluationLimits { dice }
+    }
+
+    pub fn unlimited() -> EvaluationLimits {
+        EvaluationLimits::new(u64::MAX)
+    }
+
+    /// account for rolling `dice` more dice
+    fn roll(&mut self, dice: u64) -> Result<(), EvaluationErrors> {
+        self.dice = self
+            .dice
+            .checked_sub(dice)
+            .ok_or(EvaluationErrors::DiceLimitExceeded)?;
+        Ok(())
+    }
 }
 
 pub trait DiceEvaluate {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<(Vec<i64>, Vec<i64>), EvaluationErrors>;
+
+    fn evaluate<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        rng: &mut R,
+    ) -> Result<(Vec<i64>, Vec<i64>), EvaluationErrors> {
+        self.evaluate_limited(timeout_f, &mut EvaluationLimits::unlimited(), rng)
+    }
 }
 
 impl DiceEvaluate for Dice {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<(Vec<i64>, Vec<i64>), EvaluationErrors> {
         if timeout_f() {
-            return Err(EvaluationErrors::Timeout);
+            return Err(TIMEOUT);
         }
-        let mut rolls: Vec<i64> = Vec::with_capacity(self.throws.try_into().unwrap());
-        let mut roll_counter: u8 = 0;
-        match self.dice {
-            DiceType::Number(faces) => {
-                let dist = Uniform::new_inclusive(1, faces as i64);
-                for _ in 0..self.throws {
-                    roll_counter = roll_counter.wrapping_add(1);
-                    if roll_counter == 0 && timeout_f() {
-                        return Err(EvaluationErrors::Timeout);
-                    }
-                    rolls.push(rng.sample::<i64, _>(dist));
+        let throws = match &self.dynamic_throws {
+            Some(term) => {
+                let throws = term.evaluate_limited(timeout_f, limits, rng)?.0;
+                if !(0..=MAX_DYNAMIC_THROWS).contains(&throws) {
+                    return Err(EvaluationErrors::Overflow);
                 }
+                throws as u32
             }
-            DiceType::Fudge => {
-                let dist: Uniform<i64> = Uniform::new_inclusive(-1, 1);
-                for _ in 0..self.throws {
-                    roll_counter = roll_counter.wrapping_add(1);
-                    if roll_counter == 0 && timeout_f() {
-                        return Err(EvaluationErrors::Timeout);
-                    }
-                    rolls.push(rng.sample(dist));
+            None => self.throws,
+        };
+        limits.roll(throws.into())?;
+        let mut rolls: Vec<i64> = Vec::with_capacity(throws.try_into().unwrap());
+        // rerolled values only show up here, next to their replacement
+        let mut all_rolls: Vec<i64> = Vec::with_capacity(throws.try_into().unwrap());
+        let mut roll_counter: u8 = 0;
+        let (dist, squared, explode_at) = match &self.dice {
+            DiceType::Number(faces) => (
+                Uniform::new_inclusive(1, *faces as i64),
+                false,
+                self.dice.max(),
+            ),
+            DiceType::Fudge { sides } => (
+                Uniform::new_inclusive(-i64::from(*sides), i64::from(*sides)),
+                false,
+                self.dice.max(),
+            ),
+            DiceType::Multiply(base_faces) => (
+                Uniform::new_inclusive(1, *base_faces as i64),
+                true,
+                self.dice.max(),
+            ),
+            DiceType::Dynamic(term) => {
+                let faces = term.evaluate_limited(timeout_f, limits, rng)?.0;
+                if !(1..=MAX_DYNAMIC_FACES).contains(&faces) {
+                    return Err(EvaluationErrors::Overflow);
                 }
+                (Uniform::new_inclusive(1, faces), false, faces)
             }
-            DiceType::Multiply(base_faces) => {
-                let dist = Uniform::new_inclusive(1, base_faces as i64);
-                for _ in 0..self.throws {
-                    roll_counter = roll_counter.wrapping_add(1);
-                    if roll_counter == 0 && timeout_f() {
-                        return Err(EvaluationErrors::Timeout);
-                    }
-                    rolls.push(
+        };
+        for _ in 0..throws {
+            // an exploding die keeps adding rolls while it shows its maximum face,
+            // a compounding one sums them up into a single result
+            let mut compound: i64 = 0;
+            loop {
+                roll_counter = roll_counter.wrapping_add(1);
+                if roll_counter == 0 && timeout_f() {
+                    return Err(TIMEOUT);
+                }
+                let mut sample = || {
+                    if squared {
                         rng.sample(dist)
                             .checked_mul(rng.sample(dist))
-                            .ok_or(EvaluationErrors::Overflow)?,
-                    );
+                            .ok_or(EvaluationErrors::Overflow)
+                    } else {
+                        Ok(rng.sample(dist))
+                    }
+                };
+                let mut roll = sample()?;
+                match self.reroll {
+                    Some(Reroll::Once(filter, target)) if filter.matches(roll, target) => {
+                        limits.roll(1)?;
+                        all_rolls.push(roll);
+                        roll = sample()?;
+                    }
+                    Some(Reroll::Recursive(filter, target)) => {
+                        while filter.matches(roll, target) {
+                            roll_counter = roll_counter.wrapping_add(1);
+                            if roll_counter == 0 && timeout_f() {
+                                return Err(TIMEOUT);
+                            }
+                            limits.roll(1)?;
+                            all_rolls.push(roll);
+                            roll = sample()?;
+                        }
+                    }
+                    _ => {}
+                }
+                all_rolls.push(roll);
+                if self.explode == Some(Explode::Compound) {
+                    compound = compound
+                        .checked_add(roll)
+                        .ok_or(EvaluationErrors::Overflow)?;
+                } else {
+                    rolls.push(roll);
                 }
+                if self.explode.is_none() || roll != explode_at {
+                    break;
+                }
+                // every explosion rolls another die
+                limits.roll(1)?;
+            }
+            if self.explode == Some(Explode::Compound) {
+                rolls.push(compound);
             }
         }
 
@@ -89,52 +203,55 @@ impl DiceEvaluate for Dice {
             debug!("Dice roll result for {} is {:?}", &self, &rolls);
         }
 
-        let rolls_copy = rolls.clone();
-        Ok((rolls, rolls_copy))
+        // compounding dice show their sums, as they did before
+        if self.reroll.is_none() || self.explode == Some(Explode::Compound) {
+            all_rolls = rolls.clone();
+        }
+        Ok((rolls, all_rolls))
     }
 }
 
-impl DiceEvaluate for FilteredDice {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+/// kept dice, all rolled dice and the outcome of each die that made it past rerolls
+pub type OutcomeResult = (Vec<i64>, Vec<i64>, Vec<DieOutcome>);
+
+impl FilteredDice {
+    /// like [DiceEvaluate::evaluate_limited], but also tells which dice were filtered out
+    pub fn evaluate_outcomes<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
-    ) -> Result<(Vec<i64>, Vec<i64>), EvaluationErrors> {
+    ) -> Result<OutcomeResult, EvaluationErrors> {
         let result = match self {
-            FilteredDice::Simple(dice) => dice.evaluate(timeout_f, rng),
-            FilteredDice::Filtered(dice, filter, target) => {
-                dice.evaluate(timeout_f, rng).map(|original| {
+            FilteredDice::Simple(dice) => {
+                dice.evaluate_limited(timeout_f, limits, rng)
+                    .map(|(rolls, all_rolls)| {
+                        let outcomes = rolls.iter().map(|r| DieOutcome::Kept(*r)).collect();
+                        (rolls, all_rolls, outcomes)
+                    })
+            }
+            FilteredDice::Filtered(dice, filter, target) => dice
+                .evaluate_limited(timeout_f, limits, rng)
+                .map(|(rolls, all_rolls)| {
+                    let outcomes = rolls
+                        .iter()
+                        .map(|r| {
+                            if filter.matches(*r, *target) {
+                                DieOutcome::Kept(*r)
+                            } else {
+                                DieOutcome::Filtered(*r)
+                            }
+                        })
+                        .collect();
                     (
-                        original
-                            .0
+                        rolls
                             .into_iter()
-                            .filter(match filter {
-                                Filter::Bigger => {
-                                    Box::new(|i: &i64| i > &(target.to_owned() as i64))
-                                        as Box<dyn Fn(&i64) -> bool>
-                                }
-                                Filter::BiggerEq => {
-                                    Box::new(|i: &i64| i > &(target.to_owned() as i64))
-                                        as Box<dyn Fn(&i64) -> bool>
-                                }
-                                Filter::Smaller => {
-                                    Box::new(|i: &i64| i < &(target.to_owned() as i64))
-                                        as Box<dyn Fn(&i64) -> bool>
-                                }
-                                Filter::SmallerEq => {
-                                    Box::new(|i: &i64| i <= &(target.to_owned() as i64))
-                                        as Box<dyn Fn(&i64) -> bool>
-                                }
-                                Filter::NotEq => {
-                                    Box::new(|i: &i64| i != &(target.to_owned() as i64))
-                                        as Box<dyn Fn(&i64) -> bool>
-                                }
-                            })
+                            .filter(|i| filter.matches(*i, *target))
                             .collect(),
-                        original.1,
+                        all_rolls,
+                        outcomes,
                     )
-                })
-            }
+                }),
         };
         #[cfg(feature = "logging")]
         {
@@ -144,33 +261,55 @@ impl DiceEvaluate for FilteredDice {
     }
 }
 
-impl DiceEvaluate for SelectedDice {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+impl DiceEvaluate for FilteredDice {
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<(Vec<i64>, Vec<i64>), EvaluationErrors> {
+        self.evaluate_outcomes(timeout_f, limits, rng)
+            .map(|(kept, all_rolls, _)| (kept, all_rolls))
+    }
+}
+
+impl SelectedDice {
+    /// like [DiceEvaluate::evaluate_limited], but also tells which dice were
+    /// filtered out or dropped by the selector
+    pub fn evaluate_outcomes<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
+        rng: &mut R,
+    ) -> Result<OutcomeResult, EvaluationErrors> {
         let result = match self {
-            SelectedDice::Unchanged(dice) => dice.evaluate(timeout_f, rng),
-            SelectedDice::Selected(dice, selector, max_size) => {
-                dice.evaluate(timeout_f, rng)
-                    .map(|original: (Vec<i64>, Vec<i64>)| {
-                        if original.0.len() > max_size.to_owned() as usize {
-                            let range = match selector {
-                                Selector::Higher => {
-                                    (original.0.len() - max_size.to_owned() as usize)
-                                        ..original.0.len()
-                                }
-                                Selector::Lower => (0..(max_size.to_owned() as usize)),
-                            };
-                            let mut source = original;
-                            source.0.sort_unstable();
-                            (source.0[range].to_vec(), source.1)
-                        } else {
-                            original
+            SelectedDice::Unchanged(dice) => dice.evaluate_outcomes(timeout_f, limits, rng),
+            SelectedDice::Selected(dice, selector, max_size) => dice
+                .evaluate_outcomes(timeout_f, limits, rng)
+                .map(|original: OutcomeResult| {
+                    let len = original.0.len();
+                    let max_size = max_size.to_owned() as usize;
+                    let range = match selector {
+                        Selector::Higher if len > max_size => (len - max_size)..len,
+                        Selector::Lower if len > max_size => 0..max_size,
+                        Selector::DropHigher => 0..len.saturating_sub(max_size),
+                        Selector::DropLower => max_size.min(len)..len,
+                        _ => return original,
+                    };
+                    let (mut kept, all_rolls, mut outcomes) = original;
+                    // positions of the kept dice, ordered like the sorted values
+                    let mut ranked: Vec<usize> = (0..outcomes.len())
+                        .filter(|i| matches!(outcomes[*i], DieOutcome::Kept(_)))
+                        .collect();
+                    ranked.sort_by_key(|i| outcomes[*i].value());
+                    for (rank, i) in ranked.into_iter().enumerate() {
+                        if !range.contains(&rank) {
+                            outcomes[i] = DieOutcome::Dropped(outcomes[i].value());
                         }
-                    })
-            }
+                    }
+                    kept.sort_unstable();
+                    (kept[range].to_vec(), all_rolls, outcomes)
+                }),
         };
         #[cfg(feature = "logging")]
         {
@@ -180,95 +319,1063 @@ impl DiceEvaluate for SelectedDice {
     }
 }
 
+impl DiceEvaluate for SelectedDice {
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
+        rng: &mut R,
+    ) -> Result<(Vec<i64>, Vec<i64>), EvaluationErrors> {
+        self.evaluate_outcomes(timeout_f, limits, rng)
+            .map(|(kept, all_rolls, _)| (kept, all_rolls))
+    }
+}
+
 pub trait TermEvaluate {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<(i64, Vec<i64>), EvaluationErrors>;
+
+    fn evaluate<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        rng: &mut R,
+    ) -> Result<(i64, Vec<i64>), EvaluationErrors> {
+        self.evaluate_limited(timeout_f, &mut EvaluationLimits::unlimited(), rng)
+    }
 }
 
 impl TermEvaluate for Term {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<(i64, Vec<i64>), EvaluationErrors> {
-        let result = match self {
-            Term::Constant(i) => Ok((i.to_owned(), Vec::new())),
-            Term::DiceThrow(dice) => dice.evaluate(timeout_f, rng).map(|roll_results| {
-                (
-                    roll_results.0.into_iter().reduce(|a, b| a + b).unwrap_or(0),
-                    roll_results.1,
-                )
-            }),
-            Term::SubTerm(term) => term.evaluate(timeout_f, rng),
-            Term::Calculation(left, op, right) => {
-                let left_r = left.evaluate(timeout_f, rng)?;
-                let right_r = right.evaluate(timeout_f, rng)?;
-                let result = match op {
-                    Operation::Add => left_r
-                        .0
-                        .checked_add(right_r.0)
-                        .ok_or(EvaluationErrors::Overflow),
-                    Operation::Sub => left_r
-                        .0
-                        .checked_sub(right_r.0)
-                        .ok_or(EvaluationErrors::Overflow),
-                    Operation::Mul => left_r
-                        .0
-                        .checked_mul(right_r.0)
-                        .ok_or(EvaluationErrors::Overflow),
-                    Operation::Div => left_r
-                        .0
-                        .checked_div(right_r.0)
-                        .ok_or(EvaluationErrors::DivideByZero),
-                }?;
-                Ok((result, [left_r.1, right_r.1].concat()))
+        evaluate_term(self, timeout_f, limits, rng, &mut Details::default())
+    }
+}
+
+/// what an evaluation finds out besides the result
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Details {
+    /// value of every `#label`ed term, in order
+    pub labels: Vec<(String, i64)>,
+    /// outcome of every die that made it past rerolls, in order
+    pub outcomes: Vec<DieOutcome>,
+    /// every applied selector, in order, also those that kept every die
+    pub selections: Vec<Selection>,
+}
+
+/// evaluates `term`, recording labeled terms and dice outcomes in `details`
+fn evaluate_term<T: FnMut() -> bool, R: Rng>(
+    term: &Term,
+    timeout_f: &mut T,
+    limits: &mut EvaluationLimits,
+    rng: &mut R,
+    details: &mut Details,
+) -> Result<(i64, Vec<i64>), EvaluationErrors> {
+    let result = match term {
+        Term::Constant(i) => Ok((i.to_owned(), Vec::new())),
+        Term::DiceThrow(dice) => {
+            dice.evaluate_outcomes(timeout_f, limits, rng)
+                .map(|(kept, all_rolls, outcomes)| {
+                    if let SelectedDice::Selected(_, selector, count) = dice {
+                        details.selections.push(Selection {
+                            selector: *selector,
+                            count: *count,
+                            available: outcomes
+                                .iter()
+                                .filter(|o| !matches!(o, DieOutcome::Filtered(_)))
+                                .count(),
+                        });
+                    }
+                    details.outcomes.extend(outcomes);
+                    // no kept dice at all sum up to 0
+                    (kept.iter().sum(), all_rolls)
+                })
+        }
+        Term::SuccessCount(dice) => {
+            dice.evaluate_outcomes(timeout_f, limits, rng)
+                .map(|(kept, all_rolls, outcomes)| {
+                    details.outcomes.extend(outcomes);
+                    (kept.len() as i64, all_rolls)
+                })
+        }
+        Term::SubTerm(term) | Term::Commented(term, _) => {
+            evaluate_term(term, timeout_f, limits, rng, details)
+        }
+        Term::Var(name) => Err(EvaluationErrors::UndefinedVariable(name.clone())),
+        Term::Negate(term) => {
+            let (value, rolls) = evaluate_term(term, timeout_f, limits, rng, details)?;
+            Ok((
+                value.checked_neg().ok_or(EvaluationErrors::Overflow)?,
+                rolls,
+            ))
+        }
+        Term::Function(func, args) if func.unary() => match args.as_slice() {
+            [arg] => evaluate_rounded(*func, arg, timeout_f, limits, rng, details),
+            _ => Err(EvaluationErrors::Internal),
+        },
+        Term::Function(func, args) => {
+            let mut value: Option<i64> = None;
+            // the dice of every argument are kept, so all of them show up in the roll info
+            let mut rolls = Vec::new();
+            for arg in args {
+                let (arg_value, arg_rolls) = evaluate_term(arg, timeout_f, limits, rng, details)?;
+                value = Some(match (value, func) {
+                    (None, _) => arg_value,
+                    (Some(v), Func::Min) => v.min(arg_value),
+                    (Some(v), _) => v.max(arg_value),
+                });
+                rolls.extend(arg_rolls);
             }
-        };
-        #[cfg(feature = "logging")]
-        {
-            debug!("got {:?} for term {}", &result, &self)
+            value
+                .map(|value| (value, rolls))
+                .ok_or(EvaluationErrors::Internal)
         }
-        result
+        Term::Labeled(term, label) => {
+            let result = evaluate_term(term, timeout_f, limits, rng, details)?;
+            details.labels.push((label.clone(), result.0));
+            Ok(result)
+        }
+        Term::Calculation(left, op, right) => {
+            let left_r = evaluate_term(left, timeout_f, limits, rng, details)?;
+            let right_r = evaluate_term(right, timeout_f, limits, rng, details)?;
+            let result = match op {
+                Operation::Add => left_r
+                    .0
+                    .checked_add(right_r.0)
+                    .ok_or(EvaluationErrors::Overflow),
+                Operation::Sub => left_r
+                    .0
+                    .checked_sub(right_r.0)
+                    .ok_or(EvaluationErrors::Overflow),
+                Operation::Mul => left_r
+                    .0
+                    .checked_mul(right_r.0)
+                    .ok_or(EvaluationErrors::Overflow),
+                Operation::Div => left_r
+                    .0
+                    .checked_div(right_r.0)
+                    .ok_or(EvaluationErrors::DivideByZero),
+                Operation::Mod => left_r
+                    .0
+                    .checked_rem(right_r.0)
+                    .ok_or(EvaluationErrors::DivideByZero),
+                Operation::Pow => {
+                    if right_r.0 < 0 {
+                        Err(EvaluationErrors::NegativeExponent)
+                    } else {
+                        right_r
+                            .0
+                            .try_into()
+                            .ok()
+                            .and_then(|exp: u32| left_r.0.checked_pow(exp))
+                            .ok_or(EvaluationErrors::Overflow)
+                    }
+                }
+            }?;
+            Ok((result, [left_r.1, right_r.1].concat()))
+        }
+    };
+    #[cfg(feature = "logging")]
+    {
+        debug!("got {:?} for term {}", &result, term)
+    }
+    result
+}
+
+/// `numerator / denominator` rounded down, up or to the nearest integer by `func`
+///
+/// the quotient is exact before rounding, so `floor(-7 / 2)` is -4 and `ceil(-7 / 2)` is -3
+fn rounded_div(func: Func, numerator: i64, denominator: i64) -> Result<i64, EvaluationErrors> {
+    if denominator == 0 {
+        return Err(EvaluationErrors::DivideByZero);
+    }
+    // with a positive denominator the euclidean division rounds down
+    let (numerator, denominator) = if denominator < 0 {
+        (-i128::from(numerator), -i128::from(denominator))
+    } else {
+        (i128::from(numerator), i128::from(denominator))
+    };
+    let quotient = match func {
+        Func::Ceil => -(-numerator).div_euclid(denominator),
+        // halves are rounded up, the same as rounding down the quotient plus one half
+        Func::Round => (2 * numerator + denominator).div_euclid(2 * denominator),
+        _ => numerator.div_euclid(denominator),
+    };
+    quotient.try_into().map_err(|_| EvaluationErrors::Overflow)
+}
+
+/// evaluates the single argument of a rounding function, only divisions are rounded
+fn evaluate_rounded<T: FnMut() -> bool, R: Rng>(
+    func: Func,
+    arg: &Term,
+    timeout_f: &mut T,
+    limits: &mut EvaluationLimits,
+    rng: &mut R,
+    details: &mut Details,
+) -> Result<(i64, Vec<i64>), EvaluationErrors> {
+    let mut division = arg;
+    while let Term::SubTerm(t) = division {
+        division = t;
+    }
+    match division {
+        Term::Calculation(left, Operation::Div, right) => {
+            let left_r = evaluate_term(left, timeout_f, limits, rng, details)?;
+            let right_r = evaluate_term(right, timeout_f, limits, rng, details)?;
+            Ok((
+                rounded_div(func, left_r.0, right_r.0)?,
+                [left_r.1, right_r.1].concat(),
+            ))
+        }
+        // anything else already is an integer
+        _ => evaluate_term(arg, timeout_f, limits, rng, details),
     }
 }
 
 impl TermEvaluate for Box<Term> {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<(i64, Vec<i64>), EvaluationErrors> {
-        self.as_ref().evaluate(timeout_f, rng)
+        self.as_ref().evaluate_limited(timeout_f, limits, rng)
     }
 }
 
 pub trait ExpressionEvaluate {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
-        timeout_t: &mut T,
+        timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<Vec<(i64, Vec<i64>)>, EvaluationErrors>;
+
+    fn evaluate<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        rng: &mut R,
+    ) -> Result<Vec<(i64, Vec<i64>)>, EvaluationErrors> {
+        self.evaluate_limited(timeout_f, &mut EvaluationLimits::unlimited(), rng)
+    }
+}
+
+/// a result of an expression together with the values of its labeled terms
+pub type LabeledResult = ((i64, Vec<i64>), Vec<(String, i64)>);
+
+/// a result of an expression together with the [Details] of its evaluation
+pub type DetailedResult = ((i64, Vec<i64>), Details);
+
+impl Expression {
+    /// like [ExpressionEvaluate::evaluate_limited], but every result also carries
+    /// the values of the `#label`ed terms it is made of, in order
+    pub fn evaluate_labeled<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
+        rng: &mut R,
+    ) -> Result<Vec<LabeledResult>, EvaluationErrors> {
+        self.evaluate_detailed(timeout_f, limits, rng)
+            .map(|results| {
+                results
+                    .into_iter()
+                    .map(|(result, details)| (result, details.labels))
+                    .collect()
+            })
+    }
+
+    /// like [ExpressionEvaluate::evaluate_limited], but every result also carries
+    /// its labeled terms and the outcome of each of its dice
+    pub fn evaluate_detailed<T: FnMut() -> bool, R: Rng>(
+        &self,
+        timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
+        rng: &mut R,
+    ) -> Result<Vec<DetailedResult>, EvaluationErrors> {
+        let (count, term) = match self {
+            Expression::Simple(term) => (1, term),
+            Expression::List(count, term) => (*count, term),
+        };
+        let size: usize = count.try_into().expect("failed to convert u32 to usize");
+        let mut result_collector: Vec<DetailedResult> = Vec::with_capacity(size);
+        for completed in 0..count {
+            let mut details = Details::default();
+            let result = evaluate_term(term, timeout_f, limits, rng, &mut details).map_err(
+                |err| match err {
+                    EvaluationErrors::Timeout { .. } => EvaluationErrors::Timeout {
+                        completed,
+                        total: count,
+                    },
+                    err => err,
+                },
+            )?;
+            result_collector.push((result, details));
+        }
+        Ok(result_collector)
+    }
+}
+
+impl NarrativePool {
+    /// rolls every die of the pool, in order
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> NarrativeRoll {
+        let mut rolled = Vec::with_capacity(self.size() as usize);
+        for (n, die) in &self.0 {
+            let faces = die.faces();
+            for _ in 0..*n {
+                rolled.push((*die, faces[rng.gen_range(0..faces.len())]));
+            }
+        }
+        NarrativeRoll(rolled)
+    }
+}
+
+/// evaluates `expr` with the values of its variables taken from `vars`
+///
+/// ```
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use robins_dice_roll::{dice_roll::evaluate_with_vars, Expression};
+/// use std::collections::HashMap;
+///
+/// let expr: Expression = "1d20 + STR".parse().unwrap();
+/// let mut vars = HashMap::new();
+/// vars.insert("STR".to_string(), 3);
+/// let result = evaluate_with_vars(&expr, &vars, &mut StdRng::seed_from_u64(0)).unwrap();
+/// assert!((4..=23).contains(&result[0].0));
+/// ```
+///
+/// variables without a value are an error
+///
+/// ```
+/// # use rand::{rngs::StdRng, SeedableRng};
+/// # use robins_dice_roll::{dice_roll::{evaluate_with_vars, EvaluationErrors}, Expression};
+/// # use std::collections::HashMap;
+/// let expr: Expression = "1d20 + DEX".parse().unwrap();
+/// assert_eq!(
+///     evaluate_with_vars(&expr, &HashMap::new(), &mut StdRng::seed_from_u64(0)),
+///     Err(EvaluationErrors::UndefinedVariable("DEX".to_string()))
+/// );
+/// ```
+pub fn evaluate_with_vars<R: Rng>(
+    expr: &Expression,
+    vars: &HashMap<String, i64>,
+    rng: &mut R,
+) -> Result<Vec<(i64, Vec<i64>)>, EvaluationErrors> {
+    let mut expr = expr.clone();
+    expr.term_mut()
+        .substitute(vars)
+        .map_err(EvaluationErrors::UndefinedVariable)?;
+    expr.evaluate(&mut || false, rng)
 }
 
 impl ExpressionEvaluate for Expression {
-    fn evaluate<T: FnMut() -> bool, R: Rng>(
+    fn evaluate_limited<T: FnMut() -> bool, R: Rng>(
         &self,
         timeout_f: &mut T,
+        limits: &mut EvaluationLimits,
         rng: &mut R,
     ) -> Result<Vec<(i64, Vec<i64>)>, EvaluationErrors> {
-        match self {
-            Expression::Simple(term) => term.evaluate(timeout_f, rng).map(|res| vec![res]),
-            Expression::List(count, term) => {
-                let size: usize = (*count).try_into().expect("failed to convert u32 to usize");
-                let mut result_collector: Vec<(i64, Vec<i64>)> = Vec::with_capacity(size);
-                for _ in 0..size {
-                    result_collector.push(term.evaluate(timeout_f, rng)?);
-                }
-                Ok(result_collector)
+        self.evaluate_labeled(timeout_f, limits, rng)
+            .map(|results| results.into_iter().map(|(result, _)| result).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_filter_boundary() {
+        let dice = Dice {
+            throws: 10,
+            dice: DiceType::Number(6),
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        };
+        let filtered = |filter| {
+            FilteredDice::Filtered(dice.clone(), filter, 4)
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(1))
+                .unwrap()
+        };
+        let (_, all) = filtered(Filter::Bigger);
+        let at_target = all.iter().filter(|r| **r == 4).count();
+        assert!(at_target > 0, "seed has to roll the target at least once");
+        for (filter, keeps_target) in [
+            (Filter::BiggerEq, true),
+            (Filter::SmallerEq, true),
+            (Filter::Bigger, false),
+            (Filter::Smaller, false),
+        ] {
+            let (kept, rolled) = filtered(filter);
+            assert_eq!(rolled, all);
+            assert_eq!(
+                kept.iter().filter(|r| **r == 4).count(),
+                if keeps_target { at_target } else { 0 },
+                "{}",
+                filter
+            );
+        }
+        assert_eq!(
+            filtered(Filter::BiggerEq).0,
+            all.iter().copied().filter(|r| *r >= 4).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_mod() {
+        let calc = |l, r| {
+            Term::Calculation(
+                Box::new(Term::Constant(l)),
+                Operation::Mod,
+                Box::new(Term::Constant(r)),
+            )
+            .evaluate(&mut || false, &mut StdRng::seed_from_u64(0))
+            .map(|r| r.0)
+        };
+        assert_eq!(calc(17, 6), Ok(5));
+        assert_eq!(calc(-7, 3), Ok(-1));
+        assert_eq!(calc(1, 0), Err(EvaluationErrors::DivideByZero));
+    }
+
+    #[test]
+    fn test_pow() {
+        let calc = |l, r| {
+            Term::Calculation(
+                Box::new(Term::Constant(l)),
+                Operation::Pow,
+                Box::new(Term::Constant(r)),
+            )
+            .evaluate(&mut || false, &mut StdRng::seed_from_u64(0))
+            .map(|r| r.0)
+        };
+        assert_eq!(calc(2, 10), Ok(1024));
+        assert_eq!(calc(-3, 3), Ok(-27));
+        assert_eq!(calc(5, 0), Ok(1));
+        assert_eq!(calc(2, -1), Err(EvaluationErrors::NegativeExponent));
+        assert_eq!(calc(2, 63), Err(EvaluationErrors::Overflow));
+        assert_eq!(
+            calc(1, i64::from(u32::MAX) + 1),
+            Err(EvaluationErrors::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_success_count() {
+        let term = Term::SuccessCount(FilteredDice::Filtered(
+            Dice {
+                throws: 8,
+                dice: DiceType::Number(10),
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            },
+            Filter::Bigger,
+            6,
+        ));
+        for seed in 0..64 {
+            let (count, all) = term
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert_eq!(all.len(), 8);
+            assert_eq!(count, all.iter().filter(|r| **r > 6).count() as i64);
+        }
+    }
+
+    #[test]
+    fn test_dynamic_dice() {
+        let dynamic = |faces: Term| Dice {
+            throws: 3,
+            dice: DiceType::Dynamic(Box::new(faces)),
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        };
+        let nested = dynamic(Term::DiceThrow(SelectedDice::Unchanged(
+            FilteredDice::Simple(Dice {
+                throws: 1,
+                dice: DiceType::Number(6),
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            }),
+        )));
+        for seed in 0..64 {
+            let (rolls, _) = nested
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert_eq!(rolls.len(), 3);
+            assert!(rolls.iter().all(|r| (1..=6).contains(r)));
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            dynamic(Term::Constant(1)).evaluate(&mut || false, &mut rng),
+            Ok((vec![1, 1, 1], vec![1, 1, 1]))
+        );
+        assert_eq!(
+            dynamic(Term::Constant(0)).evaluate(&mut || false, &mut rng),
+            Err(EvaluationErrors::Overflow)
+        );
+        assert_eq!(
+            dynamic(Term::Constant(-4)).evaluate(&mut || false, &mut rng),
+            Err(EvaluationErrors::Overflow)
+        );
+        assert_eq!(
+            dynamic(Term::Constant(MAX_DYNAMIC_FACES + 1)).evaluate(&mut || false, &mut rng),
+            Err(EvaluationErrors::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_dynamic_throws() {
+        let dynamic = |throws: Term| Dice {
+            throws: 0,
+            dice: DiceType::Number(6),
+            explode: None,
+            dynamic_throws: Some(Box::new(throws)),
+            reroll: None,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..64 {
+            let (rolls, _) = dynamic(Term::DiceThrow(SelectedDice::Unchanged(
+                FilteredDice::Simple(Dice {
+                    throws: 1,
+                    dice: DiceType::Number(4),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None,
+                }),
+            )))
+            .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+            .unwrap();
+            assert!((1..=4).contains(&rolls.len()));
+        }
+        assert_eq!(
+            dynamic(Term::Constant(0)).evaluate(&mut || false, &mut rng),
+            Ok((vec![], vec![]))
+        );
+        assert_eq!(
+            dynamic(Term::Constant(-1)).evaluate(&mut || false, &mut rng),
+            Err(EvaluationErrors::Overflow)
+        );
+        assert_eq!(
+            dynamic(Term::Constant(MAX_DYNAMIC_THROWS + 1)).evaluate(&mut || false, &mut rng),
+            Err(EvaluationErrors::Overflow)
+        );
+    }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_evaluate_labeled() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let expr = crate::parser::parse_expression("2{(1d1#one + 2)#three * 2}")
+            .unwrap()
+            .1;
+        let result = (
+            (6, vec![1]),
+            vec![("one".to_string(), 1), ("three".to_string(), 3)],
+        );
+        assert_eq!(
+            expr.evaluate_labeled(&mut || false, &mut EvaluationLimits::unlimited(), &mut rng),
+            Ok(vec![result.clone(), result])
+        );
+        assert_eq!(
+            expr.evaluate(&mut || false, &mut rng),
+            Ok(vec![(6, vec![1]), (6, vec![1])])
+        );
+    }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_dice_limit() {
+        let term = crate::parser::parse_term("3d6 + 2d6").unwrap().1;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut limits = EvaluationLimits::new(5);
+        assert!(term
+            .evaluate_limited(&mut || false, &mut limits, &mut rng)
+            .is_ok());
+        assert_eq!(limits.dice, 0);
+        assert_eq!(
+            term.evaluate_limited(&mut || false, &mut EvaluationLimits::new(4), &mut rng),
+            Err(EvaluationErrors::DiceLimitExceeded)
+        );
+        let list = crate::parser::parse_expression("3{2d6}").unwrap().1;
+        assert_eq!(
+            list.evaluate_limited(&mut || false, &mut EvaluationLimits::new(5), &mut rng),
+            Err(EvaluationErrors::DiceLimitExceeded)
+        );
+        let huge = Dice {
+            throws: u32::MAX,
+            dice: DiceType::Number(6),
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        };
+        assert_eq!(
+            huge.evaluate_limited(&mut || false, &mut EvaluationLimits::new(1000), &mut rng),
+            Err(EvaluationErrors::DiceLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_reroll_once() {
+        let dice = |filter: Filter, target: u32| Dice {
+            throws: 20,
+            dice: DiceType::Number(6),
+            explode: None,
+            dynamic_throws: None,
+            reroll: Some(Reroll::Once(filter, target)),
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        // every die is rerolled exactly once, even if the new value qualifies again
+        let (rolls, all) = dice(Filter::Smaller, 7)
+            .evaluate(&mut || false, &mut rng)
+            .unwrap();
+        assert_eq!(rolls.len(), 20);
+        assert_eq!(all.len(), 40);
+        assert_eq!(
+            all.iter().skip(1).step_by(2).copied().collect::<Vec<_>>(),
+            rolls
+        );
+        // only qualifying dice are rerolled
+        let (rolls, all) = dice(Filter::Smaller, 3)
+            .evaluate(&mut || false, &mut rng)
+            .unwrap();
+        assert_eq!(rolls.len(), 20);
+        assert_eq!(
+            all.len(),
+            20 + all.iter().filter(|r| **r < 3).count() - rolls.iter().filter(|r| **r < 3).count()
+        );
+        assert_eq!(
+            dice(Filter::Smaller, 7).evaluate_limited(
+                &mut || false,
+                &mut EvaluationLimits::new(39),
+                &mut rng
+            ),
+            Err(EvaluationErrors::DiceLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_reroll_recursive() {
+        let dice = |faces: u32, explode: Option<Explode>, target: u32| Dice {
+            throws: 20,
+            dice: DiceType::Number(faces),
+            explode,
+            dynamic_throws: None,
+            reroll: Some(Reroll::Recursive(Filter::Smaller, target)),
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let (rolls, all) = dice(2, None, 2).evaluate(&mut || false, &mut rng).unwrap();
+        assert_eq!(rolls, vec![2; 20]);
+        assert_eq!(all.iter().filter(|r| **r == 2).count(), 20);
+        assert!(all.len() > 20);
+        // explosions happen on the rerolled value, and their dice are rerolled as well
+        let (rolls, all) = dice(6, Some(Explode::Standard), 3)
+            .evaluate(&mut || false, &mut rng)
+            .unwrap();
+        assert!(rolls.iter().all(|r| *r >= 3));
+        assert!(rolls.len() > 20);
+        assert_eq!(rolls.len(), 20 + rolls.iter().filter(|r| **r == 6).count());
+        assert_eq!(
+            all.len(),
+            rolls.len() + all.iter().filter(|r| **r < 3).count()
+        );
+        // no face ever stops the rerolling
+        let mut calls = 0;
+        assert_eq!(
+            dice(2, None, 3).evaluate(
+                &mut || {
+                    calls += 1;
+                    calls > 4
+                },
+                &mut rng
+            ),
+            Err(TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn test_explode() {
+        let dice = Dice {
+            throws: 2,
+            dice: DiceType::Number(2),
+            explode: Some(Explode::Standard),
+            dynamic_throws: None,
+            reroll: None,
+        };
+        for seed in 0..64 {
+            let (kept, all) = dice
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert_eq!(kept, all);
+            // every die stops exploding on its first non-maximum face
+            assert_eq!(all.iter().filter(|r| **r == 1).count(), 2);
+            assert_eq!(all.last(), Some(&1));
+        }
+    }
+
+    #[test]
+    fn test_compound() {
+        let dice = Dice {
+            throws: 3,
+            dice: DiceType::Number(2),
+            explode: Some(Explode::Compound),
+            dynamic_throws: None,
+            reroll: None,
+        };
+        for seed in 0..64 {
+            let (kept, all) = dice
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert_eq!(kept, all);
+            assert_eq!(all.len(), 3);
+            // any number of 2s followed by the final 1
+            assert!(all.iter().all(|r| r % 2 == 1));
+        }
+        let mut calls = 0;
+        assert_eq!(
+            Dice {
+                throws: 1,
+                dice: DiceType::Number(1),
+                explode: Some(Explode::Compound),
+                dynamic_throws: None,
+                reroll: None,
+            }
+            .evaluate(
+                &mut || {
+                    calls += 1;
+                    calls > 4
+                },
+                &mut StdRng::seed_from_u64(0)
+            ),
+            Err(TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn test_drop() {
+        let dice = |selector, n| {
+            SelectedDice::Selected(
+                FilteredDice::Simple(Dice {
+                    throws: 4,
+                    dice: DiceType::Number(6),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None,
+                }),
+                selector,
+                n,
+            )
+        };
+        for seed in 0..16 {
+            let (kept, mut all) = dice(Selector::DropLower, 1)
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            all.sort_unstable();
+            assert_eq!(kept, all[1..].to_vec());
+            let (kept, mut all) = dice(Selector::DropHigher, 3)
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            all.sort_unstable();
+            assert_eq!(kept, all[..1].to_vec());
+            let (kept, _) = dice(Selector::DropLower, 5)
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert!(kept.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_selection_boundary() {
+        let details = |input: &str, seed| {
+            let expr = crate::parser::parse(input).unwrap();
+            let mut results = expr
+                .evaluate_detailed(
+                    &mut || false,
+                    &mut EvaluationLimits::unlimited(),
+                    &mut StdRng::seed_from_u64(seed),
+                )
+                .unwrap();
+            results.pop().unwrap()
+        };
+        for seed in 0..16 {
+            // asking for more dice than there are keeps all of them
+            let ((total, all), selected) = details("4d6h10", seed);
+            assert_eq!(total, all.iter().sum::<i64>());
+            assert_eq!(
+                selected.selections,
+                vec![Selection {
+                    selector: Selector::Higher,
+                    count: 10,
+                    available: 4
+                }]
+            );
+            assert!(selected.selections[0].keeps_all());
+            assert!(selected
+                .outcomes
+                .iter()
+                .all(|o| matches!(o, DieOutcome::Kept(_))));
+            // nothing passes the filter, so nothing is selected
+            let ((total, all), selected) = details("4d6>6h2", seed);
+            assert_eq!(total, 0);
+            assert_eq!(all.len(), 4);
+            assert_eq!(selected.selections[0].available, 0);
+            assert!(selected.selections[0].keeps_all());
+            let ((total, _), selected) = details("3d6dl3", seed);
+            assert_eq!(total, 0);
+            assert!(!selected.selections[0].keeps_all());
+            let ((_, _), selected) = details("4d6h3", seed);
+            assert!(!selected.selections[0].keeps_all());
+            assert!(details("4d6 + 2", seed).1.selections.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_function() {
+        let roll = |input: &str, seed| {
+            crate::parser::parse_term(input)
+                .unwrap()
+                .1
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+        };
+        for seed in 0..16 {
+            let (total, all) = roll("min(2d6, 8)", seed).unwrap();
+            assert_eq!(all.len(), 2);
+            assert_eq!(total, all.iter().sum::<i64>().min(8));
+            // the dice of all arguments are listed in order
+            let (total, all) = roll("max(1d4, 2, 1d6)", seed).unwrap();
+            assert_eq!(all.len(), 2);
+            assert!((1..=4).contains(&all[0]) && (1..=6).contains(&all[1]));
+            assert_eq!(total, all[0].max(2).max(all[1]));
+        }
+        assert_eq!(roll("max(3)", 0), Ok((3, vec![])));
+        assert_eq!(roll("min(1, 1/0)", 0), Err(EvaluationErrors::DivideByZero));
+        assert_eq!(
+            Term::Function(Func::Max, vec![])
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(0)),
+            Err(EvaluationErrors::Internal)
+        );
+    }
+
+    #[test]
+    fn test_negate() {
+        let roll = |input: &str, seed| {
+            crate::parser::parse_term(input)
+                .unwrap()
+                .1
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(seed))
+        };
+        for seed in 0..16 {
+            let (total, all) = roll("-(1d4 + 2)", seed).unwrap();
+            assert_eq!(total, -(all[0] + 2));
+            // the dice keep the faces they showed
+            assert!((1..=4).contains(&all[0]));
+        }
+        assert_eq!(roll("-(-3)", 0), Ok((3, vec![])));
+        assert_eq!(
+            roll("-(-9223372036854775807 - 1)", 0),
+            Err(EvaluationErrors::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        let value = |input: &str| {
+            crate::parser::parse_term(input)
+                .unwrap()
+                .1
+                .evaluate(&mut || false, &mut StdRng::seed_from_u64(0))
+                .map(|(value, _)| value)
+        };
+        for (input, floor, ceil, round) in [
+            ("7 / 2", 3, 4, 4),
+            ("-7 / 2", -4, -3, -3),
+            ("7 / -2", -4, -3, -3),
+            ("-7 / -2", 3, 4, 4),
+            ("5 / 3", 1, 2, 2),
+            ("-5 / 3", -2, -1, -2),
+            ("6 / 3", 2, 2, 2),
+            ("-6 / 3", -2, -2, -2),
+            ("0 / -4", 0, 0, 0),
+        ] {
+            assert_eq!(value(&format!("floor({})", input)), Ok(floor), "{}", input);
+            assert_eq!(value(&format!("ceil({})", input)), Ok(ceil), "{}", input);
+            assert_eq!(value(&format!("round({})", input)), Ok(round), "{}", input);
+        }
+        // only the division directly inside is rounded
+        assert_eq!(value("7 / 2"), Ok(3));
+        assert_eq!(value("-7 / 2"), Ok(-3));
+        assert_eq!(value("floor((-7 / 2))"), Ok(-4));
+        assert_eq!(value("floor(-7 / 2 + 1)"), Ok(-2));
+        assert_eq!(value("ceil(4)"), Ok(4));
+        assert_eq!(value("round(1 / 0)"), Err(EvaluationErrors::DivideByZero));
+        assert_eq!(
+            value("floor((-9223372036854775807 - 1) / -1)"),
+            Err(EvaluationErrors::Overflow)
+        );
+        let (total, all) = crate::parser::parse_term("round(2d6 / 2)")
+            .unwrap()
+            .1
+            .evaluate(&mut || false, &mut StdRng::seed_from_u64(1))
+            .unwrap();
+        assert_eq!(total, (all.iter().sum::<i64>() + 1) / 2);
+    }
+
+    #[test]
+    fn test_list_timeout() {
+        let expr: Expression = "10{2d6}".parse().unwrap();
+        // every result checks for the timeout once for each group of dice
+        let mut calls = 0;
+        assert_eq!(
+            expr.evaluate(
+                &mut || {
+                    calls += 1;
+                    calls > 4
+                },
+                &mut StdRng::seed_from_u64(0)
+            ),
+            Err(EvaluationErrors::Timeout {
+                completed: 4,
+                total: 10
+            })
+        );
+        let expr: Expression = "2d6 + 1d4".parse().unwrap();
+        assert_eq!(
+            expr.evaluate(&mut || true, &mut StdRng::seed_from_u64(0)),
+            Err(TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn test_explode_timeout() {
+        let dice = Dice {
+            throws: 1,
+            dice: DiceType::Number(1),
+            explode: Some(Explode::Standard),
+            dynamic_throws: None,
+            reroll: None,
+        };
+        let mut calls = 0;
+        assert_eq!(
+            dice.evaluate(
+                &mut || {
+                    calls += 1;
+                    calls > 4
+                },
+                &mut StdRng::seed_from_u64(0)
+            ),
+            Err(TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn test_narrative_roll() {
+        let pool = NarrativePool(vec![
+            (3, NarrativeDie::Ability),
+            (2, NarrativeDie::Challenge),
+        ]);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..64 {
+            let roll = pool.roll(&mut rng);
+            assert_eq!(roll.0.len(), 5);
+            assert!(roll.0[..3]
+                .iter()
+                .all(|(die, face)| *die == NarrativeDie::Ability
+                    && NarrativeDie::Ability.faces().contains(face)));
+            assert!(roll.0[3..]
+                .iter()
+                .all(|(die, face)| *die == NarrativeDie::Challenge
+                    && NarrativeDie::Challenge.faces().contains(face)));
+        }
+    }
+
+    #[test]
+    fn test_narrative_tally() {
+        use Symbol::*;
+        let roll = NarrativeRoll(vec![
+            (NarrativeDie::Proficiency, &[Triumph]),
+            (NarrativeDie::Ability, &[Success, Advantage]),
+            (NarrativeDie::Difficulty, &[Threat, Threat]),
+            (NarrativeDie::Challenge, &[Despair]),
+            (NarrativeDie::Setback, &[Failure]),
+            (NarrativeDie::Difficulty, &[Failure, Failure]),
+        ]);
+        let tally = roll.tally();
+        assert_eq!(
+            tally,
+            Tally {
+                successes: -2,
+                advantages: -1,
+                triumphs: 1,
+                despairs: 1
             }
+        );
+        assert!(!tally.succeeded());
+        assert_eq!(
+            tally.to_string(),
+            "2 failures, 1 threat, 1 triumph, 1 despair"
+        );
+        assert_eq!(NarrativeRoll(vec![]).tally().to_string(), "no symbols");
+    }
+
+    #[test]
+    fn test_die_outcomes() {
+        let dice = |throws| Dice {
+            throws,
+            dice: DiceType::Number(6),
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        };
+        let term = Term::Calculation(
+            Box::new(Term::DiceThrow(SelectedDice::Selected(
+                FilteredDice::Filtered(dice(6), Filter::Bigger, 1),
+                Selector::Higher,
+                3,
+            ))),
+            Operation::Add,
+            Box::new(Term::DiceThrow(SelectedDice::Unchanged(
+                FilteredDice::Simple(dice(1)),
+            ))),
+        );
+        let expr = Expression::Simple(term);
+        for seed in 0..64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let results = expr
+                .evaluate_detailed(&mut || false, &mut EvaluationLimits::unlimited(), &mut rng)
+                .unwrap();
+            let ((total, all_rolls), details) = &results[0];
+            let outcomes = &details.outcomes;
+            assert_eq!(
+                outcomes.iter().map(DieOutcome::value).collect::<Vec<_>>(),
+                *all_rolls
+            );
+            assert!(outcomes[..6].iter().all(|o| match o {
+                DieOutcome::Filtered(v) => *v == 1,
+                _ => o.value() > 1,
+            }));
+            let kept: Vec<i64> = outcomes[..6]
+                .iter()
+                .filter_map(|o| match o {
+                    DieOutcome::Kept(v) => Some(*v),
+                    _ => None,
+                })
+                .collect();
+            assert!(kept.len() <= 3);
+            // no dropped die is higher than a kept one
+            assert!(outcomes[..6].iter().all(|o| match o {
+                DieOutcome::Dropped(v) => kept.len() == 3 && kept.iter().all(|k| k >= v),
+                _ => true,
+            }));
+            assert!(matches!(outcomes[6], DieOutcome::Kept(_)));
+            assert_eq!(*total, kept.iter().sum::<i64>() + outcomes[6].value());
         }
     }
 }
@@ -14,17 +14,68 @@ Copyright 2021 Robin Marchart
    limitations under the License.
 */
 
-use std::fmt::{self, Debug};
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "StoredDiceType"))]
 pub enum DiceType {
     Number(u32),
-    Fudge,
+    /// faces from `-sides` to `sides`, `dF` is the same as `dF.1`
+    Fudge {
+        sides: u8,
+    },
+    Multiply(u32),
+    /// die with as many faces as the term evaluates to
+    Dynamic(Box<Term>),
+}
+
+/// fudge die of `dF`
+pub const DEFAULT_FUDGE_SIDES: u8 = 1;
+
+/// [DiceType] as it is stored, fudge dice were a unit variant before they had sides
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredDiceType {
+    Current(CurrentDiceType),
+    Unsided(UnsidedFudge),
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+enum CurrentDiceType {
+    Number(u32),
+    Fudge { sides: u8 },
     Multiply(u32),
+    Dynamic(Box<Term>),
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+enum UnsidedFudge {
+    Fudge,
+}
+
+#[cfg(feature = "serde")]
+impl From<StoredDiceType> for DiceType {
+    fn from(stored: StoredDiceType) -> Self {
+        match stored {
+            StoredDiceType::Current(CurrentDiceType::Number(n)) => DiceType::Number(n),
+            StoredDiceType::Current(CurrentDiceType::Fudge { sides }) => DiceType::Fudge { sides },
+            StoredDiceType::Current(CurrentDiceType::Multiply(n)) => DiceType::Multiply(n),
+            StoredDiceType::Current(CurrentDiceType::Dynamic(t)) => DiceType::Dynamic(t),
+            StoredDiceType::Unsided(UnsidedFudge::Fudge) => DiceType::Fudge {
+                sides: DEFAULT_FUDGE_SIDES,
+            },
+        }
+    }
 }
 
 impl fmt::Display for DiceType {
@@ -33,26 +84,98 @@ impl fmt::Display for DiceType {
             DiceType::Number(n) => {
                 write!(f, "d{}", n)
             }
-            DiceType::Fudge => {
+            DiceType::Fudge {
+                sides: DEFAULT_FUDGE_SIDES,
+            } => {
                 write!(f, "dF")
             }
+            DiceType::Fudge { sides } => {
+                write!(f, "dF.{}", sides)
+            }
             DiceType::Multiply(n) => {
                 write!(f, "d{}x", n)
             }
+            DiceType::Dynamic(t) => {
+                write!(f, "d({})", t)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Explode {
+    Standard,
+    Compound,
+}
+
+impl fmt::Display for Explode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Explode::Standard => {
+                write!(f, "!")
+            }
+            Explode::Compound => {
+                write!(f, "!!")
+            }
         }
     }
 }
 
+/// rerolling of dice that show a value kept by the filter
+///
+/// every roll of a die is rerolled before it is checked for explosions, so
+/// exploding dice explode on their rerolled value and the additional dice of
+/// an explosion are rerolled as well
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Reroll {
+    /// reroll a single time and keep the new value
+    Once(Filter, u32),
+    /// reroll until the value is no longer kept by the filter
+    Recursive(Filter, u32),
+}
+
+impl fmt::Display for Reroll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reroll::Once(filter, target) => {
+                write!(f, "ro{}{}", filter, target)
+            }
+            Reroll::Recursive(filter, target) => {
+                write!(f, "rr{}{}", filter, target)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Dice {
     pub throws: u32,
     pub dice: DiceType,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub explode: Option<Explode>,
+    /// number of throws as a term, `throws` is ignored if this is set
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dynamic_throws: Option<Box<Term>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reroll: Option<Reroll>,
 }
 
 impl fmt::Display for Dice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}", self.throws, self.dice)
+        match &self.dynamic_throws {
+            Some(throws) => write!(f, "({}){}", throws, self.dice)?,
+            None => write!(f, "{}{}", self.throws, self.dice)?,
+        }
+        if let Some(explode) = self.explode {
+            write!(f, "{}", explode)?;
+        }
+        if let Some(reroll) = self.reroll {
+            write!(f, "{}", reroll)?;
+        }
+        Ok(())
     }
 }
 
@@ -66,6 +189,20 @@ pub enum Filter {
     NotEq,
 }
 
+impl Filter {
+    /// whether a die showing `value` is kept by this filter with `target`
+    pub fn matches(&self, value: i64, target: u32) -> bool {
+        let target = i64::from(target);
+        match self {
+            Filter::Bigger => value > target,
+            Filter::BiggerEq => value >= target,
+            Filter::Smaller => value < target,
+            Filter::SmallerEq => value <= target,
+            Filter::NotEq => value != target,
+        }
+    }
+}
+
 impl fmt::Display for Filter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -88,7 +225,7 @@ impl fmt::Display for Filter {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FilteredDice {
     Simple(Dice),
@@ -113,6 +250,8 @@ impl fmt::Display for FilteredDice {
 pub enum Selector {
     Higher,
     Lower,
+    DropHigher,
+    DropLower,
 }
 
 impl fmt::Display for Selector {
@@ -124,11 +263,17 @@ impl fmt::Display for Selector {
             Selector::Lower => {
                 write!(f, "l")
             }
+            Selector::DropHigher => {
+                write!(f, "dh")
+            }
+            Selector::DropLower => {
+                write!(f, "dl")
+            }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SelectedDice {
     Unchanged(FilteredDice),
@@ -148,11 +293,55 @@ impl fmt::Display for SelectedDice {
     }
 }
 
+/// a selector applied to rolled dice, with the number of dice it chose from
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Selection {
+    pub selector: Selector,
+    /// the number given to the selector, like 3 for `h3`
+    pub count: u32,
+    /// dice left after filtering
+    pub available: usize,
+}
+
+impl Selection {
+    /// the selector kept every available die, like `4d6h10`
+    pub fn keeps_all(&self) -> bool {
+        match self.selector {
+            Selector::Higher | Selector::Lower => self.count as usize >= self.available,
+            Selector::DropHigher | Selector::DropLower => self.count == 0 || self.available == 0,
+        }
+    }
+}
+
+/// what happened to a single rolled die
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DieOutcome {
+    /// the die counts towards the result
+    Kept(i64),
+    /// the die was removed by a selector like `k3`
+    Dropped(i64),
+    /// the die was removed by a filter like `>3`
+    Filtered(i64),
+}
+
+impl DieOutcome {
+    /// the face the die showed
+    pub fn value(&self) -> i64 {
+        match self {
+            DieOutcome::Kept(v) | DieOutcome::Dropped(v) | DieOutcome::Filtered(v) => *v,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Operation {
     Mul,
     Div,
+    Mod,
+    Pow,
     Add,
     Sub,
 }
@@ -166,6 +355,12 @@ impl fmt::Display for Operation {
             Operation::Div => {
                 write!(f, "/")
             }
+            Operation::Mod => {
+                write!(f, "%")
+            }
+            Operation::Pow => {
+                write!(f, "^")
+            }
             Operation::Add => {
                 write!(f, "+")
             }
@@ -176,13 +371,67 @@ impl fmt::Display for Operation {
     }
 }
 
+/// function of the values of its arguments
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Func {
+    Min,
+    Max,
+    /// rounds a division `a / b` of its single argument down, any other argument is kept as it is
+    Floor,
+    /// rounds a division up
+    Ceil,
+    /// rounds a division to the nearest integer, halves are rounded up, so -2.5 becomes -2
+    Round,
+}
+
+impl Func {
+    /// whether the function takes exactly one argument, the others take any number of them
+    pub fn unary(&self) -> bool {
+        matches!(self, Func::Floor | Func::Ceil | Func::Round)
+    }
+}
+
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Func::Min => {
+                write!(f, "min")
+            }
+            Func::Max => {
+                write!(f, "max")
+            }
+            Func::Floor => {
+                write!(f, "floor")
+            }
+            Func::Ceil => {
+                write!(f, "ceil")
+            }
+            Func::Round => {
+                write!(f, "round")
+            }
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Term {
     Constant(i64),
     DiceThrow(SelectedDice),
+    SuccessCount(FilteredDice),
     Calculation(Box<Term>, Operation, Box<Term>),
     SubTerm(Box<Term>),
+    /// term annotated with a `[comment]`, which doesn't change its value
+    Commented(Box<Term>, String),
+    /// term with a `#label`, its value is reported separately
+    Labeled(Box<Term>, String),
+    /// named value, that has to be provided when evaluating
+    Var(String),
+    /// `min(a, b)`, the dice of every argument are rolled
+    Function(Func, Vec<Term>),
+    /// `-1d6`, binds tighter than any operation
+    Negate(Box<Term>),
 }
 
 impl fmt::Display for Term {
@@ -194,16 +443,183 @@ impl fmt::Display for Term {
             Term::DiceThrow(d) => {
                 write!(f, "{}", d)
             }
+            Term::SuccessCount(d) => {
+                write!(f, "{}s", d)
+            }
             Term::Calculation(l, op, r) => {
                 write!(f, "{} {} {}", l, op, r)
             }
             Term::SubTerm(t) => {
                 write!(f, "({})", t)
             }
+            Term::Commented(t, c) => {
+                write!(f, "{} [{}]", t, c)
+            }
+            Term::Labeled(t, l) => {
+                write!(f, "{}#{}", t, l)
+            }
+            Term::Var(name) => {
+                write!(f, "{}", name)
+            }
+            Term::Function(func, args) => {
+                write!(f, "{}(", func)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Term::Negate(t) => {
+                write!(f, "-{}", t)
+            }
         }
     }
 }
 
+impl Dice {
+    /// terms deciding the number of throws and faces of dynamic dice
+    fn terms(&self) -> impl Iterator<Item = &Term> {
+        let faces = match &self.dice {
+            DiceType::Dynamic(t) => Some(t.as_ref()),
+            _ => None,
+        };
+        self.dynamic_throws.as_deref().into_iter().chain(faces)
+    }
+
+    fn terms_mut(&mut self) -> impl Iterator<Item = &mut Term> {
+        let faces = match &mut self.dice {
+            DiceType::Dynamic(t) => Some(t.as_mut()),
+            _ => None,
+        };
+        self.dynamic_throws.as_deref_mut().into_iter().chain(faces)
+    }
+}
+
+impl FilteredDice {
+    fn dice(&self) -> &Dice {
+        match self {
+            FilteredDice::Simple(d) | FilteredDice::Filtered(d, _, _) => d,
+        }
+    }
+
+    fn dice_mut(&mut self) -> &mut Dice {
+        match self {
+            FilteredDice::Simple(d) | FilteredDice::Filtered(d, _, _) => d,
+        }
+    }
+}
+
+impl SelectedDice {
+    fn dice(&self) -> &Dice {
+        match self {
+            SelectedDice::Unchanged(d) | SelectedDice::Selected(d, _, _) => d.dice(),
+        }
+    }
+
+    fn dice_mut(&mut self) -> &mut Dice {
+        match self {
+            SelectedDice::Unchanged(d) | SelectedDice::Selected(d, _, _) => d.dice_mut(),
+        }
+    }
+}
+
+impl Term {
+    /// calls `f` on every variable of this term, including those deciding dynamic dice
+    fn visit_vars_mut<E, F: FnMut(&mut Term) -> Result<(), E>>(
+        &mut self,
+        f: &mut F,
+    ) -> Result<(), E> {
+        match self {
+            Term::Constant(_) => Ok(()),
+            Term::Var(_) => f(self),
+            Term::DiceThrow(d) => d
+                .dice_mut()
+                .terms_mut()
+                .try_for_each(|t| t.visit_vars_mut(f)),
+            Term::SuccessCount(d) => d
+                .dice_mut()
+                .terms_mut()
+                .try_for_each(|t| t.visit_vars_mut(f)),
+            Term::Calculation(l, _, r) => {
+                l.visit_vars_mut(f)?;
+                r.visit_vars_mut(f)
+            }
+            Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) | Term::Negate(t) => {
+                t.visit_vars_mut(f)
+            }
+            Term::Function(_, args) => args.iter_mut().try_for_each(|t| t.visit_vars_mut(f)),
+        }
+    }
+
+    fn collect_vars<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Term::Constant(_) => {}
+            Term::Var(name) => names.push(name),
+            Term::DiceThrow(d) => d.dice().terms().for_each(|t| t.collect_vars(names)),
+            Term::SuccessCount(d) => d.dice().terms().for_each(|t| t.collect_vars(names)),
+            Term::Calculation(l, _, r) => {
+                l.collect_vars(names);
+                r.collect_vars(names)
+            }
+            Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) | Term::Negate(t) => {
+                t.collect_vars(names)
+            }
+            Term::Function(_, args) => args.iter().for_each(|t| t.collect_vars(names)),
+        }
+    }
+
+    /// every group of dice in the order they are rolled, see [Expression::dice_iter]
+    pub fn dice_iter(&self) -> impl Iterator<Item = &Dice> {
+        let mut dice = Vec::new();
+        self.collect_dice(&mut dice);
+        dice.into_iter()
+    }
+
+    /// dice of this term in the order they are rolled, the dice deciding the throws and faces of
+    /// dynamic dice come before those dice
+    fn collect_dice<'a>(&'a self, dice: &mut Vec<&'a Dice>) {
+        match self {
+            Term::Constant(_) | Term::Var(_) => {}
+            Term::DiceThrow(d) => {
+                d.dice().terms().for_each(|t| t.collect_dice(dice));
+                dice.push(d.dice());
+            }
+            Term::SuccessCount(d) => {
+                d.dice().terms().for_each(|t| t.collect_dice(dice));
+                dice.push(d.dice());
+            }
+            Term::Calculation(l, _, r) => {
+                l.collect_dice(dice);
+                r.collect_dice(dice)
+            }
+            Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) | Term::Negate(t) => {
+                t.collect_dice(dice)
+            }
+            Term::Function(_, args) => args.iter().for_each(|t| t.collect_dice(dice)),
+        }
+    }
+
+    /// names of all variables of this term in order of appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_vars(&mut names);
+        names
+    }
+
+    /// replaces every variable with its value in `vars`, fails with the name of the
+    /// first variable without a value
+    pub fn substitute(&mut self, vars: &HashMap<String, i64>) -> Result<(), String> {
+        self.visit_vars_mut(&mut |t| {
+            if let Term::Var(name) = t {
+                *t = Term::Constant(*vars.get(name.as_str()).ok_or_else(|| name.clone())?);
+            }
+            Ok(())
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Expression {
@@ -211,6 +627,36 @@ pub enum Expression {
     List(u32, Term),
 }
 
+impl Expression {
+    pub fn term(&self) -> &Term {
+        match self {
+            Expression::Simple(t) | Expression::List(_, t) => t,
+        }
+    }
+
+    pub fn term_mut(&mut self) -> &mut Term {
+        match self {
+            Expression::Simple(t) | Expression::List(_, t) => t,
+        }
+    }
+
+    /// every group of dice in the order they are rolled, the dice of a list only once
+    ///
+    /// ```
+    /// use robins_dice_roll::{DiceType, Expression};
+    ///
+    /// let expr: Expression = "1d20 + (1d4)d6".parse().unwrap();
+    /// let faces: Vec<&DiceType> = expr.dice_iter().map(|d| &d.dice).collect();
+    /// assert_eq!(
+    ///     faces,
+    ///     [&DiceType::Number(20), &DiceType::Number(4), &DiceType::Number(6)]
+    /// );
+    /// ```
+    pub fn dice_iter(&self) -> impl Iterator<Item = &Dice> {
+        self.term().dice_iter()
+    }
+}
+
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -243,3 +689,238 @@ impl fmt::Display for LabeledExpression {
         }
     }
 }
+
+/// most dice a single narrative pool may contain
+pub const MAX_NARRATIVE_DICE: u32 = 100;
+
+/// symbol on the face of a narrative die
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Symbol {
+    Success,
+    Failure,
+    Advantage,
+    Threat,
+    /// counts as a success, too
+    Triumph,
+    /// counts as a failure, too
+    Despair,
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Symbol::Success => write!(f, "success"),
+            Symbol::Failure => write!(f, "failure"),
+            Symbol::Advantage => write!(f, "advantage"),
+            Symbol::Threat => write!(f, "threat"),
+            Symbol::Triumph => write!(f, "triumph"),
+            Symbol::Despair => write!(f, "despair"),
+        }
+    }
+}
+
+/// die of narrative dice systems, showing symbols instead of numbers
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NarrativeDie {
+    Boost,
+    Setback,
+    Ability,
+    Difficulty,
+    Proficiency,
+    Challenge,
+}
+
+impl NarrativeDie {
+    /// symbols on each face of the die, blank faces are empty
+    pub fn faces(&self) -> &'static [&'static [Symbol]] {
+        use Symbol::*;
+        match self {
+            NarrativeDie::Boost => &[
+                &[],
+                &[],
+                &[Success],
+                &[Success, Advantage],
+                &[Advantage, Advantage],
+                &[Advantage],
+            ],
+            NarrativeDie::Setback => &[&[], &[], &[Failure], &[Failure], &[Threat], &[Threat]],
+            NarrativeDie::Ability => &[
+                &[],
+                &[Success],
+                &[Success],
+                &[Success, Success],
+                &[Advantage],
+                &[Advantage],
+                &[Success, Advantage],
+                &[Advantage, Advantage],
+            ],
+            NarrativeDie::Difficulty => &[
+                &[],
+                &[Failure],
+                &[Failure, Failure],
+                &[Threat],
+                &[Threat],
+                &[Threat],
+                &[Threat, Threat],
+                &[Failure, Threat],
+            ],
+            NarrativeDie::Proficiency => &[
+                &[],
+                &[Success],
+                &[Success],
+                &[Success, Success],
+                &[Success, Success],
+                &[Advantage],
+                &[Success, Advantage],
+                &[Success, Advantage],
+                &[Success, Advantage],
+                &[Advantage, Advantage],
+                &[Advantage, Advantage],
+                &[Triumph],
+            ],
+            NarrativeDie::Challenge => &[
+                &[],
+                &[Failure],
+                &[Failure],
+                &[Failure, Failure],
+                &[Failure, Failure],
+                &[Threat],
+                &[Threat],
+                &[Failure, Threat],
+                &[Failure, Threat],
+                &[Threat, Threat],
+                &[Threat, Threat],
+                &[Despair],
+            ],
+        }
+    }
+}
+
+impl fmt::Display for NarrativeDie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NarrativeDie::Boost => write!(f, "b"),
+            NarrativeDie::Setback => write!(f, "s"),
+            NarrativeDie::Ability => write!(f, "a"),
+            NarrativeDie::Difficulty => write!(f, "d"),
+            NarrativeDie::Proficiency => write!(f, "p"),
+            NarrativeDie::Challenge => write!(f, "c"),
+        }
+    }
+}
+
+/// numbers of narrative dice rolled together, like `2a1p2d`
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NarrativePool(pub Vec<(u32, NarrativeDie)>);
+
+impl NarrativePool {
+    /// total number of dice in the pool
+    pub fn size(&self) -> u32 {
+        self.0
+            .iter()
+            .fold(0u32, |size, (n, _)| size.saturating_add(*n))
+    }
+}
+
+impl fmt::Display for NarrativePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, die) in &self.0 {
+            write!(f, "{}{}", n, die)?;
+        }
+        Ok(())
+    }
+}
+
+/// faces shown by the dice of a narrative pool
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NarrativeRoll(pub Vec<(NarrativeDie, &'static [Symbol])>);
+
+impl NarrativeRoll {
+    /// symbols of all dice with opposing ones cancelled
+    pub fn tally(&self) -> Tally {
+        let mut tally = Tally::default();
+        for symbol in self.0.iter().flat_map(|(_, face)| face.iter()) {
+            match symbol {
+                Symbol::Success => tally.successes += 1,
+                Symbol::Failure => tally.successes -= 1,
+                Symbol::Advantage => tally.advantages += 1,
+                Symbol::Threat => tally.advantages -= 1,
+                Symbol::Triumph => {
+                    tally.successes += 1;
+                    tally.triumphs += 1
+                }
+                Symbol::Despair => {
+                    tally.successes -= 1;
+                    tally.despairs += 1
+                }
+            }
+        }
+        tally
+    }
+}
+
+/// net symbols of a narrative roll
+///
+/// successes cancel failures and advantages cancel threats, negative values
+/// are the remaining failures and threats. Triumphs and despairs are never
+/// cancelled, but are included in the successes and failures as well
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Tally {
+    pub successes: i64,
+    pub advantages: i64,
+    pub triumphs: u32,
+    pub despairs: u32,
+}
+
+impl Tally {
+    /// whether at least one success remains
+    pub fn succeeded(&self) -> bool {
+        self.successes > 0
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // singular and plural of the symbol counted by positive and negative values
+        let counted = |n: i64, positive: (&str, &str), negative: (&str, &str)| {
+            let (count, (singular, plural)) = match n {
+                0 => return None,
+                n if n > 0 => (n, positive),
+                n => (-n, negative),
+            };
+            Some(format!(
+                "{} {}",
+                count,
+                if count == 1 { singular } else { plural }
+            ))
+        };
+        let triumph = ("triumph", "triumphs");
+        let despair = ("despair", "despairs");
+        let parts: Vec<String> = [
+            counted(
+                self.successes,
+                ("success", "successes"),
+                ("failure", "failures"),
+            ),
+            counted(
+                self.advantages,
+                ("advantage", "advantages"),
+                ("threat", "threats"),
+            ),
+            counted(self.triumphs.into(), triumph, triumph),
+            counted(self.despairs.into(), despair, despair),
+        ]
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+        if parts.is_empty() {
+            write!(f, "no symbols")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
@@ -17,6 +17,8 @@ Copyright 2021 Robin Marchart
 pub mod dice_types;
 pub use dice_types::*;
 
+pub mod builder;
+
 #[cfg(feature = "roll")]
 pub mod dice_roll;
 
@@ -24,3 +26,6 @@ pub mod dice_roll;
 pub mod parser;
 
 pub mod limits;
+
+#[cfg(feature = "stats")]
+pub mod stats;
@@ -5,31 +5,57 @@ pub trait DiceLimits {
 
 use crate::dice_types::*;
 
+/// largest face count a dynamic die may roll to
+pub const MAX_DYNAMIC_FACES: i64 = u32::MAX as i64;
+
+/// largest number of throws a dynamic throw count may roll to
+pub const MAX_DYNAMIC_THROWS: i64 = 10_000;
+
 impl DiceLimits for DiceType {
     fn min(&self) -> i64 {
         match self {
             DiceType::Number(_) => 1,
-            DiceType::Fudge => -1,
+            DiceType::Fudge { sides } => -i64::from(*sides),
             DiceType::Multiply(_) => 1,
+            DiceType::Dynamic(_) => 1,
         }
     }
 
     fn max(&self) -> i64 {
         match self {
             DiceType::Number(n) => (*n).into(),
-            DiceType::Fudge => 1,
+            DiceType::Fudge { sides } => (*sides).into(),
             DiceType::Multiply(n) => i64::from(*n) * i64::from(*n),
+            DiceType::Dynamic(_) => MAX_DYNAMIC_FACES,
         }
     }
 }
 
+/// upper bound of a single die, exploding dice are unbounded
+fn single_max(dice: &Dice) -> i64 {
+    if dice.explode.is_some() {
+        i64::MAX
+    } else {
+        dice.dice.max()
+    }
+}
+
+/// bounds of the number of throws of `dice`
+fn throws_range(dice: &Dice) -> (i64, i64) {
+    match dice.dynamic_throws {
+        Some(_) => (0, MAX_DYNAMIC_THROWS),
+        None => (i64::from(dice.throws), i64::from(dice.throws)),
+    }
+}
+
 impl DiceLimits for Dice {
     fn min(&self) -> i64 {
-        i64::from(self.throws) * self.dice.min()
+        let (low, high) = throws_range(self);
+        (low * self.dice.min()).min(high * self.dice.min())
     }
 
     fn max(&self) -> i64 {
-        i64::from(self.throws) * self.dice.max()
+        throws_range(self).1.saturating_mul(single_max(self))
     }
 }
 impl DiceLimits for FilteredDice {
@@ -47,18 +73,24 @@ impl DiceLimits for FilteredDice {
         }
     }
 }
+/// number of dice left after applying `selector` with `n` to `dice`
+fn selected_count(dice: &Dice, selector: &Selector, n: u32) -> i64 {
+    match selector {
+        Selector::Higher | Selector::Lower => i64::from(n),
+        Selector::DropHigher | Selector::DropLower => (throws_range(dice).1 - i64::from(n)).max(0),
+    }
+}
+
 impl DiceLimits for SelectedDice {
     fn min(&self) -> i64 {
         match self {
             SelectedDice::Unchanged(d) => d.min(),
-            SelectedDice::Selected(d, _, n) => {
-                match d {
+            SelectedDice::Selected(d, s, n) => {
+                let dc = match d {
                     FilteredDice::Simple(dc) => dc,
                     FilteredDice::Filtered(dc, _, _) => dc,
-                }
-                .dice
-                .min()
-                    * i64::from(*n)
+                };
+                dc.dice.min() * selected_count(dc, s, *n)
             }
         }
     }
@@ -66,15 +98,68 @@ impl DiceLimits for SelectedDice {
     fn max(&self) -> i64 {
         match self {
             SelectedDice::Unchanged(d) => d.max(),
-            SelectedDice::Selected(d, _, n) => {
-                match d {
+            SelectedDice::Selected(d, s, n) => {
+                let dc = match d {
                     FilteredDice::Simple(dc) => dc,
                     FilteredDice::Filtered(dc, _, _) => dc,
-                }
-                .dice
-                .max()
-                    * i64::from(*n)
+                };
+                single_max(dc).saturating_mul(selected_count(dc, s, *n))
             }
         }
     }
 }
+
+impl Expression {
+    /// most dice the expression throws to begin with, dynamic throw counts count as
+    /// [MAX_DYNAMIC_THROWS]
+    ///
+    /// the dice added by rerolls and explosions aren't known in advance, so they aren't counted
+    pub fn max_total_dice(&self) -> u64 {
+        let single = self
+            .dice_iter()
+            .map(|dice| throws_range(dice).1 as u64)
+            .fold(0u64, u64::saturating_add);
+        match self {
+            Expression::Simple(_) => single,
+            Expression::List(count, _) => single.saturating_mul(u64::from(*count)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_total_dice() {
+        let max = |input: &str| input.parse::<Expression>().unwrap().max_total_dice();
+        assert_eq!(max("3"), 0);
+        assert_eq!(max("1d20 + 5"), 1);
+        assert_eq!(max("4d6k3 + (2d8 - (3d4!)) * 10d10>=8s"), 19);
+        assert_eq!(max("max(2d6, 1d8) [bonus]"), 3);
+        assert_eq!(max("6{4d6k3}"), 24);
+        assert_eq!(max("3{1d6 + (2d4)}"), 9);
+        // the dice deciding the throws are thrown as well
+        assert_eq!(max("(1d4)d6"), 1 + MAX_DYNAMIC_THROWS as u64);
+        assert_eq!(max("2d(1d6)"), 3);
+    }
+
+    #[test]
+    fn test_dice_iter() {
+        let faces = |input: &str| {
+            input
+                .parse::<Expression>()
+                .unwrap()
+                .dice_iter()
+                .map(|d| d.dice.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            faces("1d20 + ((2d8 - 3d4) * -1d10)"),
+            ["d20", "d8", "d4", "d10"]
+        );
+        assert_eq!(faces("4{min(1d100, 1d6>3s)#low}"), ["d100", "d6"]);
+        assert_eq!(faces("(1d4)d(2d6)"), ["d4", "d6", "d(2d6)"]);
+        assert!(faces("1 + 2").is_empty());
+    }
+}
@@ -16,7 +16,9 @@ Copyright 2021 Robin Marchart
 
 use crate::{
     dice_types::{
-        Dice, DiceType, Expression, Filter, FilteredDice, Operation, SelectedDice, Selector, Term,
+        Dice, DiceType, Explode, Expression, Filter, FilteredDice, Func, NarrativeDie,
+        NarrativePool, Operation, Reroll, SelectedDice, Selector, Term, DEFAULT_FUDGE_SIDES,
+        MAX_NARRATIVE_DICE,
     },
     LabeledExpression,
 };
@@ -25,12 +27,13 @@ use nom::{
     branch::alt,
     bytes::complete::{tag, tag_no_case},
     character::complete::{digit1, multispace0, satisfy},
-    combinator::{map, map_res, opt, recognize, success, verify},
+    combinator::{eof, map, map_res, not, opt, peek, recognize, success, verify},
     error::context,
-    multi::{many0, many1},
+    multi::{fold_many0, many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
+use std::{fmt, str::FromStr};
 
 pub fn parse_dice_digit(input: &str) -> IResult<&str, &str> {
     alt((tag_no_case("d"), tag_no_case("w")))(input)
@@ -43,8 +46,25 @@ pub fn parse_dice_type(input: &str) -> IResult<&str, DiceType> {
             DiceType::Multiply,
         ),
         map(parse_u32, DiceType::Number),
-        map(tag_no_case("f"), |_| DiceType::Fudge),
+        map(
+            preceded(
+                tag_no_case("f"),
+                opt(preceded(
+                    tag("."),
+                    context(
+                        "Failed to parse fudge sides between 1 and 255 inclusive",
+                        verify(map_res(digit1, |s: &str| s.parse::<u8>()), |s| *s > 0),
+                    ),
+                )),
+            ),
+            |sides| DiceType::Fudge {
+                sides: sides.unwrap_or(DEFAULT_FUDGE_SIDES),
+            },
+        ),
         map(tag("%"), |_| DiceType::Number(100)),
+        map(parse_parenthesized, |faces| {
+            DiceType::Dynamic(Box::new(faces))
+        }),
     ))(input)
 }
 
@@ -65,19 +85,96 @@ pub fn parse_i64(input: &str) -> IResult<&str, i64> {
     )(input)
 }
 
-pub fn parse_dice(input: &str) -> IResult<&str, Dice> {
+pub fn parse_explode(input: &str) -> IResult<&str, Explode> {
+    alt((
+        map(terminated(tag("!!"), not(tag("="))), |_| Explode::Compound),
+        map(terminated(tag("!"), not(tag("="))), |_| Explode::Standard),
+    ))(input)
+}
+
+pub fn parse_reroll_once(input: &str) -> IResult<&str, Reroll> {
     map(
-        tuple((
+        preceded(
+            tag_no_case("ro"),
+            pair(delimited(multispace0, parse_filter, multispace0), parse_u32),
+        ),
+        |(filter, target)| Reroll::Once(filter, target),
+    )(input)
+}
+
+pub fn parse_reroll_recursive(input: &str) -> IResult<&str, Reroll> {
+    map(
+        preceded(
+            tag_no_case("rr"),
+            pair(delimited(multispace0, parse_filter, multispace0), parse_u32),
+        ),
+        |(filter, target)| Reroll::Recursive(filter, target),
+    )(input)
+}
+
+pub fn parse_reroll(input: &str) -> IResult<&str, Reroll> {
+    alt((parse_reroll_once, parse_reroll_recursive))(input)
+}
+
+/// everything of a dice after the number of throws
+type DiceKind = (DiceType, Option<Explode>, Option<Reroll>);
+
+fn parse_dice_kind(input: &str) -> IResult<&str, DiceKind> {
+    tuple((
+        preceded(parse_dice_digit, preceded(multispace0, parse_dice_type)),
+        opt(preceded(multispace0, parse_explode)),
+        opt(preceded(multispace0, parse_reroll)),
+    ))(input)
+}
+
+/// dice with a constant number of throws
+fn parse_fixed_dice(input: &str) -> IResult<&str, Dice> {
+    map(
+        pair(
             terminated(alt((parse_u32, success(1))), multispace0),
-            preceded(parse_dice_digit, preceded(multispace0, parse_dice_type)),
-        )),
-        |dice_params| Dice {
-            throws: dice_params.0,
-            dice: dice_params.1,
+            parse_dice_kind,
+        ),
+        |(throws, (dice, explode, reroll))| Dice {
+            throws,
+            dice,
+            explode,
+            dynamic_throws: None,
+            reroll,
         },
     )(input)
 }
 
+fn parse_parenthesized(input: &str) -> IResult<&str, Term> {
+    delimited(
+        tag("("),
+        delimited(multispace0, parse_term, multispace0),
+        tag(")"),
+    )(input)
+}
+
+fn dynamic_dice(throws: Term, (dice, explode, reroll): DiceKind) -> Dice {
+    Dice {
+        throws: 0,
+        dice,
+        explode,
+        dynamic_throws: Some(Box::new(throws)),
+        reroll,
+    }
+}
+
+pub fn parse_dice(input: &str) -> IResult<&str, Dice> {
+    alt((
+        parse_fixed_dice,
+        map(
+            pair(
+                terminated(parse_parenthesized, multispace0),
+                parse_dice_kind,
+            ),
+            |(throws, kind)| dynamic_dice(throws, kind),
+        ),
+    ))(input)
+}
+
 pub fn parse_filter(input: &str) -> IResult<&str, Filter> {
     alt((
         map(tag(">="), |_| Filter::BiggerEq),
@@ -108,6 +205,9 @@ pub fn parse_selector(input: &str) -> IResult<&str, Selector> {
             Selector::Higher
         }),
         map(tag_no_case("l"), |_| Selector::Lower),
+        // a lone d is never a selector, only ever the start of dh or dl
+        map(tag_no_case("dh"), |_| Selector::DropHigher),
+        map(tag_no_case("dl"), |_| Selector::DropLower),
     ))(input)
 }
 
@@ -125,101 +225,362 @@ pub fn parse_selected_dice(input: &str) -> IResult<&str, SelectedDice> {
     ))(input)
 }
 
+/// a complete term, operations bind ^ before * / % before + -
 pub fn parse_term(input: &str) -> IResult<&str, Term> {
-    alt((
-        parse_term_calculation,
-        parse_term_roll,
-        parse_term_constant,
-        parse_term_subterm,
-    ))(input)
+    parse_sum(input)
 }
 
+/// signed integer, unless it is the number of throws of negated dice like `-1d6`
 pub fn parse_term_constant(input: &str) -> IResult<&str, Term> {
-    map(parse_i64, Term::Constant)(input)
-}
-
-pub fn parse_term_subterm(input: &str) -> IResult<&str, Term> {
     map(
-        delimited(
-            tag("("),
-            delimited(multispace0, parse_term, multispace0),
-            tag(")"),
-        ),
-        |subterm| Term::SubTerm(Box::new(subterm)),
+        terminated(parse_i64, not(preceded(multispace0, parse_dice_digit))),
+        Term::Constant,
     )(input)
 }
 
-pub fn parse_term_roll(input: &str) -> IResult<&str, Term> {
-    map(parse_selected_dice, Term::DiceThrow)(input)
+/// `-` in front of an atom, `-(1d4 + 2)`
+pub fn parse_term_negation(input: &str) -> IResult<&str, Term> {
+    map(preceded(pair(tag("-"), multispace0), parse_atom), |atom| {
+        Term::Negate(Box::new(atom))
+    })(input)
 }
 
-pub fn parse_operator(input: &str) -> IResult<&str, Operation> {
+/// name of a variable, a letter followed by letters, digits and `_`
+pub fn parse_term_var(input: &str) -> IResult<&str, Term> {
+    map(
+        recognize(pair(
+            satisfy(char::is_alphabetic),
+            many0(satisfy(|c| c.is_alphanumeric() || c == '_')),
+        )),
+        |name: &str| Term::Var(name.to_string()),
+    )(input)
+}
+
+pub fn parse_func(input: &str) -> IResult<&str, Func> {
     alt((
-        map(tag("+"), |_| Operation::Add),
-        map(tag("-"), |_| Operation::Sub),
-        map(tag("*"), |_| Operation::Mul),
-        map(tag("/"), |_| Operation::Div),
+        map(tag_no_case("min"), |_| Func::Min),
+        map(tag_no_case("max"), |_| Func::Max),
+        map(tag_no_case("floor"), |_| Func::Floor),
+        map(tag_no_case("ceil"), |_| Func::Ceil),
+        map(tag_no_case("round"), |_| Func::Round),
     ))(input)
 }
 
-pub fn parse_term_calculation(input: &str) -> IResult<&str, Term> {
+/// `min(2d6, 8)`, a function applied to one or more comma separated terms
+///
+/// `floor(a / b)` and the other unary functions take a single term
+pub fn parse_term_function(input: &str) -> IResult<&str, Term> {
+    map(
+        verify(
+            pair(
+                terminated(parse_func, pair(multispace0, tag("("))),
+                terminated(
+                    separated_list1(tag(","), delimited(multispace0, parse_term, multispace0)),
+                    tag(")"),
+                ),
+            ),
+            |(func, args): &(Func, Vec<Term>)| !func.unary() || args.len() == 1,
+        ),
+        |(func, args)| Term::Function(func, args),
+    )(input)
+}
+
+pub fn parse_term_subterm(input: &str) -> IResult<&str, Term> {
+    map(parse_parenthesized, |subterm| {
+        Term::SubTerm(Box::new(subterm))
+    })(input)
+}
+
+pub fn parse_success_count(input: &str) -> IResult<&str, FilteredDice> {
     map(
         tuple((
-            alt((parse_term_roll, parse_term_constant, parse_term_subterm)),
-            delimited(multispace0, parse_operator, multispace0),
-            parse_term,
+            parse_dice,
+            delimited(multispace0, parse_filter, multispace0),
+            terminated(parse_u32, pair(multispace0, tag_no_case("s"))),
         )),
-        |calc| Term::Calculation(Box::new(calc.0), calc.1, Box::new(calc.2)),
+        |res| FilteredDice::Filtered(res.0, res.1, res.2),
     )(input)
 }
 
-fn rearange_term(root: Term) -> Term {
-    if let Term::Calculation(left_top, op_top, right_top) = root {
-        if op_top == Operation::Mul || op_top == Operation::Div {
-            if let Term::Calculation(left_child, op_child, right_child) = *right_top {
-                Term::Calculation(
-                    Box::new(Term::Calculation(left_top, op_top, left_child)),
-                    op_child,
-                    Box::new(rearange_term(*right_child)),
-                )
-            } else {
-                Term::Calculation(left_top, op_top, Box::new(rearange_term(*right_top)))
+/// filter, success count and selector of already parsed `dice`
+///
+/// parsing these in a single pass keeps the dice, and with them any nested
+/// terms, from being parsed again for every alternative
+fn parse_roll_suffix(input: &str, dice: Dice) -> IResult<&str, Term> {
+    let (input, filter) = opt(pair(
+        delimited(multispace0, parse_filter, multispace0),
+        parse_u32,
+    ))(input)?;
+    let dice = match filter {
+        Some((filter, target)) => {
+            let dice = FilteredDice::Filtered(dice, filter, target);
+            let (input, success_count) = opt(pair(multispace0, tag_no_case("s")))(input)?;
+            if success_count.is_some() {
+                return Ok((input, Term::SuccessCount(dice)));
             }
-        } else {
-            Term::Calculation(left_top, op_top, Box::new(rearange_term(*right_top)))
+            dice
+        }
+        None => FilteredDice::Simple(dice),
+    };
+    let (input, selector) = opt(pair(
+        delimited(multispace0, parse_selector, multispace0),
+        parse_u32,
+    ))(input)?;
+    Ok((
+        input,
+        Term::DiceThrow(match selector {
+            Some((selector, n)) => SelectedDice::Selected(dice, selector, n),
+            None => SelectedDice::Unchanged(dice),
+        }),
+    ))
+}
+
+pub fn parse_term_roll(input: &str) -> IResult<&str, Term> {
+    let (input, dice) = parse_dice(input)?;
+    parse_roll_suffix(input, dice)
+}
+
+/// a parenthesized term, used as number of throws if a dice follows
+fn parse_parenthesized_atom(input: &str) -> IResult<&str, Term> {
+    let (rest, term) = parse_parenthesized(input)?;
+    match preceded(multispace0, parse_dice_kind)(rest) {
+        Ok((input, kind)) => parse_roll_suffix(input, dynamic_dice(term, kind)),
+        Err(_) => Ok((rest, Term::SubTerm(Box::new(term)))),
+    }
+}
+
+/// text of a `[comment]`, brackets inside it have to be balanced
+pub fn parse_comment(input: &str) -> IResult<&str, &str> {
+    let (rest, _) = tag("[")(input)?;
+    let mut depth = 0usize;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth == 0 => return Ok((&rest[i + 1..], rest[..i].trim())),
+            ']' => depth -= 1,
+            _ => {}
         }
-    } else if let Term::SubTerm(term) = root {
-        Term::SubTerm(Box::new(rearange_term(*term)))
-    } else {
-        root
     }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::TakeUntil,
+    )))
 }
 
-pub fn parse_rearanged_term(input: &str) -> IResult<&str, Term> {
-    map(parse_term, rearange_term)(input)
+fn is_term_label_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
 }
 
-pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
+/// `#label` of a single term
+///
+/// only recognized in front of an operator, closing bracket or comment, a label at
+/// the end belongs to the whole expression
+pub fn parse_term_label(input: &str) -> IResult<&str, &str> {
+    terminated(
+        preceded(tag("#"), recognize(many1(satisfy(is_term_label_char)))),
+        peek(preceded(
+            multispace0,
+            alt((recognize(parse_operator), tag(")"), tag("}"), tag("["))),
+        )),
+    )(input)
+}
+
+/// a single operand of an operation, optionally followed by a label and a comment
+pub fn parse_atom(input: &str) -> IResult<&str, Term> {
+    let (input, atom) = alt((
+        |input| {
+            let (input, dice) = parse_fixed_dice(input)?;
+            parse_roll_suffix(input, dice)
+        },
+        parse_term_constant,
+        parse_term_negation,
+        parse_parenthesized_atom,
+        parse_term_function,
+        parse_term_var,
+    ))(input)?;
+    let (input, atom) = match parse_term_label(input) {
+        Ok((input, label)) => (input, Term::Labeled(Box::new(atom), label.to_string())),
+        Err(_) => (input, atom),
+    };
+    match preceded(multispace0, parse_comment)(input) {
+        Ok((input, comment)) => Ok((input, Term::Commented(Box::new(atom), comment.to_string()))),
+        Err(_) => Ok((input, atom)),
+    }
+}
+
+fn calculation(left: Term, op: Operation, right: Term) -> Term {
+    Term::Calculation(Box::new(left), op, Box::new(right))
+}
+
+/// left associative chain of `operand`s joined by operations parsed by `operator`
+fn parse_left_assoc<'a>(
+    input: &'a str,
+    operand: fn(&'a str) -> IResult<&'a str, Term>,
+    operator: fn(&'a str) -> IResult<&'a str, Operation>,
+) -> IResult<&'a str, Term> {
+    let (input, first) = operand(input)?;
+    fold_many0(
+        pair(delimited(multispace0, operator, multispace0), operand),
+        first,
+        |left, (op, right)| calculation(left, op, right),
+    )(input)
+}
+
+/// right associative, so 2^3^2 is 2^(3^2)
+pub fn parse_power(input: &str) -> IResult<&str, Term> {
+    map(
+        pair(
+            parse_atom,
+            opt(preceded(
+                delimited(multispace0, tag("^"), multispace0),
+                parse_power,
+            )),
+        ),
+        |(base, exponent)| match exponent {
+            Some(exponent) => calculation(base, Operation::Pow, exponent),
+            None => base,
+        },
+    )(input)
+}
+
+pub fn parse_product_operator(input: &str) -> IResult<&str, Operation> {
     alt((
-        map(
-            pair(
-                parse_u32,
-                preceded(
-                    multispace0,
-                    delimited(
-                        tag("{"),
-                        delimited(multispace0, parse_rearanged_term, multispace0),
-                        tag("}"),
-                    ),
-                ),
+        map(tag("*"), |_| Operation::Mul),
+        map(tag("/"), |_| Operation::Div),
+        // a % directly following a dice digit is consumed as d100 before this
+        map(tag("%"), |_| Operation::Mod),
+    ))(input)
+}
+
+pub fn parse_operator(input: &str) -> IResult<&str, Operation> {
+    alt((
+        parse_sum_operator,
+        parse_product_operator,
+        map(tag("^"), |_| Operation::Pow),
+    ))(input)
+}
+
+/// an operator with nothing but whitespace after it, what remains of `1d6 +`
+pub fn parse_trailing_operator(input: &str) -> IResult<&str, Operation> {
+    delimited(multispace0, parse_operator, pair(multispace0, eof))(input)
+}
+
+pub fn parse_product(input: &str) -> IResult<&str, Term> {
+    parse_left_assoc(input, parse_power, parse_product_operator)
+}
+
+pub fn parse_sum_operator(input: &str) -> IResult<&str, Operation> {
+    alt((
+        map(tag("+"), |_| Operation::Add),
+        map(tag("-"), |_| Operation::Sub),
+    ))(input)
+}
+
+pub fn parse_sum(input: &str) -> IResult<&str, Term> {
+    parse_left_assoc(input, parse_product, parse_sum_operator)
+}
+
+/// same as [parse_term], which already respects operator precedence
+pub fn parse_rearanged_term(input: &str) -> IResult<&str, Term> {
+    parse_term(input)
+}
+
+/// the operations following `first`, the leftmost operand of a term, respecting their precedence
+fn parse_term_continued(input: &str, first: Term) -> IResult<&str, Term> {
+    let (input, power) =
+        match preceded(delimited(multispace0, tag("^"), multispace0), parse_power)(input) {
+            Ok((input, exponent)) => (input, calculation(first, Operation::Pow, exponent)),
+            Err(nom::Err::Error(_)) => (input, first),
+            Err(err) => return Err(err),
+        };
+    let (input, product) = fold_many0(
+        pair(
+            delimited(multispace0, parse_product_operator, multispace0),
+            parse_power,
+        ),
+        power,
+        |left, (op, right)| calculation(left, op, right),
+    )(input)?;
+    fold_many0(
+        pair(
+            delimited(multispace0, parse_sum_operator, multispace0),
+            parse_product,
+        ),
+        product,
+        |left, (op, right)| calculation(left, op, right),
+    )(input)
+}
+
+/// `6{4d6k3}`, operations following the braces apply to every entry, so `6{4d6k3} + 1` is
+/// the same as `6{4d6k3 + 1}`
+pub fn parse_list(input: &str) -> IResult<&str, Expression> {
+    let (rest, (count, entry)) = pair(
+        parse_u32,
+        preceded(
+            multispace0,
+            delimited(
+                tag("{"),
+                delimited(multispace0, parse_term, multispace0),
+                tag("}"),
             ),
-            |list| Expression::List(list.0, list.1),
         ),
-        map(parse_rearanged_term, Expression::Simple),
-    ))(input)
+    )(input)?;
+    if peek(preceded(multispace0, parse_operator))(rest).is_err() {
+        return Ok((rest, Expression::List(count, entry)));
+    }
+    // the braces group the entry like parentheses
+    let entry = match entry {
+        Term::Calculation(_, _, _) => Term::SubTerm(Box::new(entry)),
+        entry => entry,
+    };
+    let (rest, term) = parse_term_continued(rest, entry)?;
+    Ok((rest, Expression::List(count, term)))
 }
 
-pub fn parse_labeled(input: &str) -> IResult<&str, LabeledExpression> {
+pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
+    alt((parse_list, map(parse_term, Expression::Simple)))(input)
+}
+
+/// whether any term of `term` outside of dice has a label
+fn has_term_labels(term: &Term) -> bool {
+    match term {
+        Term::Labeled(_, _) => true,
+        Term::SubTerm(t) | Term::Commented(t, _) => has_term_labels(t),
+        Term::Calculation(l, _, r) => has_term_labels(l) || has_term_labels(r),
+        _ => false,
+    }
+}
+
+/// `term` with `label` on its last operand
+fn label_last(term: Term, label: String) -> Term {
+    match term {
+        Term::Calculation(l, op, r) => Term::Calculation(l, op, Box::new(label_last(*r, label))),
+        Term::Commented(t, c) => Term::Commented(Box::new(label_last(*t, label)), c),
+        t => Term::Labeled(Box::new(t), label),
+    }
+}
+
+/// the trailing label is moved to the last term if other terms are labeled as well,
+/// so `1d20#attack + 1d6#fire` labels both dice
+fn attach_label(expression: Expression, label: String) -> LabeledExpression {
+    let labels_terms = |t: &Term| {
+        has_term_labels(t)
+            && !matches!(t, Term::Labeled(_, _))
+            && label.chars().all(is_term_label_char)
+    };
+    match expression {
+        Expression::Simple(t) if labels_terms(&t) => {
+            LabeledExpression::Unlabeled(Expression::Simple(label_last(t, label)))
+        }
+        Expression::List(n, t) if labels_terms(&t) => {
+            LabeledExpression::Unlabeled(Expression::List(n, label_last(t, label)))
+        }
+        e => LabeledExpression::Labeled(e, label),
+    }
+}
+
+/// expression with an optional label made of characters matching `is_label_char`
+fn labeled(input: &str, is_label_char: fn(char) -> bool) -> IResult<&str, LabeledExpression> {
     map(
         pair(
             parse_expression,
@@ -227,7 +588,7 @@ pub fn parse_labeled(input: &str) -> IResult<&str, LabeledExpression> {
                 pair(tag("#"), multispace0),
                 map(
                     many0(terminated(
-                        recognize(many1(satisfy(|c| !(c.is_whitespace() || c == '\n')))),
+                        recognize(many1(satisfy(is_label_char))),
                         multispace0,
                     )),
                     |labels: Vec<&str>| {
@@ -241,12 +602,150 @@ pub fn parse_labeled(input: &str) -> IResult<&str, LabeledExpression> {
             )),
         ),
         |r| match r {
-            (e, Some(l)) => LabeledExpression::Labeled(e, l),
+            (e, Some(l)) => attach_label(e, l),
             (e, None) => LabeledExpression::Unlabeled(e),
         },
     )(input)
 }
 
+pub fn parse_labeled(input: &str) -> IResult<&str, LabeledExpression> {
+    labeled(input, |c| !(c.is_whitespace() || c == '\n'))
+}
+
+/// a single entry of [parse_multi], its label ends at the next `;`
+pub fn parse_multi_entry(input: &str) -> IResult<&str, LabeledExpression> {
+    labeled(input, |c| !(c.is_whitespace() || c == ';'))
+}
+
+/// several labeled expressions separated by `;`
+pub fn parse_multi(input: &str) -> IResult<&str, Vec<LabeledExpression>> {
+    separated_list1(
+        delimited(multispace0, tag(";"), multispace0),
+        parse_multi_entry,
+    )(input)
+}
+
+pub fn parse_narrative_die(input: &str) -> IResult<&str, NarrativeDie> {
+    alt((
+        map(tag_no_case("b"), |_| NarrativeDie::Boost),
+        map(tag_no_case("s"), |_| NarrativeDie::Setback),
+        map(tag_no_case("a"), |_| NarrativeDie::Ability),
+        map(tag_no_case("d"), |_| NarrativeDie::Difficulty),
+        map(tag_no_case("p"), |_| NarrativeDie::Proficiency),
+        map(tag_no_case("c"), |_| NarrativeDie::Challenge),
+    ))(input)
+}
+
+/// narrative dice like `2a 1p 2d`, a missing count means a single die
+pub fn parse_narrative_pool(input: &str) -> IResult<&str, NarrativePool> {
+    map(
+        verify(
+            many1(preceded(
+                multispace0,
+                pair(
+                    terminated(alt((parse_u32, success(1))), multispace0),
+                    parse_narrative_die,
+                ),
+            )),
+            |dice: &Vec<(u32, NarrativeDie)>| {
+                dice.iter()
+                    .try_fold(0u32, |size, (n, _)| size.checked_add(*n))
+                    .is_some_and(|size| size <= MAX_NARRATIVE_DICE)
+            },
+        ),
+        NarrativePool,
+    )(input)
+}
+
+/// error of parsing a complete expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiceParseError {
+    /// the input ended in the middle of an expression
+    Incomplete,
+    /// no expression starts at the byte offset `position`
+    Unexpected { position: usize },
+    /// an expression was parsed, but input is left from byte offset `position`
+    TrailingInput { position: usize },
+    /// the expression ends with the operator at byte offset `position`
+    TrailingOperator { position: usize },
+}
+
+impl fmt::Display for DiceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceParseError::Incomplete => write!(f, "incomplete expression"),
+            DiceParseError::Unexpected { position } => {
+                write!(f, "unexpected input at position {}", position)
+            }
+            DiceParseError::TrailingInput { position } => {
+                write!(f, "trailing input at position {}", position)
+            }
+            DiceParseError::TrailingOperator { position } => {
+                write!(
+                    f,
+                    "expression ends with an operator at position {}",
+                    position
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiceParseError {}
+
+/// applies `parser` to all of `input`, surrounding whitespace is ignored
+fn parse_complete<'a, T>(
+    input: &'a str,
+    parser: fn(&'a str) -> IResult<&'a str, T>,
+) -> Result<T, DiceParseError> {
+    match terminated(preceded(multispace0, parser), multispace0)(input) {
+        Ok(("", result)) => Ok(result),
+        Ok((rest, _)) => {
+            let position = input.len() - rest.len();
+            Err(match parse_trailing_operator(rest) {
+                Ok(_) => DiceParseError::TrailingOperator {
+                    position: position + rest.len() - rest.trim_start().len(),
+                },
+                Err(_) => DiceParseError::TrailingInput { position },
+            })
+        }
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(DiceParseError::Unexpected {
+            position: input.len() - e.input.len(),
+        }),
+        Err(nom::Err::Incomplete(_)) => Err(DiceParseError::Incomplete),
+    }
+}
+
+/// parses all of `input` as an expression
+///
+/// this is the entry point for users of the library, the nom parsers of this
+/// module are meant for composing them into bigger grammars
+pub fn parse(input: &str) -> Result<Expression, DiceParseError> {
+    parse_complete(input, parse_expression)
+}
+
+/// parses all of `input` as an expression with an optional `#label`
+pub fn parse_labeled_expression(input: &str) -> Result<LabeledExpression, DiceParseError> {
+    parse_complete(input, parse_labeled)
+}
+
+impl FromStr for Expression {
+    type Err = DiceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+impl FromStr for LabeledExpression {
+    type Err = DiceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_labeled_expression(s)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -307,8 +806,20 @@ mod tests {
         assert_eq!(parse_dice_type("1"), Ok(("", DiceType::Number(1))));
         assert_eq!(parse_dice_type("1337"), Ok(("", DiceType::Number(1337))));
         assert_eq!(parse_dice_type("%"), Ok(("", DiceType::Number(100))));
-        assert_eq!(parse_dice_type("f"), Ok(("", DiceType::Fudge)));
-        assert_eq!(parse_dice_type("F"), Ok(("", DiceType::Fudge)));
+        assert_eq!(parse_dice_type("f"), Ok(("", DiceType::Fudge { sides: 1 })));
+        assert_eq!(parse_dice_type("F"), Ok(("", DiceType::Fudge { sides: 1 })));
+        assert_eq!(
+            parse_dice_type("F.1"),
+            Ok(("", DiceType::Fudge { sides: 1 }))
+        );
+        assert_eq!(
+            parse_dice_type("f.2"),
+            Ok(("", DiceType::Fudge { sides: 2 }))
+        );
+        assert_eq!(
+            parse_dice_type("f.0"),
+            Ok((".0", DiceType::Fudge { sides: 1 }))
+        );
         assert_eq!(parse_dice_type("1x"), Ok(("", DiceType::Multiply(1))));
         assert_eq!(parse_dice_type("6969X"), Ok(("", DiceType::Multiply(6969))));
         assert_eq!(
@@ -327,7 +838,10 @@ mod tests {
                 "",
                 Dice {
                     throws: 1,
-                    dice: DiceType::Number(1)
+                    dice: DiceType::Number(1),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
                 }
             ))
         );
@@ -337,7 +851,10 @@ mod tests {
                 "",
                 Dice {
                     throws: 1,
-                    dice: DiceType::Number(100)
+                    dice: DiceType::Number(100),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
                 }
             ))
         );
@@ -347,44 +864,47 @@ mod tests {
                 "",
                 Dice {
                     throws: 20,
-                    dice: DiceType::Multiply(3)
+                    dice: DiceType::Multiply(3),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
                 }
             ))
         );
     }
 
     #[test]
-    fn test_parse_filter() {
-        assert_eq!(parse_filter("<"), Ok(("", Filter::Smaller)));
-        assert_eq!(parse_filter("<="), Ok(("", Filter::SmallerEq)));
-        assert_eq!(parse_filter(">"), Ok(("", Filter::Bigger)));
-        assert_eq!(parse_filter(">="), Ok(("", Filter::BiggerEq)));
-        assert_eq!(parse_filter("!="), Ok(("", Filter::NotEq)));
-        assert_eq!(parse_filter("!=3"), Ok(("3", Filter::NotEq)));
-        assert!(parse_filter("==").is_err());
-        assert!(parse_filter("").is_err());
-    }
-
-    #[test]
-    fn test_parse_filtered_dice() {
+    fn test_parse_explode() {
+        assert_eq!(parse_explode("!"), Ok(("", Explode::Standard)));
+        assert_eq!(parse_explode("!+1"), Ok(("+1", Explode::Standard)));
+        assert_eq!(parse_explode("!!"), Ok(("", Explode::Compound)));
+        assert_eq!(parse_explode("!!!"), Ok(("!", Explode::Compound)));
+        assert!(parse_explode("!=").is_err());
+        assert!(parse_explode("").is_err());
         assert_eq!(
-            parse_filtered_dice("d4"),
+            parse_dice("3d6!"),
             Ok((
                 "",
-                FilteredDice::Simple(Dice {
-                    throws: 1,
-                    dice: DiceType::Number(4)
-                })
+                Dice {
+                    throws: 3,
+                    dice: DiceType::Number(6),
+                    explode: Some(Explode::Standard),
+                    dynamic_throws: None,
+                    reroll: None
+                }
             ))
         );
         assert_eq!(
-            parse_filtered_dice("2d2!=2"),
+            parse_filtered_dice("3d6!!=2"),
             Ok((
                 "",
                 FilteredDice::Filtered(
                     Dice {
-                        throws: 2,
-                        dice: DiceType::Number(2)
+                        throws: 3,
+                        dice: DiceType::Number(6),
+                        explode: Some(Explode::Standard),
+                        dynamic_throws: None,
+                        reroll: None
                     },
                     Filter::NotEq,
                     2
@@ -392,26 +912,226 @@ mod tests {
             ))
         );
         assert_eq!(
-            parse_filtered_dice("10   w  10  \t x \t  < \t 75"),
+            parse_dice("3d6!").map(|d| d.1.to_string()),
+            Ok("3d6!".to_string())
+        );
+        assert_eq!(
+            parse_dice("4d6 !!").map(|d| d.1.to_string()),
+            Ok("4d6!!".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_success_count() {
+        assert_eq!(
+            parse_term_roll("8d10>=7s"),
             Ok((
                 "",
-                FilteredDice::Filtered(
+                Term::SuccessCount(FilteredDice::Filtered(
                     Dice {
-                        throws: 10,
-                        dice: DiceType::Multiply(10)
+                        throws: 8,
+                        dice: DiceType::Number(10),
+                        explode: None,
+                        dynamic_throws: None,
+                        reroll: None
                     },
-                    Filter::Smaller,
-                    75
-                )
+                    Filter::BiggerEq,
+                    7
+                ))
             ))
         );
         assert_eq!(
-            parse_filtered_dice("69d69>"),
-            Ok((
+            parse_term_roll("8d10 > 7 s").map(|t| t.1.to_string()),
+            Ok("8d10>7s".to_string())
+        );
+        assert!(matches!(
+            parse_term_roll("8d10>7"),
+            Ok(("", Term::DiceThrow(_)))
+        ));
+        assert!(matches!(
+            parse_labeled("2 + 6d6!>5s"),
+            Ok(("", LabeledExpression::Unlabeled(Expression::Simple(_))))
+        ));
+    }
+
+    #[test]
+    fn test_parse_dynamic_dice() {
+        assert_eq!(
+            parse_dice("1d(1d6)"),
+            Ok((
+                "",
+                Dice {
+                    throws: 1,
+                    dice: DiceType::Dynamic(Box::new(Term::DiceThrow(SelectedDice::Unchanged(
+                        FilteredDice::Simple(Dice {
+                            throws: 1,
+                            dice: DiceType::Number(6),
+                            explode: None,
+                            dynamic_throws: None,
+                            reroll: None
+                        })
+                    )))),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
+                }
+            ))
+        );
+        assert_eq!(
+            parse_term("2d( 2 * 1d4 )k1 + 1").map(|t| t.1.to_string()),
+            Ok("2d(2 * 1d4)h1 + 1".to_string())
+        );
+        assert_eq!(
+            parse_term("1d(1d(1d6))!").map(|t| t.1.to_string()),
+            Ok("1d(1d(1d6))!".to_string())
+        );
+        assert!(parse_dice("1d()").is_err());
+    }
+
+    #[test]
+    fn test_parse_dynamic_throws() {
+        let dice = parse_dice("(1d4)d6").unwrap();
+        assert_eq!(dice.0, "");
+        assert_eq!(
+            dice.1.dynamic_throws,
+            Some(Box::new(parse_term("1d4").unwrap().1))
+        );
+        assert_eq!(dice.1.dice, DiceType::Number(6));
+        assert_eq!(
+            parse_term("( 1d4 + 1 ) d6 >= 5s * 2").map(|t| t.1.to_string()),
+            Ok("(1d4 + 1)d6>=5s * 2".to_string())
+        );
+        assert_eq!(
+            parse_term("(2)d(1d6)k1").map(|t| t.1.to_string()),
+            Ok("(2)d(1d6)h1".to_string())
+        );
+        assert_eq!(
+            parse_term("(2) * 3").map(|t| t.1.to_string()),
+            Ok("(2) * 3".to_string())
+        );
+        // a dice digit without a dice type leaves the subterm as it is
+        assert_eq!(parse_term("(2)dx").map(|t| t.0), Ok("dx"));
+    }
+
+    #[test]
+    fn test_parse_deep_nesting() {
+        let nested = format!("{}1{}", "(".repeat(64), ")d2".repeat(64));
+        assert_eq!(parse_term(&nested).map(|t| t.0), Ok(""));
+        let nested = format!("{}1{}", "(".repeat(64), ")".repeat(64));
+        assert_eq!(parse_term(&nested).map(|t| t.0), Ok(""));
+    }
+
+    #[test]
+    fn test_parse_reroll() {
+        assert_eq!(
+            parse_reroll("ro<3"),
+            Ok(("", Reroll::Once(Filter::Smaller, 3)))
+        );
+        assert_eq!(
+            parse_reroll("RO <= 2"),
+            Ok(("", Reroll::Once(Filter::SmallerEq, 2)))
+        );
+        assert!(parse_reroll("ro3").is_err());
+        assert_eq!(
+            parse_reroll("rr<2"),
+            Ok(("", Reroll::Recursive(Filter::Smaller, 2)))
+        );
+        assert_eq!(parse_term("3d6!rr=1").map(|t| t.0), Ok("rr=1"));
+        assert_eq!(
+            parse_term("3d6!!RR <= 2k2").map(|t| t.1.to_string()),
+            Ok("3d6!!rr<=2h2".to_string())
+        );
+        assert_eq!(
+            parse_dice("2d6ro<3"),
+            Ok((
+                "",
+                Dice {
+                    throws: 2,
+                    dice: DiceType::Number(6),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: Some(Reroll::Once(Filter::Smaller, 3))
+                }
+            ))
+        );
+        assert_eq!(parse_term("4d6!ro=1").map(|t| t.0), Ok("ro=1"));
+        assert_eq!(
+            parse_term("4d6 ! ro!=6 >= 5s").map(|t| t.1.to_string()),
+            Ok("4d6!ro!=6>=5s".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(parse_filter("<"), Ok(("", Filter::Smaller)));
+        assert_eq!(parse_filter("<="), Ok(("", Filter::SmallerEq)));
+        assert_eq!(parse_filter(">"), Ok(("", Filter::Bigger)));
+        assert_eq!(parse_filter(">="), Ok(("", Filter::BiggerEq)));
+        assert_eq!(parse_filter("!="), Ok(("", Filter::NotEq)));
+        assert_eq!(parse_filter("!=3"), Ok(("3", Filter::NotEq)));
+        assert!(parse_filter("==").is_err());
+        assert!(parse_filter("").is_err());
+    }
+
+    #[test]
+    fn test_parse_filtered_dice() {
+        assert_eq!(
+            parse_filtered_dice("d4"),
+            Ok((
+                "",
+                FilteredDice::Simple(Dice {
+                    throws: 1,
+                    dice: DiceType::Number(4),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
+                })
+            ))
+        );
+        assert_eq!(
+            parse_filtered_dice("2d2!=2"),
+            Ok((
+                "",
+                FilteredDice::Filtered(
+                    Dice {
+                        throws: 2,
+                        dice: DiceType::Number(2),
+                        explode: None,
+                        dynamic_throws: None,
+                        reroll: None
+                    },
+                    Filter::NotEq,
+                    2
+                )
+            ))
+        );
+        assert_eq!(
+            parse_filtered_dice("10   w  10  \t x \t  < \t 75"),
+            Ok((
+                "",
+                FilteredDice::Filtered(
+                    Dice {
+                        throws: 10,
+                        dice: DiceType::Multiply(10),
+                        explode: None,
+                        dynamic_throws: None,
+                        reroll: None
+                    },
+                    Filter::Smaller,
+                    75
+                )
+            ))
+        );
+        assert_eq!(
+            parse_filtered_dice("69d69>"),
+            Ok((
                 ">",
                 FilteredDice::Simple(Dice {
                     throws: 69,
-                    dice: DiceType::Number(69)
+                    dice: DiceType::Number(69),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
                 })
             ))
         );
@@ -427,6 +1147,10 @@ mod tests {
         assert_eq!(parse_selector("l"), Ok(("", Selector::Lower)));
         assert_eq!(parse_selector("L"), Ok(("", Selector::Lower)));
         assert_eq!(parse_selector("hl"), Ok(("l", Selector::Higher)));
+        assert_eq!(parse_selector("dh"), Ok(("", Selector::DropHigher)));
+        assert_eq!(parse_selector("DL"), Ok(("", Selector::DropLower)));
+        assert!(parse_selector("d").is_err());
+        assert!(parse_selector("d6").is_err());
         assert!(parse_selector("").is_err());
     }
 
@@ -438,7 +1162,10 @@ mod tests {
                 "",
                 SelectedDice::Unchanged(FilteredDice::Simple(Dice {
                     throws: 1,
-                    dice: DiceType::Number(3)
+                    dice: DiceType::Number(3),
+                    explode: None,
+                    dynamic_throws: None,
+                    reroll: None
                 }))
             ))
         );
@@ -450,7 +1177,10 @@ mod tests {
                     FilteredDice::Filtered(
                         Dice {
                             throws: 4,
-                            dice: DiceType::Multiply(10)
+                            dice: DiceType::Multiply(10),
+                            explode: None,
+                            dynamic_throws: None,
+                            reroll: None
                         },
                         Filter::Bigger,
                         50
@@ -468,7 +1198,10 @@ mod tests {
                     FilteredDice::Filtered(
                         Dice {
                             throws: 4,
-                            dice: DiceType::Multiply(10)
+                            dice: DiceType::Multiply(10),
+                            explode: None,
+                            dynamic_throws: None,
+                            reroll: None
                         },
                         Filter::Bigger,
                         50
@@ -478,6 +1211,27 @@ mod tests {
                 )
             ))
         );
+        assert_eq!(
+            parse_selected_dice("4d6dl1"),
+            Ok((
+                "",
+                SelectedDice::Selected(
+                    FilteredDice::Simple(Dice {
+                        throws: 4,
+                        dice: DiceType::Number(6),
+                        explode: None,
+                        dynamic_throws: None,
+                        reroll: None
+                    }),
+                    Selector::DropLower,
+                    1
+                )
+            ))
+        );
+        assert_eq!(
+            parse_selected_dice("4d6 dh 2").map(|d| d.1.to_string()),
+            Ok("4d6dh2".to_string())
+        );
         assert!(parse_selected_dice("").is_err());
     }
 
@@ -489,37 +1243,580 @@ mod tests {
             Ok((
                 "",
                 Term::Calculation(
-                    Box::new(Term::DiceThrow(SelectedDice::Unchanged(
-                        FilteredDice::Simple(Dice {
-                            throws: 1,
-                            dice: DiceType::Number(3)
-                        })
-                    ))),
-                    Operation::Add,
                     Box::new(Term::Calculation(
                         Box::new(Term::DiceThrow(SelectedDice::Unchanged(
                             FilteredDice::Simple(Dice {
-                                throws: 66,
-                                dice: DiceType::Fudge
+                                throws: 1,
+                                dice: DiceType::Number(3),
+                                explode: None,
+                                dynamic_throws: None,
+                                reroll: None
                             })
                         ))),
-                        Operation::Mul,
+                        Operation::Add,
                         Box::new(Term::Calculation(
+                            Box::new(Term::DiceThrow(SelectedDice::Unchanged(
+                                FilteredDice::Simple(Dice {
+                                    throws: 66,
+                                    dice: DiceType::Fudge { sides: 1 },
+                                    explode: None,
+                                    dynamic_throws: None,
+                                    reroll: None
+                                })
+                            ))),
+                            Operation::Mul,
                             Box::new(Term::DiceThrow(SelectedDice::Unchanged(
                                 FilteredDice::Simple(Dice {
                                     throws: 4,
-                                    dice: DiceType::Multiply(3)
+                                    dice: DiceType::Multiply(3),
+                                    explode: None,
+                                    dynamic_throws: None,
+                                    reroll: None
                                 })
                             ))),
-                            Operation::Sub,
-                            Box::new(Term::Constant(1))
                         ))
-                    ))
+                    )),
+                    Operation::Sub,
+                    Box::new(Term::Constant(1))
                 )
             ))
         );
         assert!(parse_term("").is_err())
     }
 
-    fn test_parse_expr() {}
+    #[test]
+    fn test_parse_precedence() {
+        assert_eq!(
+            parse_term("1 + 2 * 3 + 4").map(|t| t.1.to_string()),
+            Ok("1 + 2 * 3 + 4".to_string())
+        );
+        assert_eq!(
+            parse_term("10 - 2 - 3").map(|t| t.1),
+            Ok(calculation(
+                calculation(Term::Constant(10), Operation::Sub, Term::Constant(2)),
+                Operation::Sub,
+                Term::Constant(3)
+            ))
+        );
+        assert_eq!(
+            parse_term("(1 + 2) * 3").map(|t| t.1),
+            Ok(calculation(
+                Term::SubTerm(Box::new(calculation(
+                    Term::Constant(1),
+                    Operation::Add,
+                    Term::Constant(2)
+                ))),
+                Operation::Mul,
+                Term::Constant(3)
+            ))
+        );
+    }
+
+    #[cfg(feature = "roll")]
+    #[test]
+    fn test_evaluate_precedence() {
+        use crate::dice_roll::TermEvaluate;
+        let eval = |input| {
+            parse_term(input)
+                .unwrap()
+                .1
+                .evaluate(&mut || false, &mut rand::thread_rng())
+                .unwrap()
+                .0
+        };
+        assert_eq!(eval("2 + 3 * 4"), 14);
+        assert_eq!(eval("10 - 2 - 3"), 5);
+        assert_eq!(eval("1 + 2 * 3 + 4"), 11);
+        assert_eq!(eval("100 / 10 / 5"), 2);
+        assert_eq!(eval("2 * 3 ^ 2"), 18);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512);
+        assert_eq!(eval("(2 + 3) * 4"), 20);
+    }
+
+    #[test]
+    fn test_parse_pow() {
+        let calc = |l, op, r| Term::Calculation(Box::new(l), op, Box::new(r));
+        assert_eq!(
+            parse_term("2 ^ 1d4").map(|t| t.1.to_string()),
+            Ok("2 ^ 1d4".to_string())
+        );
+        // 2 * 3 ^ 2 + 1 == (2 * (3 ^ 2)) + 1
+        assert_eq!(
+            parse_term("2 * 3 ^ 2 + 1").map(|t| t.1),
+            Ok(calc(
+                calc(
+                    Term::Constant(2),
+                    Operation::Mul,
+                    calc(Term::Constant(3), Operation::Pow, Term::Constant(2))
+                ),
+                Operation::Add,
+                Term::Constant(1)
+            ))
+        );
+        // ^ is right associative
+        assert_eq!(
+            parse_term("2^3^2").map(|t| t.1),
+            Ok(calc(
+                Term::Constant(2),
+                Operation::Pow,
+                calc(Term::Constant(3), Operation::Pow, Term::Constant(2))
+            ))
+        );
+        // * and / stay left associative
+        assert_eq!(
+            parse_term("8 / 4 * 2").map(|t| t.1),
+            Ok(calc(
+                calc(Term::Constant(8), Operation::Div, Term::Constant(4)),
+                Operation::Mul,
+                Term::Constant(2)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_mod() {
+        assert_eq!(
+            parse_term("1d20 % 6"),
+            Ok((
+                "",
+                Term::Calculation(
+                    Box::new(Term::DiceThrow(SelectedDice::Unchanged(
+                        FilteredDice::Simple(Dice {
+                            throws: 1,
+                            dice: DiceType::Number(20),
+                            explode: None,
+                            dynamic_throws: None,
+                            reroll: None
+                        })
+                    ))),
+                    Operation::Mod,
+                    Box::new(Term::Constant(6))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_term("d%%7").map(|t| t.1.to_string()),
+            Ok("1d100 % 7".to_string())
+        );
+        // % binds like * and /
+        assert_eq!(
+            parse_term("10 % 4 + 1").map(|t| t.1),
+            Ok(Term::Calculation(
+                Box::new(Term::Calculation(
+                    Box::new(Term::Constant(10)),
+                    Operation::Mod,
+                    Box::new(Term::Constant(4))
+                )),
+                Operation::Add,
+                Box::new(Term::Constant(1))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        assert_eq!(
+            parse_expression("6{4d6k3}"),
+            Ok((
+                "",
+                Expression::List(
+                    6,
+                    Term::DiceThrow(SelectedDice::Selected(
+                        FilteredDice::Simple(Dice {
+                            throws: 4,
+                            dice: DiceType::Number(6),
+                            explode: None,
+                            dynamic_throws: None,
+                            reroll: None
+                        }),
+                        Selector::Higher,
+                        3
+                    ))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_expression("1"),
+            Ok(("", Expression::Simple(Term::Constant(1))))
+        );
+        assert!(parse_expression("").is_err());
+    }
+
+    #[test]
+    fn test_parse_list_calculation() {
+        let expr = |input| parse_expression(input).map(|(rest, e)| (rest, e.to_string()));
+        assert_eq!(expr("6{4d6k3} + 10"), Ok(("", "6{4d6h3 + 10}".to_string())));
+        assert_eq!(
+            parse_expression("6{4d6k3} + 10"),
+            parse_expression("6{4d6k3 + 10}")
+        );
+        // the braces group the entry
+        assert_eq!(
+            expr("3{1d6 + 1} * 2 - 1"),
+            Ok(("", "3{(1d6 + 1) * 2 - 1}".to_string()))
+        );
+        assert_eq!(expr("2{2}^3 * 2"), Ok(("", "2{2 ^ 3 * 2}".to_string())));
+        assert_eq!(
+            parse_expression("3{1d6 + 1} * 2"),
+            parse_expression("3{(1d6 + 1) * 2}")
+        );
+        assert_eq!(expr("3{1d6 + 1}"), Ok(("", "3{1d6 + 1}".to_string())));
+        assert_eq!(
+            parse_labeled("6{4d6k3} + 1#stats"),
+            Ok((
+                "",
+                LabeledExpression::Labeled(
+                    parse_expression("6{4d6k3 + 1}").unwrap().1,
+                    "stats".to_string()
+                )
+            ))
+        );
+        assert_eq!(
+            parse("6{1d6} +"),
+            Err(DiceParseError::TrailingOperator { position: 7 })
+        );
+    }
+
+    #[test]
+    fn test_parse_comment() {
+        assert_eq!(parse_comment("[to hit] + 1"), Ok((" + 1", "to hit")));
+        assert_eq!(
+            parse_comment("[ see [PHB] p. 5 ]"),
+            Ok(("", "see [PHB] p. 5"))
+        );
+        assert!(parse_comment("[unbalanced [bracket]").is_err());
+        let d20 = Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(Dice {
+            throws: 1,
+            dice: DiceType::Number(20),
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        })));
+        let term = calculation(
+            calculation(
+                d20,
+                Operation::Add,
+                Term::Commented(Box::new(Term::Constant(5)), "to hit".to_string()),
+            ),
+            Operation::Add,
+            Term::Commented(Box::new(Term::Constant(2)), "crit [x2]".to_string()),
+        );
+        assert_eq!(
+            parse_term("1d20+5 [to hit]+2[crit [x2]]"),
+            Ok(("", term.clone()))
+        );
+        assert_eq!(term.to_string(), "1d20 + 5 [to hit] + 2 [crit [x2]]");
+        assert_eq!(parse_term(&term.to_string()), Ok(("", term)));
+    }
+
+    #[test]
+    fn test_parse_term_labels() {
+        let d = |n| {
+            Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(Dice {
+                throws: 1,
+                dice: DiceType::Number(n),
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            })))
+        };
+        let labeled = |t, l: &str| Term::Labeled(Box::new(t), l.to_string());
+        let attack = Expression::Simple(calculation(
+            labeled(d(20), "attack"),
+            Operation::Add,
+            labeled(d(6), "fire"),
+        ));
+        assert_eq!(
+            parse_labeled("1d20#attack + 1d6#fire"),
+            Ok(("", LabeledExpression::Unlabeled(attack.clone())))
+        );
+        assert_eq!(attack.to_string(), "1d20#attack + 1d6#fire");
+        assert_eq!(
+            parse_labeled("1d20 + 1d6#fire"),
+            Ok((
+                "",
+                LabeledExpression::Labeled(
+                    Expression::Simple(calculation(d(20), Operation::Add, d(6))),
+                    "fire".to_string()
+                )
+            ))
+        );
+        assert_eq!(
+            parse_labeled("1d20#hit + 5#long label"),
+            Ok((
+                "",
+                LabeledExpression::Labeled(
+                    Expression::Simple(calculation(
+                        labeled(d(20), "hit"),
+                        Operation::Add,
+                        Term::Constant(5)
+                    )),
+                    "long label".to_string()
+                )
+            ))
+        );
+        assert_eq!(
+            parse_term("(1d4#bless) * 2"),
+            Ok((
+                "",
+                calculation(
+                    Term::SubTerm(Box::new(labeled(d(4), "bless"))),
+                    Operation::Mul,
+                    Term::Constant(2)
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            " 2 + 3 ".parse::<Expression>(),
+            Ok(Expression::Simple(calculation(
+                Term::Constant(2),
+                Operation::Add,
+                Term::Constant(3)
+            )))
+        );
+        assert_eq!(
+            "4#label".parse::<LabeledExpression>(),
+            Ok(LabeledExpression::Labeled(
+                Expression::Simple(Term::Constant(4)),
+                "label".to_string()
+            ))
+        );
+        assert_eq!(
+            "1d6 + 2 foo".parse::<Expression>(),
+            Err(DiceParseError::TrailingInput { position: 8 })
+        );
+        assert_eq!(parse(" ?"), Err(DiceParseError::Unexpected { position: 1 }));
+        assert_eq!(
+            parse("1d6 +").unwrap_err().to_string(),
+            "expression ends with an operator at position 4"
+        );
+        assert_eq!(
+            parse("2d6*"),
+            Err(DiceParseError::TrailingOperator { position: 3 })
+        );
+        assert_eq!(
+            parse("(1d6 + 2) / "),
+            Err(DiceParseError::TrailingOperator { position: 10 })
+        );
+        assert_eq!(
+            parse("1d6 + +"),
+            Err(DiceParseError::TrailingInput { position: 4 })
+        );
+        assert_eq!(
+            parse_labeled_expression("1d6#label with spaces"),
+            "1d6#label with spaces".parse()
+        );
+        assert_eq!(parse("4dF.2").unwrap().to_string(), "4dF.2");
+        assert_eq!(parse("4dF.1").unwrap().to_string(), "4dF");
+    }
+
+    #[test]
+    fn test_parse_function() {
+        let d6 = |throws| {
+            Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(Dice {
+                throws,
+                dice: DiceType::Number(6),
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            })))
+        };
+        let term = Term::Function(Func::Min, vec![d6(2), Term::Constant(8)]);
+        assert_eq!(parse_term("min(2d6, 8)"), Ok(("", term.clone())));
+        assert_eq!(parse_term("MIN ( 2d6 ,8 )"), Ok(("", term.clone())));
+        assert_eq!(term.to_string(), "min(2d6, 8)");
+        assert_eq!(
+            parse_term("max(1d6 + 1, (2))*2"),
+            Ok((
+                "",
+                calculation(
+                    Term::Function(
+                        Func::Max,
+                        vec![
+                            calculation(d6(1), Operation::Add, Term::Constant(1)),
+                            Term::SubTerm(Box::new(Term::Constant(2)))
+                        ]
+                    ),
+                    Operation::Mul,
+                    Term::Constant(2)
+                )
+            ))
+        );
+        // without arguments there is no function, just a variable
+        assert!(parse("min()").is_err());
+        assert_eq!(parse_term("max"), Ok(("", Term::Var("max".to_string()))));
+        assert_eq!(
+            parse_term("floor(1d6 / 2)").map(|(rest, term)| (rest, term.to_string())),
+            Ok(("", "floor(1d6 / 2)".to_string()))
+        );
+        assert!(parse("round(1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_parse_negation() {
+        let d = |throws, faces| {
+            Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(Dice {
+                throws,
+                dice: DiceType::Number(faces),
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            })))
+        };
+        let negate = |term| Term::Negate(Box::new(term));
+        assert_eq!(parse_term("-1d6"), Ok(("", negate(d(1, 6)))));
+        assert_eq!(parse_term("- d6"), Ok(("", negate(d(1, 6)))));
+        assert_eq!(parse_term("-5"), Ok(("", Term::Constant(-5))));
+        assert_eq!(
+            parse_term("-(1d4+2)"),
+            Ok((
+                "",
+                negate(Term::SubTerm(Box::new(calculation(
+                    d(1, 4),
+                    Operation::Add,
+                    Term::Constant(2)
+                ))))
+            ))
+        );
+        // tighter than any operation
+        assert_eq!(
+            parse_term("2 * -1d6 ^ 2"),
+            Ok((
+                "",
+                calculation(
+                    Term::Constant(2),
+                    Operation::Mul,
+                    calculation(negate(d(1, 6)), Operation::Pow, Term::Constant(2))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_term("1d6 - -1d4"),
+            Ok(("", calculation(d(1, 6), Operation::Sub, negate(d(1, 4)))))
+        );
+        assert_eq!(
+            parse_term("1d6-1d4"),
+            Ok(("", calculation(d(1, 6), Operation::Sub, d(1, 4))))
+        );
+        for input in [
+            "-1d6",
+            "-(1d4 + 2)",
+            "1d6 - -1d4",
+            "-d(2)",
+            "--5",
+            "-1d20#hit",
+        ] {
+            let (_, term) = parse_term(input).unwrap();
+            assert_eq!(parse_term(&term.to_string()), Ok(("", term)), "{}", input);
+        }
+        assert_eq!(parse_term("-1d6").unwrap().1.to_string(), "-1d6");
+    }
+
+    #[test]
+    fn test_parse_var() {
+        let d20 = Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(Dice {
+            throws: 1,
+            dice: DiceType::Number(20),
+            explode: None,
+            dynamic_throws: None,
+            reroll: None,
+        })));
+        let term = calculation(d20, Operation::Add, Term::Var("STR".to_string()));
+        assert_eq!(parse_term("1d20 + STR"), Ok(("", term.clone())));
+        assert_eq!(term.to_string(), "1d20 + STR");
+        assert_eq!(
+            parse_term("d6"),
+            parse_term("1d6"),
+            "dice take precedence over names"
+        );
+        assert_eq!(
+            parse_term("dex_mod2"),
+            Ok(("", Term::Var("dex_mod2".to_string())))
+        );
+        let dynamic = parse_term("(lvl)d6 + 2d(HD) + str").unwrap().1;
+        assert_eq!(dynamic.variables(), vec!["lvl", "HD", "str"]);
+        let mut substituted = dynamic.clone();
+        let vars = vec![("lvl".to_string(), 3), ("HD".to_string(), 8)]
+            .into_iter()
+            .collect();
+        assert_eq!(substituted.substitute(&vars), Err("str".to_string()));
+        let mut substituted = dynamic;
+        let vars = vec![
+            ("lvl".to_string(), 3),
+            ("HD".to_string(), 8),
+            ("str".to_string(), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(substituted.substitute(&vars), Ok(()));
+        assert_eq!(substituted.to_string(), "(3)d6 + 2d(8) + 1");
+    }
+
+    #[test]
+    fn test_parse_multi() {
+        let d20 = Expression::Simple(Term::DiceThrow(SelectedDice::Unchanged(
+            FilteredDice::Simple(Dice {
+                throws: 1,
+                dice: DiceType::Number(20),
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            }),
+        )));
+        assert_eq!(
+            parse_multi("1d20 ; 5"),
+            Ok((
+                "",
+                vec![
+                    LabeledExpression::Unlabeled(d20.clone()),
+                    LabeledExpression::Unlabeled(Expression::Simple(Term::Constant(5)))
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_multi("1d20#to hit;5#damage"),
+            Ok((
+                "",
+                vec![
+                    LabeledExpression::Labeled(d20.clone(), "to hit".to_string()),
+                    LabeledExpression::Labeled(
+                        Expression::Simple(Term::Constant(5)),
+                        "damage".to_string()
+                    )
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_multi("1d20"),
+            Ok(("", vec![LabeledExpression::Unlabeled(d20)]))
+        );
+        assert_eq!(parse_multi("1d20;").map(|r| r.0), Ok(";"));
+    }
+
+    #[test]
+    fn test_parse_narrative_pool() {
+        assert_eq!(
+            parse_narrative_pool("2a 1P 2d c"),
+            Ok((
+                "",
+                NarrativePool(vec![
+                    (2, NarrativeDie::Ability),
+                    (1, NarrativeDie::Proficiency),
+                    (2, NarrativeDie::Difficulty),
+                    (1, NarrativeDie::Challenge)
+                ])
+            ))
+        );
+        assert_eq!(
+            parse_narrative_pool("3b2s").map(|p| p.1.to_string()),
+            Ok("3b2s".to_string())
+        );
+        assert!(parse_narrative_pool("100a").is_ok());
+        assert!(parse_narrative_pool("100a 1d").is_err());
+        assert!(parse_narrative_pool("2x").is_err());
+    }
 }
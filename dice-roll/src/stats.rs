@@ -0,0 +1,1027 @@
+/*
+Copyright 2021 Robin Marchart
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::dice_types::*;
+use std::{convert::TryFrom, fmt};
+
+#[cfg(feature = "roll")]
+use crate::dice_roll::{EvaluationErrors, EvaluationLimits, TermEvaluate};
+#[cfg(feature = "roll")]
+use rand::Rng;
+use std::collections::BTreeMap;
+
+/// largest dice pool the exact binomial computation is done for
+pub const MAX_POOL_SIZE: u32 = 10_000;
+
+/// largest base face count of a multiplying die the success probability is computed for
+pub const MAX_MULTIPLY_FACES: u32 = 1_000_000;
+
+/// largest number of distinct values a distribution may span
+pub const MAX_DISTRIBUTION_SIZE: usize = 100_000;
+
+/// largest number of multiplications spent on an exact distribution
+pub const MAX_CONVOLUTION_STEPS: u64 = 50_000_000;
+
+/// number of rolls a distribution is estimated from if it can't be computed exactly
+pub const DISTRIBUTION_SAMPLES: u32 = 100_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StatsError {
+    Unsupported,
+    TooLarge,
+}
+
+impl fmt::Display for StatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsError::Unsupported => {
+                write!(f, "unsupported expression")
+            }
+            StatsError::TooLarge => {
+                write!(f, "expression too large")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StatsError {}
+
+/// number of integers in `low..=high` standing in relation `filter` to `target`
+fn count_matching(low: i64, high: i64, filter: Filter, target: i64) -> u64 {
+    let (from, to) = match filter {
+        Filter::Bigger => (low.max(target + 1), high),
+        Filter::BiggerEq => (low.max(target), high),
+        Filter::Smaller => (low, high.min(target - 1)),
+        Filter::SmallerEq => (low, high.min(target)),
+        Filter::NotEq => {
+            let all = (high - low + 1) as u64;
+            return if (low..=high).contains(&target) {
+                all - 1
+            } else {
+                all
+            };
+        }
+    };
+    if from > to {
+        0
+    } else {
+        (to - from + 1) as u64
+    }
+}
+
+/// probability of a single die of `dice` being kept by `filter` `target`
+pub fn success_probability(
+    dice: &DiceType,
+    filter: Filter,
+    target: u32,
+) -> Result<f64, StatsError> {
+    let target = i64::from(target);
+    match *dice {
+        DiceType::Number(faces) => {
+            let faces = i64::from(faces);
+            Ok(count_matching(1, faces, filter, target) as f64 / faces as f64)
+        }
+        DiceType::Fudge { sides } => {
+            let sides = i64::from(sides);
+            Ok(count_matching(-sides, sides, filter, target) as f64 / (2 * sides + 1) as f64)
+        }
+        DiceType::Multiply(faces) => {
+            if faces > MAX_MULTIPLY_FACES {
+                return Err(StatsError::TooLarge);
+            }
+            let faces = i64::from(faces);
+            // for a fixed first die a, a*b stands in relation to target exactly
+            // when b stands in the same relation to target/a rounded accordingly
+            let matching: u64 = (1..=faces)
+                .map(|a| match filter {
+                    Filter::Bigger => count_matching(1, faces, Filter::Bigger, target / a),
+                    Filter::BiggerEq => {
+                        count_matching(1, faces, Filter::BiggerEq, (target + a - 1) / a)
+                    }
+                    Filter::Smaller => {
+                        count_matching(1, faces, Filter::Smaller, (target + a - 1) / a)
+                    }
+                    Filter::SmallerEq => count_matching(1, faces, Filter::SmallerEq, target / a),
+                    Filter::NotEq => {
+                        if target % a == 0 {
+                            count_matching(1, faces, Filter::NotEq, target / a)
+                        } else {
+                            faces as u64
+                        }
+                    }
+                })
+                .sum();
+            Ok(matching as f64 / (faces * faces) as f64)
+        }
+        DiceType::Dynamic(_) => Err(StatsError::Unsupported),
+    }
+}
+
+/// distribution of the number of successes in a dice pool
+#[derive(Debug, PartialEq, Clone)]
+pub struct PoolOdds {
+    /// expected number of successes
+    pub expected: f64,
+    /// probability of exactly `k` successes at index `k`
+    pub distribution: Vec<f64>,
+}
+
+impl PoolOdds {
+    /// probability of at least `k` successes
+    pub fn at_least(&self, k: usize) -> f64 {
+        self.distribution.iter().skip(k).sum::<f64>().min(1.0)
+    }
+}
+
+/// exact binomial distribution of the number of dice kept by the filter of `dice`
+///
+/// unfiltered dice always succeed, exploding and rerolled dice are not supported
+pub fn pool_odds(dice: &FilteredDice) -> Result<PoolOdds, StatsError> {
+    let (dice, p) = match dice {
+        FilteredDice::Simple(d) => (d, 1.0),
+        FilteredDice::Filtered(d, filter, target) => {
+            (d, success_probability(&d.dice, *filter, *target)?)
+        }
+    };
+    if dice.explode.is_some() || dice.dynamic_throws.is_some() || dice.reroll.is_some() {
+        return Err(StatsError::Unsupported);
+    }
+    if dice.throws > MAX_POOL_SIZE {
+        return Err(StatsError::TooLarge);
+    }
+    let n = dice.throws as usize;
+    let distribution = if p <= 0.0 {
+        let mut d = vec![0.0; n + 1];
+        d[0] = 1.0;
+        d
+    } else if p >= 1.0 {
+        let mut d = vec![0.0; n + 1];
+        d[n] = 1.0;
+        d
+    } else {
+        // ln(k!) for k in 0..=n
+        let mut ln_fact = Vec::with_capacity(n + 1);
+        ln_fact.push(0.0f64);
+        for i in 1..=n {
+            ln_fact.push(ln_fact[i - 1] + (i as f64).ln());
+        }
+        (0..=n)
+            .map(|k| {
+                (ln_fact[n] - ln_fact[k] - ln_fact[n - k]
+                    + k as f64 * p.ln()
+                    + (n - k) as f64 * (1.0 - p).ln())
+                .exp()
+            })
+            .collect()
+    };
+    Ok(PoolOdds {
+        expected: n as f64 * p,
+        distribution,
+    })
+}
+
+/// probability mass function of the result of a term
+#[derive(Debug, PartialEq, Clone)]
+pub struct Distribution {
+    /// smallest possible result
+    pub min: i64,
+    /// probability of the result `min + i` at index `i`
+    pub probabilities: Vec<f64>,
+    /// false if the distribution was estimated by sampling
+    pub exact: bool,
+}
+
+impl Distribution {
+    fn constant(value: i64) -> Distribution {
+        Distribution {
+            min: value,
+            probabilities: vec![1.0],
+            exact: true,
+        }
+    }
+
+    /// uniform distribution over `min..min + faces`
+    fn uniform(min: i64, faces: usize) -> Result<Distribution, StatsError> {
+        if faces > MAX_DISTRIBUTION_SIZE {
+            return Err(StatsError::TooLarge);
+        }
+        Ok(Distribution {
+            min,
+            probabilities: vec![1.0 / faces as f64; faces],
+            exact: true,
+        })
+    }
+
+    /// largest possible result
+    pub fn max(&self) -> i64 {
+        self.min + self.probabilities.len() as i64 - 1
+    }
+
+    /// probability of exactly `value`
+    pub fn probability(&self, value: i64) -> f64 {
+        value
+            .checked_sub(self.min)
+            .and_then(|i| usize::try_from(i).ok())
+            .and_then(|i| self.probabilities.get(i))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// probability of at least `value`
+    pub fn at_least(&self, value: i64) -> f64 {
+        let skip = value.saturating_sub(self.min).max(0);
+        let skip = usize::try_from(skip).unwrap_or(usize::MAX);
+        self.probabilities.iter().skip(skip).sum::<f64>().min(1.0)
+    }
+
+    /// expected result
+    pub fn mean(&self) -> f64 {
+        self.probabilities
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (self.min as f64 + i as f64) * p)
+            .sum()
+    }
+
+    fn negate(mut self) -> Result<Distribution, StatsError> {
+        self.min = self.max().checked_neg().ok_or(StatsError::TooLarge)?;
+        self.probabilities.reverse();
+        Ok(self)
+    }
+
+    /// distribution of the sum of two independent results
+    fn convolve(
+        &self,
+        other: &Distribution,
+        budget: &mut Budget,
+    ) -> Result<Distribution, StatsError> {
+        let len = self.probabilities.len() + other.probabilities.len() - 1;
+        budget.steps = budget
+            .steps
+            .checked_sub(self.probabilities.len() as u64 * other.probabilities.len() as u64)
+            .ok_or(StatsError::TooLarge)?;
+        if len > budget.size {
+            return Err(StatsError::TooLarge);
+        }
+        let mut probabilities = vec![0.0; len];
+        for (i, a) in self.probabilities.iter().enumerate() {
+            for (j, b) in other.probabilities.iter().enumerate() {
+                probabilities[i + j] += a * b;
+            }
+        }
+        Ok(Distribution {
+            min: self
+                .min
+                .checked_add(other.min)
+                .ok_or(StatsError::TooLarge)?,
+            probabilities,
+            exact: self.exact && other.exact,
+        })
+    }
+
+    /// distribution of the result multiplied by `factor`
+    fn scale(self, factor: i64, budget: &Budget) -> Result<Distribution, StatsError> {
+        if factor == 0 {
+            return Ok(Distribution::constant(0));
+        }
+        if factor < 0 {
+            return self.negate()?.scale(-factor, budget);
+        }
+        let stride = usize::try_from(factor).map_err(|_| StatsError::TooLarge)?;
+        let len = (self.probabilities.len() - 1)
+            .checked_mul(stride)
+            .filter(|len| *len < budget.size)
+            .ok_or(StatsError::TooLarge)?
+            + 1;
+        let mut probabilities = vec![0.0; len];
+        for (i, p) in self.probabilities.iter().enumerate() {
+            probabilities[i * stride] = *p;
+        }
+        Ok(Distribution {
+            min: self.min.checked_mul(factor).ok_or(StatsError::TooLarge)?,
+            probabilities,
+            exact: self.exact,
+        })
+    }
+
+    /// the single possible result, if there is only one
+    fn as_constant(&self) -> Option<i64> {
+        match self.probabilities.as_slice() {
+            [_] => Some(self.min),
+            _ => None,
+        }
+    }
+}
+
+/// limits of the computation of an exact distribution
+struct Budget {
+    /// most values a distribution may span
+    size: usize,
+    /// multiplications left to spend
+    steps: u64,
+}
+
+impl Budget {
+    fn new(size: usize) -> Budget {
+        Budget {
+            size,
+            steps: MAX_CONVOLUTION_STEPS,
+        }
+    }
+}
+
+fn single_die_distribution(dice: &DiceType) -> Result<Distribution, StatsError> {
+    match *dice {
+        DiceType::Number(faces) => Distribution::uniform(1, faces as usize),
+        DiceType::Fudge { sides } => {
+            Distribution::uniform(-i64::from(sides), 2 * sides as usize + 1)
+        }
+        DiceType::Multiply(faces) => {
+            let faces = faces as usize;
+            let len = faces.checked_mul(faces).ok_or(StatsError::TooLarge)?;
+            if len > MAX_DISTRIBUTION_SIZE {
+                return Err(StatsError::TooLarge);
+            }
+            let p = 1.0 / len as f64;
+            let mut probabilities = vec![0.0; len];
+            for a in 1..=faces {
+                for b in 1..=faces {
+                    probabilities[a * b - 1] += p;
+                }
+            }
+            Ok(Distribution {
+                min: 1,
+                probabilities,
+                exact: true,
+            })
+        }
+        DiceType::Dynamic(_) => Err(StatsError::Unsupported),
+    }
+}
+
+/// sum of `dice.throws` dice, convolved by repeated squaring
+fn dice_distribution(dice: &Dice, budget: &mut Budget) -> Result<Distribution, StatsError> {
+    if dice.explode.is_some() || dice.dynamic_throws.is_some() || dice.reroll.is_some() {
+        return Err(StatsError::Unsupported);
+    }
+    let mut square = single_die_distribution(&dice.dice)?;
+    let mut result = Distribution::constant(0);
+    let mut throws = dice.throws;
+    while throws > 0 {
+        if throws & 1 == 1 {
+            result = result.convolve(&square, budget)?;
+        }
+        throws >>= 1;
+        if throws > 0 {
+            square = square.convolve(&square, budget)?;
+        }
+    }
+    Ok(result)
+}
+
+fn exact(term: &Term, budget: &mut Budget) -> Result<Distribution, StatsError> {
+    match term {
+        Term::Constant(c) => Ok(Distribution::constant(*c)),
+        Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) => exact(t, budget),
+        Term::Negate(t) => exact(t, budget)?.negate(),
+        Term::DiceThrow(SelectedDice::Unchanged(FilteredDice::Simple(dice))) => {
+            dice_distribution(dice, budget)
+        }
+        Term::Calculation(l, Operation::Add, r) => {
+            exact(l, budget)?.convolve(&exact(r, budget)?, budget)
+        }
+        Term::Calculation(l, Operation::Sub, r) => {
+            exact(l, budget)?.convolve(&exact(r, budget)?.negate()?, budget)
+        }
+        Term::Calculation(l, Operation::Mul, r) => {
+            let (l, r) = (exact(l, budget)?, exact(r, budget)?);
+            match (l.as_constant(), r.as_constant()) {
+                (_, Some(factor)) => l.scale(factor, budget),
+                (Some(factor), _) => r.scale(factor, budget),
+                _ => Err(StatsError::Unsupported),
+            }
+        }
+        _ => Err(StatsError::Unsupported),
+    }
+}
+
+/// exact distribution of sums and differences of simple dice and constants,
+/// which may be multiplied by constants
+pub fn exact_distribution(term: &Term) -> Result<Distribution, StatsError> {
+    exact(term, &mut Budget::new(MAX_DISTRIBUTION_SIZE))
+}
+
+impl Expression {
+    /// probability of each possible result, of each entry for lists
+    ///
+    /// fails with [StatsError::TooLarge] if the results span more than
+    /// `max_outcomes` values
+    pub fn distribution(&self, max_outcomes: usize) -> Result<BTreeMap<i64, f64>, StatsError> {
+        let distribution = exact(self.term(), &mut Budget::new(max_outcomes))?;
+        if distribution.probabilities.len() > max_outcomes {
+            return Err(StatsError::TooLarge);
+        }
+        Ok((distribution.min..)
+            .zip(distribution.probabilities)
+            .filter(|(_, p)| *p > 0.0)
+            .collect())
+    }
+}
+
+/// distribution estimated from `samples` rolls of `term`, each rolling at most `dice_limit` dice
+#[cfg(feature = "roll")]
+pub fn sampled_distribution<T: FnMut() -> bool, R: Rng>(
+    term: &Term,
+    samples: u32,
+    dice_limit: u64,
+    timeout_f: &mut T,
+    rng: &mut R,
+) -> Result<Distribution, StatsError> {
+    let samples = samples.max(1);
+    let mut counts = BTreeMap::new();
+    for _ in 0..samples {
+        let (value, _) = term
+            .evaluate_limited(timeout_f, &mut EvaluationLimits::new(dice_limit), rng)
+            .map_err(|err| match err {
+                EvaluationErrors::Timeout { .. } | EvaluationErrors::DiceLimitExceeded => {
+                    StatsError::TooLarge
+                }
+                _ => StatsError::Unsupported,
+            })?;
+        *counts.entry(value).or_insert(0u32) += 1;
+    }
+    let (min, max) = match (counts.keys().next(), counts.keys().next_back()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return Err(StatsError::Unsupported),
+    };
+    let len = max
+        .checked_sub(min)
+        .and_then(|d| usize::try_from(d).ok())
+        .filter(|d| *d < MAX_DISTRIBUTION_SIZE)
+        .ok_or(StatsError::TooLarge)?
+        + 1;
+    let mut probabilities = vec![0.0; len];
+    for (value, count) in counts {
+        probabilities[(value - min) as usize] = f64::from(count) / f64::from(samples);
+    }
+    Ok(Distribution {
+        min,
+        probabilities,
+        exact: false,
+    })
+}
+
+/// exact distribution of `term` if feasible, estimated by sampling otherwise
+///
+/// each sample rolls at most `dice_limit` dice
+#[cfg(feature = "roll")]
+pub fn distribution<T: FnMut() -> bool, R: Rng>(
+    term: &Term,
+    dice_limit: u64,
+    timeout_f: &mut T,
+    rng: &mut R,
+) -> Result<Distribution, StatsError> {
+    exact_distribution(term)
+        .or_else(|_| sampled_distribution(term, DISTRIBUTION_SAMPLES, dice_limit, timeout_f, rng))
+}
+
+/// largest number of outcomes enumerated for the statistics of selected dice
+pub const MAX_SELECTION_OUTCOMES: u64 = 1_000_000;
+
+/// expected value and variance of a result
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Statistics {
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl Statistics {
+    fn constant(value: f64) -> Statistics {
+        Statistics {
+            mean: value,
+            variance: 0.0,
+        }
+    }
+
+    /// statistics from the expected value of the result and of its square
+    fn from_moments(mean: f64, square_mean: f64) -> Statistics {
+        Statistics {
+            mean,
+            variance: (square_mean - mean * mean).max(0.0),
+        }
+    }
+
+    fn square_mean(&self) -> f64 {
+        self.variance + self.mean * self.mean
+    }
+
+    /// statistics of the sum of `n` independent results
+    fn repeat(self, n: u32) -> Statistics {
+        Statistics {
+            mean: self.mean * f64::from(n),
+            variance: self.variance * f64::from(n),
+        }
+    }
+
+    /// standard deviation of the result
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// how many standard deviations `value` is above the mean, `None` if the result can't vary
+    pub fn z_score(&self, value: i64) -> Option<f64> {
+        if self.variance > 0.0 {
+            Some((value as f64 - self.mean) / self.std_dev())
+        } else {
+            None
+        }
+    }
+}
+
+/// filter and target deciding which dice are kept
+type KeptBy = Option<(Filter, u32)>;
+
+/// sums of `x` and `x²` over all `x` in `from..=to` for positive `from`
+fn range_sums(from: i64, to: i64) -> (f64, f64) {
+    if from > to {
+        return (0.0, 0.0);
+    }
+    let sum = |n: f64| n * (n + 1.0) / 2.0;
+    let squares = |n: f64| n * (n + 1.0) * (2.0 * n + 1.0) / 6.0;
+    let (low, high) = ((from - 1) as f64, to as f64);
+    (sum(high) - sum(low), squares(high) - squares(low))
+}
+
+/// faces of a single die with nonzero probability
+fn die_outcomes(dice: &DiceType) -> Result<Vec<(i64, f64)>, StatsError> {
+    let distribution = single_die_distribution(dice)?;
+    Ok(distribution
+        .probabilities
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| **p > 0.0)
+        .map(|(i, p)| (distribution.min + i as i64, *p))
+        .collect())
+}
+
+/// statistics of a single die, counting it as 0 if it isn't kept by `filter`
+fn die_statistics(dice: &DiceType, filter: KeptBy) -> Result<Statistics, StatsError> {
+    match (dice, filter) {
+        (DiceType::Number(faces), None) => {
+            let faces = f64::from(*faces);
+            Ok(Statistics {
+                mean: (faces + 1.0) / 2.0,
+                variance: (faces * faces - 1.0) / 12.0,
+            })
+        }
+        (DiceType::Number(faces), Some((filter, target))) => {
+            let (faces, target) = (i64::from(*faces), i64::from(target));
+            let (sum, squares) = match filter {
+                Filter::Bigger => range_sums(target + 1, faces),
+                Filter::BiggerEq => range_sums(target.max(1), faces),
+                Filter::Smaller => range_sums(1, faces.min(target - 1)),
+                Filter::SmallerEq => range_sums(1, faces.min(target)),
+                Filter::NotEq => {
+                    let (sum, squares) = range_sums(1, faces);
+                    if (1..=faces).contains(&target) {
+                        let target = target as f64;
+                        (sum - target, squares - target * target)
+                    } else {
+                        (sum, squares)
+                    }
+                }
+            };
+            let faces = faces as f64;
+            Ok(Statistics::from_moments(sum / faces, squares / faces))
+        }
+        (DiceType::Multiply(faces), None) => {
+            // the product of two independent dice
+            let faces = f64::from(*faces);
+            let mean = (faces + 1.0) / 2.0;
+            let square_mean = (faces + 1.0) * (2.0 * faces + 1.0) / 6.0;
+            Ok(Statistics::from_moments(
+                mean * mean,
+                square_mean * square_mean,
+            ))
+        }
+        (DiceType::Dynamic(_), _) => Err(StatsError::Unsupported),
+        (dice, filter) => {
+            let (mean, square_mean) = die_outcomes(dice)?
+                .into_iter()
+                .filter(|(value, _)| filter.is_none_or(|(f, t)| f.matches(*value, t)))
+                .fold((0.0, 0.0), |(mean, square_mean), (value, p)| {
+                    let value = value as f64;
+                    (mean + value * p, square_mean + value * value * p)
+                });
+            Ok(Statistics::from_moments(mean, square_mean))
+        }
+    }
+}
+
+/// plain dice and their filter, exploding, rerolled and dynamic dice are not supported
+fn plain_dice(dice: &FilteredDice) -> Result<(&Dice, KeptBy), StatsError> {
+    let (dice, filter) = match dice {
+        FilteredDice::Simple(d) => (d, None),
+        FilteredDice::Filtered(d, filter, target) => (d, Some((*filter, *target))),
+    };
+    if dice.explode.is_some() || dice.dynamic_throws.is_some() || dice.reroll.is_some() {
+        return Err(StatsError::Unsupported);
+    }
+    Ok((dice, filter))
+}
+
+/// statistics of selected dice, enumerating every possible roll until `timeout_f` is true
+fn selected_statistics<T: FnMut() -> bool>(
+    dice: &FilteredDice,
+    selector: Selector,
+    count: u32,
+    timeout_f: &mut T,
+) -> Result<Statistics, StatsError> {
+    let (dice, filter) = plain_dice(dice)?;
+    let faces = die_outcomes(&dice.dice)?;
+    let outcomes = (faces.len() as u64)
+        .checked_pow(dice.throws)
+        .filter(|outcomes| *outcomes <= MAX_SELECTION_OUTCOMES)
+        .ok_or(StatsError::Unsupported)?;
+    let count = count as usize;
+    let mut indices = vec![0usize; dice.throws as usize];
+    let mut kept = Vec::with_capacity(indices.len());
+    let (mut mean, mut square_mean) = (0.0, 0.0);
+    let mut counter: u8 = 0;
+    for _ in 0..outcomes {
+        counter = counter.wrapping_add(1);
+        if counter == 0 && timeout_f() {
+            return Err(StatsError::TooLarge);
+        }
+        kept.clear();
+        let mut p = 1.0;
+        for i in &indices {
+            let (value, q) = faces[*i];
+            p *= q;
+            if filter.is_none_or(|(f, t)| f.matches(value, t)) {
+                kept.push(value);
+            }
+        }
+        kept.sort_unstable();
+        let len = kept.len();
+        let range = match selector {
+            Selector::Higher => len.saturating_sub(count)..len,
+            Selector::Lower => 0..count.min(len),
+            Selector::DropHigher => 0..len.saturating_sub(count),
+            Selector::DropLower => count.min(len)..len,
+        };
+        let value = kept[range].iter().sum::<i64>() as f64;
+        mean += value * p;
+        square_mean += value * value * p;
+        // advance to the next roll
+        for i in indices.iter_mut() {
+            *i += 1;
+            if *i < faces.len() {
+                break;
+            }
+            *i = 0;
+        }
+    }
+    Ok(Statistics::from_moments(mean, square_mean))
+}
+
+/// statistics of `term`, combining those of its independent parts
+///
+/// gives up with [StatsError::TooLarge] once `timeout_f` is true
+pub fn term_statistics<T: FnMut() -> bool>(
+    term: &Term,
+    timeout_f: &mut T,
+) -> Result<Statistics, StatsError> {
+    match term {
+        Term::Constant(c) => Ok(Statistics::constant(*c as f64)),
+        Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) => {
+            term_statistics(t, timeout_f)
+        }
+        Term::Var(_) | Term::Function(_, _) => Err(StatsError::Unsupported),
+        Term::Negate(t) => term_statistics(t, timeout_f).map(|s| Statistics {
+            mean: -s.mean,
+            variance: s.variance,
+        }),
+        Term::DiceThrow(SelectedDice::Unchanged(dice)) => {
+            let (dice, filter) = plain_dice(dice)?;
+            Ok(die_statistics(&dice.dice, filter)?.repeat(dice.throws))
+        }
+        Term::DiceThrow(SelectedDice::Selected(dice, selector, count)) => {
+            selected_statistics(dice, *selector, *count, timeout_f)
+        }
+        Term::SuccessCount(dice) => {
+            let (dice, filter) = plain_dice(dice)?;
+            let p = match filter {
+                Some((filter, target)) => success_probability(&dice.dice, filter, target)?,
+                None => 1.0,
+            };
+            Ok(Statistics {
+                mean: p,
+                variance: p * (1.0 - p),
+            }
+            .repeat(dice.throws))
+        }
+        Term::Calculation(l, op, r) => {
+            let (l, r) = (
+                term_statistics(l, timeout_f)?,
+                term_statistics(r, timeout_f)?,
+            );
+            match op {
+                Operation::Add => Ok(Statistics {
+                    mean: l.mean + r.mean,
+                    variance: l.variance + r.variance,
+                }),
+                Operation::Sub => Ok(Statistics {
+                    mean: l.mean - r.mean,
+                    variance: l.variance + r.variance,
+                }),
+                Operation::Mul => Ok(Statistics::from_moments(
+                    l.mean * r.mean,
+                    l.square_mean() * r.square_mean(),
+                )),
+                Operation::Div | Operation::Mod | Operation::Pow => Err(StatsError::Unsupported),
+            }
+        }
+    }
+}
+
+impl Expression {
+    /// expected value and variance of the result, of each entry for lists
+    pub fn statistics(&self) -> Result<Statistics, StatsError> {
+        self.statistics_with_timeout(&mut || false)
+    }
+
+    /// same as [Expression::statistics], but gives up with [StatsError::TooLarge] once
+    /// `timeout_f` is true
+    pub fn statistics_with_timeout<T: FnMut() -> bool>(
+        &self,
+        timeout_f: &mut T,
+    ) -> Result<Statistics, StatsError> {
+        term_statistics(self.term(), timeout_f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(throws: u32, dice: DiceType, filter: Filter, target: u32) -> FilteredDice {
+        FilteredDice::Filtered(
+            Dice {
+                throws,
+                dice,
+                explode: None,
+                dynamic_throws: None,
+                reroll: None,
+            },
+            filter,
+            target,
+        )
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_success_probability() {
+        assert_close(
+            success_probability(&DiceType::Number(10), Filter::BiggerEq, 8).unwrap(),
+            0.3,
+        );
+        assert_close(
+            success_probability(&DiceType::Number(6), Filter::Bigger, 6).unwrap(),
+            0.0,
+        );
+        assert_close(
+            success_probability(&DiceType::Fudge { sides: 1 }, Filter::NotEq, 1).unwrap(),
+            2.0 / 3.0,
+        );
+        assert_close(
+            success_probability(&DiceType::Fudge { sides: 2 }, Filter::BiggerEq, 1).unwrap(),
+            0.4,
+        );
+        // products of 2d2x: 1, 2, 2, 4
+        assert_close(
+            success_probability(&DiceType::Multiply(2), Filter::BiggerEq, 2).unwrap(),
+            0.75,
+        );
+        assert_close(
+            success_probability(&DiceType::Multiply(2), Filter::Smaller, 2).unwrap(),
+            0.25,
+        );
+        assert_close(
+            success_probability(&DiceType::Multiply(2), Filter::NotEq, 2).unwrap(),
+            0.5,
+        );
+    }
+
+    #[test]
+    fn test_pool_odds() {
+        let odds = pool_odds(&pool(10, DiceType::Number(10), Filter::BiggerEq, 8)).unwrap();
+        assert_close(odds.expected, 3.0);
+        assert_eq!(odds.distribution.len(), 11);
+        assert_close(odds.distribution.iter().sum(), 1.0);
+        assert_close(odds.distribution[0], 0.7f64.powi(10));
+        assert_close(odds.at_least(0), 1.0);
+        assert_close(odds.at_least(10), 0.3f64.powi(10));
+
+        let certain = pool_odds(&pool(3, DiceType::Number(6), Filter::BiggerEq, 1)).unwrap();
+        assert_eq!(certain.distribution, vec![0.0, 0.0, 0.0, 1.0]);
+
+        assert_eq!(
+            pool_odds(&pool(
+                MAX_POOL_SIZE + 1,
+                DiceType::Number(6),
+                Filter::Bigger,
+                3
+            )),
+            Err(StatsError::TooLarge)
+        );
+    }
+
+    #[cfg(feature = "parser")]
+    fn term(input: &str) -> Term {
+        crate::parser::parse_term(input).unwrap().1
+    }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_exact_distribution() {
+        let d6 = exact_distribution(&term("1d6")).unwrap();
+        assert_eq!((d6.min, d6.max()), (1, 6));
+        assert_close(d6.mean(), 3.5);
+
+        let sum = exact_distribution(&term("2d6 + 1")).unwrap();
+        assert!(sum.exact);
+        assert_eq!((sum.min, sum.max()), (3, 13));
+        assert_close(sum.probability(8), 6.0 / 36.0);
+        assert_close(sum.probability(3), 1.0 / 36.0);
+        assert_close(sum.probability(14), 0.0);
+        assert_close(sum.at_least(12), 3.0 / 36.0);
+        assert_close(sum.probabilities.iter().sum(), 1.0);
+
+        let diff = exact_distribution(&term("1d4 - 1d4")).unwrap();
+        assert_eq!((diff.min, diff.max()), (-3, 3));
+        assert_close(diff.probability(0), 0.25);
+        assert_close(diff.mean(), 0.0);
+
+        let negated = exact_distribution(&term("10 - -(1d4 + 2)")).unwrap();
+        assert_eq!((negated.min, negated.max()), (13, 16));
+        assert_close(negated.probability(13), 0.25);
+
+        let fudge = exact_distribution(&term("4dF")).unwrap();
+        assert_close(fudge.probability(4), 1.0 / 81.0);
+
+        // products of 1d2x: 1, 2, 2, 4
+        let multiply = exact_distribution(&term("1d2x")).unwrap();
+        assert_close(multiply.probability(2), 0.5);
+        assert_close(multiply.probability(3), 0.0);
+
+        assert_eq!(
+            exact_distribution(&term("4d6k3")),
+            Err(StatsError::Unsupported)
+        );
+        assert_eq!(
+            exact_distribution(&term("100000d100000")),
+            Err(StatsError::TooLarge)
+        );
+    }
+
+    #[cfg(all(feature = "roll", feature = "parser"))]
+    #[test]
+    fn test_sampled_distribution() {
+        use rand::{rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(0);
+        let keep = distribution(&term("4d6k3"), 1000, &mut || false, &mut rng).unwrap();
+        assert!(!keep.exact);
+        assert_eq!((keep.min, keep.max()), (3, 18));
+        assert!((keep.mean() - 12.24).abs() < 0.1);
+        assert_close(keep.probabilities.iter().sum(), 1.0);
+
+        let sum = distribution(&term("3d6"), 1000, &mut || false, &mut rng).unwrap();
+        assert!(sum.exact);
+
+        assert_eq!(
+            distribution(&term("1d6 / 0"), 1000, &mut || false, &mut rng),
+            Err(StatsError::Unsupported)
+        );
+        assert_eq!(
+            distribution(&term("1d6!"), 1000, &mut || true, &mut rng),
+            Err(StatsError::TooLarge)
+        );
+        // the dice limit applies to every sample, before any of its dice are rolled
+        assert_eq!(
+            distribution(&term("4000000000d6!"), 1000, &mut || false, &mut rng),
+            Err(StatsError::TooLarge)
+        );
+    }
+
+    #[cfg(feature = "parser")]
+    fn statistics(input: &str) -> Result<Statistics, StatsError> {
+        crate::parser::parse(input).unwrap().statistics()
+    }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_statistics() {
+        let sum = statistics("3d6").unwrap();
+        assert_close(sum.mean, 10.5);
+        assert_close(sum.variance, 8.75);
+
+        let diff = statistics("2d6 - 1d4 + 3").unwrap();
+        assert_close(diff.mean, 7.0 - 2.5 + 3.0);
+        assert_close(diff.variance, 70.0 / 12.0 + 15.0 / 12.0);
+
+        let fudge = statistics("4dF").unwrap();
+        assert_close(fudge.mean, 0.0);
+        assert_close(fudge.variance, 8.0 / 3.0);
+
+        // products of 1d2x: 1, 2, 2, 4
+        let multiply = statistics("1d2x").unwrap();
+        assert_close(multiply.mean, 2.25);
+        assert_close(multiply.variance, 25.0 / 4.0 - 2.25 * 2.25);
+
+        let scaled = statistics("2 * 1d6").unwrap();
+        assert_close(scaled.mean, 7.0);
+        assert_close(scaled.variance, 4.0 * 35.0 / 12.0);
+
+        // 4 and 6 are kept and summed
+        let filtered = statistics("1d6>3").unwrap();
+        assert_close(filtered.mean, 15.0 / 6.0);
+        assert_close(filtered.variance, 77.0 / 6.0 - 6.25);
+
+        let successes = statistics("10d10>=8s").unwrap();
+        assert_close(successes.mean, 3.0);
+        assert_close(successes.variance, 2.1);
+
+        let keep = statistics("4d6k3").unwrap();
+        assert_close(keep.mean, 15869.0 / 1296.0);
+        let dropped = statistics("4d6dl1").unwrap();
+        assert_eq!(dropped, keep);
+        let highest = statistics("2d20h1").unwrap();
+        assert_close(highest.mean, 13.825);
+
+        let list = statistics("6{3d6}").unwrap();
+        assert_close(list.mean, 10.5);
+
+        let d20 = statistics("1d20").unwrap();
+        assert_close(d20.z_score(20).unwrap(), 9.5 / (399.0f64 / 12.0).sqrt());
+        assert_close(d20.z_score(1).unwrap(), -9.5 / (399.0f64 / 12.0).sqrt());
+        assert_eq!(statistics("3").unwrap().z_score(3), None);
+
+        assert_eq!(statistics("20d20k1"), Err(StatsError::Unsupported));
+        assert_eq!(statistics("1d6!"), Err(StatsError::Unsupported));
+        assert_eq!(statistics("1d6 / 2"), Err(StatsError::Unsupported));
+        // 2^19 outcomes are enumerated, but not after the timeout
+        assert!(statistics("19d2k1").is_ok());
+        assert_eq!(
+            crate::parser::parse("19d2k1")
+                .unwrap()
+                .statistics_with_timeout(&mut || true),
+            Err(StatsError::TooLarge)
+        );
+    }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_expression_distribution() {
+        let distribution = |input: &str, max_outcomes: usize| {
+            crate::parser::parse(input)
+                .unwrap()
+                .distribution(max_outcomes)
+        };
+        let sum = distribution("2d6", 11).unwrap();
+        assert_eq!(sum.len(), 11);
+        assert_close(sum[&7], 6.0 / 36.0);
+        assert_close(sum.values().sum(), 1.0);
+
+        let scaled = distribution("2 * 1d4 + 1", 7).unwrap();
+        assert_eq!(scaled.keys().copied().collect::<Vec<_>>(), vec![3, 5, 7, 9]);
+        assert_close(scaled[&5], 0.25);
+
+        let negated = distribution("1dF * -3", 7).unwrap();
+        assert_eq!(negated.keys().copied().collect::<Vec<_>>(), vec![-3, 0, 3]);
+
+        let list = distribution("6{(1d4)*(2)}", 7).unwrap();
+        assert_close(list[&8], 0.25);
+
+        assert_eq!(distribution("2d6", 10), Err(StatsError::TooLarge));
+        assert_eq!(distribution("3 * 1d6", 15), Err(StatsError::TooLarge));
+        assert_eq!(distribution("1d6 * 1d6", 100), Err(StatsError::Unsupported));
+        assert_eq!(distribution("1d6!", 100), Err(StatsError::Unsupported));
+    }
+}
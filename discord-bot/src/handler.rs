@@ -1,9 +1,11 @@
 use async_trait::async_trait;
-use bot_utils::client_utils::ClientUtils;
+use bot_utils::client_utils::{Access, ClientUtils};
 use serenity::{
     model::{
-        channel::Message,
+        channel::{Message, Reaction, ReactionType},
+        gateway::Ready,
         id::{GuildId, UserId},
+        interactions::Interaction,
     },
     prelude::EventHandler,
 };
@@ -12,62 +14,179 @@ pub(crate) struct DiscordBotHandler {
     pub(crate) guild_utils: ClientUtils<GuildId>,
     pub(crate) dm_utils: ClientUtils<UserId>,
     pub(crate) invite_url: String,
+    pub(crate) rerolls: Rerolls,
+    /// shown when a roll times out, see [DEFAULT_TIMEOUT_MESSAGE]
+    pub(crate) timeout_message: String,
+    /// largest roll whose dice are shown with the roll info
+    pub(crate) roll_info_limits: RollInfoLimits,
+    /// register slash commands once connected
+    pub(crate) slash_commands: bool,
+    /// everyone may reroll a result, not just whoever requested it
+    pub(crate) reroll_by_anyone: bool,
 }
 #[async_trait]
 impl EventHandler for DiscordBotHandler {
     async fn message(&self, ctx: serenity::client::Context, message: Message) {
         if message.author.bot {
-        } else if let Some(guild) = message.guild_id {
+            return;
+        }
+        let content = with_attached_config(&message).await;
+        let channel = message.channel_id.to_string();
+        if let Some(guild) = message.guild_id {
             if let Some(response) = self
                 .guild_utils
-                .eval(guild, &message.content, || {
-                    check_priviledged_access(&ctx, &message)
-                })
+                .eval(
+                    guild,
+                    &message.author.id.to_string(),
+                    Some(&channel),
+                    &content,
+                    || check_priviledged_access(&ctx, &message),
+                )
                 .await
             {
-                respond(ctx, message, response, &self.invite_url).await;
+                respond(ctx, message, response, self).await;
             }
         } else if let Some(response) = self
             .dm_utils
-            .eval(message.author.id, &message.content, || {
-                std::future::ready(true)
-            })
+            .eval(
+                message.author.id,
+                &message.author.id.to_string(),
+                Some(&channel),
+                &content,
+                || std::future::ready(Access::from(true)),
+            )
             .await
         {
-            respond(ctx, message, response, &self.invite_url).await;
+            respond(ctx, message, response, self).await;
+        }
+    }
+
+    async fn ready(&self, ctx: serenity::client::Context, ready: Ready) {
+        log::info!("connected as {}", ready.user.name);
+        if self.slash_commands {
+            register_commands(&ctx).await;
+        }
+    }
+
+    async fn interaction_create(&self, ctx: serenity::client::Context, interaction: Interaction) {
+        let interaction = match interaction {
+            Interaction::ApplicationCommand(interaction) => interaction,
+            _ => return,
+        };
+        let text = match slash_command_text(&interaction.data.name, &interaction.data.options) {
+            Some(text) => text,
+            None => {
+                log::warn!("unknown slash command {}", interaction.data.name);
+                return;
+            }
+        };
+        let access = slash_command_access(&interaction);
+        let user = interaction.user.id.to_string();
+        let channel = interaction.channel_id.to_string();
+        let check = || std::future::ready(access);
+        let response = match interaction.guild_id {
+            Some(guild) => {
+                self.guild_utils
+                    .eval_command(guild, &user, Some(&channel), &text, check)
+                    .await
+            }
+            None => {
+                self.dm_utils
+                    .eval_command(interaction.user.id, &user, Some(&channel), &text, check)
+                    .await
+            }
+        };
+        // a paused client ignores slash commands just like text commands
+        if let Some(response) = response {
+            // only the user who asked for a secret roll sees its result
+            let ephemeral = matches!(response, CommandResult::PrivateRoll(..));
+            respond_slash_command(
+                &ctx,
+                &interaction,
+                slash_command_response(response, &self.timeout_message),
+                ephemeral,
+            )
+            .await;
+        }
+    }
+
+    async fn reaction_add(&self, ctx: serenity::client::Context, reaction: Reaction) {
+        if !matches!(&reaction.emoji, ReactionType::Unicode(e) if *e == REROLL_EMOJI.to_string()) {
+            return;
+        }
+        let user = match reaction.user_id {
+            Some(user) if user != ctx.cache.current_user_id().await => user,
+            _ => return,
+        };
+        let Reroll {
+            request,
+            expressions,
+        } = match self.rerolls.get(reaction.message_id) {
+            Some(reroll) => reroll,
+            None => {
+                log::debug!("no roll to repeat for message {}", reaction.message_id);
+                return;
+            }
+        };
+        if !self.reroll_by_anyone && user != request.author.id {
+            log::debug!("{} may not repeat the roll of {}", user, request.author.id);
+            return;
+        }
+        // the roll is repeated as if its author had requested it again
+        let author = request.author.id.to_string();
+        let channel = request.channel_id.to_string();
+        let response = match request.guild_id {
+            Some(guild) => {
+                self.guild_utils
+                    .reroll(guild, &author, Some(&channel), expressions)
+                    .await
+            }
+            None => {
+                self.dm_utils
+                    .reroll(request.author.id, &author, Some(&channel), expressions)
+                    .await
+            }
+        };
+        if let Some(response) = response {
+            respond(ctx, request, response, self).await
         }
     }
 }
 
-async fn check_priviledged_access(context: &serenity::client::Context, message: &Message) -> bool {
+/// the guild owner and members with the administrator permission may do everything,
+/// other members only what their roles allow
+async fn check_priviledged_access(
+    context: &serenity::client::Context,
+    message: &Message,
+) -> Access {
     match message.guild_id {
         Some(guild) => match guild.to_partial_guild(&context).await {
             Ok(g) => {
                 if g.owner_id == message.author.id {
-                    true
+                    Access::from(true)
                 } else {
                     match g.member(&context, message.author.id).await {
-                        Ok(member) => {
-                            for roll in member.roles.iter().map(|id| g.roles.get(id)).flatten() {
-                                if roll.permissions.administrator() {
-                                    return true;
-                                }
-                            }
-                            false
-                        }
+                        Ok(member) => Access {
+                            administrator: member
+                                .roles
+                                .iter()
+                                .filter_map(|id| g.roles.get(id))
+                                .any(|role| role.permissions.administrator()),
+                            roles: member.roles.iter().map(|id| id.to_string()).collect(),
+                        },
                         Err(err) => {
                             log::warn!("unable to get member {}: {}", &message.author.id, err);
-                            false
+                            Access::default()
                         }
                     }
                 }
             }
             Err(err) => {
                 log::warn!("unable to retrieve guild {}: {}", &guild, err);
-                false
+                Access::default()
             }
         },
-        None => true, //user is always allowed to run every command in dm channels
+        None => Access::from(true), //user is always allowed to run every command in dm channels
     }
 }
 
@@ -75,31 +194,72 @@ use bot_utils::client_utils::CommandResult;
 
 mod help;
 use help::help;
+mod interaction;
+use interaction::{
+    register_commands, respond_slash_command, slash_command_access, slash_command_response,
+    slash_command_text,
+};
+mod roll_help;
+use roll_help::roll_help;
 mod command_prefix;
-use command_prefix::{get_command_prefix, set_command_prefix};
+use command_prefix::{
+    get_command_prefix, prefix_conflict, set_channel_command_prefix, set_command_prefix,
+};
 mod roll_prefix;
-use roll_prefix::{add_roll_prefix, list_roll_prefix, remove_roll_prefix};
+use roll_prefix::{
+    add_roll_prefix, clear_roll_prefixes, get_roll_shorthand, list_roll_prefix, remove_roll_prefix,
+    set_roll_shorthand,
+};
 mod alias;
-use alias::{add_alias, list_aliases, remove_alias};
+use alias::{
+    add_alias, alias_limit_reached, clear_aliases, copy_alias, invalid_alias_call, list_alias_page,
+    list_aliases, remove_alias, set_alias_hints, unknown_alias,
+};
+mod reroll;
+pub(crate) use reroll::Rerolls;
+use reroll::{Reroll, REROLL_EMOJI};
 mod roll;
-use roll::roll;
+use roll::{busy, nothing_to_repeat, private_roll, rate_limited, roll, trailing_operator};
+pub(crate) use roll::{RollInfoLimits, DEFAULT_TIMEOUT_MESSAGE};
 mod permissions;
-use permissions::insufficent_permissions;
+use permissions::{
+    add_privileged_role, insufficent_permissions, list_privileged_roles, remove_privileged_role,
+};
 mod info;
 use info::info;
 mod roll_info;
-use roll_info::{get_roll_info, set_roll_info};
+use roll_info::{
+    get_list_order, get_roll_info, get_show_luck, set_list_order, set_roll_info, set_show_luck,
+};
+mod pause;
+use pause::{pause, reload, resume};
+mod odds;
+use odds::{odds, pool_odds};
+mod narrative;
+use narrative::narrative;
+mod modifier;
+use modifier::{get_modifier, set_modifier};
+mod config;
+use config::{export_config, import_config, with_attached_config};
+mod critical;
+use critical::{get_critical_message, set_critical_message};
+mod genstats;
+use genstats::{genstats, get_stats_config, set_stats_config};
+mod history;
+use history::roll_history;
+mod dice_policy;
+use dice_policy::{dice_type_not_allowed, get_dice_policy, set_dice_banned, set_max_faces};
 
 async fn respond(
     context: serenity::client::Context,
     message: serenity::model::channel::Message,
     response: CommandResult,
-    invite_url: &str,
+    handler: &DiscordBotHandler,
 ) {
     match response {
         CommandResult::Help(prefix) => help(context, message, prefix).await,
-        CommandResult::RollHelp => {}
-        CommandResult::Info => info(context, message, invite_url).await,
+        CommandResult::RollHelp => roll_help(context, message).await,
+        CommandResult::Info => info(context, message, &handler.invite_url).await,
         CommandResult::SetCommandPrefix(prefix) => {
             set_command_prefix(context, message, prefix).await
         }
@@ -107,18 +267,109 @@ async fn respond(
             get_command_prefix(context, message, prefix).await
         }
         CommandResult::AddRollPrefix(result) => add_roll_prefix(context, message, result).await,
+        CommandResult::PrefixConflict(prefix) => prefix_conflict(context, message, prefix).await,
         CommandResult::RemoveRollPrefix(result) => {
             remove_roll_prefix(context, message, result).await
         }
+        CommandResult::ClearRollPrefixes(cleared) => {
+            clear_roll_prefixes(context, message, cleared).await
+        }
         CommandResult::ListRollPrefix(prefixes) => {
             list_roll_prefix(context, message, prefixes).await
         }
+        CommandResult::SetChannelCommandPrefix(prefix) => {
+            set_channel_command_prefix(context, message, prefix).await
+        }
+        CommandResult::AddPrivilegedRole(result) => {
+            add_privileged_role(context, message, result).await
+        }
+        CommandResult::RemovePrivilegedRole(result) => {
+            remove_privileged_role(context, message, result).await
+        }
+        CommandResult::ListPrivilegedRoles(roles) => {
+            list_privileged_roles(context, message, roles).await
+        }
         CommandResult::AddAlias => add_alias(context, message).await,
+        CommandResult::AliasLimitReached(err) => alias_limit_reached(context, message, err).await,
         CommandResult::RemoveAlias(result) => remove_alias(context, message, result).await,
+        CommandResult::ClearAliases(cleared) => clear_aliases(context, message, cleared).await,
+        CommandResult::CopyAlias(result) => copy_alias(context, message, result).await,
         CommandResult::ListAliases(aliases) => list_aliases(context, message, aliases).await,
-        CommandResult::Roll(rolls, info) => roll(&context, message, rolls, info).await,
+        CommandResult::ListAliasPage(page) => list_alias_page(context, message, page).await,
+        CommandResult::Roll(rolls, info, order) => {
+            roll(&context, message, rolls, info, order, handler).await
+        }
+        CommandResult::PrivateRoll(rolls, info, order) => {
+            private_roll(&context, message, rolls, info, order, handler).await
+        }
         CommandResult::InsufficentPermission => insufficent_permissions(context, message).await,
         CommandResult::GetRollInfo(info) => get_roll_info(context, message, info).await,
         CommandResult::SetRollInfo => set_roll_info(context, message).await,
+        CommandResult::GetShowLuck(show_luck) => get_show_luck(context, message, show_luck).await,
+        CommandResult::SetShowLuck => set_show_luck(context, message).await,
+        CommandResult::Pause => pause(context, message).await,
+        CommandResult::Resume => resume(context, message).await,
+        CommandResult::Reload => reload(context, message).await,
+        CommandResult::PoolOdds(text, result) => pool_odds(context, message, text, result).await,
+        CommandResult::Odds(text, result) => odds(context, message, text, result).await,
+        CommandResult::Narrative(text, roll) => narrative(context, message, text, roll).await,
+        CommandResult::Busy => busy(context, message).await,
+        CommandResult::NothingToRepeat => nothing_to_repeat(context, message).await,
+        CommandResult::RateLimited { retry_after } => {
+            rate_limited(context, message, retry_after).await
+        }
+        CommandResult::TrailingOperator(text) => trailing_operator(context, message, text).await,
+        CommandResult::InvalidAliasCall(err) => invalid_alias_call(context, message, err).await,
+        CommandResult::GenStats(scores, rerolls, order) => {
+            genstats(
+                context,
+                message,
+                *scores,
+                rerolls,
+                order,
+                &handler.timeout_message,
+            )
+            .await
+        }
+        CommandResult::SetStatsRoll | CommandResult::SetStatsMinTotal => {
+            set_stats_config(context, message).await
+        }
+        CommandResult::GetStatsConfig(roll, min_total) => {
+            get_stats_config(context, message, roll, min_total).await
+        }
+        CommandResult::SetAliasHints => set_alias_hints(context, message).await,
+        CommandResult::SetRollShorthand => set_roll_shorthand(context, message).await,
+        CommandResult::GetRollShorthand(shorthand) => {
+            get_roll_shorthand(context, message, shorthand).await
+        }
+        CommandResult::SetCriticalMessage(critical, result) => {
+            set_critical_message(context, message, critical, result).await
+        }
+        CommandResult::GetCriticalMessage(critical, text) => {
+            get_critical_message(context, message, critical, text).await
+        }
+        CommandResult::ExportConfig(config) => export_config(context, message, config).await,
+        CommandResult::ImportConfig(result) => import_config(context, message, result).await,
+        CommandResult::History(entries) => roll_history(context, message, entries).await,
+        CommandResult::SetDiceBanned(_, _, result) => {
+            set_dice_banned(context, message, result).await
+        }
+        CommandResult::SetMaxFaces => set_max_faces(context, message).await,
+        CommandResult::GetDicePolicy(policy) => get_dice_policy(context, message, policy).await,
+        CommandResult::DiceTypeNotAllowed(dice) => {
+            dice_type_not_allowed(context, message, dice).await
+        }
+        CommandResult::SetListOrder => set_list_order(context, message).await,
+        CommandResult::GetListOrder(order) => get_list_order(context, message, order).await,
+        CommandResult::SetModifier => set_modifier(context, message).await,
+        CommandResult::GetModifier(modifier) => get_modifier(context, message, modifier).await,
+        CommandResult::UnknownAlias(alias, suggestions) => {
+            unknown_alias(context, message, alias, suggestions).await
+        }
+        CommandResult::Multiple(results) => {
+            for result in results {
+                Box::pin(respond(context.clone(), message.clone(), result, handler)).await
+            }
+        }
     }
 }
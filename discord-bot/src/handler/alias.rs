@@ -1,3 +1,6 @@
+use bot_utils::client_utils::{
+    AliasCallError, AliasCopyError, AliasLimitError, AliasPage, ALIAS_PAGE_SIZE,
+};
 use serenity::{client::Context, model::channel::Message};
 
 pub(crate) async fn add_alias(context: Context, message: Message) {
@@ -6,6 +9,12 @@ pub(crate) async fn add_alias(context: Context, message: Message) {
     }
 }
 
+pub(crate) async fn alias_limit_reached(context: Context, message: Message, err: AliasLimitError) {
+    if let Err(err) = Message::reply(&message, &context, format!("*{}*", err)).await {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
 pub(crate) async fn remove_alias(context: Context, message: Message, result: Result<(), ()>) {
     if let Err(err) = Message::react(
         &message,
@@ -21,18 +30,206 @@ pub(crate) async fn remove_alias(context: Context, message: Message, result: Res
     }
 }
 
-pub(crate) async fn list_aliases(
+pub(crate) async fn clear_aliases(context: Context, message: Message, cleared: usize) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        format!("removed {} shared aliases", cleared),
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+pub(crate) async fn copy_alias(
     context: Context,
     message: Message,
-    aliases: Vec<(String, String)>,
+    result: Result<(), AliasCopyError>,
+) {
+    match result {
+        Ok(_) => {
+            if let Err(err) = Message::react(&message, &context, '✅').await {
+                log::warn!("unable to react to message {}: {}", message.id, err)
+            }
+        }
+        Err(err) => {
+            if let Err(err) = Message::reply(&message, &context, format!("*{}*", err)).await {
+                log::warn!("Unable to reply to message: {}", err)
+            }
+        }
+    }
+}
+
+pub(crate) async fn set_alias_hints(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn unknown_alias(
+    context: Context,
+    message: Message,
+    alias: String,
+    suggestions: Vec<String>,
 ) {
-    if let Some(m) = aliases
+    let suggestions = suggestions
         .iter()
-        .map(|(alias, expr)| format!("`{}` => `{}`", alias, expr))
-        .reduce(|p1, p2| format!("{}\n{}", p1, p2))
+        .map(|s| format!("`{}`", s))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        format!("no alias `{}`, did you mean {}?", alias, suggestions),
+    )
+    .await
     {
-        if let Err(err) = Message::reply(&message, &context, m).await {
-            log::warn!("Unable to reply to message: {}", err)
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+pub(crate) async fn invalid_alias_call(context: Context, message: Message, err: AliasCallError) {
+    if let Err(err) = Message::reply(&message, &context, format!("*{}*", err)).await {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+/// longest message content discord accepts, in characters
+pub(super) const MAX_MESSAGE_LEN: usize = 2000;
+
+/// name of the file longer alias lists are sent as
+const ALIAS_FILE_NAME: &str = "aliases.txt";
+
+/// the alias list to send inline, unless it is too long for a single message
+pub(super) fn inline_aliases(aliases: &[(String, String)]) -> Option<String> {
+    let list = aliases
+        .iter()
+        .map(|(alias, expr)| format!("`{}` => `{}`", alias, expr))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if list.chars().count() <= MAX_MESSAGE_LEN {
+        Some(list)
+    } else {
+        None
+    }
+}
+
+/// `page` as a single message, aliases that don't fit are left out
+pub(super) fn page_text(page: &AliasPage) -> String {
+    if page.pages == 0 {
+        return "*there are no aliases*".to_string();
+    } else if page.aliases.is_empty() {
+        return format!(
+            "*there is no page {}, the alias list has {} pages*",
+            page.page, page.pages
+        );
+    }
+    let footer = format!("*page {} of {}*", page.page, page.pages);
+    let mut text = String::new();
+    for (alias, expr) in &page.aliases {
+        let line = format!("`{}` => `{}`\n", alias, expr);
+        // leaves room to mark that the remaining aliases were left out
+        if text.chars().count() + line.chars().count() + footer.chars().count() + 2
+            > MAX_MESSAGE_LEN
+        {
+            text.push_str("…\n");
+            break;
         }
+        text.push_str(&line);
+    }
+    text + &footer
+}
+
+pub(crate) async fn list_alias_page(context: Context, message: Message, page: AliasPage) {
+    if let Err(err) = Message::reply(&message, &context, page_text(&page)).await {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+/// the complete alias list as plain text
+fn alias_file(aliases: &[(String, String)]) -> String {
+    aliases
+        .iter()
+        .map(|(alias, expr)| format!("{} => {}\n", alias, expr))
+        .collect()
+}
+
+pub(crate) async fn list_aliases(
+    context: Context,
+    message: Message,
+    aliases: Vec<(String, String)>,
+) {
+    if aliases.is_empty() {
+        return;
+    }
+    let result = match inline_aliases(&aliases) {
+        Some(list) => Message::reply(&message, &context, list).await,
+        None => {
+            let file = alias_file(&aliases);
+            message
+                .channel_id
+                .send_message(&context, |m| {
+                    m.content(format!(
+                        "*{} aliases, `alias list 1` shows the first {} of them*",
+                        aliases.len(),
+                        ALIAS_PAGE_SIZE
+                    ))
+                    .add_file((file.as_bytes(), ALIAS_FILE_NAME))
+                    .reference_message(&message)
+                    .allowed_mentions(|m| m.empty_users())
+                })
+                .await
+        }
+    };
+    if let Err(err) = result {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_list() {
+        let aliases = vec![
+            ("stats".to_string(), "6{4d6k3}".to_string()),
+            ("hit".to_string(), "1d20+5".to_string()),
+        ];
+        assert_eq!(
+            inline_aliases(&aliases),
+            Some("`stats` => `6{4d6k3}`\n`hit` => `1d20+5`".to_string())
+        );
+        assert_eq!(alias_file(&aliases), "stats => 6{4d6k3}\nhit => 1d20+5\n");
+        let many: Vec<(String, String)> = (0..200)
+            .map(|i| (format!("alias{}", i), "1d20".to_string()))
+            .collect();
+        assert_eq!(inline_aliases(&many), None);
+    }
+
+    #[test]
+    fn test_page_text() {
+        let aliases = |n: usize, expr: &str| -> Vec<(String, String)> {
+            (0..n)
+                .map(|i| (format!("alias{}", i), expr.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            page_text(&AliasPage::new(aliases(25, "1d20"), 2)),
+            "`alias20` => `1d20`\n`alias21` => `1d20`\n`alias22` => `1d20`\n`alias23` => `1d20`\n\
+             `alias24` => `1d20`\n*page 2 of 2*"
+        );
+        assert_eq!(
+            page_text(&AliasPage::new(aliases(25, "1d20"), 3)),
+            "*there is no page 3, the alias list has 2 pages*"
+        );
+        assert_eq!(
+            page_text(&AliasPage::new(Vec::new(), 1)),
+            "*there are no aliases*"
+        );
+        let long = page_text(&AliasPage::new(aliases(20, &"1d20 + ".repeat(40)), 1));
+        assert!(long.chars().count() <= MAX_MESSAGE_LEN);
+        assert!(long.ends_with("…\n*page 1 of 1*"));
     }
 }
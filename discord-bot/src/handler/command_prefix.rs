@@ -39,3 +39,45 @@ pub(crate) async fn get_command_prefix(
         log::warn!("Unable to reply to message {}: {}", message.id, err)
     }
 }
+
+/// the nickname shows the prefix of the whole server, so it is left alone here
+pub(crate) async fn set_channel_command_prefix(
+    context: serenity::client::Context,
+    message: Message,
+    prefix: Option<String>,
+) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+    if prefix.is_none() {
+        if let Err(err) = Message::reply(
+            &message,
+            &context,
+            "this channel uses the command prefix of the server again",
+        )
+        .await
+        {
+            log::warn!("Unable to reply to message {}: {}", message.id, err)
+        }
+    }
+}
+
+/// neither prefix was changed, rolls starting with the shared one would be taken for commands
+pub(crate) async fn prefix_conflict(
+    context: serenity::client::Context,
+    message: Message,
+    prefix: String,
+) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        format!(
+            "*`{}` would be both the command prefix and a roll prefix, rolls starting with it would be taken for commands*",
+            prefix
+        ),
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message {}: {}", message.id, err)
+    }
+}
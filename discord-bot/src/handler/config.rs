@@ -0,0 +1,66 @@
+use bot_utils::client_utils::ImportError;
+use serenity::{client::Context, model::channel::Message};
+
+/// name of the file an exported configuration is sent as
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// largest attached configuration that is downloaded for `config import`
+const MAX_IMPORT_SIZE: u64 = 256 * 1024;
+
+/// the content of `message`, followed by its attached configuration if it ends with `import`
+pub(crate) async fn with_attached_config(message: &Message) -> String {
+    let file = match message.attachments.first() {
+        Some(file)
+            if file.size <= MAX_IMPORT_SIZE
+                && message
+                    .content
+                    .trim_end()
+                    .to_lowercase()
+                    .ends_with("import") =>
+        {
+            file
+        }
+        _ => return message.content.clone(),
+    };
+    match file.download().await.map(String::from_utf8) {
+        Ok(Ok(config)) => format!("{} {}", message.content, config),
+        Ok(Err(err)) => {
+            log::warn!("attachment {} is no text: {}", file.filename, err);
+            message.content.clone()
+        }
+        Err(err) => {
+            log::warn!("unable to download attachment {}: {}", file.filename, err);
+            message.content.clone()
+        }
+    }
+}
+
+pub(crate) async fn export_config(context: Context, message: Message, config: String) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.add_file((config.as_bytes(), CONFIG_FILE_NAME))
+                .reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+pub(crate) async fn import_config(
+    context: Context,
+    message: Message,
+    result: Result<(), ImportError>,
+) {
+    let response = match result {
+        Ok(_) => Message::react(&message, &context, '✅').await.err(),
+        Err(err) => Message::reply(&message, &context, format!("*{}*", err))
+            .await
+            .err(),
+    };
+    if let Some(err) = response {
+        log::warn!("unable to respond to message {}: {}", message.id, err)
+    }
+}
@@ -0,0 +1,49 @@
+use bot_utils::client_utils::{Critical, CRITICAL_VALUE_PLACEHOLDER, MAX_CRITICAL_MESSAGE_LEN};
+use serenity::{client::Context, model::channel::Message};
+
+pub(crate) async fn set_critical_message(
+    context: Context,
+    message: Message,
+    critical: Critical,
+    result: Result<(), ()>,
+) {
+    let response = match result {
+        Ok(_) => Message::react(&message, &context, '✅').await.err(),
+        Err(_) => Message::reply(
+            &message,
+            &context,
+            format!(
+                "the {} message can be at most {} characters long",
+                critical, MAX_CRITICAL_MESSAGE_LEN
+            ),
+        )
+        .await
+        .err(),
+    };
+    if let Some(err) = response {
+        log::warn!("unable to respond to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_critical_message(
+    context: Context,
+    message: Message,
+    critical: Critical,
+    text: Option<String>,
+) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        match text {
+            Some(t) => format!(
+                "{} rolls are announced with `{}`, where `{}` is the rolled total",
+                critical, t, CRITICAL_VALUE_PLACEHOLDER
+            ),
+            None => format!("{} rolls are not announced", critical),
+        },
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
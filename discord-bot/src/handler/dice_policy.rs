@@ -0,0 +1,43 @@
+use bot_utils::client_utils::DicePolicy;
+use serenity::{client::Context, model::channel::Message};
+
+pub(crate) async fn set_dice_banned(context: Context, message: Message, result: Result<(), ()>) {
+    if let Err(err) = Message::react(
+        &message,
+        &context,
+        match result {
+            Ok(_) => '✅',
+            Err(_) => '❌',
+        },
+    )
+    .await
+    {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn set_max_faces(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_dice_policy(context: Context, message: Message, policy: DicePolicy) {
+    if let Err(err) = Message::reply(&message, &context, policy.to_string()).await {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+pub(crate) async fn dice_type_not_allowed(context: Context, message: Message, dice: String) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.content(format!("*`{}` may not be rolled here*", dice))
+                .reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
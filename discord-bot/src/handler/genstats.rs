@@ -0,0 +1,80 @@
+use bot_utils::client_utils::{
+    render::{error_message, ordered, render_scores, Style},
+    ListOrder, RollExprResult,
+};
+use serenity::{client::Context, model::channel::Message};
+
+pub(crate) async fn genstats(
+    context: Context,
+    message: Message,
+    scores: RollExprResult,
+    rerolls: u32,
+    order: ListOrder,
+    timeout_message: &str,
+) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            match &scores.roll {
+                Ok(r) => {
+                    let description = render_scores(&ordered(r, order), Style::Markdown);
+                    m.embed(|e| {
+                        e.title(format!("Ability scores ({})", scores.text))
+                            .description(if rerolls > 0 {
+                                format!(
+                                    "{}\n*rerolled {} time{} for a low total*",
+                                    description,
+                                    rerolls,
+                                    if rerolls == 1 { "" } else { "s" }
+                                )
+                            } else {
+                                description
+                            })
+                    });
+                }
+                Err(e) => {
+                    m.content(error_message(
+                        e,
+                        &scores.text,
+                        timeout_message,
+                        Style::Markdown,
+                    ));
+                }
+            };
+            m.reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
+
+pub(crate) async fn set_stats_config(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_stats_config(
+    context: Context,
+    message: Message,
+    roll: String,
+    min_total: Option<i64>,
+) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        match min_total {
+            Some(min) => format!(
+                "ability scores are rolled with `{}` until their total is at least {}",
+                roll, min
+            ),
+            None => format!("ability scores are rolled with `{}`", roll),
+        },
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
@@ -12,8 +12,10 @@ pub(crate) async fn help(
 all Commands are prefixed with `command-prefix`, which currently is {}.
 The prefix is recognized both with or without following whitespace.
 Both tab and newline are recognized as whitespace. Several whitespace characters are also accepted.
+Up to 4 commands can be given at once by separating them with `;`, like `cp get ; roll 1d20`.
                             ",&prefix)).field("Privileged Commands", "
 Some commands require special permissions to use. They are prefixed with \\* in this overview.
+They are open to the server owner, administrators and members of privileged roles. Administrators manage these roles with `privileged-roles add [role]`, `pr a [role]`, `pr remove [role]`, `pr r [role]` and `pr list`, `pr l`.
 ", false)
                       .field(
                           "General Help Commands",
@@ -21,6 +23,7 @@ Some commands require special permissions to use. They are prefixed with \\* in
 `help`, `h` => show this help text
 `roll-help`, `roll_help`, `rh` => show help on roll syntax
 `info`, `i` => show extra info about this Bot
+`/roll`, `/gmroll`, `/alias` and `/prefix` are also available as slash commands.
 ",
                           false
                       ).field(
@@ -28,14 +31,40 @@ Some commands require special permissions to use. They are prefixed with \\* in
                           "
 The Command Prefix group always begins with `command-prefix`, `command_prefix` or `cp` followed by whitespace. The commands in this group are:
 
-\\* `set [prefix]` , `s [prefix]` => set command prefix to `[prefix]`. Whitespace Characters are not allowed in `[prefix]`.
+\\* `set [prefix]` , `s [prefix]` => set command prefix to `[prefix]`. Put `[prefix]` in quotes to include whitespace, like `cp set \"roll \"`.
 `get`, `g` => get command prefix.
+\\* `channel set [prefix]`, `c s [prefix]` => use `[prefix]` in this channel only, `channel clear`, `c c` goes back to the server prefix.
 ",
                           false
                       ).field(
                           "Roll",
                           "
 `roll [roll-statement]`, `r [roll-statement]` => roll dice as described in `[roll-statement]`. See roll-help for Information on the grammar for this.
+Separate several statements with `;` to roll them at once, annotate terms with `[comments]` and label them like `1d20#hit + 1d6#fire` to see each value. React with 🔁 to a result to roll it again.
+`pool-odds [dice]`, `po [dice]` => show the chance for each number of successes of a dice pool like `10d10>=8`.
+`odds [term]`, `roll-odds [term]` => show the chance for each result of a term like `2d6 + 3`. Terms other than sums of plain dice are estimated by rolling them many times.
+`!`, `roll .`, `r .` => repeat your last roll in this channel.
+`gmroll [roll-statement]`, `whisper [roll-statement]` => roll secretly, the result is sent to you as a direct message.
+`roll-history`, `history` => show your latest rolls.
+`modifier set [n]`, `m s [n]` => add `[n]` to all your rolls until `modifier clear`, `m c`. `modifier get`, `m g` shows the current one.
+`genstats`, `gs` => roll the six ability scores. Admins can change the roll with `gs set 6{[term]}` or `gs reset` and reroll low arrays with `gs min [total]` or `gs min off`. `gs get` shows the current settings.
+",
+                          false
+                      ).field(
+                          "Narrative Dice",
+                          "
+`narrative [dice]`, `nr [dice]` => roll symbol dice like `2a 1p 2d 1c`, cancelling successes against failures and advantages against threats.
+The dice are `b`oost, `s`etback, `a`bility, `d`ifficulty, `p`roficiency and `c`hallenge.
+",
+                          false
+                      ).field(
+                          "Dice Policy",
+                          "
+The Dice Policy group always begins with `dice` followed by whitespace.
+
+\\* `ban [kind]`, `allow [kind]` => forbid or allow again rolling `multiply` (`dNx`), `fudge` (`dF`) or `dynamic` (`d(term)`) dice.
+\\* `max-faces [n]` => forbid dice with more than `[n]` faces, and `d(term)` dice whose faces are only known once rolled. `max-faces off` lifts the limit.
+`get`, `g` => show which dice are forbidden.
 ",
                           false
                       ).field(
@@ -46,7 +75,10 @@ This is an alternate Way to roll `[roll statement]`s. Just append your `[roll st
 
 \\* `add [prefix]`, `a [prefix]` => add `[prefix]` to the list of roll prefixes.
 \\* `remove [prefix]`, `r [prefix]` => remove `[prefix]` from the list of roll prefixes.
-`list`, `l` => list roll prefixes on this Server
+\\* `clear` => remove every roll prefix.
+`list`, `l` => list roll prefixes in this channel
+\\* `channel add [prefix]`, `c a [prefix]` and `channel remove [prefix]`, `c r [prefix]` => roll prefixes of this channel only, they replace those of the Server.
+\\* `roll-shorthand set [token]`, `rs s [token]` => roll messages starting with `[token]` and whitespace, like `r 1d20`. Disable with `rs off`.
 ",
                           false
                       ).field(
@@ -56,9 +88,41 @@ The Alias group always begins with `alias` or `a`, followed by whitespace.
 This allows to specify messages, which will be interpreted as roll statements, if they are the only content of the message.
 One usage of this is to enable saving roll statements like 6{4d6k3}, the statement used to roll for stats in D&D
 
-\\* `add [alias] [roll statement]`, `a [alias] [roll statement]` => Adds `[alias]` as an alias for `[roll statement]`.
-\\* `remove [alias]`, `r [alias]` => remove `[alias]` from known aliases.
-`list`, `l` => list known aliases.
+`add [alias] [roll statement]`, `a [alias] [roll statement]` => Adds `[alias]` as an alias for `[roll statement]`, on a Server only for you. `$1`..`$n` in it are filled with arguments, like `atk 5` for `1d20+$1`. Names with spaces go in quotes, like `alias add \"sneak attack\" 3d6`.
+`remove [alias]`, `r [alias]` => remove your `[alias]`, removing a shared one is privileged.
+`list`, `l` => list the aliases you can use, `list [page]` shows them 20 at a time.
+\\* `clear` => remove every shared alias.
+`copy-from-dm [alias]` => copy `[alias]` from your direct messages with this Bot into your personal aliases on this Server.
+\\* `promote [alias]` => share your personal `[alias]` with everyone on this Server.
+\\* `hints [true|false]` => if enabled, single words resembling a known alias get answered with suggestions.
+",
+                          false
+                      ).field(
+                          "Pause",
+                          "
+\\* `pause` => stop responding to anything but `resume` on this Server.
+\\* `resume` => start responding again.
+\\* `reload` => read the settings and aliases of this Server from the database again, after editing it by hand.
+\\* `config export` => send all settings and aliases of this Server as a file.
+\\* `config import [json]` => replace all settings and aliases with an exported file, attached or pasted after `import`.
+",
+                          false
+                      ).field(
+                          "Display",
+                          "
+`roll-info set [true|false]`, `ri s [true|false]` => show the single dice of each roll.
+`list-order set [unsorted|ascending|descending]`, `lo s [u|a|d]` => order in which the results of a list like `6{4d6k3}` are shown.
+`luck set [true|false]` => tell how many standard deviations a single result is above or below average.
+All settings can be read with `get`, `g`.
+",
+                          false
+                      ).field(
+                          "Criticals",
+                          "
+A natural 20 or 1 on the only d20 of a roll, like `1d20 + 5` or `2d20k1`, can be announced below the result.
+\\* `critical set [message]`, `crit s [message]` => announce a natural 20 with `[message]`, `{value}` is replaced by the rolled total.
+\\* `fumble set [message]` => announce a natural 1 the same way.
+\\* `critical off`, `fumble off` => stop announcing them. Without `set` or `off` the message is shown.
 ",
                           false
                       ).footer(|f|{
@@ -0,0 +1,44 @@
+use super::alias::MAX_MESSAGE_LEN;
+use bot_utils::client_utils::{
+    render::{render_history, Style},
+    HistoryEntry,
+};
+use serenity::{client::Context, model::channel::Message};
+
+/// the latest rolls of `entries` that fit into a single message
+fn history_text(entries: &[HistoryEntry]) -> String {
+    let mut shown = entries.len();
+    loop {
+        let text = render_history(&entries[..shown], Style::Markdown);
+        if shown <= 1 || text.chars().count() <= MAX_MESSAGE_LEN {
+            break text;
+        }
+        shown -= 1;
+    }
+}
+
+pub(crate) async fn roll_history(context: Context, message: Message, entries: Vec<HistoryEntry>) {
+    if let Err(err) = Message::reply(&message, &context, history_text(&entries)).await {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_text() {
+        let entries: Vec<_> = (0..100)
+            .map(|i| HistoryEntry {
+                expression: format!("{}d6 + {}", i, "1 + ".repeat(10)),
+                label: None,
+                results: vec![i],
+            })
+            .collect();
+        let text = history_text(&entries);
+        assert!(text.chars().count() <= MAX_MESSAGE_LEN);
+        // the latest rolls are kept
+        assert!(text.starts_with("0d6"));
+    }
+}
@@ -0,0 +1,283 @@
+use super::alias::{inline_aliases, page_text};
+use bot_utils::client_utils::{render::render, render::Style, Access, CommandResult};
+use serenity::{
+    client::Context,
+    model::interactions::{
+        application_command::{
+            ApplicationCommand, ApplicationCommandInteraction,
+            ApplicationCommandInteractionDataOption, ApplicationCommandOptionType,
+        },
+        InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+    },
+};
+
+/// registers `/roll`, `/gmroll`, `/alias` and `/prefix` for all guilds and direct messages
+pub(crate) async fn register_commands(context: &Context) {
+    if let Err(err) = ApplicationCommand::set_global_application_commands(&context, |commands| {
+        commands
+            .create_application_command(|c| {
+                c.name("roll").description("roll dice").create_option(|o| {
+                    o.name("expression")
+                        .description("what to roll, like 1d20+5")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                })
+            })
+            .create_application_command(|c| {
+                c.name("gmroll")
+                    .description("roll dice, only you see the result")
+                    .create_option(|o| {
+                        o.name("expression")
+                            .description("what to roll, like 1d20+5")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("alias")
+                    .description("manage aliases")
+                    .create_option(|o| {
+                        o.name("add")
+                            .description("add or replace an alias")
+                            .kind(ApplicationCommandOptionType::SubCommand)
+                            .create_sub_option(|o| {
+                                o.name("name")
+                                    .description("name of the alias")
+                                    .kind(ApplicationCommandOptionType::String)
+                                    .required(true)
+                            })
+                            .create_sub_option(|o| {
+                                o.name("expression")
+                                    .description("what the alias rolls")
+                                    .kind(ApplicationCommandOptionType::String)
+                                    .required(true)
+                            })
+                    })
+                    .create_option(|o| {
+                        o.name("remove")
+                            .description("remove an alias")
+                            .kind(ApplicationCommandOptionType::SubCommand)
+                            .create_sub_option(|o| {
+                                o.name("name")
+                                    .description("name of the alias")
+                                    .kind(ApplicationCommandOptionType::String)
+                                    .required(true)
+                            })
+                    })
+                    .create_option(|o| {
+                        o.name("list")
+                            .description("list all aliases")
+                            .kind(ApplicationCommandOptionType::SubCommand)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("prefix")
+                    .description("the prefix of text commands")
+                    .create_option(|o| {
+                        o.name("get")
+                            .description("show the command prefix")
+                            .kind(ApplicationCommandOptionType::SubCommand)
+                    })
+                    .create_option(|o| {
+                        o.name("set")
+                            .description("change the command prefix")
+                            .kind(ApplicationCommandOptionType::SubCommand)
+                            .create_sub_option(|o| {
+                                o.name("prefix")
+                                    .description("the new prefix, without whitespace")
+                                    .kind(ApplicationCommandOptionType::String)
+                                    .required(true)
+                            })
+                    })
+            })
+    })
+    .await
+    {
+        log::warn!("unable to register slash commands: {}", err)
+    }
+}
+
+/// string value of the option `name`
+fn option<'a>(
+    options: &'a [ApplicationCommandInteractionDataOption],
+    name: &str,
+) -> Option<&'a str> {
+    options
+        .iter()
+        .find(|o| o.name == name)
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_str())
+}
+
+/// the text command equivalent to a slash command, without the command prefix
+pub(crate) fn slash_command_text(
+    name: &str,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> Option<String> {
+    let sub = options.first();
+    let sub_options = sub.map(|s| s.options.as_slice()).unwrap_or_default();
+    match (name, sub.map(|s| s.name.as_str())) {
+        ("roll", _) => Some(format!("roll {}", option(options, "expression")?)),
+        ("gmroll", _) => Some(format!("gmroll {}", option(options, "expression")?)),
+        ("alias", Some("add")) => Some(format!(
+            "alias add {} {}",
+            option(sub_options, "name")?,
+            option(sub_options, "expression")?
+        )),
+        ("alias", Some("remove")) => Some(format!("alias remove {}", option(sub_options, "name")?)),
+        ("alias", Some("list")) => Some("alias list".to_string()),
+        ("prefix", Some("get")) => Some("command-prefix get".to_string()),
+        ("prefix", Some("set")) => Some(format!(
+            "command-prefix set {}",
+            option(sub_options, "prefix")?
+        )),
+        _ => None,
+    }
+}
+
+/// permissions of the invoking member, everything is allowed in direct messages
+pub(crate) fn slash_command_access(interaction: &ApplicationCommandInteraction) -> Access {
+    match (&interaction.member, interaction.guild_id) {
+        (Some(member), Some(_)) => Access {
+            administrator: member.permissions.is_some_and(|p| p.administrator()),
+            roles: member.roles.iter().map(|id| id.to_string()).collect(),
+        },
+        (None, Some(_)) => Access::default(),
+        (_, None) => Access::from(true),
+    }
+}
+
+fn done(result: &Result<(), ()>, failed: &str) -> String {
+    match result {
+        Ok(_) => "✅".to_string(),
+        Err(_) => format!("*{}*", failed),
+    }
+}
+
+/// reply to a slash command, the results of text commands without one are not expected
+pub(crate) fn slash_command_response(result: CommandResult, timeout_message: &str) -> String {
+    match result {
+        CommandResult::Roll(rolls, roll_info, order)
+        | CommandResult::PrivateRoll(rolls, roll_info, order) => rolls
+            .iter()
+            .map(|roll| render(roll, order, roll_info, timeout_message, Style::Markdown))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::Multiple(results) => results
+            .into_iter()
+            .map(|r| slash_command_response(r, timeout_message))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::Busy => "*Too many rolls at once, please try again in a moment*".to_string(),
+        CommandResult::RateLimited { retry_after } => format!(
+            "*You are rolling too often, please wait {} seconds*",
+            retry_after.as_secs() + 1
+        ),
+        CommandResult::TrailingOperator(text) => format!(
+            "*`{}` ends with an operator, the term after it is missing*",
+            text
+        ),
+        CommandResult::AddAlias => "✅".to_string(),
+        CommandResult::AliasLimitReached(err) => format!("*{}*", err),
+        CommandResult::RemoveAlias(result) => done(&result, "no such alias"),
+        CommandResult::ClearAliases(cleared) => format!("removed {} shared aliases", cleared),
+        CommandResult::ListAliases(aliases) if aliases.is_empty() => {
+            "*there are no aliases*".to_string()
+        }
+        CommandResult::ListAliases(aliases) => inline_aliases(&aliases).unwrap_or_else(|| {
+            format!(
+                "*{} aliases, too many to show here. The alias list command sends them as a file*",
+                aliases.len()
+            )
+        }),
+        CommandResult::ListAliasPage(page) => page_text(&page),
+        CommandResult::InvalidAliasCall(err) => format!("*{}*", err),
+        CommandResult::GetCommandPrefix(prefix) | CommandResult::SetCommandPrefix(prefix) => {
+            format!("the command prefix is `{}`", prefix)
+        }
+        CommandResult::PrefixConflict(prefix) => format!(
+            "*`{}` would be both the command prefix and a roll prefix, rolls starting with it would be taken for commands*",
+            prefix
+        ),
+        CommandResult::DiceTypeNotAllowed(dice) => {
+            format!("*`{}` may not be rolled here*", dice)
+        }
+        CommandResult::InsufficentPermission => {
+            "*you are not allowed to use this command*".to_string()
+        }
+        CommandResult::Help(prefix) => format!(
+            "*that is not a valid command, send `{}help` for an overview*",
+            prefix
+        ),
+        _ => "✅".to_string(),
+    }
+}
+
+pub(crate) async fn respond_slash_command(
+    context: &Context,
+    interaction: &ApplicationCommandInteraction,
+    content: String,
+    ephemeral: bool,
+) {
+    if let Err(err) = interaction
+        .create_interaction_response(context, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    if ephemeral {
+                        d.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL);
+                    }
+                    d.content(content).allowed_mentions(|m| m.empty_users())
+                })
+        })
+        .await
+    {
+        log::warn!(
+            "unable to respond to interaction {}: {}",
+            interaction.id,
+            err
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn options(value: serde_json::Value) -> Vec<ApplicationCommandInteractionDataOption> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_slash_command_text() {
+        assert_eq!(
+            slash_command_text(
+                "roll",
+                &options(json!([{"name": "expression", "type": 3, "value": "1d20+5"}]))
+            ),
+            Some("roll 1d20+5".to_string())
+        );
+        assert_eq!(
+            slash_command_text(
+                "alias",
+                &options(json!([{"name": "add", "type": 1, "options": [
+                    {"name": "name", "type": 3, "value": "atk"},
+                    {"name": "expression", "type": 3, "value": "1d20+$1"}
+                ]}]))
+            ),
+            Some("alias add atk 1d20+$1".to_string())
+        );
+        assert_eq!(
+            slash_command_text(
+                "gmroll",
+                &options(json!([{"name": "expression", "type": 3, "value": "1d20"}]))
+            ),
+            Some("gmroll 1d20".to_string())
+        );
+        assert_eq!(
+            slash_command_text("prefix", &options(json!([{"name": "get", "type": 1}]))),
+            Some("command-prefix get".to_string())
+        );
+        assert_eq!(slash_command_text("alias", &[]), None);
+    }
+}
@@ -0,0 +1,22 @@
+use serenity::{client::Context, model::channel::Message};
+
+pub(crate) async fn set_modifier(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_modifier(context: Context, message: Message, modifier: Option<i64>) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        &match modifier {
+            Some(m) => format!("your rolls are modified by `{:+}`", m),
+            None => "no modifier is set".to_string(),
+        },
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
@@ -0,0 +1,37 @@
+use bot_utils::client_utils::{
+    render::{render_faces, Style},
+    NarrativeRoll,
+};
+use serenity::{client::Context, model::channel::Message};
+
+pub(crate) async fn narrative(
+    context: Context,
+    message: Message,
+    text: String,
+    roll: NarrativeRoll,
+) {
+    let tally = roll.tally();
+    let outcome = if tally.succeeded() {
+        "Success"
+    } else {
+        "Failure"
+    };
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.embed(|e| {
+                e.title(&text).description(format!(
+                    "**{}**: {}\n{}",
+                    outcome,
+                    tally,
+                    Style::Markdown.block(&render_faces(&roll))
+                ))
+            })
+            .reference_message(&message)
+            .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
@@ -0,0 +1,81 @@
+use bot_utils::client_utils::{
+    render::{distribution_table, pool_odds_table, Style},
+    Distribution, PoolOdds, StatsError,
+};
+use serenity::{client::Context, model::channel::Message};
+use std::sync::Arc;
+
+pub(crate) async fn pool_odds(
+    context: Context,
+    message: Message,
+    text: String,
+    odds: Result<PoolOdds, StatsError>,
+) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            match odds {
+                Ok(odds) => {
+                    m.embed(|e| {
+                        e.title(&text).description(format!(
+                            "expected successes: **{:.2}**\n{}",
+                            odds.expected,
+                            Style::Markdown.block(&pool_odds_table(&odds))
+                        ))
+                    });
+                }
+                Err(StatsError::TooLarge) => {
+                    m.content(format!("*`{}` is too large to compute*", text));
+                }
+                Err(StatsError::Unsupported) => {
+                    m.content(format!("*Odds for `{}` are not supported*", text));
+                }
+            };
+            m.reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
+
+pub(crate) async fn odds(
+    context: Context,
+    message: Message,
+    text: String,
+    distribution: Result<Arc<Distribution>, StatsError>,
+) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            match distribution {
+                Ok(distribution) => {
+                    m.embed(|e| {
+                        e.title(&text).description(format!(
+                            "{}: **{:.2}**\n{}",
+                            if distribution.exact {
+                                "expected result"
+                            } else {
+                                "estimated expected result"
+                            },
+                            distribution.mean(),
+                            Style::Markdown.block(&distribution_table(&distribution))
+                        ))
+                    });
+                }
+                Err(StatsError::TooLarge) => {
+                    m.content(format!("*`{}` is too complex to compute exactly*", text));
+                }
+                Err(StatsError::Unsupported) => {
+                    m.content(format!("*Odds for `{}` are not supported*", text));
+                }
+            };
+            m.reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
@@ -0,0 +1,19 @@
+use serenity::{client::Context, model::channel::Message};
+
+pub(crate) async fn pause(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '⏸').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn reload(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '🔄').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn resume(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '▶').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
@@ -5,3 +5,65 @@ pub(crate) async fn insufficent_permissions(context: Context, message: Message)
         log::warn!("unable to add reaction to message {}: {}", message.id, err);
     }
 }
+
+pub(crate) async fn add_privileged_role(
+    context: Context,
+    message: Message,
+    result: Result<(), ()>,
+) {
+    if let Err(err) = Message::react(
+        &message,
+        &context,
+        match result {
+            Ok(_) => '✅',
+            Err(_) => '❌',
+        },
+    )
+    .await
+    {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn remove_privileged_role(
+    context: Context,
+    message: Message,
+    result: Result<(), ()>,
+) {
+    if let Err(err) = Message::react(
+        &message,
+        &context,
+        match result {
+            Ok(_) => '✅',
+            Err(_) => '❌',
+        },
+    )
+    .await
+    {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn list_privileged_roles(context: Context, message: Message, roles: Vec<String>) {
+    let content = if roles.is_empty() {
+        "only administrators may use privileged commands".to_string()
+    } else {
+        roles
+            .iter()
+            .map(|r| format!("<@&{}>", r))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    // the roles are only listed, nobody should be pinged by it
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.reference_message((message.channel_id, message.id))
+                .allowed_mentions(|m| m.empty_users().empty_roles())
+                .content(content)
+        })
+        .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
@@ -0,0 +1,47 @@
+use bot_utils::client_utils::VersionedRollExpr;
+use cached::{Cached, TimedSizedCache};
+use serenity::model::{channel::Message, id::MessageId};
+use std::sync::{Arc, Mutex};
+
+/// reacting with this emoji to a roll result rolls it again
+pub(crate) const REROLL_EMOJI: char = '🔁';
+
+/// result messages are remembered for this many seconds
+const REROLL_LIFESPAN: u64 = 60 * 60;
+
+/// at most this many result messages are remembered
+const REROLL_CAPACITY: usize = 4096;
+
+/// a roll that can be repeated
+#[derive(Debug, Clone)]
+pub(crate) struct Reroll {
+    /// the message that requested the roll, the repeated roll replies to it
+    pub(crate) request: Message,
+    /// what was rolled, independent of the prefixes and aliases used to request it
+    pub(crate) expressions: Vec<Arc<VersionedRollExpr>>,
+}
+
+/// maps the id of a roll result to the roll it shows
+pub(crate) struct Rerolls {
+    rolls: Mutex<TimedSizedCache<MessageId, Reroll>>,
+}
+
+impl Rerolls {
+    pub(crate) fn new() -> Rerolls {
+        Rerolls {
+            rolls: Mutex::new(TimedSizedCache::with_size_and_lifespan(
+                REROLL_CAPACITY,
+                REROLL_LIFESPAN,
+            )),
+        }
+    }
+
+    pub(crate) fn insert(&self, result: MessageId, reroll: Reroll) {
+        self.rolls.lock().unwrap().cache_set(result, reroll);
+    }
+
+    /// the roll shown by `result`, unless it was forgotten already
+    pub(crate) fn get(&self, result: MessageId) -> Option<Reroll> {
+        self.rolls.lock().unwrap().cache_get(&result).cloned()
+    }
+}
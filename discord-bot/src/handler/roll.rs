@@ -1,61 +1,133 @@
-use bot_utils::client_utils::{EvaluationErrors, RollExprResult};
-use serenity::{client::Context, model::channel::Message};
+use bot_utils::{
+    bots::{Map, Value},
+    client_utils::{
+        render::{
+            error_message, ordered, render, render_audit, render_content, render_dice,
+            render_total, Style,
+        },
+        ListOrder, RollExprResult,
+    },
+};
+use serenity::{client::Context, http::AttachmentType, model::channel::Message};
+use std::{borrow::Cow, convert::TryInto, time::Duration};
+
+use super::{
+    alias::MAX_MESSAGE_LEN,
+    reroll::{Reroll, REROLL_EMOJI},
+    DiscordBotHandler,
+};
+
+/// shown when a roll takes too long, `{expression}` is replaced by the rolled expression
+pub(crate) const DEFAULT_TIMEOUT_MESSAGE: &str =
+    "*Rolling `{expression}` took too long. Try fewer dice, smaller lists or fewer exploding dice.*";
+
+/// largest roll whose dice are shown in an embed, more would make it hard to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RollInfoLimits {
+    /// results of a list
+    pub(crate) max_results: usize,
+    /// dice of the first result
+    pub(crate) max_dice: usize,
+}
+
+impl Default for RollInfoLimits {
+    fn default() -> Self {
+        RollInfoLimits {
+            max_results: 10,
+            max_dice: 20,
+        }
+    }
+}
+
+impl RollInfoLimits {
+    /// `roll_info_max_results` and `roll_info_max_dice` of the discord section, the defaults
+    /// for those missing
+    pub(crate) fn from_config(config: &Map<String, Value>) -> RollInfoLimits {
+        let default = RollInfoLimits::default();
+        let limit = |key: &str, default: usize| {
+            config
+                .get(key)
+                .and_then(|l| l.as_integer())
+                .and_then(|l| l.try_into().ok())
+                .unwrap_or(default)
+        };
+        RollInfoLimits {
+            max_results: limit("roll_info_max_results", default.max_results),
+            max_dice: limit("roll_info_max_dice", default.max_dice),
+        }
+    }
+}
+
+/// longest embed description discord accepts, in characters
+const MAX_EMBED_DESCRIPTION_LEN: usize = 4096;
+
+/// name of the file results too long for a message are sent as
+const ROLL_FILE_NAME: &str = "roll.txt";
+
+/// posted instead of a result too long for a message, with the total if it is shown
+fn oversized_summary(roll: &RollExprResult, extended_info: bool) -> String {
+    let summary = "*the result is too long for a message, it is attached as a file*".to_string();
+    match render_total(roll, Style::Markdown) {
+        Some(total) if extended_info => format!("{}\n{}", summary, total),
+        _ => summary,
+    }
+}
 
 pub(crate) async fn roll(
     context: &Context,
     message: Message,
     rolls: Vec<RollExprResult>,
     extended_info: bool,
+    order: ListOrder,
+    handler: &DiscordBotHandler,
 ) {
+    let timeout_message = handler.timeout_message.as_str();
+    let limits = handler.roll_info_limits;
+    // every result rolls all expressions of the request again
+    let expressions: Vec<_> = rolls.iter().filter_map(|r| r.expression.clone()).collect();
     for roll in rolls {
-        if let Err(err) = message
+        let content = match &roll.roll {
+            Ok(r) => {
+                let content = render_content(&roll, &ordered(r, order), Style::Markdown);
+                // the total of a list is part of the roll info
+                match render_total(&roll, Style::Markdown) {
+                    Some(total) if extended_info => format!("{}\n{}", content, total),
+                    _ => content,
+                }
+            }
+            Err(e) => error_message(e, &roll.text, timeout_message, Style::Markdown),
+        };
+        let file = (content.chars().count() > MAX_MESSAGE_LEN)
+            .then(|| render(&roll, order, extended_info, timeout_message, Style::Plain));
+        match message
             .channel_id
             .send_message(context, |m| {
-                match roll.roll {
-                    Ok(r) => {
-                        let roll_line = format!(
-                            "{} => [{}]",
-                            roll.text,
-                            r.iter()
-                                .map(|result| format!("`{}`", result.0))
-                                .reduce(|r1, r2| format!("{}, {}", r1, r2))
-                                .unwrap_or_else(|| " ".to_string())
-                        );
-                        m.content(if let Some(l) = roll.label {
-                            format!("**{}**\n{}", l, roll_line)
-                        } else {
-                            roll_line
-                        });
-                        if extended_info
-                            && r.len() < 11
-                            && r.get(0).map_or(false, |r| r.1.len() < 21)
+                match (&file, &roll.roll) {
+                    (Some(file), _) => {
+                        m.content(oversized_summary(&roll, extended_info))
+                            .add_file((file.as_bytes(), ROLL_FILE_NAME));
+                    }
+                    (None, Ok(r)) => {
+                        let r = ordered(r, order);
+                        m.content(&content);
+                        let dice = render_dice(&r, roll.filter, Style::Markdown);
+                        // the dice of a success pool are always shown next to the count
+                        if (extended_info || roll.successes)
+                            && r.len() <= limits.max_results
+                            && r.first().is_some_and(|r| r.1.len() <= limits.max_dice)
+                            && dice.chars().count() <= MAX_EMBED_DESCRIPTION_LEN
                         {
                             m.embed(|e| {
-                                e.description(
-                                    r.iter()
-                                        .map(|r| {
-                                            format!(
-                                                "[{}]",
-                                                r.1.iter()
-                                                    .map(|r| format!("`{}`", r))
-                                                    .reduce(|r1, r2| format!("{}, {}", r1, r2))
-                                                    .unwrap_or_else(|| " ".to_string())
-                                            )
-                                        })
-                                        .reduce(|r1, r2| format!("{}\n{}", r1, r2))
-                                        .unwrap(),
-                                )
+                                e.description(dice);
+                                match render_audit(&roll) {
+                                    Some(audit) if extended_info => e.footer(|f| f.text(audit)),
+                                    _ => e,
+                                }
                             });
                         }
                     }
-                    Err(e) => {
-                        m.content(match e {
-                            EvaluationErrors::DivideByZero => {
-                                "*Division by 0 detected*".to_string()
-                            }
-                            EvaluationErrors::Timeout => "*Timeout*".to_string(),
-                            EvaluationErrors::Overflow => "*Overflow detected*".to_string(),
-                        });
+                    (None, Err(_)) => {
+                        m.content(&content);
                     }
                 };
                 m.reference_message(&message)
@@ -63,7 +135,221 @@ pub(crate) async fn roll(
             })
             .await
         {
-            log::warn!("unable to reply to message {}: {}", message.id, err);
+            Ok(result) => {
+                handler.rerolls.insert(
+                    result.id,
+                    Reroll {
+                        request: message.clone(),
+                        expressions: expressions.clone(),
+                    },
+                );
+                if let Err(err) = result.react(context, REROLL_EMOJI).await {
+                    log::warn!("unable to react to message {}: {}", result.id, err);
+                }
+            }
+            Err(err) => log::warn!("unable to reply to message {}: {}", message.id, err),
+        }
+    }
+}
+
+/// sends the result of a secret roll to its author, a request in a guild channel is deleted
+/// once the result was delivered
+pub(crate) async fn private_roll(
+    context: &Context,
+    message: Message,
+    rolls: Vec<RollExprResult>,
+    extended_info: bool,
+    order: ListOrder,
+    handler: &DiscordBotHandler,
+) {
+    let render_all = |style| {
+        rolls
+            .iter()
+            .map(|roll| render(roll, order, extended_info, &handler.timeout_message, style))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let content = match message.guild_id {
+        Some(_) => format!(
+            "secret roll in <#{}>\n{}",
+            message.channel_id,
+            render_all(Style::Markdown)
+        ),
+        None => render_all(Style::Markdown),
+    };
+    let file = (content.chars().count() > MAX_MESSAGE_LEN).then(|| render_all(Style::Plain));
+    let sent = message
+        .author
+        .direct_message(context, |m| match &file {
+            Some(file) => m
+                .content("*the secret roll is too long for a single message*")
+                .add_file(AttachmentType::Bytes {
+                    // the builder of a direct message can't borrow the file
+                    data: Cow::Owned(file.clone().into_bytes()),
+                    filename: ROLL_FILE_NAME.to_string(),
+                }),
+            None => m.content(&content),
+        })
+        .await;
+    match (sent, message.guild_id) {
+        (Ok(_), Some(_)) => {
+            if let Err(err) = message.delete(context).await {
+                log::warn!("unable to delete message {}: {}", message.id, err);
+            }
         }
+        (Ok(_), None) => {}
+        (Err(err), _) => {
+            log::warn!(
+                "unable to send a direct message to {}: {}",
+                message.author.id,
+                err
+            );
+            if let Err(err) = message
+                .reply(
+                    context,
+                    "*unable to send you the result, please allow direct messages from this server*",
+                )
+                .await
+            {
+                log::warn!("unable to reply to message {}: {}", message.id, err);
+            }
+        }
+    }
+}
+
+pub(crate) async fn nothing_to_repeat(context: Context, message: Message) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.content("*nothing to repeat, you haven't rolled anything here yet*")
+                .reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
+
+pub(crate) async fn busy(context: Context, message: Message) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.content("*Too many rolls at once, please try again in a moment*")
+                .reference_message(&message)
+                .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
+
+pub(crate) async fn rate_limited(context: Context, message: Message, retry_after: Duration) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.content(format!(
+                "*You are rolling too often, please wait {} seconds*",
+                retry_after.as_secs() + 1
+            ))
+            .reference_message(&message)
+            .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
+
+pub(crate) async fn trailing_operator(context: Context, message: Message, text: String) {
+    if let Err(err) = message
+        .channel_id
+        .send_message(&context, |m| {
+            m.content(format!(
+                "*`{}` ends with an operator, the term after it is missing*",
+                text
+            ))
+            .reference_message(&message)
+            .allowed_mentions(|m| m.empty_users())
+        })
+        .await
+    {
+        log::warn!("unable to reply to message {}: {}", message.id, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bot_utils::client_utils::EvaluationErrors;
+
+    #[test]
+    fn test_roll_info_limits() {
+        let config: Map<String, Value> =
+            toml::from_str("roll_info_max_dice = 50\nroll_info_max_results = -1").unwrap();
+        assert_eq!(
+            RollInfoLimits::from_config(&config),
+            RollInfoLimits {
+                max_results: 10,
+                max_dice: 50
+            }
+        );
+        assert_eq!(
+            RollInfoLimits::from_config(&Map::new()),
+            RollInfoLimits::default()
+        );
+    }
+
+    #[test]
+    fn test_oversized_roll() {
+        let roll = RollExprResult {
+            roll: Ok((0..1000).map(|_| (6, vec![6], Vec::new())).collect()),
+            text: "1000{1d6}".to_string(),
+            label: None,
+            successes: false,
+            filter: None,
+            comments: Vec::new(),
+            breakdown: Vec::new(),
+            modifier: None,
+            natural_d20: false,
+            announcements: Vec::new(),
+            statistics: None,
+            seed: None,
+            duration: None,
+            total: Some(6000),
+            expression: None,
+        };
+        let results = ordered(roll.roll.as_ref().unwrap(), ListOrder::Unsorted);
+        assert!(
+            render_content(&roll, &results, Style::Markdown)
+                .chars()
+                .count()
+                > MAX_MESSAGE_LEN
+        );
+        assert_eq!(
+            oversized_summary(&roll, true),
+            "*the result is too long for a message, it is attached as a file*\nTotal: `6000`"
+        );
+        assert_eq!(
+            oversized_summary(&roll, false),
+            "*the result is too long for a message, it is attached as a file*"
+        );
+    }
+
+    #[test]
+    fn test_timeout_message() {
+        assert_eq!(
+            error_message(
+                &EvaluationErrors::Timeout {
+                    completed: 0,
+                    total: 1
+                },
+                "100d6!",
+                DEFAULT_TIMEOUT_MESSAGE,
+                Style::Markdown
+            ),
+            "*Rolling `100d6!` took too long. Try fewer dice, smaller lists or fewer exploding dice.*"
+        );
     }
 }
@@ -0,0 +1,68 @@
+pub(crate) async fn roll_help(
+    context: serenity::client::Context,
+    message: serenity::model::channel::Message,
+) {
+    if let Err(err) = message.channel_id.send_message(&context, |m| {
+                m.reference_message((message.channel_id,message.id))
+                    .allowed_mentions(|mentions|mentions.empty_users())
+                 .embed(|e|{
+                     e.title("Roll Syntax")
+                      .description("
+A `[roll statement]` is a term made of dice and numbers, optionally followed by a label.
+Whitespace between the parts of a term is ignored.
+")
+                      .field(
+                          "Dice",
+                          "
+`[n]d[sides]` => roll `[n]` dice with `[sides]` sides, like `3d6`. `w` works the same as `d`, without `[n]` a single dice is rolled.
+`d%` => a d100. `dF` => a fudge dice showing -1, 0 or 1, `dF.2` shows -2 to 2. `d6x` => two d6 multiplied with each other.
+`(1d4)d6`, `d(1d4+2)` => the number of throws or sides is rolled first.
+`!` => reroll and add dice showing their maximum, `!!` adds them to the same dice.
+`ro<2`, `rr<2` => reroll dice matching the condition once or until they don't.
+",
+                          false
+                      ).field(
+                          "Filters",
+                          "
+`3d6>4` => only keep dice matching the condition. The conditions are `>`, `>=`, `<`, `<=` and `!=`.
+`10d10>=8s` => count the dice matching the condition as successes instead of adding them up.
+",
+                          false
+                      ).field(
+                          "Selectors",
+                          "
+`4d6k3`, `4d6h3` => keep the 3 highest dice. `2d20l1` => keep the lowest dice.
+`4d6dl1` => drop the lowest dice, `4d6dh1` drops the highest one.
+Selectors follow filters, like `5d10>2k3`.
+",
+                          false
+                      ).field(
+                          "Calculations",
+                          "
+Terms are combined with `+`, `-`, `*`, `/`, `%` and `^`, like `2d6+1d4*2`. `^` binds before `*`, `/` and `%`, those before `+` and `-`.
+Parentheses group terms, like `(1d6+2)*2`, a `-` in front negates them, like `-1d6` or `-(1d4+2)`.
+`min(2d6, 8)`, `max(1d4, 2)` => the lowest or highest value of the terms, all of them are rolled.
+`floor(1d6/2)`, `ceil(1d6/2)`, `round(1d6/2)` => round a division down, up or to the nearest number instead of toward 0.
+",
+                          false
+                      ).field(
+                          "Lists",
+                          "
+`6{4d6k3}` => roll the term in braces 6 times and show every result.
+`6{4d6k3} + 1` => calculations after the braces apply to every result, the same as `6{4d6k3 + 1}`.
+",
+                          false
+                      ).field(
+                          "Labels and Comments",
+                          "
+`1d20+5 # attack` => label the whole result.
+`1d20#hit + 1d6#fire` => label single terms to see the value of each.
+`1d8 [sword] + 2 [strength]` => annotate terms with comments in brackets.
+",
+                          false
+                      )
+                 })
+            }).await{
+        log::warn!("Unable to reply to message {}: {}",message.id,err);
+    }
+}
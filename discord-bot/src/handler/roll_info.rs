@@ -1,3 +1,4 @@
+use bot_utils::client_utils::ListOrder;
 use serenity::{client::Context, model::channel::Message};
 
 pub(crate) async fn set_roll_info(context: Context, message: Message) {
@@ -20,3 +21,42 @@ pub(crate) async fn get_roll_info(context: Context, message: Message, roll_info:
         log::warn!("Unable to reply to message: {}", err)
     }
 }
+
+pub(crate) async fn set_show_luck(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_show_luck(context: Context, message: Message, show_luck: bool) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        &format!(
+            "showing how lucky a roll was is set to `{}`",
+            if show_luck { "on" } else { "off" }
+        ),
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
+pub(crate) async fn set_list_order(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_list_order(context: Context, message: Message, order: ListOrder) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        &format!("list results are displayed `{}`", order),
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
@@ -30,6 +30,18 @@ pub(crate) async fn remove_roll_prefix(context: Context, message: Message, resul
     }
 }
 
+pub(crate) async fn clear_roll_prefixes(context: Context, message: Message, cleared: usize) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        format!("removed {} roll prefixes", cleared),
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
+
 pub(crate) async fn list_roll_prefix(context: Context, message: Message, prefixes: Vec<String>) {
     if let Some(m) = prefixes
         .iter()
@@ -41,3 +53,28 @@ pub(crate) async fn list_roll_prefix(context: Context, message: Message, prefixe
         }
     }
 }
+
+pub(crate) async fn set_roll_shorthand(context: Context, message: Message) {
+    if let Err(err) = Message::react(&message, &context, '✅').await {
+        log::warn!("unable to react to message {}: {}", message.id, err)
+    }
+}
+
+pub(crate) async fn get_roll_shorthand(
+    context: Context,
+    message: Message,
+    shorthand: Option<String>,
+) {
+    if let Err(err) = Message::reply(
+        &message,
+        &context,
+        match shorthand {
+            Some(s) => format!("messages starting with `{} ` are rolled", s),
+            None => "the roll shorthand is disabled".to_string(),
+        },
+    )
+    .await
+    {
+        log::warn!("Unable to reply to message: {}", err)
+    }
+}
@@ -3,9 +3,12 @@ use bot_utils::{
     client_utils::{ClientUtilsBuilder, ClientUtilsConfig},
 };
 
-use serenity::client::{Client, ClientBuilder};
+use serenity::{
+    client::{Client, ClientBuilder},
+    model::id::UserId,
+};
 
-use std::sync::Arc;
+use std::{convert::TryInto, sync::Arc};
 
 pub struct DiscordBot {
     client: Client,
@@ -21,6 +24,11 @@ impl Bot for DiscordBot {
 
 pub struct DiscordBotBuilder {
     invite_url: String,
+    timeout_message: String,
+    roll_info_limits: RollInfoLimits,
+    /// id of the bot application, slash commands are only available if it is known
+    application_id: Option<u64>,
+    reroll_by_anyone: bool,
     token: String,
     dm_utils: ClientUtilsConfig,
     guild_utils: ClientUtilsConfig,
@@ -36,12 +44,23 @@ impl BotBuilder for DiscordBotBuilder {
         mut stop: S,
     ) -> Self::B {
         let dm_utils = utils.lock().unwrap().get_from_config(self.dm_utils);
-        let guild_utils = utils.lock().unwrap().get_from_config(self.guild_utils);
-        let client = ClientBuilder::new(self.token)
+        let mut guild_utils = utils.lock().unwrap().get_from_config(self.guild_utils);
+        guild_utils.link_dm_aliases(&dm_utils, |user| user.parse().ok().map(UserId));
+        guild_utils.scope_aliases_per_user();
+        let mut client = ClientBuilder::new(self.token);
+        if let Some(id) = self.application_id {
+            client = client.application_id(id);
+        }
+        let client = client
             .event_handler(DiscordBotHandler {
                 dm_utils,
                 guild_utils,
                 invite_url: self.invite_url,
+                rerolls: Rerolls::new(),
+                timeout_message: self.timeout_message,
+                roll_info_limits: self.roll_info_limits,
+                slash_commands: self.application_id.is_some(),
+                reroll_by_anyone: self.reroll_by_anyone,
             })
             .await
             .unwrap();
@@ -85,6 +104,23 @@ impl BotConfig for DiscordBotConfig {
                 "https://example.com".to_string()
             }
         };
+        let timeout_message = discord_config
+            .get("timeout_message")
+            .and_then(|m| m.as_str())
+            .unwrap_or(DEFAULT_TIMEOUT_MESSAGE)
+            .to_owned();
+        let roll_info_limits = RollInfoLimits::from_config(discord_config);
+        let application_id = discord_config
+            .get("application_id")
+            .and_then(|i| i.as_integer())
+            .and_then(|i| i.try_into().ok());
+        if application_id.is_none() {
+            log::warn!("Unable to read discord application_id, slash commands are disabled");
+        }
+        let reroll_by_anyone = discord_config
+            .get("reroll_by_anyone")
+            .and_then(|r| r.as_bool())
+            .unwrap_or(false);
         let dm_utils = ClientUtilsConfig::from_config(
             "discord-dm",
             match discord_config.get_mut("dm").and_then(|c| c.as_table_mut()) {
@@ -118,6 +154,10 @@ impl BotConfig for DiscordBotConfig {
         );
         DiscordBotBuilder {
             invite_url,
+            timeout_message,
+            roll_info_limits,
+            application_id,
+            reroll_by_anyone,
             token,
             dm_utils,
             guild_utils,
@@ -126,4 +166,4 @@ impl BotConfig for DiscordBotConfig {
 }
 
 mod handler;
-use handler::DiscordBotHandler;
+use handler::{DiscordBotHandler, Rerolls, RollInfoLimits, DEFAULT_TIMEOUT_MESSAGE};
@@ -0,0 +1,350 @@
+//! the few endpoints of the Matrix client-server API the bot needs
+
+use reqwest::{Method, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WhoAmI {
+    pub(crate) user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Sync {
+    pub(crate) next_batch: String,
+    #[serde(default)]
+    pub(crate) rooms: Rooms,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Rooms {
+    /// rooms the bot is in, by id
+    #[serde(default)]
+    pub(crate) join: HashMap<String, JoinedRoom>,
+    /// rooms the bot was invited to, by id
+    #[serde(default)]
+    pub(crate) invite: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct JoinedRoom {
+    #[serde(default)]
+    pub(crate) timeline: Timeline,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Timeline {
+    #[serde(default)]
+    pub(crate) events: Vec<Event>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Event {
+    #[serde(rename = "type")]
+    pub(crate) kind: String,
+    pub(crate) event_id: String,
+    pub(crate) sender: String,
+    /// differs between the kinds of events
+    #[serde(default)]
+    pub(crate) content: serde_json::Value,
+}
+
+impl Event {
+    /// text of a plain text message, edits of earlier messages are ignored
+    pub(crate) fn text(&self) -> Option<&str> {
+        if self.kind != "m.room.message"
+            || self.content.get("msgtype").and_then(|t| t.as_str()) != Some("m.text")
+            || self.content.get("m.new_content").is_some()
+        {
+            return None;
+        }
+        self.content.get("body").and_then(|b| b.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PowerLevels {
+    #[serde(default)]
+    pub(crate) users: HashMap<String, i64>,
+    #[serde(default)]
+    pub(crate) users_default: i64,
+    /// level needed to change the settings of the room
+    #[serde(default = "default_state_level")]
+    pub(crate) state_default: i64,
+}
+
+fn default_state_level() -> i64 {
+    50
+}
+
+impl PowerLevels {
+    pub(crate) fn level(&self, user: &str) -> i64 {
+        self.users.get(user).copied().unwrap_or(self.users_default)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SendResponse {
+    event_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoomResponse {
+    room_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadResponse {
+    content_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    errcode: String,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Http(reqwest::Error),
+    /// the request was refused, with the error code and reason the homeserver gave
+    Api(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+            Error::Api(errcode, error) => write!(f, "{}: {}", errcode, error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+pub(crate) struct Api {
+    client: reqwest::Client,
+    homeserver: Url,
+    token: String,
+    /// transaction ids of sent messages have to be unique for the access token
+    txn_prefix: u128,
+    txn_counter: AtomicU64,
+}
+
+impl Api {
+    pub(crate) fn new(homeserver: Url, token: String) -> Api {
+        Api {
+            client: reqwest::Client::new(),
+            homeserver,
+            token,
+            txn_prefix: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default(),
+            txn_counter: AtomicU64::new(0),
+        }
+    }
+
+    /// url of the client-server endpoint made of `path`, every segment is escaped
+    fn url(&self, path: &[&str]) -> Url {
+        self.api_url("client", path)
+    }
+
+    /// url of `path` in the `api` of the homeserver, like `client` or `media`
+    fn api_url(&self, api: &str, path: &[&str]) -> Url {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .expect("the homeserver url can't be a base")
+            .pop_if_empty()
+            .extend(["_matrix", api, "v3"].iter().chain(path));
+        url
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<serde_json::Value>,
+    ) -> Result<T, Error> {
+        let mut request = self.client.request(method, url).bearer_auth(&self.token);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        Self::response(request.send().await?).await
+    }
+
+    async fn response<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, Error> {
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let status = response.status();
+            Err(match response.json::<ErrorResponse>().await {
+                Ok(ErrorResponse { errcode, error }) => {
+                    Error::Api(errcode, error.unwrap_or_else(|| status.to_string()))
+                }
+                Err(_) => Error::Api("M_UNKNOWN".to_string(), status.to_string()),
+            })
+        }
+    }
+
+    pub(crate) async fn whoami(&self) -> Result<WhoAmI, Error> {
+        self.call(Method::GET, self.url(&["account", "whoami"]), None)
+            .await
+    }
+
+    /// waits up to `timeout` seconds for events after `since`, everything the bot may see
+    /// without `since`
+    pub(crate) async fn sync(&self, since: Option<&str>, timeout: u32) -> Result<Sync, Error> {
+        let mut url = self.url(&["sync"]);
+        url.query_pairs_mut()
+            .append_pair("timeout", &(timeout * 1000).to_string());
+        if let Some(since) = since {
+            url.query_pairs_mut().append_pair("since", since);
+        }
+        self.call(Method::GET, url, None).await
+    }
+
+    pub(crate) async fn join(&self, room: &str) -> Result<(), Error> {
+        self.call::<RoomResponse>(Method::POST, self.url(&["join", room]), Some(json!({})))
+            .await
+            .map(|_| ())
+    }
+
+    /// sends `html` as a notice, in reply to `reply_to` if it is set, with `text` for clients
+    /// without formatting
+    pub(crate) async fn send_message(
+        &self,
+        room: &str,
+        reply_to: Option<&str>,
+        text: &str,
+        html: &str,
+    ) -> Result<String, Error> {
+        self.send(
+            room,
+            reply_to,
+            json!({
+                "msgtype": "m.notice",
+                "body": text,
+                "format": "org.matrix.custom.html",
+                "formatted_body": html,
+            }),
+        )
+        .await
+    }
+
+    /// uploads `content` and sends it as a file named `file_name`, in reply to `reply_to`
+    pub(crate) async fn send_file(
+        &self,
+        room: &str,
+        reply_to: Option<&str>,
+        file_name: &str,
+        mimetype: &str,
+        content: Vec<u8>,
+    ) -> Result<String, Error> {
+        let size = content.len();
+        let mut url = self.api_url("media", &["upload"]);
+        url.query_pairs_mut().append_pair("filename", file_name);
+        let upload: UploadResponse = Self::response(
+            self.client
+                .post(url)
+                .bearer_auth(&self.token)
+                .header(reqwest::header::CONTENT_TYPE, mimetype)
+                .body(content)
+                .send()
+                .await?,
+        )
+        .await?;
+        self.send(
+            room,
+            reply_to,
+            json!({
+                "msgtype": "m.file",
+                "body": file_name,
+                "url": upload.content_uri,
+                "info": {"mimetype": mimetype, "size": size},
+            }),
+        )
+        .await
+    }
+
+    async fn send(
+        &self,
+        room: &str,
+        reply_to: Option<&str>,
+        mut content: serde_json::Value,
+    ) -> Result<String, Error> {
+        let txn = format!(
+            "{}.{}",
+            self.txn_prefix,
+            self.txn_counter.fetch_add(1, Ordering::Relaxed)
+        );
+        if let Some(reply_to) = reply_to {
+            content["m.relates_to"] = json!({"m.in_reply_to": {"event_id": reply_to}});
+        }
+        self.call::<SendResponse>(
+            Method::PUT,
+            self.url(&["rooms", room, "send", "m.room.message", &txn]),
+            Some(content),
+        )
+        .await
+        .map(|response| response.event_id)
+    }
+
+    pub(crate) async fn power_levels(&self, room: &str) -> Result<PowerLevels, Error> {
+        self.call(
+            Method::GET,
+            self.url(&["rooms", room, "state", "m.room.power_levels", ""]),
+            None,
+        )
+        .await
+    }
+
+    /// opens a direct chat with `user` and returns its id, the user still has to accept the
+    /// invitation
+    pub(crate) async fn create_direct_room(&self, user: &str) -> Result<String, Error> {
+        self.call::<RoomResponse>(
+            Method::POST,
+            self.url(&["createRoom"]),
+            Some(json!({
+                "is_direct": true,
+                "invite": [user],
+                "preset": "trusted_private_chat",
+            })),
+        )
+        .await
+        .map(|response| response.room_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let api = Api::new(
+            Url::parse("https://matrix.example.org/").unwrap(),
+            String::new(),
+        );
+        assert_eq!(
+            api.url(&["rooms", "!abc:example.org", "send", "m.room.message", "1"])
+                .as_str(),
+            "https://matrix.example.org/_matrix/client/v3/rooms/!abc:example.org/send/m.room.message/1"
+        );
+        assert_eq!(
+            api.url(&["rooms", "!a/b?c#d:example.org", "state", "m.room.power_levels", ""])
+                .as_str(),
+            "https://matrix.example.org/_matrix/client/v3/rooms/!a%2Fb%3Fc%23d:example.org/state/m.room.power_levels/"
+        );
+    }
+}
@@ -0,0 +1,484 @@
+use crate::api::{Api, Event};
+use bot_utils::client_utils::{
+    render::{
+        distribution_table, error_message, ordered, pool_odds_table, render, render_faces,
+        render_history, render_scores, Style,
+    },
+    Access, ClientUtils, CommandResult, StatsError, CRITICAL_VALUE_PLACEHOLDER,
+    MAX_CRITICAL_MESSAGE_LEN,
+};
+use std::{collections::HashMap, sync::Mutex};
+
+/// rolls that take too long are answered with this, `{expression}` is replaced by the roll
+pub(crate) const DEFAULT_TIMEOUT_MESSAGE: &str =
+    "<i>Rolling <code>{expression}</code> took too long. Try fewer dice, smaller lists or fewer exploding dice.</i>";
+
+/// events may be up to 64 KiB, and a reply holds its text twice, with and without formatting
+const MAX_MESSAGE_LEN: usize = 30000;
+
+/// name of the file an exported configuration is sent as
+const CONFIG_FILE_NAME: &str = "config.json";
+
+pub(crate) struct MatrixBotHandler {
+    pub(crate) api: Api,
+    /// id of the bot, its own messages are ignored
+    pub(crate) user_id: String,
+    pub(crate) utils: ClientUtils<String>,
+    /// shown when a roll times out, see [DEFAULT_TIMEOUT_MESSAGE]
+    pub(crate) timeout_message: String,
+    /// direct chats the bot opened for secret rolls, by user
+    pub(crate) direct_rooms: Mutex<HashMap<String, String>>,
+}
+
+impl MatrixBotHandler {
+    pub(crate) async fn message(&self, room: String, event: Event) {
+        let text = match event.text() {
+            Some(text) if event.sender != self.user_id => strip_reply_fallback(text),
+            _ => return,
+        };
+        let check = || self.check_permission(&room, &event.sender);
+        let response = match self
+            .utils
+            .eval(room.clone(), &event.sender, Some(&room), text, check)
+            .await
+        {
+            // a configuration rarely fits into a single message
+            Some(CommandResult::ExportConfig(config)) => {
+                if let Err(err) = self
+                    .api
+                    .send_file(
+                        &room,
+                        Some(&event.event_id),
+                        CONFIG_FILE_NAME,
+                        "application/json",
+                        config.into_bytes(),
+                    )
+                    .await
+                {
+                    log::warn!("unable to send the configuration of {}: {}", room, err);
+                }
+                return;
+            }
+            Some(response) => response,
+            None => return,
+        };
+        let private = matches!(response, CommandResult::PrivateRoll(..));
+        let mut content = response_text(response, &self.timeout_message);
+        if content.len() > MAX_MESSAGE_LEN {
+            content = "<i>the result is too long for a single message</i>".to_string();
+        }
+        // secret rolls go to a direct chat with their author
+        if private {
+            match self.direct_room(&event.sender).await {
+                Ok(direct) => {
+                    if self.send(&direct, None, &content).await {
+                        return;
+                    }
+                }
+                Err(err) => log::warn!(
+                    "unable to open a direct chat with {}: {}",
+                    event.sender,
+                    err
+                ),
+            }
+            content = "<i>unable to send you the result in a direct chat</i>".to_string();
+        }
+        self.send(&room, Some(&event.event_id), &content).await;
+    }
+
+    /// `false` if the message couldn't be sent
+    async fn send(&self, room: &str, reply_to: Option<&str>, html: &str) -> bool {
+        match self
+            .api
+            .send_message(
+                room,
+                reply_to,
+                &plain_text(html),
+                &html.replace('\n', "<br>"),
+            )
+            .await
+        {
+            Ok(_) => true,
+            Err(err) => {
+                log::warn!("unable to send message to {}: {}", room, err);
+                false
+            }
+        }
+    }
+
+    async fn direct_room(&self, user: &str) -> Result<String, crate::api::Error> {
+        if let Some(room) = self.direct_rooms.lock().unwrap().get(user) {
+            return Ok(room.clone());
+        }
+        let room = self.api.create_direct_room(user).await?;
+        self.direct_rooms
+            .lock()
+            .unwrap()
+            .insert(user.to_string(), room.clone());
+        Ok(room)
+    }
+
+    /// users who may change the settings of the room may change those of the bot, Matrix has
+    /// no roles to grant privileges to
+    async fn check_permission(&self, room: &str, user: &str) -> Access {
+        match self.api.power_levels(room).await {
+            Ok(levels) => Access::from(levels.level(user) >= levels.state_default),
+            Err(err) => {
+                log::warn!("unable to get power levels of {}: {}", room, err);
+                Access::default()
+            }
+        }
+    }
+}
+
+/// `body` without the quote of the message it replies to, which clients put in front of it
+pub(crate) fn strip_reply_fallback(body: &str) -> &str {
+    if !body.starts_with("> ") {
+        return body;
+    }
+    match body.find("\n\n") {
+        Some(end) => &body[end + 2..],
+        None => body,
+    }
+}
+
+/// `html` for clients without formatting, only the markup the responses use is removed
+pub(crate) fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn done(result: &Result<(), ()>, failed: &str) -> String {
+    match result {
+        Ok(_) => "✅".to_string(),
+        Err(_) => format!("<i>{}</i>", failed),
+    }
+}
+
+fn help(prefix: &str) -> String {
+    format!(
+        "<b>Roll Bot</b>
+All commands start with the command prefix <code>{0}</code>.
+<code>{0}roll 1d20+5</code> =&gt; roll dice, <code>{0}roll-help</code> explains the syntax.
+<code>{0}alias add [alias] [roll statement]</code> =&gt; roll the statement with <code>{0}roll [alias]</code>, <code>alias remove</code> and <code>alias list</code> manage them.
+<code>{0}command-prefix set [prefix]</code> =&gt; change the command prefix, only for users who may change the room settings.
+<code>{0}roll-prefix add [prefix]</code> =&gt; roll messages starting with <code>[prefix]</code>, only for users who may change the room settings.
+<code>{0}roll-info set true</code> =&gt; show the single dice of each roll.",
+        Style::Html.escape(prefix)
+    )
+}
+
+fn roll_help() -> String {
+    "<b>Roll Syntax</b>
+<code>[n]d[sides]</code> =&gt; roll <code>[n]</code> dice with <code>[sides]</code> sides, like <code>3d6</code>. <code>dF</code> rolls a fudge dice, <code>dF.2</code> one showing -2 to 2.
+<code>3d6&gt;4</code> =&gt; only keep dice matching the condition, <code>10d10&gt;=8s</code> counts them as successes.
+<code>4d6k3</code> =&gt; keep the 3 highest dice, <code>4d6dl1</code> drops the lowest one.
+<code>2d6+1d4*2</code> =&gt; combine terms with <code>+</code>, <code>-</code>, <code>*</code>, <code>/</code>, <code>%</code> and <code>^</code>.
+<code>min(2d6, 8)</code>, <code>max(1d4, 2)</code> =&gt; the lowest or highest value of the terms.
+<code>floor(1d6/2)</code>, <code>ceil(1d6/2)</code>, <code>round(1d6/2)</code> =&gt; round a division down, up or to the nearest number.
+<code>6{4d6k3}</code> =&gt; roll the term in braces 6 times, <code>6{4d6k3} + 1</code> adds 1 to every result.
+<code>1d20+5 # attack</code> =&gt; label the result, <code>1d8 [sword]</code> annotates a term."
+        .to_string()
+}
+
+/// reply to `result`, formatted as HTML
+pub(crate) fn response_text(result: CommandResult, timeout_message: &str) -> String {
+    let code = |text: &str| Style::Html.code(&Style::Html.escape(text));
+    match result {
+        CommandResult::Roll(rolls, roll_info, order)
+        | CommandResult::PrivateRoll(rolls, roll_info, order) => rolls
+            .iter()
+            .map(|roll| render(roll, order, roll_info, timeout_message, Style::Html))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::Multiple(results) => results
+            .into_iter()
+            .map(|r| response_text(r, timeout_message))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::Help(prefix) => help(&prefix),
+        CommandResult::RollHelp => roll_help(),
+        CommandResult::Info => {
+            "<i>Roll Bot, source at https://github.com/RobinMarchart/roll-bot</i>".to_string()
+        }
+        CommandResult::Busy => {
+            "<i>Too many rolls at once, please try again in a moment</i>".to_string()
+        }
+        CommandResult::RateLimited { retry_after } => format!(
+            "<i>You are rolling too often, please wait {} seconds</i>",
+            retry_after.as_secs() + 1
+        ),
+        CommandResult::TrailingOperator(text) => format!(
+            "<i>{} ends with an operator, the term after it is missing</i>",
+            code(&text)
+        ),
+        CommandResult::AliasLimitReached(err) => {
+            format!("<i>{}</i>", Style::Html.escape(&err.to_string()))
+        }
+        CommandResult::RemoveAlias(result) => done(&result, "no such alias"),
+        CommandResult::ClearAliases(cleared) => format!("removed {} shared aliases", cleared),
+        CommandResult::ListAliases(aliases) if aliases.is_empty() => {
+            "<i>there are no aliases</i>".to_string()
+        }
+        CommandResult::ListAliases(aliases) => aliases
+            .iter()
+            .map(|(alias, expr)| format!("{} =&gt; {}", code(alias), code(expr)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::ListAliasPage(page) if page.aliases.is_empty() => format!(
+            "<i>there is no page {}, the alias list has {} pages</i>",
+            page.page, page.pages
+        ),
+        CommandResult::ListAliasPage(page) => page
+            .aliases
+            .iter()
+            .map(|(alias, expr)| format!("{} =&gt; {}\n", code(alias), code(expr)))
+            .chain(std::iter::once(format!(
+                "<i>page {} of {}</i>",
+                page.page, page.pages
+            )))
+            .collect(),
+        CommandResult::UnknownAlias(alias, suggestions) => format!(
+            "no alias {}, did you mean {}?",
+            code(&alias),
+            suggestions
+                .iter()
+                .map(|s| code(s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        CommandResult::InvalidAliasCall(err) => {
+            format!("<i>{}</i>", Style::Html.escape(&err.to_string()))
+        }
+        CommandResult::GetCommandPrefix(prefix) | CommandResult::SetCommandPrefix(prefix) => {
+            format!("the command prefix is {}", code(&prefix))
+        }
+        CommandResult::SetChannelCommandPrefix(Some(prefix)) => {
+            format!("the command prefix of this room is {}", code(&prefix))
+        }
+        CommandResult::AddRollPrefix(result) => done(&result, "the roll prefix already exists"),
+        CommandResult::PrefixConflict(prefix) => format!(
+            "<i>{} would be both the command prefix and a roll prefix, rolls starting with it would be taken for commands</i>",
+            code(&prefix)
+        ),
+        CommandResult::RemoveRollPrefix(result) => done(&result, "no such roll prefix"),
+        CommandResult::ClearRollPrefixes(cleared) => format!("removed {} roll prefixes", cleared),
+        CommandResult::ListRollPrefix(prefixes) if prefixes.is_empty() => {
+            "<i>there are no roll prefixes</i>".to_string()
+        }
+        CommandResult::ListRollPrefix(prefixes) => prefixes
+            .iter()
+            .map(|p| code(p))
+            .collect::<Vec<_>>()
+            .join(", "),
+        CommandResult::GetRollInfo(roll_info) => format!("roll info is {}", roll_info),
+        CommandResult::GetShowLuck(show_luck) => format!("luck is {}", show_luck),
+        CommandResult::GetListOrder(order) => format!("the list order is {:?}", order),
+        CommandResult::History(entries) => render_history(&entries, Style::Html),
+        CommandResult::NothingToRepeat => {
+            "<i>nothing to repeat, you haven't rolled anything here yet</i>".to_string()
+        }
+        CommandResult::SetDiceBanned(_, banned, result) => done(
+            &result,
+            if banned {
+                "the dice already are forbidden"
+            } else {
+                "the dice already are allowed"
+            },
+        ),
+        CommandResult::GetDicePolicy(policy) => policy.to_string(),
+        CommandResult::DiceTypeNotAllowed(dice) => {
+            format!("<i>{} may not be rolled here</i>", code(&dice))
+        }
+        CommandResult::InsufficentPermission => {
+            "<i>only users who may change the room settings may use this command</i>".to_string()
+        }
+        CommandResult::SetChannelCommandPrefix(None) => {
+            "this room uses the command prefix of the bot again".to_string()
+        }
+        CommandResult::AddPrivilegedRole(result) => {
+            done(&result, "the role already is privileged")
+        }
+        CommandResult::RemovePrivilegedRole(result) => done(&result, "the role isn't privileged"),
+        CommandResult::ListPrivilegedRoles(roles) if roles.is_empty() => {
+            "only users who may change the room settings may use privileged commands".to_string()
+        }
+        CommandResult::ListPrivilegedRoles(roles) => roles
+            .iter()
+            .map(|r| code(r))
+            .collect::<Vec<_>>()
+            .join(", "),
+        CommandResult::CopyAlias(Ok(_)) | CommandResult::ImportConfig(Ok(_)) => "✅".to_string(),
+        CommandResult::CopyAlias(Err(err)) => {
+            format!("<i>{}</i>", Style::Html.escape(&err.to_string()))
+        }
+        CommandResult::ImportConfig(Err(err)) => {
+            format!("<i>{}</i>", Style::Html.escape(&err.to_string()))
+        }
+        CommandResult::ExportConfig(config) => Style::Html.block(&config),
+        CommandResult::PoolOdds(text, Ok(odds)) => format!(
+            "<b>{}</b>\nexpected successes: <b>{:.2}</b>\n{}",
+            Style::Html.escape(&text),
+            odds.expected,
+            Style::Html.block(&pool_odds_table(&odds))
+        ),
+        CommandResult::PoolOdds(text, Err(StatsError::TooLarge)) => {
+            format!("<i>{} is too large to compute</i>", code(&text))
+        }
+        CommandResult::Odds(text, Ok(distribution)) => format!(
+            "<b>{}</b>\n{}: <b>{:.2}</b>\n{}",
+            Style::Html.escape(&text),
+            if distribution.exact {
+                "expected result"
+            } else {
+                "estimated expected result"
+            },
+            distribution.mean(),
+            Style::Html.block(&distribution_table(&distribution))
+        ),
+        CommandResult::Odds(text, Err(StatsError::TooLarge)) => {
+            format!("<i>{} is too complex to compute exactly</i>", code(&text))
+        }
+        CommandResult::PoolOdds(text, Err(StatsError::Unsupported))
+        | CommandResult::Odds(text, Err(StatsError::Unsupported)) => {
+            format!("<i>Odds for {} are not supported</i>", code(&text))
+        }
+        CommandResult::Narrative(text, roll) => {
+            let tally = roll.tally();
+            format!(
+                "<b>{}</b>\n<b>{}</b>: {}\n{}",
+                Style::Html.escape(&text),
+                if tally.succeeded() {
+                    "Success"
+                } else {
+                    "Failure"
+                },
+                Style::Html.escape(&tally.to_string()),
+                Style::Html.block(&render_faces(&roll))
+            )
+        }
+        CommandResult::GenStats(scores, rerolls, order) => match &scores.roll {
+            Ok(r) => {
+                let mut content = format!(
+                    "<b>Ability scores ({})</b>\n{}",
+                    Style::Html.escape(&scores.text),
+                    render_scores(&ordered(r, order), Style::Html)
+                );
+                if rerolls > 0 {
+                    content = format!(
+                        "{}\n<i>rerolled {} time{} for a low total</i>",
+                        content,
+                        rerolls,
+                        if rerolls == 1 { "" } else { "s" }
+                    );
+                }
+                content
+            }
+            Err(e) => error_message(e, &scores.text, timeout_message, Style::Html),
+        },
+        CommandResult::GetStatsConfig(roll, Some(min)) => format!(
+            "ability scores are rolled with {} until their total is at least {}",
+            code(&roll),
+            min
+        ),
+        CommandResult::GetStatsConfig(roll, None) => {
+            format!("ability scores are rolled with {}", code(&roll))
+        }
+        CommandResult::GetModifier(Some(modifier)) => format!(
+            "your rolls are modified by {}",
+            code(&format!("{:+}", modifier))
+        ),
+        CommandResult::GetModifier(None) => "no modifier is set".to_string(),
+        CommandResult::GetRollShorthand(Some(shorthand)) => format!(
+            "messages starting with {} are rolled",
+            code(&format!("{} ", shorthand))
+        ),
+        CommandResult::GetRollShorthand(None) => "the roll shorthand is disabled".to_string(),
+        CommandResult::SetCriticalMessage(_, Ok(_)) => "✅".to_string(),
+        CommandResult::SetCriticalMessage(critical, Err(_)) => format!(
+            "<i>the {} message can be at most {} characters long</i>",
+            critical, MAX_CRITICAL_MESSAGE_LEN
+        ),
+        CommandResult::GetCriticalMessage(critical, Some(text)) => format!(
+            "{} rolls are announced with {}, where {} is the rolled total",
+            critical,
+            code(&text),
+            code(CRITICAL_VALUE_PLACEHOLDER)
+        ),
+        CommandResult::GetCriticalMessage(critical, None) => {
+            format!("{} rolls are not announced", critical)
+        }
+        CommandResult::Pause => "⏸".to_string(),
+        CommandResult::Resume => "▶".to_string(),
+        CommandResult::Reload => "🔄".to_string(),
+        CommandResult::AddAlias
+        | CommandResult::SetRollInfo
+        | CommandResult::SetShowLuck
+        | CommandResult::SetStatsRoll
+        | CommandResult::SetStatsMinTotal
+        | CommandResult::SetAliasHints
+        | CommandResult::SetModifier
+        | CommandResult::SetListOrder
+        | CommandResult::SetRollShorthand
+        | CommandResult::SetMaxFaces => "✅".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bot_utils::client_utils::AliasCopyError;
+
+    #[test]
+    fn test_strip_reply_fallback() {
+        assert_eq!(strip_reply_fallback("rrb!roll 1d20"), "rrb!roll 1d20");
+        assert_eq!(
+            strip_reply_fallback("> <@bot:example.org> 17\n> more\n\nrrb!roll 1d20"),
+            "rrb!roll 1d20"
+        );
+        assert_eq!(strip_reply_fallback("> quoted"), "> quoted");
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            plain_text(&response_text(
+                CommandResult::ListAliases(vec![("atk".to_string(), "1d20>5".to_string())]),
+                DEFAULT_TIMEOUT_MESSAGE
+            )),
+            "atk => 1d20>5"
+        );
+        assert_eq!(
+            plain_text(&response_text(
+                CommandResult::GetCommandPrefix("<&>".to_string()),
+                DEFAULT_TIMEOUT_MESSAGE
+            )),
+            "the command prefix is <&>"
+        );
+        assert_eq!(
+            plain_text(&response_text(
+                CommandResult::CopyAlias(Err(AliasCopyError::NotFound)),
+                DEFAULT_TIMEOUT_MESSAGE
+            )),
+            AliasCopyError::NotFound.to_string()
+        );
+        assert_eq!(
+            plain_text(&response_text(
+                CommandResult::GetStatsConfig("6{4d6k3}".to_string(), Some(70)),
+                DEFAULT_TIMEOUT_MESSAGE
+            )),
+            "ability scores are rolled with 6{4d6k3} until their total is at least 70"
+        );
+    }
+}
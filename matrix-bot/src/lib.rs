@@ -0,0 +1,189 @@
+use bot_utils::{
+    bots::{async_trait, Bot, BotBuilder, BotConfig, Map, StopListener, Value},
+    client_utils::{ClientUtilsBuilder, ClientUtilsConfig},
+};
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// seconds a single sync request waits for new events
+const POLL_TIMEOUT: u32 = 30;
+
+/// pause before syncing again after a failed request
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const DEFAULT_HOMESERVER: &str = "https://matrix.org";
+
+pub struct MatrixBot {
+    /// `None` if no token was provided
+    handler: Option<Arc<MatrixBotHandler>>,
+    stopped: tokio::sync::watch::Receiver<bool>,
+}
+
+impl MatrixBot {
+    /// joins the rooms the bot was invited to
+    async fn join(handler: &MatrixBotHandler, rooms: impl Iterator<Item = String>) {
+        for room in rooms {
+            match handler.api.join(&room).await {
+                Ok(_) => log::info!("joined matrix room {}", room),
+                Err(err) => log::warn!("unable to join matrix room {}: {}", room, err),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Bot for MatrixBot {
+    async fn run(mut self) {
+        let handler = match self.handler {
+            Some(h) => h,
+            None => return,
+        };
+        // messages sent before the bot started are skipped
+        let mut since: Option<String> = None;
+        loop {
+            let timeout = if since.is_some() { POLL_TIMEOUT } else { 0 };
+            let sync = tokio::select! {
+                _ = self.stopped.changed() => break,
+                sync = handler.api.sync(since.as_deref(), timeout) => sync,
+            };
+            match sync {
+                Ok(sync) => {
+                    MatrixBot::join(&handler, sync.rooms.invite.into_keys()).await;
+                    if since.is_some() {
+                        for (room, joined) in sync.rooms.join {
+                            for event in joined.timeline.events {
+                                let handler = handler.clone();
+                                let room = room.clone();
+                                tokio::task::spawn(
+                                    async move { handler.message(room, event).await },
+                                );
+                            }
+                        }
+                    }
+                    since = Some(sync.next_batch);
+                }
+                Err(err) => {
+                    log::warn!("unable to sync with matrix: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+        log::info!("matrix bot stopped")
+    }
+}
+
+pub struct MatrixBotBuilder {
+    timeout_message: String,
+    homeserver: String,
+    token: Option<String>,
+    utils: ClientUtilsConfig,
+}
+
+#[async_trait]
+impl BotBuilder for MatrixBotBuilder {
+    type B = MatrixBot;
+
+    async fn build<S: StopListener>(
+        self,
+        utils: Arc<std::sync::Mutex<ClientUtilsBuilder>>,
+        mut stop: S,
+    ) -> Self::B {
+        let (sender, stopped) = tokio::sync::watch::channel(false);
+        tokio::task::spawn(async move {
+            stop.wait_stop().await;
+            let _ = sender.send(true);
+        });
+        let token = match self.token {
+            Some(token) => token,
+            None => {
+                return MatrixBot {
+                    handler: None,
+                    stopped,
+                }
+            }
+        };
+        let homeserver = self.homeserver;
+        let homeserver = reqwest::Url::parse(&homeserver)
+            .unwrap_or_else(|err| panic!("invalid matrix homeserver {}: {}", homeserver, err));
+        let api = Api::new(homeserver, token);
+        let user_id = api
+            .whoami()
+            .await
+            .expect("unable to connect to matrix")
+            .user_id;
+        log::info!("connected to matrix as {}", user_id);
+        let mut utils = utils.lock().unwrap().get_from_config(self.utils);
+        utils.scope_aliases_per_user();
+        MatrixBot {
+            handler: Some(Arc::new(MatrixBotHandler {
+                api,
+                user_id,
+                utils,
+                timeout_message: self.timeout_message,
+                direct_rooms: std::sync::Mutex::new(HashMap::new()),
+            })),
+            stopped,
+        }
+    }
+}
+
+pub struct MatrixBotConfig {}
+
+impl BotConfig for MatrixBotConfig {
+    type Builder = MatrixBotBuilder;
+
+    fn config(self, config: &mut Map<String, Value>) -> Self::Builder {
+        let token = std::env::var("MATRIX_TOKEN").ok();
+        if token.is_none() {
+            log::warn!("No Matrix access token provided in MATRIX_TOKEN env var, the matrix bot is disabled");
+        }
+        let matrix_config = match config.get_mut("matrix").and_then(|t| t.as_table_mut()) {
+            Some(t) => t,
+            None => {
+                log::warn!("Missing matrix section in config");
+                config.insert("matrix".to_string(), Value::from(Map::new()));
+                config.get_mut("matrix").unwrap().as_table_mut().unwrap()
+            }
+        };
+        let timeout_message = matrix_config
+            .get("timeout_message")
+            .and_then(|m| m.as_str())
+            .unwrap_or(DEFAULT_TIMEOUT_MESSAGE)
+            .to_owned();
+        let homeserver = match matrix_config.get("homeserver").and_then(|h| h.as_str()) {
+            Some(h) => h.to_owned(),
+            None => {
+                matrix_config.insert("homeserver".to_string(), Value::from(DEFAULT_HOMESERVER));
+                DEFAULT_HOMESERVER.to_owned()
+            }
+        };
+        let utils = ClientUtilsConfig::from_config(
+            "matrix",
+            match matrix_config
+                .get_mut("rooms")
+                .and_then(|c| c.as_table_mut())
+            {
+                Some(t) => t,
+                None => {
+                    matrix_config.insert("rooms".to_string(), Value::from(Map::new()));
+                    matrix_config
+                        .get_mut("rooms")
+                        .unwrap()
+                        .as_table_mut()
+                        .unwrap()
+                }
+            },
+        );
+        MatrixBotBuilder {
+            timeout_message,
+            homeserver,
+            token,
+            utils,
+        }
+    }
+}
+
+mod api;
+use api::Api;
+mod handler;
+use handler::{MatrixBotHandler, DEFAULT_TIMEOUT_MESSAGE};
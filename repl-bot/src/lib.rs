@@ -0,0 +1,116 @@
+//! a bot reading commands from stdin and printing the results, for trying out
+//! aliases and prefixes without any chat platform
+
+use bot_utils::{
+    bots::{async_trait, Bot, BotBuilder, BotConfig, Map, StopListener, Value},
+    client_utils::{Access, ClientUtils, ClientUtilsBuilder, ClientUtilsConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::{io::BufRead, sync::Arc};
+use tokio::sync::mpsc;
+
+/// the only client of the repl
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReplId;
+
+/// the user every line is sent as
+const USER: &str = "repl";
+
+pub struct ReplBot {
+    utils: ClientUtils<ReplId>,
+    stopped: tokio::sync::watch::Receiver<bool>,
+}
+
+/// lines of stdin, read on a thread of their own so a pending read doesn't hold up shutting down
+fn read_lines() -> mpsc::UnboundedReceiver<String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    log::warn!("unable to read stdin: {}", err);
+                    break;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+#[async_trait]
+impl Bot for ReplBot {
+    async fn run(mut self) {
+        let mut lines = read_lines();
+        loop {
+            let line = tokio::select! {
+                _ = self.stopped.changed() => break,
+                line = lines.recv() => match line {
+                    Some(line) => line,
+                    None => break,
+                },
+            };
+            if let Some(result) = self
+                .utils
+                .eval(ReplId, USER, None, &line, || {
+                    std::future::ready(Access::from(true))
+                })
+                .await
+            {
+                println!("{}", response_text(result));
+            }
+        }
+        log::info!("repl bot stopped")
+    }
+}
+
+pub struct ReplBotBuilder {
+    utils: ClientUtilsConfig,
+}
+
+#[async_trait]
+impl BotBuilder for ReplBotBuilder {
+    type B = ReplBot;
+
+    async fn build<S: StopListener>(
+        self,
+        utils: Arc<std::sync::Mutex<ClientUtilsBuilder>>,
+        mut stop: S,
+    ) -> Self::B {
+        let (sender, stopped) = tokio::sync::watch::channel(false);
+        tokio::task::spawn(async move {
+            stop.wait_stop().await;
+            let _ = sender.send(true);
+        });
+        ReplBot {
+            utils: utils.lock().unwrap().get_from_config(self.utils),
+            stopped,
+        }
+    }
+}
+
+pub struct ReplBotConfig {}
+
+impl BotConfig for ReplBotConfig {
+    type Builder = ReplBotBuilder;
+
+    fn config(self, config: &mut Map<String, Value>) -> Self::Builder {
+        let repl_config = match config.get_mut("repl").and_then(|r| r.as_table_mut()) {
+            Some(r) => r,
+            None => {
+                config.insert("repl".to_string(), Value::from(Map::new()));
+                config.get_mut("repl").unwrap().as_table_mut().unwrap()
+            }
+        };
+        ReplBotBuilder {
+            utils: ClientUtilsConfig::from_config("repl", repl_config),
+        }
+    }
+}
+
+mod output;
+pub use output::response_text;
@@ -0,0 +1,15 @@
+use bot_utils::bot_manager::BotManagerBuilder;
+use repl_bot::ReplBotConfig;
+
+#[tokio::main(flavor = "multi_thread", worker_threads = 2)]
+async fn main() {
+    pretty_env_logger::init();
+    let config_path = std::env::args()
+        .nth(1)
+        .expect("missing first command line argument with config file");
+    BotManagerBuilder::new(config_path, ReplBotConfig {})
+        .build_async()
+        .await
+        .run()
+        .await;
+}
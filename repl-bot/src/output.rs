@@ -0,0 +1,104 @@
+use bot_utils::client_utils::{
+    render::{render, render_history, Style},
+    CommandResult,
+};
+
+/// shown when a roll times out, `{expression}` is replaced by the roll
+pub(crate) const TIMEOUT_MESSAGE: &str = "Rolling {expression} took too long.";
+
+fn done(result: &Result<(), ()>, failed: &str) -> String {
+    match result {
+        Ok(_) => "ok".to_string(),
+        Err(_) => failed.to_string(),
+    }
+}
+
+/// `result` as plain text, results without a text of their own are shown as they are
+pub fn response_text(result: CommandResult) -> String {
+    match result {
+        CommandResult::Roll(rolls, roll_info, order)
+        | CommandResult::PrivateRoll(rolls, roll_info, order) => rolls
+            .iter()
+            .map(|roll| render(roll, order, roll_info, TIMEOUT_MESSAGE, Style::Plain))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::Multiple(results) => results
+            .into_iter()
+            .map(response_text)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::Help(prefix) => format!("commands start with {}", prefix),
+        CommandResult::Busy => "too many rolls at once".to_string(),
+        CommandResult::RateLimited { retry_after } => {
+            format!("rate limited for {:.1}s", retry_after.as_secs_f64())
+        }
+        CommandResult::TrailingOperator(text) => format!("{} ends with an operator", text),
+        CommandResult::AddAlias => "ok".to_string(),
+        CommandResult::AliasLimitReached(err) => err.to_string(),
+        CommandResult::RemoveAlias(result) => done(&result, "no such alias"),
+        CommandResult::ClearAliases(cleared) => format!("removed {} shared aliases", cleared),
+        CommandResult::ListAliases(aliases) if aliases.is_empty() => "no aliases".to_string(),
+        CommandResult::ListAliases(aliases) => aliases
+            .iter()
+            .map(|(alias, expr)| format!("{} => {}", alias, expr))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CommandResult::ListAliasPage(page) => page
+            .aliases
+            .iter()
+            .map(|(alias, expr)| format!("{} => {}\n", alias, expr))
+            .chain(std::iter::once(format!(
+                "page {} of {}",
+                page.page, page.pages
+            )))
+            .collect(),
+        CommandResult::UnknownAlias(alias, suggestions) => format!(
+            "no alias {}, did you mean {}?",
+            alias,
+            suggestions.join(", ")
+        ),
+        CommandResult::InvalidAliasCall(err) => err.to_string(),
+        CommandResult::GetCommandPrefix(prefix) | CommandResult::SetCommandPrefix(prefix) => {
+            format!("command prefix {}", prefix)
+        }
+        CommandResult::AddRollPrefix(result) => done(&result, "the roll prefix already exists"),
+        CommandResult::PrefixConflict(prefix) => format!(
+            "{} would be both the command prefix and a roll prefix",
+            prefix
+        ),
+        CommandResult::RemoveRollPrefix(result) => done(&result, "no such roll prefix"),
+        CommandResult::ClearRollPrefixes(cleared) => format!("removed {} roll prefixes", cleared),
+        CommandResult::ListRollPrefix(prefixes) => format!("roll prefixes {}", prefixes.join(" ")),
+        CommandResult::History(entries) => render_history(&entries, Style::Plain),
+        CommandResult::NothingToRepeat => "nothing to repeat".to_string(),
+        CommandResult::SetDiceBanned(_, banned, result) => done(
+            &result,
+            if banned {
+                "already forbidden"
+            } else {
+                "already allowed"
+            },
+        ),
+        CommandResult::GetDicePolicy(policy) => policy.to_string(),
+        CommandResult::DiceTypeNotAllowed(dice) => format!("{} may not be rolled", dice),
+        result => format!("{:?}", result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_text() {
+        assert_eq!(
+            response_text(CommandResult::Multiple(vec![
+                CommandResult::GetCommandPrefix("!".to_string()),
+                CommandResult::ListAliases(vec![("atk".to_string(), "1d20+5".to_string())]),
+                CommandResult::RemoveAlias(Err(())),
+            ])),
+            "command prefix !\natk => 1d20+5\nno such alias"
+        );
+        assert_eq!(response_text(CommandResult::Pause), "Pause");
+    }
+}
@@ -1,47 +1,44 @@
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use rand_xoshiro::Xoshiro256PlusPlus;
-use robins_dice_roll::{dice_roll::DiceEvaluate, limits::DiceLimits};
-use std::convert::{TryFrom, TryInto};
+use robins_dice_roll::{dice_roll::ExpressionEvaluate, Expression, Term};
+use std::collections::BTreeMap;
 
-fn main() {
-    let (dice, num) = {
-        let mut args = std::env::args().skip(1);
-        let dice = args
-            .next()
-            .and_then(|a| {
-                robins_dice_roll::parser::parse_selected_dice(&a)
-                    .ok()
-                    .map(|r| r.1)
-            })
-            .expect("first arg should be selected dice");
-        (
-            dice,
-            args.next()
-                .and_then(|a| u32::from_str_radix(&a, 10).ok())
-                .unwrap_or(1),
-        )
-    };
-    let mut master_rng = ChaCha20Rng::from_entropy();
+mod output;
 
-    let (result_min, result_max) = (dice.min(), dice.max());
+/// histograms are written as npy files unless another format is given, `--histogram` prints a
+/// chart of the sums instead and only writes files if asked to with `--format` or `--out`
+///
+/// the throws histogram counts the faces of every die only if the expression is a single group of
+/// dice like `4d6k3`, for any other expression it is the same as the one of the sums
+const USAGE: &str =
+    "usage: roll-cmd <expression> [rolls] [--format csv|json|npy] [--out <prefix>] [--histogram] [--seed <u64>] \
+     [--threads <n>]";
 
-    let mut results: Vec<i64> = vec![0; (result_max - result_min + 2).try_into().unwrap()];
-    *results.get_mut(0).unwrap() = result_min;
+/// width of the chart if the terminal doesn't tell its own by `COLUMNS`
+const DEFAULT_WIDTH: usize = 80;
 
-    let (throw_min, throw_max) = {
-        let dice_type = match match dice {
-            robins_dice_roll::SelectedDice::Unchanged(d) => d,
-            robins_dice_roll::SelectedDice::Selected(d, _, _) => d,
-        } {
-            robins_dice_roll::FilteredDice::Simple(d) => d,
-            robins_dice_roll::FilteredDice::Filtered(d, _, _) => d,
+/// the expression is a single group of dice, so its throws are faces of the same die
+fn single_dice(expr: &Expression) -> bool {
+    fn dice_term(term: &Term) -> bool {
+        match term {
+            Term::DiceThrow(_) => true,
+            Term::SubTerm(t) | Term::Commented(t, _) | Term::Labeled(t, _) => dice_term(t),
+            _ => false,
         }
-        .dice;
-        (dice_type.min(), dice_type.max())
-    };
-    let mut throws: Vec<i64> = vec![0; (throw_max - throw_min + 2).try_into().unwrap()];
-    *throws.get_mut(0).unwrap() = throw_min;
+    }
+    dice_term(expr.term())
+}
+
+/// counts of each sum and of each throw of `num` rolls of `expr`
+fn sample(
+    expr: &Expression,
+    single_dice: bool,
+    num: u32,
+    master_rng: &mut ChaCha20Rng,
+) -> (BTreeMap<i64, i64>, BTreeMap<i64, i64>) {
+    let mut results: BTreeMap<i64, i64> = BTreeMap::new();
+    let mut throws: BTreeMap<i64, i64> = BTreeMap::new();
 
     for result in (0..num)
         .map(|_| {
@@ -49,34 +46,138 @@ fn main() {
             master_rng.fill(&mut seed);
             Xoshiro256PlusPlus::from_seed(seed)
         })
-        .map(|mut r| dice.evaluate(&mut || false, &mut r))
+        .map(|mut r| expr.evaluate(&mut || false, &mut r))
     {
-        let result = result.unwrap();
-        let result_into = results
-            .get_mut(
-                usize::try_from(
-                    result
-                        .0
-                        .iter()
-                        .map(|i| i.clone())
-                        .reduce(|i1, i2| i1 + i2)
-                        .unwrap_or(1)
-                        - result_min
-                        + 1,
-                )
-                .unwrap(),
-            )
-            .unwrap();
-        *result_into = *result_into + 1;
+        for (sum, dice) in result.unwrap_or_else(|err| panic!("unable to roll: {:?}", err)) {
+            *results.entry(sum).or_insert(0) += 1;
+            if single_dice {
+                for throw in dice {
+                    *throws.entry(throw).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    (results, throws)
+}
+
+/// adds the counts of `from` to `into`
+fn merge(into: &mut BTreeMap<i64, i64>, from: BTreeMap<i64, i64>) {
+    for (value, count) in from {
+        *into.entry(value).or_insert(0) += count;
+    }
+}
+
+fn main() {
+    let (expr, num, format, prefix, chart, seed, threads) = {
+        let mut positional = Vec::new();
+        let mut format = None;
+        let mut prefix = None;
+        let mut chart = false;
+        let mut seed = None;
+        let mut threads = 1;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = Some(
+                        args.next()
+                            .expect(USAGE)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    )
+                }
+                "--out" => prefix = Some(args.next().expect(USAGE)),
+                "--histogram" => chart = true,
+                "--threads" => {
+                    threads = args
+                        .next()
+                        .and_then(|t| t.parse::<u32>().ok())
+                        .filter(|t| *t > 0)
+                        .expect(USAGE)
+                }
+                "--seed" => {
+                    seed = Some(
+                        args.next()
+                            .and_then(|s| s.parse::<u64>().ok())
+                            .expect(USAGE),
+                    )
+                }
+                _ => positional.push(arg),
+            }
+        }
+        let mut positional = positional.into_iter();
+        let expr = robins_dice_roll::parser::parse(&positional.next().expect(USAGE))
+            .unwrap_or_else(|err| panic!("first arg should be a roll expression: {}", err));
+        (
+            expr,
+            positional
+                .next()
+                .and_then(|a| a.parse::<u32>().ok())
+                .unwrap_or(1),
+            format,
+            prefix,
+            chart,
+            seed,
+            threads,
+        )
+    };
+    // every run can be repeated with the seed it printed
+    let seed = seed.unwrap_or_else(|| {
+        let seed = rand::random();
+        eprintln!("seed {}", seed);
+        seed
+    });
+    let mut master_rng = ChaCha20Rng::seed_from_u64(seed);
+    let single_dice = single_dice(&expr);
 
-        result.1.iter().for_each(|r| {
-            let throw_into = throws
-                .get_mut(usize::try_from(r - throw_min + 1).unwrap())
-                .unwrap();
-            *throw_into = *throw_into + 1;
+    let (results, mut throws) = if threads == 1 {
+        sample(&expr, single_dice, num, &mut master_rng)
+    } else {
+        // every thread gets a generator of its own, seeded by the master generator
+        let rngs: Vec<_> = (0..threads)
+            .map(|_| ChaCha20Rng::from_seed(master_rng.gen()))
+            .collect();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = rngs
+                .into_iter()
+                .zip(0..)
+                .map(|(mut rng, thread)| {
+                    let expr = &expr;
+                    let num = num / threads + u32::from(thread < num % threads);
+                    scope.spawn(move || sample(expr, single_dice, num, &mut rng))
+                })
+                .collect();
+            let mut results = BTreeMap::new();
+            let mut throws = BTreeMap::new();
+            for handle in handles {
+                let (thread_results, thread_throws) = handle.join().unwrap();
+                merge(&mut results, thread_results);
+                merge(&mut throws, thread_throws);
+            }
+            (results, throws)
         })
+    };
+    if !single_dice {
+        throws = results.clone();
     }
 
-    npy::to_file("throws.npy", throws).unwrap();
-    npy::to_file("rolls.npy", results).unwrap();
+    let results = output::histogram(&results);
+    if chart {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        print!("{}", output::chart(&results, width));
+    }
+    if !chart || format.is_some() || prefix.is_some() {
+        let format = format.unwrap_or(output::Format::Npy);
+        output::write(
+            output::histogram(&throws),
+            "throws",
+            prefix.as_deref(),
+            format,
+        )
+        .unwrap();
+        output::write(results, "rolls", prefix.as_deref(), format).unwrap();
+    }
 }
@@ -0,0 +1,150 @@
+//! writers for the histograms, each one is the lowest value followed by the count of every value
+//! from there on
+
+use std::{collections::BTreeMap, fmt::Write, io, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Npy,
+    /// `value,count` columns
+    Csv,
+    /// array of `{"value": .., "count": ..}` objects
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "npy" => Ok(Format::Npy),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {}, expected csv, json or npy", s)),
+        }
+    }
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Npy => "npy",
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+}
+
+/// histogram of the `counts` of each value, values between them are counted as 0
+pub(crate) fn histogram(counts: &BTreeMap<i64, i64>) -> Vec<i64> {
+    let (min, max) = match (counts.keys().next(), counts.keys().next_back()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return vec![0],
+    };
+    let mut histogram = vec![0; (max - min + 2) as usize];
+    histogram[0] = min;
+    for (value, count) in counts {
+        histogram[(value - min + 1) as usize] = *count;
+    }
+    histogram
+}
+
+/// values and their counts
+fn counts(histogram: &[i64]) -> impl Iterator<Item = (i64, i64)> + '_ {
+    let (min, counts) = histogram
+        .split_first()
+        .expect("histogram without lowest value");
+    counts
+        .iter()
+        .enumerate()
+        .map(move |(i, count)| (min + i as i64, *count))
+}
+
+fn csv(histogram: &[i64]) -> String {
+    let mut out = "value,count\n".to_string();
+    for (value, count) in counts(histogram) {
+        writeln!(out, "{},{}", value, count).unwrap();
+    }
+    out
+}
+
+fn json(histogram: &[i64]) -> String {
+    let entries: Vec<_> = counts(histogram)
+        .map(|(value, count)| format!("{{\"value\":{},\"count\":{}}}", value, count))
+        .collect();
+    format!("[{}]\n", entries.join(","))
+}
+
+/// horizontal bar chart of `histogram` in lines of at most `width` characters, like
+/// `  7 | ######## 1234`
+pub(crate) fn chart(histogram: &[i64], width: usize) -> String {
+    let value_width = counts(histogram)
+        .map(|(value, _)| value.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let max_count = counts(histogram).map(|(_, count)| count).max().unwrap_or(0);
+    let count_width = max_count.to_string().len();
+    let bar_width = width.saturating_sub(value_width + count_width + 4).max(1);
+    let mut out = String::new();
+    for (value, count) in counts(histogram) {
+        let bar = if max_count > 0 {
+            (count as f64 / max_count as f64 * bar_width as f64).round() as usize
+        } else {
+            0
+        };
+        writeln!(
+            out,
+            "{:>value_width$} | {} {}",
+            value,
+            "#".repeat(bar),
+            count,
+            value_width = value_width
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// writes `histogram` to `<prefix>-<name>.<extension>`, or `<name>.<extension>` without prefix
+pub(crate) fn write(
+    histogram: Vec<i64>,
+    name: &str,
+    prefix: Option<&str>,
+    format: Format,
+) -> io::Result<()> {
+    let path = match prefix {
+        Some(prefix) => format!("{}-{}.{}", prefix, name, format.extension()),
+        None => format!("{}.{}", name, format.extension()),
+    };
+    match format {
+        Format::Npy => npy::to_file(path, histogram),
+        Format::Csv => std::fs::write(path, csv(&histogram)),
+        Format::Json => std::fs::write(path, json(&histogram)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_formats() {
+        let counts = vec![-1, 3, 0, 5];
+        assert_eq!(csv(&counts), "value,count\n-1,3\n0,0\n1,5\n");
+        assert_eq!(
+            json(&counts),
+            "[{\"value\":-1,\"count\":3},{\"value\":0,\"count\":0},{\"value\":1,\"count\":5}]\n"
+        );
+        assert_eq!(
+            histogram(&[(1, 5), (-1, 3)].iter().copied().collect()),
+            counts
+        );
+        assert_eq!(histogram(&BTreeMap::new()), vec![0]);
+        assert_eq!(
+            chart(&[-1, 4, 0, 8], 16),
+            "-1 | ##### 4\n 0 |  0\n 1 | ######### 8\n"
+        );
+        assert_eq!("csv".parse(), Ok(Format::Csv));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
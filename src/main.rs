@@ -1,16 +1,42 @@
 use bot_utils::bot_manager::BotManagerBuilder;
 use discord_bot::DiscordBotConfig;
+use matrix_bot::MatrixBotConfig;
+use telegram_bot::TelegramBotConfig;
+
+/// format given by `--log-format=<format>`, otherwise by the `LOG_FORMAT` environment variable
+fn log_format(args: &[String]) -> Option<String> {
+    args.iter()
+        .find_map(|arg| arg.strip_prefix("--log-format="))
+        .map(|format| format.to_string())
+        .or_else(|| std::env::var("LOG_FORMAT").ok())
+}
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() {
-    pretty_env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match log_format(&args).as_deref() {
+        Some("json") => bot_utils::logging::init_json(),
+        None | Some("pretty") => pretty_env_logger::init(),
+        Some(format) => {
+            pretty_env_logger::init();
+            log::warn!("unknown log format {}, using pretty", format)
+        }
+    }
     log::info!("logger created");
-    let config_path = std::env::args()
-        .nth(1)
-        .expect("missing first command line argument with config file");
-    BotManagerBuilder::new(config_path, DiscordBotConfig {})
-        .build_async()
-        .await
-        .run()
-        .await;
+    let config_path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .expect("missing command line argument with config file")
+        .clone();
+    BotManagerBuilder::new(
+        config_path,
+        (
+            DiscordBotConfig {},
+            (TelegramBotConfig {}, MatrixBotConfig {}),
+        ),
+    )
+    .build_async()
+    .await
+    .run()
+    .await;
 }
@@ -0,0 +1,184 @@
+//! the few methods of the Telegram Bot API the bot needs
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use std::fmt;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct User {
+    pub(crate) id: i64,
+    pub(crate) is_bot: bool,
+    pub(crate) username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Chat {
+    pub(crate) id: i64,
+    /// `private`, `group`, `supergroup` or `channel`
+    #[serde(rename = "type")]
+    pub(crate) kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Message {
+    pub(crate) message_id: i64,
+    pub(crate) from: Option<User>,
+    pub(crate) chat: Chat,
+    pub(crate) text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Update {
+    pub(crate) update_id: i64,
+    pub(crate) message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatMember {
+    pub(crate) user: User,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Http(reqwest::Error),
+    /// the request was refused, with the reason Telegram gave
+    Api(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+            Error::Api(description) => write!(f, "{}", description),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+pub(crate) struct Api {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Api {
+    pub(crate) fn new(token: &str) -> Api {
+        Api {
+            client: reqwest::Client::new(),
+            url: format!("https://api.telegram.org/bot{}/", token),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, Error> {
+        let response: Response<T> = self
+            .client
+            .post(format!("{}{}", self.url, method))
+            .json(&params)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Self::result(method, response)
+    }
+
+    fn result<T>(method: &str, response: Response<T>) -> Result<T, Error> {
+        match response {
+            Response {
+                ok: true,
+                result: Some(result),
+                ..
+            } => Ok(result),
+            Response { description, .. } => Err(Error::Api(
+                description.unwrap_or_else(|| format!("{} failed", method)),
+            )),
+        }
+    }
+
+    pub(crate) async fn get_me(&self) -> Result<User, Error> {
+        self.call("getMe", json!({})).await
+    }
+
+    /// waits up to `timeout` seconds for updates after `offset`
+    pub(crate) async fn get_updates(
+        &self,
+        offset: i64,
+        timeout: u32,
+    ) -> Result<Vec<Update>, Error> {
+        self.call(
+            "getUpdates",
+            json!({"offset": offset, "timeout": timeout, "allowed_updates": ["message"]}),
+        )
+        .await
+    }
+
+    /// sends `text` formatted as HTML in reply to `reply_to`
+    pub(crate) async fn send_message(
+        &self,
+        chat: i64,
+        reply_to: i64,
+        text: &str,
+    ) -> Result<Message, Error> {
+        self.call(
+            "sendMessage",
+            json!({
+                "chat_id": chat,
+                "text": text,
+                "parse_mode": "HTML",
+                "reply_to_message_id": reply_to,
+                "allow_sending_without_reply": true,
+                "disable_web_page_preview": true,
+            }),
+        )
+        .await
+    }
+
+    /// sends `content` as a file named `file_name` in reply to `reply_to`
+    pub(crate) async fn send_document(
+        &self,
+        chat: i64,
+        reply_to: i64,
+        file_name: &str,
+        content: Vec<u8>,
+    ) -> Result<Message, Error> {
+        let form = reqwest::multipart::Form::new()
+            .text("chat_id", chat.to_string())
+            .text("reply_to_message_id", reply_to.to_string())
+            .text("allow_sending_without_reply", "true")
+            .part(
+                "document",
+                reqwest::multipart::Part::bytes(content).file_name(file_name.to_string()),
+            );
+        let response: Response<Message> = self
+            .client
+            .post(format!("{}sendDocument", self.url))
+            .multipart(form)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Self::result("sendDocument", response)
+    }
+
+    pub(crate) async fn get_chat_administrators(
+        &self,
+        chat: i64,
+    ) -> Result<Vec<ChatMember>, Error> {
+        self.call("getChatAdministrators", json!({ "chat_id": chat }))
+            .await
+    }
+}
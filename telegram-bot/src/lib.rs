@@ -0,0 +1,178 @@
+use bot_utils::{
+    bots::{async_trait, Bot, BotBuilder, BotConfig, Map, StopListener, Value},
+    client_utils::{ClientUtilsBuilder, ClientUtilsConfig},
+};
+
+use std::{sync::Arc, time::Duration};
+
+/// seconds a single request for updates waits for new messages
+const POLL_TIMEOUT: u32 = 30;
+
+/// pause before asking for updates again after a failed request
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+pub struct TelegramBot {
+    /// `None` if no token was provided
+    handler: Option<Arc<TelegramBotHandler>>,
+    stopped: tokio::sync::watch::Receiver<bool>,
+}
+
+#[async_trait]
+impl Bot for TelegramBot {
+    async fn run(mut self) {
+        let handler = match self.handler {
+            Some(h) => h,
+            None => return,
+        };
+        let mut offset = 0;
+        loop {
+            let updates = tokio::select! {
+                _ = self.stopped.changed() => break,
+                updates = handler.api.get_updates(offset, POLL_TIMEOUT) => updates,
+            };
+            match updates {
+                Ok(updates) => {
+                    for update in updates {
+                        offset = offset.max(update.update_id + 1);
+                        if let Some(message) = update.message {
+                            let handler = handler.clone();
+                            tokio::task::spawn(async move { handler.message(message).await });
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!("unable to get telegram updates: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+        log::info!("telegram bot stopped")
+    }
+}
+
+pub struct TelegramBotBuilder {
+    timeout_message: String,
+    token: Option<String>,
+    private_utils: ClientUtilsConfig,
+    group_utils: ClientUtilsConfig,
+}
+
+#[async_trait]
+impl BotBuilder for TelegramBotBuilder {
+    type B = TelegramBot;
+
+    async fn build<S: StopListener>(
+        self,
+        utils: Arc<std::sync::Mutex<ClientUtilsBuilder>>,
+        mut stop: S,
+    ) -> Self::B {
+        let (sender, stopped) = tokio::sync::watch::channel(false);
+        tokio::task::spawn(async move {
+            stop.wait_stop().await;
+            let _ = sender.send(true);
+        });
+        let token = match self.token {
+            Some(token) => token,
+            None => {
+                return TelegramBot {
+                    handler: None,
+                    stopped,
+                }
+            }
+        };
+        let api = Api::new(&token);
+        let username = api
+            .get_me()
+            .await
+            .expect("unable to connect to telegram")
+            .username
+            .unwrap_or_default();
+        log::info!("connected to telegram as {}", username);
+        let private_utils = utils.lock().unwrap().get_from_config(self.private_utils);
+        let mut group_utils = utils.lock().unwrap().get_from_config(self.group_utils);
+        // the private chat with a user has the id of that user
+        group_utils.link_dm_aliases(&private_utils, |user| user.parse().ok());
+        group_utils.scope_aliases_per_user();
+        TelegramBot {
+            handler: Some(Arc::new(TelegramBotHandler {
+                api,
+                username,
+                private_utils,
+                group_utils,
+                timeout_message: self.timeout_message,
+            })),
+            stopped,
+        }
+    }
+}
+
+pub struct TelegramBotConfig {}
+
+impl BotConfig for TelegramBotConfig {
+    type Builder = TelegramBotBuilder;
+
+    fn config(self, config: &mut Map<String, Value>) -> Self::Builder {
+        let token = std::env::var("TELEGRAM_TOKEN").ok();
+        if token.is_none() {
+            log::warn!("No Telegram API Token provided in TELEGRAM_TOKEN env var, the telegram bot is disabled");
+        }
+        let telegram_config = match config.get_mut("telegram").and_then(|t| t.as_table_mut()) {
+            Some(t) => t,
+            None => {
+                log::warn!("Missing telegram section in config");
+                config.insert("telegram".to_string(), Value::from(Map::new()));
+                config.get_mut("telegram").unwrap().as_table_mut().unwrap()
+            }
+        };
+        let timeout_message = telegram_config
+            .get("timeout_message")
+            .and_then(|m| m.as_str())
+            .unwrap_or(DEFAULT_TIMEOUT_MESSAGE)
+            .to_owned();
+        let private_utils = ClientUtilsConfig::from_config(
+            "telegram-private",
+            match telegram_config
+                .get_mut("private")
+                .and_then(|c| c.as_table_mut())
+            {
+                Some(t) => t,
+                None => {
+                    telegram_config.insert("private".to_string(), Value::from(Map::new()));
+                    telegram_config
+                        .get_mut("private")
+                        .unwrap()
+                        .as_table_mut()
+                        .unwrap()
+                }
+            },
+        );
+        let group_utils = ClientUtilsConfig::from_config(
+            "telegram-group",
+            match telegram_config
+                .get_mut("group")
+                .and_then(|c| c.as_table_mut())
+            {
+                Some(t) => t,
+                None => {
+                    telegram_config.insert("group".to_string(), Value::from(Map::new()));
+                    telegram_config
+                        .get_mut("group")
+                        .unwrap()
+                        .as_table_mut()
+                        .unwrap()
+                }
+            },
+        );
+        TelegramBotBuilder {
+            timeout_message,
+            token,
+            private_utils,
+            group_utils,
+        }
+    }
+}
+
+mod api;
+use api::Api;
+mod handler;
+use handler::{TelegramBotHandler, DEFAULT_TIMEOUT_MESSAGE};